@@ -0,0 +1,1240 @@
+//! Assist extracting the selected statements or expression into a new function.
+
+use format_buf::format;
+use hir::{db::HirDatabase, HasSource, HirDisplay, Mutability, Ty, TypeCtor};
+use ra_fmt::leading_indent;
+use ra_syntax::{
+    ast::{self, AstNode, NameOwner, TypeParamsOwner},
+    NodeOrToken, SyntaxElement,
+    SyntaxKind::{
+        BREAK_EXPR, CONTINUE_EXPR, FOR_EXPR, IDENT, IMPL_BLOCK, ITEM_LIST, LAMBDA_EXPR, LIFETIME,
+        LITERAL, LOOP_EXPR, PATH_EXPR, RETURN_EXPR, TOKEN_TREE, TRY_EXPR, WHILE_EXPR,
+    },
+    SyntaxNode, TextRange, TextUnit,
+};
+use test_utils::tested_by;
+
+use crate::{Assist, AssistCtx, AssistId};
+
+const FN_NAME: &str = "fun_name";
+
+pub(crate) fn extract_function(mut ctx: AssistCtx<impl HirDatabase>) -> Option<Assist> {
+    if ctx.frange.range.is_empty() {
+        return None;
+    }
+    let range = trimmed_range(ctx.covering_element(), ctx.frange.range)?;
+    let body = FunctionBody::new(ctx.covering_node_for_range(range), range)?;
+    let fn_def = body.nodes[0].ancestors().find_map(ast::FnDef::cast)?;
+
+    let db = ctx.db;
+    let analyzer = hir::SourceAnalyzer::new(db, ctx.frange.file_id, &body.nodes[0], None);
+    let locals = local_usages(&analyzer, &fn_def);
+    let loops = enclosing_loops(&body, &fn_def);
+
+    let mut params = Vec::new();
+    let mut outputs = Vec::new();
+    for local in &locals {
+        let decl_range = local.pat.syntax().text_range();
+        let is_declared_inside = decl_range.is_subrange(&range);
+        let used_inside = local.usages.iter().any(|it| it.range.is_subrange(&range));
+        // The next iteration of a loop runs the selection again, so it uses
+        // the locals declared before the loop after the selection too.
+        let used_after = loops.iter().any(|it| !decl_range.is_subrange(it))
+            || local.usages.iter().any(|it| it.range.start() >= range.end());
+        if is_declared_inside && used_after {
+            outputs.push(Output::new(db, &analyzer, &local.pat)?);
+        } else if !is_declared_inside && used_inside {
+            params.push(Param::new(db, &analyzer, local, range, used_after)?);
+        }
+    }
+
+    let self_usages = self_usages(&body);
+    let self_param = if self_usages.is_empty() {
+        None
+    } else {
+        let in_loop = !loops.is_empty();
+        Some(self_param(db, &analyzer, &fn_def, &self_usages, range, in_loop)?)
+    };
+    let impl_block = fn_def
+        .syntax()
+        .parent()
+        .filter(|it| it.kind() == ITEM_LIST)
+        .and_then(|it| it.parent())
+        .filter(|it| it.kind() == IMPL_BLOCK);
+    if self_param.is_some() && impl_block.is_none() {
+        return None;
+    }
+
+    let flow = ControlFlow::new(&body)?;
+    let value = match &body.tail_expr {
+        Some(expr) => {
+            let ty = analyzer.type_of(db, expr).map(|ty| ty.display(db).to_string())?;
+            if ty.contains('{') {
+                return None;
+            }
+            if ty == "()" {
+                None
+            } else {
+                Some(ty)
+            }
+        }
+        None => match outputs.as_slice() {
+            [] => None,
+            [output] => Some(output.ty.clone()),
+            outputs => {
+                let tys = outputs.iter().map(|it| it.ty.as_str()).collect::<Vec<_>>();
+                Some(format!("({})", tys.join(", ")))
+            }
+        },
+    };
+    let ret_kind = RetKind::new(&fn_def, &flow, value.is_some())?;
+
+    // Methods go into the impl, free functions after the outermost item
+    let anchor = match (&self_param, &impl_block) {
+        (Some(_), _) => fn_def.syntax().clone(),
+        (None, Some(impl_block)) => impl_block.clone(),
+        (None, None) => fn_def.syntax().clone(),
+    };
+    let old_indent = leading_indent(&body.nodes[0]).unwrap_or_default();
+    let new_indent = leading_indent(&anchor).unwrap_or_default();
+
+    ctx.add_action(AssistId("extract_function"), "extract into function", move |edit| {
+        let call_expr = {
+            let args = params.iter().map(|it| it.arg()).collect::<Vec<_>>().join(", ");
+            let receiver = if self_param.is_some() { "self." } else { "" };
+            format!("{}{}({})", receiver, FN_NAME, args)
+        };
+        let (call_site, cursor_offset) =
+            call_site(&call_expr, &ret_kind, &body, &outputs, &old_indent);
+
+        let mut buf = String::new();
+        format!(buf, "\n\n{}fn {}", new_indent, FN_NAME);
+        let generics = generics(&fn_def, &params, &value, &ret_kind);
+        buf.push_str(&generics.0);
+        let params_text = self_param
+            .map(self_param_text)
+            .into_iter()
+            .chain(params.iter().map(|it| it.text()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(buf, "({})", params_text);
+        if let Some(ret_ty) = ret_kind.ret_ty(value.as_ref()) {
+            format!(buf, " -> {}", ret_ty);
+        }
+        buf.push_str(&generics.1);
+        buf.push_str(" {");
+        let body_indent = format!("{}    ", new_indent);
+        let body_text = body_text(&fn_def, &body, &params, &flow, &ret_kind, &outputs);
+        for line in body_text.lines() {
+            let line = if line.starts_with(old_indent.as_str()) {
+                &line[old_indent.len()..]
+            } else {
+                line.trim_start()
+            };
+            if line.is_empty() {
+                buf.push_str("\n");
+            } else {
+                format!(buf, "\n{}{}", body_indent, line);
+            }
+        }
+        format!(buf, "\n{}}}", new_indent);
+
+        edit.target(range);
+        edit.replace(range, call_site);
+        edit.insert(anchor.text_range().end(), buf);
+        edit.set_cursor(range.start() + cursor_offset);
+    });
+
+    ctx.build()
+}
+
+/// The selected code, without surrounding whitespace.
+struct FunctionBody {
+    range: TextRange,
+    /// The selected statements or the single selected expression.
+    nodes: Vec<SyntaxNode>,
+    /// The expression which produces the value of the selected code.
+    tail_expr: Option<ast::Expr>,
+}
+
+impl FunctionBody {
+    fn new(covering: SyntaxElement, range: TextRange) -> Option<FunctionBody> {
+        let node = match covering {
+            NodeOrToken::Node(it) => it,
+            NodeOrToken::Token(it) => it.parent(),
+        };
+        if node.text_range() == range {
+            if let Some(expr) = ast::Expr::cast(node.clone()) {
+                return match node.kind() {
+                    // These are not worth a function
+                    LITERAL | PATH_EXPR => None,
+                    _ if node.parent().and_then(ast::FnDef::cast).is_some() => None,
+                    _ => Some(FunctionBody { range, nodes: vec![node], tail_expr: Some(expr) }),
+                };
+            }
+        }
+
+        let block = node.ancestors().find_map(ast::Block::cast)?;
+        let children = block
+            .statements()
+            .map(|it| it.syntax().clone())
+            .chain(block.expr().map(|it| it.syntax().clone()));
+        let mut nodes = Vec::new();
+        for child in children {
+            let child_range = child.text_range();
+            if child_range.is_subrange(&range) {
+                nodes.push(child);
+            } else if child_range.start() < range.end() && range.start() < child_range.end() {
+                // Only a part of a statement is selected
+                return None;
+            }
+        }
+        let first = nodes.first()?.text_range();
+        let last = nodes.last()?.text_range();
+        if first.start() != range.start() || last.end() != range.end() {
+            return None;
+        }
+        let tail_expr = block.expr().filter(|it| it.syntax().text_range() == last);
+        Some(FunctionBody { range, nodes, tail_expr })
+    }
+}
+
+fn trimmed_range(covering: SyntaxElement, selection: TextRange) -> Option<TextRange> {
+    let node = match covering {
+        NodeOrToken::Node(it) => it,
+        NodeOrToken::Token(it) => it.parent(),
+    };
+    let mut tokens = node
+        .descendants_with_tokens()
+        .filter_map(|it| it.into_token())
+        .filter(|it| !it.kind().is_trivia() && it.text_range().is_subrange(&selection));
+    let first = tokens.next()?;
+    let last = tokens.last().unwrap_or_else(|| first.clone());
+    Some(TextRange::from_to(first.text_range().start(), last.text_range().end()))
+}
+
+/// A local variable of the function, together with all references to it.
+struct Local {
+    pat: ast::BindPat,
+    usages: Vec<Usage>,
+}
+
+struct Usage {
+    range: TextRange,
+    /// `None` for usages inside macro calls, which we can only guess by name.
+    name_ref: Option<ast::NameRef>,
+}
+
+fn local_usages(analyzer: &hir::SourceAnalyzer, fn_def: &ast::FnDef) -> Vec<Local> {
+    let root = fn_def.syntax().ancestors().last().unwrap();
+    let mut res: Vec<Local> = Vec::new();
+    for name_ref in fn_def.syntax().descendants().filter_map(ast::NameRef::cast) {
+        if !is_local_ref(&name_ref) {
+            continue;
+        }
+        let pat = match analyzer.resolve_local_name(&name_ref).map(|it| it.ptr()) {
+            Some(hir::Either::A(ptr)) => match ptr.to_node(&root) {
+                ast::Pat::BindPat(it) => it,
+                _ => continue,
+            },
+            _ => continue,
+        };
+        let usage = Usage { range: name_ref.syntax().text_range(), name_ref: Some(name_ref) };
+        match res.iter_mut().find(|it| it.pat == pat) {
+            Some(local) => local.usages.push(usage),
+            None => res.push(Local { pat, usages: vec![usage] }),
+        }
+    }
+
+    // FIXME: arguments of macro calls are not resolved, so we look for the
+    // innermost visible binding with the same name instead.
+    let bind_pats =
+        fn_def.syntax().descendants().filter_map(ast::BindPat::cast).collect::<Vec<_>>();
+    let macro_idents = fn_def
+        .syntax()
+        .descendants()
+        .filter(|it| it.kind() == TOKEN_TREE)
+        .flat_map(|it| it.children_with_tokens())
+        .filter_map(|it| it.into_token())
+        .filter(|it| it.kind() == IDENT);
+    for ident in macro_idents {
+        let range = ident.text_range();
+        let pat = bind_pats
+            .iter()
+            .filter(|pat| pat.name().map_or(false, |it| it.text() == ident.text()))
+            .filter(|pat| pat.syntax().text_range().end() <= range.start())
+            .filter(|pat| is_visible_at(pat, range.start()))
+            .last();
+        let pat = match pat {
+            Some(it) => it,
+            None => continue,
+        };
+        let usage = Usage { range, name_ref: None };
+        match res.iter_mut().find(|it| it.pat == *pat) {
+            Some(local) => local.usages.push(usage),
+            None => res.push(Local { pat: pat.clone(), usages: vec![usage] }),
+        }
+    }
+
+    res.sort_by_key(|it| it.pat.syntax().text_range().start());
+    res
+}
+
+/// The ranges of the loops of `fn_def` around the selection.
+fn enclosing_loops(body: &FunctionBody, fn_def: &ast::FnDef) -> Vec<TextRange> {
+    body.nodes[0]
+        .ancestors()
+        .skip(1)
+        .take_while(|it| it != fn_def.syntax())
+        .filter(|it| match it.kind() {
+            LOOP_EXPR | WHILE_EXPR | FOR_EXPR => true,
+            _ => false,
+        })
+        .map(|it| it.text_range())
+        .collect()
+}
+
+/// Whether `name_ref` could refer to a local variable, that is whether it is
+/// a single segment path expression or a field shorthand.
+fn is_local_ref(name_ref: &ast::NameRef) -> bool {
+    let parent = match name_ref.syntax().parent() {
+        Some(it) => it,
+        None => return false,
+    };
+    if let Some(field) = ast::RecordField::cast(parent.clone()) {
+        return field.expr().is_none();
+    }
+    let path = match ast::PathSegment::cast(parent).and_then(|it| it.syntax().parent()) {
+        Some(it) => it,
+        None => return false,
+    };
+    match ast::Path::cast(path) {
+        Some(path) => {
+            path.qualifier().is_none()
+                && path.syntax().parent().and_then(ast::PathExpr::cast).is_some()
+        }
+        None => false,
+    }
+}
+
+fn is_visible_at(pat: &ast::BindPat, offset: TextUnit) -> bool {
+    match pat.syntax().ancestors().find_map(ast::Block::cast) {
+        Some(block) => block.syntax().text_range().contains(offset),
+        // A parameter
+        None => true,
+    }
+}
+
+fn self_usages(body: &FunctionBody) -> Vec<ast::PathExpr> {
+    let mut res = Vec::new();
+    for node in &body.nodes {
+        visit(node, &mut |node| {
+            if let Some(path_expr) = ast::PathExpr::cast(node.clone()) {
+                let is_self = path_expr
+                    .path()
+                    .and_then(|it| it.segment())
+                    .and_then(|it| it.kind())
+                    .map_or(false, |it| it == ast::PathSegmentKind::SelfKw);
+                if is_self {
+                    res.push(path_expr);
+                }
+            }
+            // `self` of nested functions is a different one
+            ast::ModuleItem::cast(node.clone()).is_none()
+        });
+    }
+    res
+}
+
+fn self_param(
+    db: &impl HirDatabase,
+    analyzer: &hir::SourceAnalyzer,
+    fn_def: &ast::FnDef,
+    usages: &[ast::PathExpr],
+    range: TextRange,
+    in_loop: bool,
+) -> Option<ast::SelfParamKind> {
+    let kind = fn_def.param_list()?.self_param()?.kind();
+    let mutated = usages.iter().any(|it| is_mutated(db, analyzer, it));
+    let used_after = in_loop
+        || fn_def
+            .syntax()
+            .descendants()
+            .filter_map(ast::PathSegment::cast)
+            .filter(|it| it.syntax().text_range().start() >= range.end())
+            .any(|it| it.kind() == Some(ast::PathSegmentKind::SelfKw));
+    let res = match kind {
+        _ if mutated => ast::SelfParamKind::MutRef,
+        ast::SelfParamKind::Owned if !used_after => ast::SelfParamKind::Owned,
+        _ => ast::SelfParamKind::Ref,
+    };
+    Some(res)
+}
+
+fn self_param_text(kind: ast::SelfParamKind) -> &'static str {
+    match kind {
+        ast::SelfParamKind::Owned => "self",
+        ast::SelfParamKind::Ref => "&self",
+        ast::SelfParamKind::MutRef => "&mut self",
+    }
+}
+
+/// Whether the value of `path_expr` is modified, that is, whether it is
+/// assigned to, mutably borrowed or used as the receiver of a `&mut self`
+/// method. Fields and indices of the value count as well.
+fn is_mutated(
+    db: &impl HirDatabase,
+    analyzer: &hir::SourceAnalyzer,
+    path_expr: &ast::PathExpr,
+) -> bool {
+    let mut place = path_expr.syntax().clone();
+    while let Some(parent) = place.parent() {
+        let base = if let Some(field) = ast::FieldExpr::cast(parent.clone()) {
+            field.expr()
+        } else if let Some(index) = ast::IndexExpr::cast(parent.clone()) {
+            index.base()
+        } else {
+            None
+        };
+        if base.map(|it| it.syntax().clone()) != Some(place.clone()) {
+            break;
+        }
+        place = parent;
+    }
+
+    let parent = match place.parent() {
+        Some(it) => it,
+        None => return false,
+    };
+    if let Some(bin_expr) = ast::BinExpr::cast(parent.clone()) {
+        let is_lhs = bin_expr.lhs().map(|it| it.syntax().clone()) == Some(place);
+        return is_lhs && bin_expr.op_kind().map_or(false, is_assignment);
+    }
+    if let Some(ref_expr) = ast::RefExpr::cast(parent.clone()) {
+        return ref_expr.is_mut();
+    }
+    if let Some(method_call) = ast::MethodCallExpr::cast(parent) {
+        if method_call.expr().map(|it| it.syntax().clone()) != Some(place) {
+            return false;
+        }
+        return analyzer
+            .resolve_method_call(&method_call)
+            .and_then(|it| it.source(db).ast.param_list()?.self_param())
+            .map_or(false, |it| it.kind() == ast::SelfParamKind::MutRef);
+    }
+    false
+}
+
+fn is_assignment(op: ast::BinOp) -> bool {
+    match op {
+        ast::BinOp::Assignment
+        | ast::BinOp::AddAssign
+        | ast::BinOp::DivAssign
+        | ast::BinOp::MulAssign
+        | ast::BinOp::RemAssign
+        | ast::BinOp::ShrAssign
+        | ast::BinOp::ShlAssign
+        | ast::BinOp::SubAssign
+        | ast::BinOp::BitOrAssign
+        | ast::BinOp::BitAndAssign
+        | ast::BinOp::BitXorAssign => true,
+        _ => false,
+    }
+}
+
+/// A local declared outside of the selection and used inside it.
+struct Param {
+    name: String,
+    ty: String,
+    kind: ParamKind,
+    /// Usages inside the selection.
+    usages: Vec<ast::NameRef>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParamKind {
+    Value,
+    MutValue,
+    SharedRef,
+    MutRef,
+}
+
+impl Param {
+    fn new(
+        db: &impl HirDatabase,
+        analyzer: &hir::SourceAnalyzer,
+        local: &Local,
+        range: TextRange,
+        used_after: bool,
+    ) -> Option<Param> {
+        let name = local.pat.name()?.text().to_string();
+        let ty = analyzer.type_of_pat(db, &local.pat.clone().into())?;
+        let ty_text = ty.display(db).to_string();
+        if ty_text.contains('{') {
+            return None;
+        }
+        let usages = local
+            .usages
+            .iter()
+            .filter(|it| it.range.is_subrange(&range))
+            .filter_map(|it| it.name_ref.clone())
+            .collect::<Vec<_>>();
+        // Mutating through a reference doesn't need a mutable binding
+        let mutated = ty.as_reference().is_none()
+            && usages.iter().any(|name_ref| {
+                name_ref
+                    .syntax()
+                    .ancestors()
+                    .find_map(ast::PathExpr::cast)
+                    .map_or(false, |it| is_mutated(db, analyzer, &it))
+            });
+        let kind = match (used_after, mutated) {
+            (false, false) => ParamKind::Value,
+            (false, true) => ParamKind::MutValue,
+            (true, false) if is_copy(&ty) => ParamKind::Value,
+            (true, false) => ParamKind::SharedRef,
+            (true, true) => ParamKind::MutRef,
+        };
+        Some(Param { name, ty: ty_text, kind, usages })
+    }
+
+    fn arg(&self) -> String {
+        match self.kind {
+            ParamKind::Value | ParamKind::MutValue => self.name.clone(),
+            ParamKind::SharedRef => format!("&{}", self.name),
+            ParamKind::MutRef => format!("&mut {}", self.name),
+        }
+    }
+
+    fn text(&self) -> String {
+        match self.kind {
+            ParamKind::Value => format!("{}: {}", self.name, self.ty),
+            ParamKind::MutValue => format!("mut {}: {}", self.name, self.ty),
+            ParamKind::SharedRef => format!("{}: &{}", self.name, self.ty),
+            ParamKind::MutRef => format!("{}: &mut {}", self.name, self.ty),
+        }
+    }
+
+    fn is_ref(&self) -> bool {
+        self.kind == ParamKind::SharedRef || self.kind == ParamKind::MutRef
+    }
+}
+
+/// Types which are certainly `Copy`, so they can be passed by value even if
+/// they are used after the call.
+fn is_copy(ty: &Ty) -> bool {
+    match ty {
+        Ty::Apply(a_ty) => match a_ty.ctor {
+            TypeCtor::Bool
+            | TypeCtor::Char
+            | TypeCtor::Int(_)
+            | TypeCtor::Float(_)
+            | TypeCtor::Never
+            | TypeCtor::RawPtr(_)
+            | TypeCtor::FnPtr { .. }
+            | TypeCtor::FnDef(_)
+            | TypeCtor::Ref(Mutability::Shared) => true,
+            TypeCtor::Tuple { .. } => a_ty.parameters.iter().all(is_copy),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// A local declared inside of the selection and used after it.
+struct Output {
+    name: String,
+    ty: String,
+    is_mut: bool,
+}
+
+impl Output {
+    fn new(
+        db: &impl HirDatabase,
+        analyzer: &hir::SourceAnalyzer,
+        pat: &ast::BindPat,
+    ) -> Option<Output> {
+        let name = pat.name()?.text().to_string();
+        let ty = analyzer.type_of_pat(db, &pat.clone().into())?.display(db).to_string();
+        if ty.contains('{') {
+            return None;
+        }
+        Some(Output { name, ty, is_mut: pat.is_mutable() })
+    }
+}
+
+/// Control flow which leaves the selection early.
+#[derive(Default)]
+struct ControlFlow {
+    returns: Vec<ast::ReturnExpr>,
+    /// `break` and `continue` of loops around the selection.
+    loop_jumps: Vec<SyntaxNode>,
+    has_try: bool,
+}
+
+impl ControlFlow {
+    fn new(body: &FunctionBody) -> Option<ControlFlow> {
+        let mut res = ControlFlow::default();
+        for node in &body.nodes {
+            res.collect(node, false);
+        }
+        let has_break = res.loop_jumps.iter().any(|it| it.kind() == BREAK_EXPR);
+        let has_continue = res.loop_jumps.iter().any(|it| it.kind() == CONTINUE_EXPR);
+        let has_labels_or_values = res.loop_jumps.iter().any(|jump| {
+            jump.children_with_tokens().any(|it| it.kind() == LIFETIME)
+                || ast::BreakExpr::cast(jump.clone()).and_then(|it| it.expr()).is_some()
+        });
+        let kinds = [!res.returns.is_empty(), has_break, has_continue, res.has_try];
+        if kinds.iter().filter(|&&it| it).count() > 1 || has_labels_or_values {
+            tested_by!(extract_function_complex_control_flow);
+            return None;
+        }
+        Some(res)
+    }
+
+    fn collect(&mut self, node: &SyntaxNode, in_loop: bool) {
+        match node.kind() {
+            LAMBDA_EXPR => return,
+            RETURN_EXPR => self.returns.extend(ast::ReturnExpr::cast(node.clone())),
+            TRY_EXPR => self.has_try = true,
+            BREAK_EXPR | CONTINUE_EXPR if !in_loop => self.loop_jumps.push(node.clone()),
+            _ if ast::ModuleItem::cast(node.clone()).is_some() => return,
+            _ => (),
+        }
+        let in_loop = in_loop
+            || match node.kind() {
+                LOOP_EXPR | WHILE_EXPR | FOR_EXPR => true,
+                _ => false,
+            };
+        for child in node.children() {
+            self.collect(&child, in_loop);
+        }
+    }
+}
+
+/// How the new function reports the value and the control flow of the
+/// selection back to the call site.
+enum RetKind {
+    /// The value of the selection, if any, is returned as is.
+    Value,
+    /// `?` is kept in the new function, which returns the return type of the
+    /// original function with the value type in place of the success type,
+    /// that is `prefix` + value type + `suffix`.
+    Try { prefix: String, suffix: String, wrapper: &'static str },
+    /// `return` of a function returning `()`, or `break` or `continue`: the
+    /// new function returns whether to jump.
+    Jump { jump: &'static str },
+    /// `return value`: the new function returns `Some(value)`.
+    Return { ret_ty: String },
+}
+
+impl RetKind {
+    fn new(fn_def: &ast::FnDef, flow: &ControlFlow, has_value: bool) -> Option<RetKind> {
+        let fn_ret_ty = fn_def.ret_type().and_then(|it| it.type_ref());
+        if flow.has_try {
+            let path_ty = match fn_ret_ty? {
+                ast::TypeRef::PathType(it) => it,
+                _ => return None,
+            };
+            let segment = path_ty.path()?.segment()?;
+            let wrapper = if segment.name_ref()?.text() == "Option" { "Some" } else { "Ok" };
+            let value_ty = segment.type_arg_list()?.type_args().next()?.syntax().text_range();
+            let text = path_ty.syntax().text().to_string();
+            let start = path_ty.syntax().text_range().start();
+            let prefix = text[..(value_ty.start() - start).to_usize()].to_string();
+            let suffix = text[(value_ty.end() - start).to_usize()..].to_string();
+            return Some(RetKind::Try { prefix, suffix, wrapper });
+        }
+        let jump = if !flow.returns.is_empty() {
+            match fn_ret_ty {
+                Some(ret_ty) => {
+                    if has_value {
+                        return None;
+                    }
+                    return Some(RetKind::Return { ret_ty: ret_ty.syntax().text().to_string() });
+                }
+                None => "return",
+            }
+        } else {
+            match flow.loop_jumps.first().map(|it| it.kind()) {
+                Some(BREAK_EXPR) => "break",
+                Some(_) => "continue",
+                None => return Some(RetKind::Value),
+            }
+        };
+        if has_value {
+            return None;
+        }
+        Some(RetKind::Jump { jump })
+    }
+
+    fn ret_ty(&self, value: Option<&String>) -> Option<String> {
+        match self {
+            RetKind::Value => value.cloned(),
+            RetKind::Try { prefix, suffix, .. } => {
+                Some(format!("{}{}{}", prefix, value.map_or("()", |it| it.as_str()), suffix))
+            }
+            RetKind::Jump { .. } => Some("bool".to_string()),
+            RetKind::Return { ret_ty } => Some(format!("Option<{}>", ret_ty)),
+        }
+    }
+}
+
+/// Returns the call site text and the offset of the function name in it.
+fn call_site(
+    call_expr: &str,
+    ret_kind: &RetKind,
+    body: &FunctionBody,
+    outputs: &[Output],
+    indent: &str,
+) -> (String, TextUnit) {
+    let (prefix, suffix) = match ret_kind {
+        RetKind::Jump { jump } => {
+            ("if ".to_string(), format!(" {{\n{}    {};\n{}}}", indent, jump, indent))
+        }
+        RetKind::Return { .. } => (
+            "if let Some(value) = ".to_string(),
+            format!(" {{\n{}    return value;\n{}}}", indent, indent),
+        ),
+        RetKind::Value | RetKind::Try { .. } => {
+            let try_op = if let RetKind::Try { .. } = ret_kind { "?" } else { "" };
+            let prefix = match outputs {
+                _ if body.tail_expr.is_some() => String::new(),
+                [] => String::new(),
+                [output] => format!("let {} = ", output_pat(output)),
+                outputs => {
+                    let pats = outputs.iter().map(output_pat).collect::<Vec<_>>();
+                    format!("let ({}) = ", pats.join(", "))
+                }
+            };
+            let semi = if body.tail_expr.is_some() { "" } else { ";" };
+            (prefix, format!("{}{}", try_op, semi))
+        }
+    };
+    let receiver = &call_expr[..call_expr.find(FN_NAME).unwrap_or(0)];
+    let offset = TextUnit::of_str(&prefix) + TextUnit::of_str(receiver);
+    (format!("{}{}{}", prefix, call_expr, suffix), offset)
+}
+
+fn output_pat(output: &Output) -> String {
+    if output.is_mut {
+        format!("mut {}", output.name)
+    } else {
+        output.name.clone()
+    }
+}
+
+/// The selected code, with the changes needed to move it into a function.
+fn body_text(
+    fn_def: &ast::FnDef,
+    body: &FunctionBody,
+    params: &[Param],
+    flow: &ControlFlow,
+    ret_kind: &RetKind,
+    outputs: &[Output],
+) -> String {
+    let mut edits: Vec<(TextRange, String)> = Vec::new();
+
+    // Dereference parameters which are passed by reference, unless they are
+    // auto-dereferenced anyway
+    for name_ref in params.iter().filter(|it| it.is_ref()).flat_map(|it| it.usages.iter()) {
+        let name = name_ref.text().to_string();
+        if let Some(field) = name_ref.syntax().parent().and_then(ast::RecordField::cast) {
+            edits.push((field.syntax().text_range(), format!("{}: *{}", name, name)));
+            continue;
+        }
+        let path_expr = match name_ref.syntax().ancestors().find_map(ast::PathExpr::cast) {
+            Some(it) => it,
+            None => continue,
+        };
+        let is_auto_deref = path_expr.syntax().parent().map_or(false, |parent| {
+            let base = if let Some(it) = ast::MethodCallExpr::cast(parent.clone()) {
+                it.expr()
+            } else if let Some(it) = ast::FieldExpr::cast(parent.clone()) {
+                it.expr()
+            } else if let Some(it) = ast::IndexExpr::cast(parent) {
+                it.base()
+            } else {
+                None
+            };
+            base.map(|it| it.syntax().clone()) == Some(path_expr.syntax().clone())
+        });
+        if is_auto_deref {
+            continue;
+        }
+        // `*x?` is `*(x?)`
+        let is_postfix_operand = path_expr.syntax().parent().map_or(false, |parent| {
+            ast::TryExpr::cast(parent.clone()).is_some()
+                || ast::CallExpr::cast(parent)
+                    .and_then(|it| it.expr())
+                    .map(|it| it.syntax().clone())
+                    == Some(path_expr.syntax().clone())
+        });
+        let deref = if is_postfix_operand { format!("(*{})", name) } else { format!("*{}", name) };
+        edits.push((path_expr.syntax().text_range(), deref));
+    }
+
+    match ret_kind {
+        RetKind::Jump { .. } => {
+            let jumps = flow
+                .returns
+                .iter()
+                .map(|it| it.syntax().clone())
+                .chain(flow.loop_jumps.iter().cloned());
+            for jump in jumps {
+                edits.push((jump.text_range(), "return true".to_string()));
+            }
+        }
+        RetKind::Return { .. } => {
+            for ret in &flow.returns {
+                match ret.expr() {
+                    Some(expr) => {
+                        let range = expr.syntax().text_range();
+                        edits
+                            .push((TextRange::offset_len(range.start(), 0.into()), "Some(".into()));
+                        edits.push((TextRange::offset_len(range.end(), 0.into()), ")".into()));
+                    }
+                    None => edits.push((ret.syntax().text_range(), "return None".into())),
+                }
+            }
+        }
+        RetKind::Value | RetKind::Try { .. } => (),
+    }
+    if let (RetKind::Try { wrapper, .. }, Some(tail_expr)) = (ret_kind, &body.tail_expr) {
+        let range = tail_expr.syntax().text_range();
+        edits.push((TextRange::offset_len(range.start(), 0.into()), format!("{}(", wrapper)));
+        edits.push((TextRange::offset_len(range.end(), 0.into()), ")".into()));
+    }
+
+    edits.sort_by_key(|(range, _)| (range.start(), range.end()));
+    let fn_text = fn_def.syntax().text().to_string();
+    let fn_start = fn_def.syntax().text_range().start();
+    let slice = |start: TextUnit, end: TextUnit| {
+        &fn_text[(start - fn_start).to_usize()..(end - fn_start).to_usize()]
+    };
+    let mut res = String::new();
+    let mut last = body.range.start();
+    for (range, text) in edits {
+        res.push_str(slice(last, range.start()));
+        res.push_str(&text);
+        last = range.end();
+    }
+    res.push_str(slice(last, body.range.end()));
+
+    let value = match outputs {
+        [] => None,
+        [output] => Some(output.name.clone()),
+        outputs => {
+            let names = outputs.iter().map(|it| it.name.as_str()).collect::<Vec<_>>();
+            Some(format!("({})", names.join(", ")))
+        }
+    };
+    let tail = match ret_kind {
+        _ if body.tail_expr.is_some() => None,
+        RetKind::Value => value,
+        RetKind::Try { wrapper, .. } => {
+            Some(format!("{}({})", wrapper, value.unwrap_or_else(|| "()".to_string())))
+        }
+        RetKind::Jump { .. } => Some("false".to_string()),
+        RetKind::Return { .. } => Some("None".to_string()),
+    };
+    if let Some(tail) = tail {
+        res.push_str("\n");
+        res.push_str(&tail);
+    }
+    res
+}
+
+/// Returns the generic parameters and the where clause of the original
+/// function if the new function needs them.
+fn generics(
+    fn_def: &ast::FnDef,
+    params: &[Param],
+    value: &Option<String>,
+    ret_kind: &RetKind,
+) -> (String, String) {
+    let type_params = match fn_def.type_param_list() {
+        Some(it) => it,
+        None => return (String::new(), String::new()),
+    };
+    let names = type_params
+        .type_params()
+        .filter_map(|it| it.name())
+        .map(|it| it.text().to_string())
+        .collect::<Vec<_>>();
+    let mentions_type_param = |ty: &str| {
+        ty.split(|c: char| !c.is_alphanumeric() && c != '_')
+            .any(|word| names.iter().any(|it| it == word))
+    };
+    let is_generic = params.iter().any(|it| mentions_type_param(&it.ty))
+        || ret_kind.ret_ty(value.as_ref()).map_or(false, |it| mentions_type_param(&it));
+    if !is_generic {
+        return (String::new(), String::new());
+    }
+    let where_clause = fn_def.where_clause().map(|it| format!(" {}", it.syntax().text()));
+    (type_params.syntax().text().to_string(), where_clause.unwrap_or_default())
+}
+
+/// Calls `f` for `node` and its descendants, skipping the descendants of
+/// nodes for which `f` returns `false`.
+fn visit(node: &SyntaxNode, f: &mut impl FnMut(&SyntaxNode) -> bool) {
+    if f(node) {
+        for child in node.children() {
+            visit(&child, f);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::helpers::{check_assist_range, check_assist_range_not_applicable};
+    use test_utils::covers;
+
+    use super::*;
+
+    #[test]
+    fn extract_statements_with_params_and_output() {
+        check_assist_range(
+            extract_function,
+            r#"
+fn main() {
+    let n = 1;
+    <|>let m = n + 1;
+    let k = m * 2;<|>
+    println!("{}", k);
+}"#,
+            r#"
+fn main() {
+    let n = 1;
+    let k = <|>fun_name(n);
+    println!("{}", k);
+}
+
+fn fun_name(n: i32) -> i32 {
+    let m = n + 1;
+    let k = m * 2;
+    k
+}"#,
+        );
+    }
+
+    #[test]
+    fn extract_expression() {
+        check_assist_range(
+            extract_function,
+            r#"
+fn foo(a: u32, b: u32) -> u32 {
+    <|>a * 2 + b<|>
+}"#,
+            r#"
+fn foo(a: u32, b: u32) -> u32 {
+    <|>fun_name(a, b)
+}
+
+fn fun_name(a: u32, b: u32) -> u32 {
+    a * 2 + b
+}"#,
+        );
+    }
+
+    #[test]
+    fn extract_with_multiple_outputs() {
+        check_assist_range(
+            extract_function,
+            r#"
+fn main() {
+    <|>let a = 1u8;
+    let mut b = 2u16;<|>
+    b += a as u16;
+}"#,
+            r#"
+fn main() {
+    let (a, mut b) = <|>fun_name();
+    b += a as u16;
+}
+
+fn fun_name() -> (u8, u16) {
+    let a = 1u8;
+    let mut b = 2u16;
+    (a, b)
+}"#,
+        );
+    }
+
+    #[test]
+    fn extract_passes_by_reference_if_used_after() {
+        check_assist_range(
+            extract_function,
+            r#"
+struct S { x: u32 }
+fn main() {
+    let mut s = S { x: 0 };
+    let v = S { x: 1 };
+    <|>s.x += v.x;
+    s = S { x: s.x };<|>
+    let _ = (s, v);
+}"#,
+            r#"
+struct S { x: u32 }
+fn main() {
+    let mut s = S { x: 0 };
+    let v = S { x: 1 };
+    <|>fun_name(&mut s, &v);
+    let _ = (s, v);
+}
+
+fn fun_name(s: &mut S, v: &S) {
+    s.x += v.x;
+    *s = S { x: s.x };
+}"#,
+        );
+    }
+
+    #[test]
+    fn extract_passes_by_reference_if_used_in_next_iteration() {
+        check_assist_range(
+            extract_function,
+            r#"
+fn main() {
+    let mut i = 0u32;
+    loop {
+        if i > 10 { break }
+        <|>i += 1;<|>
+    }
+}"#,
+            r#"
+fn main() {
+    let mut i = 0u32;
+    loop {
+        if i > 10 { break }
+        <|>fun_name(&mut i);
+    }
+}
+
+fn fun_name(i: &mut u32) {
+    *i += 1;
+}"#,
+        );
+    }
+
+    #[test]
+    fn extract_moves_values_not_used_after() {
+        check_assist_range(
+            extract_function,
+            r#"
+struct S;
+impl S {
+    fn consume(self) {}
+}
+fn main() {
+    let s = S;
+    <|>s.consume();<|>
+}"#,
+            r#"
+struct S;
+impl S {
+    fn consume(self) {}
+}
+fn main() {
+    let s = S;
+    <|>fun_name(s);
+}
+
+fn fun_name(s: S) {
+    s.consume();
+}"#,
+        );
+    }
+
+    #[test]
+    fn extract_method_in_impl() {
+        check_assist_range(
+            extract_function,
+            r#"
+struct Counter { n: u32 }
+impl Counter {
+    fn bump(&mut self, by: u32) -> u32 {
+        <|>self.n += by;<|>
+        self.n
+    }
+}"#,
+            r#"
+struct Counter { n: u32 }
+impl Counter {
+    fn bump(&mut self, by: u32) -> u32 {
+        self.<|>fun_name(by);
+        self.n
+    }
+
+    fn fun_name(&mut self, by: u32) {
+        self.n += by;
+    }
+}"#,
+        );
+    }
+
+    #[test]
+    fn extract_free_function_from_impl_without_self() {
+        check_assist_range(
+            extract_function,
+            r#"
+struct S;
+impl S {
+    fn foo(x: u32) -> u32 {
+        <|>x * x<|>
+    }
+}"#,
+            r#"
+struct S;
+impl S {
+    fn foo(x: u32) -> u32 {
+        <|>fun_name(x)
+    }
+}
+
+fn fun_name(x: u32) -> u32 {
+    x * x
+}"#,
+        );
+    }
+
+    #[test]
+    fn extract_with_return() {
+        check_assist_range(
+            extract_function,
+            r#"
+fn foo(x: i32) -> i32 {
+    <|>if x < 0 {
+        return 0;
+    }<|>
+    x
+}"#,
+            r#"
+fn foo(x: i32) -> i32 {
+    if let Some(value) = <|>fun_name(x) {
+        return value;
+    }
+    x
+}
+
+fn fun_name(x: i32) -> Option<i32> {
+    if x < 0 {
+        return Some(0);
+    }
+    None
+}"#,
+        );
+    }
+
+    #[test]
+    fn extract_with_break() {
+        check_assist_range(
+            extract_function,
+            r#"
+fn foo() {
+    let mut i = 0;
+    loop {
+        <|>if i > 10 {
+            break;
+        }<|>
+        i += 1;
+    }
+}"#,
+            r#"
+fn foo() {
+    let mut i = 0;
+    loop {
+        if <|>fun_name(i) {
+            break;
+        }
+        i += 1;
+    }
+}
+
+fn fun_name(i: i32) -> bool {
+    if i > 10 {
+        return true;
+    }
+    false
+}"#,
+        );
+    }
+
+    #[test]
+    fn extract_with_try() {
+        check_assist_range(
+            extract_function,
+            r#"
+enum Option<T> { Some(T), None }
+fn foo(x: Option<u32>, y: u32) -> Option<u32> {
+    <|>x?;<|>
+    Option::Some(y)
+}"#,
+            r#"
+enum Option<T> { Some(T), None }
+fn foo(x: Option<u32>, y: u32) -> Option<u32> {
+    <|>fun_name(x)?;
+    Option::Some(y)
+}
+
+fn fun_name(x: Option<u32>) -> Option<()> {
+    x?;
+    Some(())
+}"#,
+        );
+    }
+
+    #[test]
+    fn extract_with_generics() {
+        check_assist_range(
+            extract_function,
+            r#"
+fn foo<T: Copy>(t: T) -> T where T: Default {
+    <|>let u = t;<|>
+    u
+}"#,
+            r#"
+fn foo<T: Copy>(t: T) -> T where T: Default {
+    let u = <|>fun_name(t);
+    u
+}
+
+fn fun_name<T: Copy>(t: T) -> T where T: Default {
+    let u = t;
+    u
+}"#,
+        );
+    }
+
+    #[test]
+    fn extract_not_applicable_for_partial_statements() {
+        check_assist_range_not_applicable(
+            extract_function,
+            r#"
+fn foo() {
+    let a = <|>1;
+    let b = 2;<|>
+}"#,
+        );
+    }
+
+    #[test]
+    fn extract_not_applicable_for_mixed_control_flow() {
+        covers!(extract_function_complex_control_flow);
+        check_assist_range_not_applicable(
+            extract_function,
+            r#"
+fn foo(x: i32) -> i32 {
+    loop {
+        <|>if x > 0 {
+            return 1;
+        }
+        if x < 0 {
+            break;
+        }<|>
+    }
+    0
+}"#,
+        );
+    }
+}
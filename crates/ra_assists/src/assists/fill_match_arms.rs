@@ -3,30 +3,69 @@
 use std::iter;
 
 use hir::{db::HirDatabase, Adt, HasSource};
-use ra_syntax::ast::{self, make, AstNode, NameOwner};
+use ra_fmt::leading_indent;
+use ra_syntax::{
+    ast::{self, make, AstNode, NameOwner},
+    T,
+};
 
 use crate::{Assist, AssistCtx, AssistId};
 
 pub(crate) fn fill_match_arms(mut ctx: AssistCtx<impl HirDatabase>) -> Option<Assist> {
     let match_expr = ctx.node_at_offset::<ast::MatchExpr>()?;
     let match_arm_list = match_expr.match_arm_list()?;
+    let expr = match_expr.expr()?;
+    let file_id = ctx.frange.file_id;
+    let analyzer = hir::SourceAnalyzer::new(ctx.db, file_id, expr.syntax(), None);
 
-    // We already have some match arms, so we don't provide any assists.
-    // Unless if there is only one trivial match arm possibly created
-    // by match postfix complete. Trivial match arm is the catch all arm.
+    // If there are no arms, or only a trivial catch all arm (possibly created
+    // by match postfix complete), we replace them with all the variants.
+    // Otherwise, we only add arms for the variants which are not covered yet.
     let mut existing_arms = match_arm_list.arms();
-    if let Some(arm) = existing_arms.next() {
-        if !is_trivial(&arm) || existing_arms.next().is_some() {
-            return None;
-        }
+    let last_arm = match (existing_arms.next(), existing_arms.next()) {
+        (None, _) => None,
+        (Some(ref arm), None) if is_trivial(arm) => None,
+        _ => Some(match_arm_list.arms().last()?),
     };
 
-    let expr = match_expr.expr()?;
-    let enum_def = {
-        let file_id = ctx.frange.file_id;
-        let analyzer = hir::SourceAnalyzer::new(ctx.db, file_id, expr.syntax(), None);
-        resolve_enum_def(ctx.db, &analyzer, &expr)?
-    };
+    if let Some(last_arm) = last_arm {
+        let mut variants = Vec::new();
+        for pat in analyzer.missing_match_arms(ctx.db, &match_expr)? {
+            let variant = pat.enum_variant()?;
+            if !variants.contains(&variant) {
+                variants.push(variant);
+            }
+        }
+        let enum_variants = variants.first()?.parent_enum(ctx.db).variants(ctx.db);
+        variants.sort_by_key(|variant| enum_variants.iter().position(|it| it == variant));
+        let db = ctx.db;
+        ctx.add_action(AssistId("fill_match_arms"), "fill match arms", |edit| {
+            let indent =
+                leading_indent(last_arm.syntax()).map(|it| it.to_string()).unwrap_or_default();
+            let mut buf = String::new();
+            if needs_comma(&last_arm) {
+                buf.push(',');
+            }
+            let arms = variants
+                .into_iter()
+                .filter_map(|variant| build_pat(variant.source(db).ast))
+                .map(|pat| make::match_arm(iter::once(pat), make::expr_unit()));
+            for arm in arms {
+                buf.push_str(&format!("\n{}{},", indent, arm.syntax()));
+            }
+            let offset = match last_arm.syntax().next_sibling_or_token() {
+                Some(ref next) if next.kind() == T![,] => next.text_range().end(),
+                _ => last_arm.syntax().text_range().end(),
+            };
+
+            edit.target(match_expr.syntax().text_range());
+            edit.set_cursor(expr.syntax().text_range().start());
+            edit.insert(offset, buf);
+        });
+        return ctx.build();
+    }
+
+    let enum_def = resolve_enum_def(ctx.db, &analyzer, &expr)?;
     let variant_list = enum_def.variant_list()?;
 
     ctx.add_action(AssistId("fill_match_arms"), "fill match arms", |edit| {
@@ -44,6 +83,18 @@ pub(crate) fn fill_match_arms(mut ctx: AssistCtx<impl HirDatabase>) -> Option<As
     ctx.build()
 }
 
+/// Arms whose body is not a block need a comma before the next arm.
+fn needs_comma(arm: &ast::MatchArm) -> bool {
+    let has_comma = match arm.syntax().next_sibling_or_token() {
+        Some(next) => next.kind() == T![,],
+        None => false,
+    };
+    match arm.expr() {
+        Some(ast::Expr::BlockExpr(_)) => false,
+        _ => !has_comma,
+    }
+}
+
 fn is_trivial(arm: &ast::MatchArm) -> bool {
     arm.pats().any(|pat| match pat {
         ast::Pat::PlaceholderPat(..) => true,
@@ -88,7 +139,7 @@ fn build_pat(var: ast::EnumVariant) -> Option<ast::Pat> {
 
 #[cfg(test)]
 mod tests {
-    use crate::helpers::{check_assist, check_assist_target};
+    use crate::helpers::{check_assist, check_assist_not_applicable, check_assist_target};
 
     use super::fill_match_arms;
 
@@ -226,4 +277,62 @@ mod tests {
             "#,
         );
     }
+
+    #[test]
+    fn fill_match_arms_adds_missing_arms() {
+        check_assist(
+            fill_match_arms,
+            r#"
+            enum A {
+                As,
+                Bs,
+                Cs(u32),
+                Ds{ x: usize },
+            }
+
+            fn main() {
+                match A::As<|> {
+                    A::Bs => {}
+                    A::Cs(0) => (),
+                    A::Cs(_) => ()
+                }
+            }
+            "#,
+            r#"
+            enum A {
+                As,
+                Bs,
+                Cs(u32),
+                Ds{ x: usize },
+            }
+
+            fn main() {
+                match <|>A::As {
+                    A::Bs => {}
+                    A::Cs(0) => (),
+                    A::Cs(_) => (),
+                    A::As => (),
+                    A::Ds{ x } => (),
+                }
+            }
+            "#,
+        );
+    }
+
+    #[test]
+    fn fill_match_arms_all_covered() {
+        check_assist_not_applicable(
+            fill_match_arms,
+            r#"
+            enum E { X, Y }
+
+            fn foo(e: &E) {
+                match e<|> {
+                    E::X => {}
+                    E::Y => {}
+                }
+            }
+            "#,
+        );
+    }
 }
@@ -0,0 +1,672 @@
+//! Assists generating the boilerplate of a struct from its fields: a `new`
+//! function, getters, setters and a `Default` impl.
+
+use hir::{db::HirDatabase, FromSource, HasSource};
+use ra_db::FileId;
+use ra_fmt::{leading_indent, reindent};
+use ra_syntax::{
+    ast::{self, make, AstNode, AttrsOwner, NameOwner, TypeAscriptionOwner, VisibilityOwner},
+    TextUnit,
+};
+
+use crate::{assist_ctx::AssistBuilder, Assist, AssistCtx, AssistId};
+
+/// rustfmt's default maximum width, less the indent of a method.
+const MAX_SIGNATURE_WIDTH: usize = 96;
+/// rustfmt's default maximum width of the fields of a struct literal on one
+/// line.
+const MAX_STRUCT_LIT_WIDTH: usize = 18;
+
+/// Adds a `new` function, which takes a value for each field of the struct.
+pub(crate) fn add_new(mut ctx: AssistCtx<impl HirDatabase>) -> Option<Assist> {
+    let strukt = ctx.node_at_offset::<ast::StructDef>()?;
+    let fields = match strukt.kind() {
+        ast::StructKind::Named(it) => it.fields().collect::<Vec<_>>(),
+        _ => return None,
+    };
+    let impls = StructImpls::new(ctx.db, ctx.frange.file_id, &strukt)?;
+    if impls.find_method(ctx.db, "new").is_some() {
+        return None;
+    }
+    let fields = fields
+        .iter()
+        .map(|field| {
+            let name = field.name()?.text().to_string();
+            let ty = field.ascribed_type()?.syntax().text().to_string();
+            Some((name, ty))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let method = new_fn(&visibility(&strukt), &fields);
+
+    ctx.add_action(AssistId("add_new"), "add `new`", |edit| {
+        edit.target(strukt.syntax().text_range());
+        impls.insert_methods(edit, &strukt, &[method]);
+    });
+    ctx.build()
+}
+
+/// Adds a getter for each of the selected fields of a struct.
+pub(crate) fn add_getters(mut ctx: AssistCtx<impl HirDatabase>) -> Option<Assist> {
+    let (strukt, fields) = selected_fields(&ctx)?;
+    let impls = StructImpls::new(ctx.db, ctx.frange.file_id, &strukt)?;
+    let vis = visibility(&strukt);
+    let mut names = Vec::new();
+    let mut methods = Vec::new();
+    for field in fields.iter() {
+        let name = field.name()?.text().to_string();
+        if impls.find_method(ctx.db, &name).is_some() {
+            continue;
+        }
+        let ty = getter_type(ctx.db, impls.strukt, &name, &field.ascribed_type()?)?;
+        methods.push(format!("{}fn {}(&self) -> {} {{\n    &self.{}\n}}", vis, name, ty, name));
+        names.push(name);
+    }
+    let label = match names.as_slice() {
+        [] => return None,
+        [name] => format!("add getter for `{}`", name),
+        _ => "add getters".to_string(),
+    };
+
+    ctx.add_action(AssistId("add_getters"), label, |edit| {
+        edit.target(strukt.syntax().text_range());
+        impls.insert_methods(edit, &strukt, &methods);
+    });
+    ctx.build()
+}
+
+/// Adds a setter for each of the selected fields of a struct.
+pub(crate) fn add_setters(mut ctx: AssistCtx<impl HirDatabase>) -> Option<Assist> {
+    let (strukt, fields) = selected_fields(&ctx)?;
+    let impls = StructImpls::new(ctx.db, ctx.frange.file_id, &strukt)?;
+    let vis = visibility(&strukt);
+    let mut names = Vec::new();
+    let mut methods = Vec::new();
+    for field in fields.iter() {
+        let name = field.name()?.text().to_string();
+        let setter = format!("set_{}", name);
+        if impls.find_method(ctx.db, &setter).is_some() {
+            continue;
+        }
+        let ty = field.ascribed_type()?.syntax().text().to_string();
+        methods.push(format!(
+            "{}fn {}(&mut self, {}: {}) {{\n    self.{} = {};\n}}",
+            vis, setter, name, ty, name, name
+        ));
+        names.push(name);
+    }
+    let label = match names.as_slice() {
+        [] => return None,
+        [name] => format!("add setter for `{}`", name),
+        _ => "add setters".to_string(),
+    };
+
+    ctx.add_action(AssistId("add_setters"), label, |edit| {
+        edit.target(strukt.syntax().text_range());
+        impls.insert_methods(edit, &strukt, &methods);
+    });
+    ctx.build()
+}
+
+/// Adds an impl of `Default` for the struct, which calls `new` if it takes no
+/// arguments, and uses the defaults of the fields otherwise.
+pub(crate) fn add_default_impl(mut ctx: AssistCtx<impl HirDatabase>) -> Option<Assist> {
+    let strukt = ctx.node_at_offset::<ast::StructDef>()?;
+    let impls = StructImpls::new(ctx.db, ctx.frange.file_id, &strukt)?;
+    if derives_default(&strukt) || implements_default(ctx.db, impls.strukt) {
+        return None;
+    }
+    let has_default_new = impls.find_method(ctx.db, "new").map_or(false, |new| {
+        new.source(ctx.db)
+            .ast
+            .param_list()
+            .map_or(true, |it| it.self_param().is_none() && it.params().next().is_none())
+    });
+    let body = if has_default_new {
+        "Self::new()".to_string()
+    } else {
+        match strukt.kind() {
+            ast::StructKind::Named(fields) => {
+                let fields = fields
+                    .fields()
+                    .map(|field| {
+                        Some(format!("    {}: Default::default(),\n", field.name()?.text()))
+                    })
+                    .collect::<Option<String>>()?;
+                if fields.is_empty() {
+                    "Self {}".to_string()
+                } else {
+                    format!("Self {{\n{}}}", fields)
+                }
+            }
+            ast::StructKind::Tuple(fields) => {
+                let fields = fields.fields().map(|_| "Default::default()").collect::<Vec<_>>();
+                format!("Self({})", fields.join(", "))
+            }
+            ast::StructKind::Unit => "Self".to_string(),
+        }
+    };
+    let header = make::impl_header(&strukt.clone().into(), Some("Default"))?;
+
+    ctx.add_action(AssistId("add_default_impl"), "add `Default` impl", |edit| {
+        edit.target(strukt.syntax().text_range());
+        let mut buf = format!("\n\n{} {{\n    ", header);
+        let cursor = TextUnit::of_str(&buf);
+        buf.push_str("fn default() -> Self {\n        ");
+        buf.push_str(&reindent(&body, "        "));
+        buf.push_str("\n    }\n}");
+        let offset = strukt.syntax().text_range().end();
+        edit.insert(offset, buf);
+        edit.set_cursor(offset + cursor);
+    });
+    ctx.build()
+}
+
+/// The inherent impls of a struct.
+struct StructImpls {
+    strukt: hir::Struct,
+    /// The first inherent impl of the struct in the current file, where new
+    /// methods are added.
+    local: Option<ast::ImplBlock>,
+    impls: Vec<hir::ImplBlock>,
+}
+
+impl StructImpls {
+    fn new(db: &impl HirDatabase, file_id: FileId, strukt: &ast::StructDef) -> Option<StructImpls> {
+        let src = hir::Source { file_id: file_id.into(), ast: strukt.clone() };
+        let hir_struct = hir::Struct::from_source(db, src)?;
+        let krate = hir_struct.krate(db)?;
+        let impls =
+            db.impls_in_crate(krate).lookup_impl_blocks(&hir_struct.ty(db)).collect::<Vec<_>>();
+        let local = impls
+            .iter()
+            .map(|it| it.source(db))
+            .find(|it| it.file_id == hir::HirFileId::from(file_id))
+            .map(|it| it.ast);
+        Some(StructImpls { strukt: hir_struct, local, impls })
+    }
+
+    fn find_method(&self, db: &impl HirDatabase, name: &str) -> Option<hir::Function> {
+        self.impls.iter().flat_map(|it| it.items(db)).find_map(|item| match item {
+            hir::AssocItem::Function(it) if it.name(db).to_string() == name => Some(it),
+            _ => None,
+        })
+    }
+
+    /// Adds `methods` at the end of the local impl of the struct, or to a new
+    /// impl after the struct.
+    fn insert_methods(
+        &self,
+        edit: &mut AssistBuilder,
+        strukt: &ast::StructDef,
+        methods: &[String],
+    ) {
+        let (impl_block, item_list) =
+            match self.local.as_ref().and_then(|it| Some((it, it.item_list()?))) {
+                Some(it) => it,
+                None => {
+                    let header = match make::impl_header(&strukt.clone().into(), None) {
+                        Some(it) => it,
+                        None => return,
+                    };
+                    let mut buf = format!("\n\n{} {{\n    ", header);
+                    let cursor = TextUnit::of_str(&buf);
+                    let methods = methods.iter().map(|it| reindent(it, "    ")).collect::<Vec<_>>();
+                    buf.push_str(&methods.join("\n\n    "));
+                    buf.push_str("\n}");
+                    let offset = strukt.syntax().text_range().end();
+                    edit.insert(offset, buf);
+                    edit.set_cursor(offset + cursor);
+                    return;
+                }
+            };
+
+        let impl_indent = leading_indent(impl_block.syntax()).unwrap_or_default();
+        let indent = format!("{}    ", impl_indent);
+        let (offset, first_separator) = match item_list.impl_items().last() {
+            Some(it) => (it.syntax().text_range().end(), "\n\n"),
+            None => match item_list.syntax().first_token() {
+                Some(l_curly) => (l_curly.text_range().end(), "\n"),
+                None => return,
+            },
+        };
+        let mut buf = String::new();
+        let mut cursor = None;
+        for (idx, method) in methods.iter().enumerate() {
+            buf.push_str(if idx == 0 { first_separator } else { "\n\n" });
+            buf.push_str(&indent);
+            cursor.get_or_insert(TextUnit::of_str(&buf));
+            buf.push_str(&reindent(method, &indent));
+        }
+        if !item_list.syntax().text().contains_char('\n') {
+            buf.push('\n');
+            buf.push_str(&impl_indent);
+        }
+        edit.insert(offset, buf);
+        if let Some(cursor) = cursor {
+            edit.set_cursor(offset + cursor);
+        }
+    }
+}
+
+/// The struct at the cursor, and its fields which are selected, or the one
+/// under the cursor if nothing is selected.
+fn selected_fields(
+    ctx: &AssistCtx<impl HirDatabase>,
+) -> Option<(ast::StructDef, Vec<ast::RecordFieldDef>)> {
+    let strukt = ctx.node_at_offset::<ast::StructDef>()?;
+    let field_list = match strukt.kind() {
+        ast::StructKind::Named(it) => it,
+        _ => return None,
+    };
+    let range = ctx.frange.range;
+    let fields = field_list
+        .fields()
+        .filter(|field| {
+            let field_range = field.syntax().text_range();
+            if range.is_empty() {
+                field_range.contains_inclusive(range.start())
+            } else {
+                field_range.intersection(&range).map_or(false, |it| !it.is_empty())
+            }
+        })
+        .collect::<Vec<_>>();
+    if fields.is_empty() {
+        return None;
+    }
+    Some((strukt, fields))
+}
+
+fn visibility(strukt: &ast::StructDef) -> String {
+    strukt.visibility().map(|it| format!("{} ", it.syntax().text())).unwrap_or_default()
+}
+
+fn new_fn(vis: &str, fields: &[(String, String)]) -> String {
+    let params = fields.iter().map(|(name, ty)| format!("{}: {}", name, ty)).collect::<Vec<_>>();
+    let mut buf = format!("{}fn new({}) -> Self {{\n", vis, params.join(", "));
+    if buf.len() > MAX_SIGNATURE_WIDTH {
+        buf = format!("{}fn new(\n", vis);
+        params.iter().for_each(|it| buf.push_str(&format!("    {},\n", it)));
+        buf.push_str(") -> Self {\n");
+    }
+
+    let names = fields.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+    let init = names.join(", ");
+    if init.is_empty() {
+        buf.push_str("    Self {}\n");
+    } else if init.len() <= MAX_STRUCT_LIT_WIDTH {
+        buf.push_str(&format!("    Self {{ {} }}\n", init));
+    } else {
+        buf.push_str("    Self {\n");
+        names.iter().for_each(|it| buf.push_str(&format!("        {},\n", it)));
+        buf.push_str("    }\n");
+    }
+    buf.push_str("}");
+    buf
+}
+
+/// The type returned by the getter of a field: `&str` for a `String`, `&[T]`
+/// for a `Vec<T>` and a reference to the type of the field otherwise.
+fn getter_type(
+    db: &impl HirDatabase,
+    strukt: hir::Struct,
+    name: &str,
+    type_ref: &ast::TypeRef,
+) -> Option<String> {
+    let field = strukt.fields(db).into_iter().find(|it| it.name(db).to_string() == name)?;
+    let adt_name = match field.ty(db).as_adt() {
+        Some((hir::Adt::Struct(it), _)) => it.name(db).map(|it| it.to_string()),
+        _ => None,
+    };
+    let res = match adt_name.as_ref().map(String::as_str) {
+        Some("String") => "&str".to_string(),
+        Some("Vec") => match vec_element_type(type_ref) {
+            Some(element) => format!("&[{}]", element.syntax().text()),
+            None => format!("&{}", type_ref.syntax().text()),
+        },
+        _ => format!("&{}", type_ref.syntax().text()),
+    };
+    Some(res)
+}
+
+fn vec_element_type(type_ref: &ast::TypeRef) -> Option<ast::TypeRef> {
+    let path = match type_ref {
+        ast::TypeRef::PathType(it) => it.path()?,
+        _ => return None,
+    };
+    path.segment()?.type_arg_list()?.type_args().next()?.type_ref()
+}
+
+fn derives_default(strukt: &ast::StructDef) -> bool {
+    strukt.attrs().filter_map(|it| it.as_simple_call()).any(|(name, args)| {
+        name == "derive"
+            && args
+                .syntax()
+                .text()
+                .to_string()
+                .split(|c: char| !c.is_alphanumeric() && c != '_')
+                .any(|it| it == "Default")
+    })
+}
+
+fn implements_default(db: &impl HirDatabase, strukt: hir::Struct) -> bool {
+    let krate = match strukt.krate(db) {
+        Some(it) => it,
+        None => return false,
+    };
+    let impls = db.impls_in_crate(krate);
+    let res = impls.all_impls().any(|impl_block| {
+        let is_default = impl_block
+            .target_trait_ref(db)
+            .and_then(|it| it.trait_.name(db))
+            .map_or(false, |it| it.to_string() == "Default");
+        is_default
+            && impl_block.target_ty(db).as_adt().map(|(adt, _)| adt)
+                == Some(hir::Adt::Struct(strukt))
+    });
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::{check_assist, check_assist_not_applicable, check_assist_range};
+
+    #[test]
+    fn add_new_to_new_impl() {
+        check_assist(
+            add_new,
+            "
+pub struct Foo<|> {
+    a: u32,
+    b: String,
+}",
+            "
+pub struct Foo {
+    a: u32,
+    b: String,
+}
+
+impl Foo {
+    <|>pub fn new(a: u32, b: String) -> Self {
+        Self { a, b }
+    }
+}",
+        );
+    }
+
+    #[test]
+    fn add_new_to_existing_impl() {
+        check_assist(
+            add_new,
+            "
+pub(crate) struct Foo<T: Clone> {
+    a: T,<|>
+}
+
+impl<T: Clone> Foo<T> {
+    fn len(&self) -> usize {
+        0
+    }
+}",
+            "
+pub(crate) struct Foo<T: Clone> {
+    a: T,
+}
+
+impl<T: Clone> Foo<T> {
+    fn len(&self) -> usize {
+        0
+    }
+
+    <|>pub(crate) fn new(a: T) -> Self {
+        Self { a }
+    }
+}",
+        );
+    }
+
+    #[test]
+    fn add_new_to_empty_impl() {
+        check_assist(
+            add_new,
+            "
+struct Foo {<|>}
+
+impl Foo {}",
+            "
+struct Foo {}
+
+impl Foo {
+    <|>fn new() -> Self {
+        Self {}
+    }
+}",
+        );
+    }
+
+    #[test]
+    fn add_new_with_many_fields() {
+        check_assist(
+            add_new,
+            "
+struct Person<|> {
+    first_name: String,
+    last_name: String,
+    email_address: String,
+    phone_number: String,
+    age: u32,
+}",
+            "
+struct Person {
+    first_name: String,
+    last_name: String,
+    email_address: String,
+    phone_number: String,
+    age: u32,
+}
+
+impl Person {
+    <|>fn new(
+        first_name: String,
+        last_name: String,
+        email_address: String,
+        phone_number: String,
+        age: u32,
+    ) -> Self {
+        Self {
+            first_name,
+            last_name,
+            email_address,
+            phone_number,
+            age,
+        }
+    }
+}",
+        );
+    }
+
+    #[test]
+    fn add_new_not_applicable_if_new_exists() {
+        check_assist_not_applicable(
+            add_new,
+            "
+struct Foo<|> { a: u32 }
+
+impl Foo {
+    fn new() -> Self { Foo { a: 0 } }
+}",
+        );
+    }
+
+    #[test]
+    fn add_getter_for_field_at_cursor() {
+        check_assist(
+            add_getters,
+            "
+pub struct Foo {
+    a: u32,
+    b: String<|>,
+}",
+            "
+pub struct Foo {
+    a: u32,
+    b: String,
+}
+
+impl Foo {
+    <|>pub fn b(&self) -> &String {
+        &self.b
+    }
+}",
+        );
+    }
+
+    #[test]
+    fn add_getters_for_selected_fields() {
+        check_assist_range(
+            add_getters,
+            "
+struct String;
+struct Vec<T>(T);
+
+struct Foo {
+    <|>name: String,
+    items: Vec<u8>,
+    count: usize,<|>
+}
+
+impl Foo {
+    fn count(&self) -> usize {
+        self.count
+    }
+}",
+            "
+struct String;
+struct Vec<T>(T);
+
+struct Foo {
+    name: String,
+    items: Vec<u8>,
+    count: usize,
+}
+
+impl Foo {
+    fn count(&self) -> usize {
+        self.count
+    }
+
+    <|>fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn items(&self) -> &[u8] {
+        &self.items
+    }
+}",
+        );
+    }
+
+    #[test]
+    fn add_setter_for_field_at_cursor() {
+        check_assist(
+            add_setters,
+            "
+pub struct Foo {
+    <|>a: u32,
+}",
+            "
+pub struct Foo {
+    a: u32,
+}
+
+impl Foo {
+    <|>pub fn set_a(&mut self, a: u32) {
+        self.a = a;
+    }
+}",
+        );
+    }
+
+    #[test]
+    fn add_setters_not_applicable_if_setters_exist() {
+        check_assist_not_applicable(
+            add_setters,
+            "
+struct Foo {
+    <|>a: u32,
+}
+
+impl Foo {
+    fn set_a(&mut self, a: u32) {}
+}",
+        );
+    }
+
+    #[test]
+    fn add_default_impl_with_field_defaults() {
+        check_assist(
+            add_default_impl,
+            "
+struct Foo<T: Clone><|> {
+    a: T,
+    b: u32,
+}",
+            "
+struct Foo<T: Clone> {
+    a: T,
+    b: u32,
+}
+
+impl<T: Clone + Default> Default for Foo<T> {
+    <|>fn default() -> Self {
+        Self {
+            a: Default::default(),
+            b: Default::default(),
+        }
+    }
+}",
+        );
+    }
+
+    #[test]
+    fn add_default_impl_calling_new() {
+        check_assist(
+            add_default_impl,
+            "
+struct Foo<|>(u32);
+
+impl Foo {
+    fn new() -> Self { Foo(92) }
+}",
+            "
+struct Foo(u32);
+
+impl Default for Foo {
+    <|>fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Foo {
+    fn new() -> Self { Foo(92) }
+}",
+        );
+    }
+
+    #[test]
+    fn add_default_impl_not_applicable_if_implemented() {
+        check_assist_not_applicable(
+            add_default_impl,
+            "
+#[derive(Debug, Default)]
+struct Foo<|>;",
+        );
+        check_assist_not_applicable(
+            add_default_impl,
+            "
+trait Default {}
+struct Foo<|>;
+impl Default for Foo {}",
+        );
+    }
+}
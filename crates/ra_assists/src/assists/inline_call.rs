@@ -0,0 +1,1246 @@
+//! Assists inlining the body of a function at one or all of its call sites.
+
+use format_buf::format;
+use hir::{db::HirDatabase, HasSource, HirDisplay, Ty, TypeCtor};
+use ra_db::FileId;
+use ra_fmt::leading_indent;
+use ra_syntax::{
+    ast::{self, ArgListOwner, AstNode, NameOwner, TypeParamsOwner, VisibilityOwner},
+    SyntaxKind::{
+        IDENT, IMPL_TRAIT_TYPE, ITEM_LIST, LAMBDA_EXPR, LIFETIME, PATH, PATH_TYPE,
+        PLACEHOLDER_TYPE, RETURN_EXPR, TOKEN_TREE, TRAIT_DEF, TRY_EXPR, WHITESPACE,
+    },
+    SyntaxNode, TextRange, TextUnit, T,
+};
+use test_utils::tested_by;
+
+use super::inline_local_variable::needs_parens;
+use crate::{Assist, AssistCtx, AssistId};
+
+pub(crate) fn inline_call(mut ctx: AssistCtx<impl HirDatabase>) -> Option<Assist> {
+    let name_ref = ctx.node_at_offset::<ast::NameRef>()?;
+    let call = CallSite::from_name_ref(&name_ref)?;
+    let db = ctx.db;
+    let file_id = ctx.frange.file_id;
+    let analyzer = hir::SourceAnalyzer::new(db, file_id, call.syntax(), None);
+    let function = call.resolve(db, &analyzer)?;
+
+    // FIXME: functions from other files are not inlined, because the names
+    // used in their bodies might not be in scope at the call site.
+    let src = function.source(db);
+    if src.file_id != file_id.into() {
+        return None;
+    }
+    // Unqualified names of the body only resolve in the module of the function
+    if module_of(db, file_id, call.syntax()) != Some(function.module(db)) {
+        return None;
+    }
+    let fn_def = src.ast;
+    let name = fn_def.name()?.text().to_string();
+    let callee = Callee::new(db, file_id, &fn_def)?;
+    let inlined = callee.inline(db, &analyzer, &call)?;
+    let call_range = call.syntax().text_range();
+
+    ctx.add_action(AssistId("inline_call"), format!("inline `{}`", name), |edit| {
+        edit.target(call_range);
+        edit.replace(call_range, inlined);
+        edit.set_cursor(call_range.start());
+    });
+
+    if let Some(edits) = inline_all_calls(db, file_id, function, &callee, &name) {
+        let cursor = edits
+            .iter()
+            .filter(|(range, _)| range.end() <= call_range.start())
+            .fold(call_range.start().to_usize(), |offset, (range, text)| {
+                offset + text.len() - range.len().to_usize()
+            });
+        ctx.add_action(
+            AssistId("inline_all_calls"),
+            format!("inline all calls to `{}`", name),
+            |edit| {
+                edit.target(call_range);
+                for (range, text) in edits {
+                    edit.replace(range, text);
+                }
+                edit.set_cursor(TextUnit::from_usize(cursor));
+            },
+        );
+    }
+
+    ctx.build()
+}
+
+/// Inlines every call of a private function and deletes the function, if it
+/// isn't used in any other way. The calls are all in the file of the function
+/// unless its module has children in other files, in which case we give up.
+/// We also give up if some calls are in other modules, as `inline_call` does.
+fn inline_all_calls(
+    db: &impl HirDatabase,
+    file_id: FileId,
+    function: hir::Function,
+    callee: &Callee,
+    name: &str,
+) -> Option<Vec<(TextRange, String)>> {
+    let fn_def = &callee.fn_def;
+    if fn_def.visibility().is_some() || callee.in_trait_impl {
+        return None;
+    }
+    let fn_range = fn_def.syntax().text_range();
+    let root = fn_def.syntax().ancestors().last()?;
+    // The function is visible in the child modules of its module, and we
+    // don't look for calls in the files of those
+    let scope = fn_def
+        .syntax()
+        .ancestors()
+        .find_map(ast::Module::cast)
+        .map_or_else(|| root.clone(), |it| it.syntax().clone());
+    if scope.descendants().filter_map(ast::Module::cast).any(|it| it.item_list().is_none()) {
+        return None;
+    }
+
+    let mut calls = Vec::new();
+    for name_ref in root.descendants().filter_map(ast::NameRef::cast) {
+        if name_ref.text() != name {
+            continue;
+        }
+        let analyzer = hir::SourceAnalyzer::new(db, file_id, name_ref.syntax(), None);
+        if let Some(call) = CallSite::from_name_ref(&name_ref) {
+            if call.resolve(db, &analyzer) == Some(function) {
+                if call.syntax().text_range().is_subrange(&fn_range)
+                    || module_of(db, file_id, call.syntax()) != Some(function.module(db))
+                {
+                    return None;
+                }
+                calls.push((call, analyzer));
+            }
+            continue;
+        }
+        let path = match name_ref.syntax().ancestors().find_map(ast::Path::cast) {
+            Some(it) => it,
+            None => continue,
+        };
+        match analyzer.resolve_path(db, &path) {
+            Some(hir::PathResolution::Def(hir::ModuleDef::Function(it)))
+            | Some(hir::PathResolution::AssocItem(hir::AssocItem::Function(it)))
+                if it == function =>
+            {
+                return None
+            }
+            _ => (),
+        }
+    }
+
+    let ranges = calls.iter().map(|(call, _)| call.syntax().text_range()).collect::<Vec<_>>();
+    let is_nested = ranges
+        .iter()
+        .enumerate()
+        .any(|(i, range)| ranges.iter().enumerate().any(|(j, it)| i != j && it.is_subrange(range)));
+    if is_nested {
+        return None;
+    }
+
+    let mut edits = Vec::new();
+    for (call, analyzer) in &calls {
+        edits.push((call.syntax().text_range(), callee.inline(db, analyzer, call)?));
+    }
+    let delete_range = match fn_def.syntax().prev_sibling_or_token() {
+        Some(ws) if ws.kind() == WHITESPACE => {
+            TextRange::from_to(ws.text_range().start(), fn_range.end())
+        }
+        _ => fn_range,
+    };
+    edits.push((delete_range, String::new()));
+    edits.sort_by_key(|(range, _)| range.start());
+    Some(edits)
+}
+
+fn module_of(db: &impl HirDatabase, file_id: FileId, node: &SyntaxNode) -> Option<hir::Module> {
+    let src = hir::ModuleSource::from_child_node(db, file_id, node);
+    hir::Module::from_definition(db, hir::Source { file_id: file_id.into(), ast: src })
+}
+
+/// A call of a function, `foo(a, b)` or `x.foo(a)`.
+enum CallSite {
+    Call(ast::CallExpr),
+    MethodCall(ast::MethodCallExpr),
+}
+
+impl CallSite {
+    fn from_name_ref(name_ref: &ast::NameRef) -> Option<CallSite> {
+        let parent = name_ref.syntax().parent()?;
+        if let Some(call) = ast::MethodCallExpr::cast(parent.clone()) {
+            return Some(CallSite::MethodCall(call));
+        }
+        let path = ast::PathSegment::cast(parent)?.parent_path();
+        let path_expr = ast::PathExpr::cast(path.syntax().parent()?)?;
+        let call = ast::CallExpr::cast(path_expr.syntax().parent()?)?;
+        if call.expr()?.syntax() != path_expr.syntax() {
+            return None;
+        }
+        Some(CallSite::Call(call))
+    }
+
+    fn syntax(&self) -> &SyntaxNode {
+        match self {
+            CallSite::Call(it) => it.syntax(),
+            CallSite::MethodCall(it) => it.syntax(),
+        }
+    }
+
+    fn args(&self) -> Vec<ast::Expr> {
+        let arg_list = match self {
+            CallSite::Call(it) => it.arg_list(),
+            CallSite::MethodCall(it) => it.arg_list(),
+        };
+        arg_list.into_iter().flat_map(|it| it.args()).collect()
+    }
+
+    fn resolve(
+        &self,
+        db: &impl HirDatabase,
+        analyzer: &hir::SourceAnalyzer,
+    ) -> Option<hir::Function> {
+        match self {
+            CallSite::Call(call) => {
+                let path = match call.expr()? {
+                    ast::Expr::PathExpr(it) => it.path()?,
+                    _ => return None,
+                };
+                match analyzer.resolve_path(db, &path)? {
+                    hir::PathResolution::Def(hir::ModuleDef::Function(it))
+                    | hir::PathResolution::AssocItem(hir::AssocItem::Function(it)) => Some(it),
+                    _ => None,
+                }
+            }
+            CallSite::MethodCall(call) => analyzer.resolve_method_call(call),
+        }
+    }
+}
+
+/// The function to inline, with the usages of its parameters.
+struct Callee {
+    fn_def: ast::FnDef,
+    body: ast::Block,
+    params: Vec<Param>,
+    self_param: Option<(ast::SelfParamKind, Vec<ast::PathExpr>)>,
+    /// The type parameters of the impl and of the function, in the order of
+    /// the substitutions of the function, with their usages in the body.
+    type_params: Vec<(String, Vec<ast::NameRef>)>,
+    impl_block: Option<ast::ImplBlock>,
+    in_trait_impl: bool,
+    /// Usages of `Self` in the body.
+    self_type_usages: Vec<ast::NameRef>,
+    /// Names bound in the body, which must not capture the arguments.
+    locals: Vec<String>,
+}
+
+struct Param {
+    pat: ast::Pat,
+    /// The type of the parameter, unless it can't be written in a `let`, like
+    /// `impl Trait` or a type with lifetimes.
+    ty: Option<ast::TypeRef>,
+    /// The usages of the parameter, if it is a plain binding which can be
+    /// replaced by the argument.
+    usages: Option<Vec<ast::NameRef>>,
+}
+
+impl Callee {
+    fn new(db: &impl HirDatabase, file_id: FileId, fn_def: &ast::FnDef) -> Option<Callee> {
+        let body = fn_def.body()?.block()?;
+        let container =
+            fn_def.syntax().parent().filter(|it| it.kind() == ITEM_LIST).and_then(|it| it.parent());
+        if container.as_ref().map(|it| it.kind()) == Some(TRAIT_DEF) {
+            return None;
+        }
+        let impl_block = container.and_then(ast::ImplBlock::cast);
+
+        // FIXME: early returns could be turned into a labeled block once
+        // those are stable
+        let has_early_return = body.syntax().descendants().any(|node| {
+            (node.kind() == RETURN_EXPR || node.kind() == TRY_EXPR)
+                && !node
+                    .ancestors()
+                    .take_while(|it| it != body.syntax())
+                    .any(|it| it.kind() == LAMBDA_EXPR || ast::ModuleItem::cast(it).is_some())
+        });
+        if has_early_return {
+            tested_by!(inline_call_early_return);
+            return None;
+        }
+
+        let macro_idents = body
+            .syntax()
+            .descendants()
+            .filter(|it| it.kind() == TOKEN_TREE)
+            .flat_map(|it| it.children_with_tokens())
+            .filter_map(|it| it.into_token())
+            .filter(|it| it.kind() == IDENT || it.kind() == T![self])
+            .map(|it| it.text().to_string())
+            .collect::<Vec<_>>();
+        let in_macro = |name: &str| macro_idents.iter().any(|it| it == name);
+        if in_macro("self") || in_macro("Self") {
+            return None;
+        }
+
+        let analyzer = hir::SourceAnalyzer::new(db, file_id, body.syntax(), None);
+        let name_refs =
+            body.syntax().descendants().filter_map(ast::NameRef::cast).collect::<Vec<_>>();
+        let param_list = fn_def.param_list()?;
+        let params = param_list
+            .params()
+            .map(|param| {
+                let pat = param.pat()?;
+                let ty = param.ascribed_type().filter(|ty| {
+                    !ty.syntax().descendants_with_tokens().any(|it| {
+                        it.kind() == IMPL_TRAIT_TYPE
+                            || it.kind() == LIFETIME
+                            || it.kind() == PLACEHOLDER_TYPE
+                    })
+                });
+                let usages = match &pat {
+                    ast::Pat::BindPat(bind_pat)
+                        if !bind_pat.is_ref()
+                            && !bind_pat.is_mutable()
+                            && bind_pat.pat().is_none()
+                            && !in_macro(&bind_pat.name()?.text()) =>
+                    {
+                        let refs = analyzer.find_all_refs(bind_pat);
+                        let usages = name_refs
+                            .iter()
+                            .filter(|it| refs.iter().any(|r| r.range == it.syntax().text_range()))
+                            .filter(|it| is_value_usage(it))
+                            .cloned()
+                            .collect();
+                        Some(usages)
+                    }
+                    _ => None,
+                };
+                Some(Param { pat, ty, usages })
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        let self_param = param_list.self_param().map(|it| {
+            let usages = body
+                .syntax()
+                .descendants()
+                .filter_map(ast::PathExpr::cast)
+                .filter(|it| {
+                    it.path().and_then(|it| it.segment()).map(|it| it.kind())
+                        == Some(Some(ast::PathSegmentKind::SelfKw))
+                })
+                .collect();
+            (it.kind(), usages)
+        });
+
+        // The types of the parameters are spelled out in `let`s too
+        let param_type_refs = params
+            .iter()
+            .filter_map(|it| it.ty.as_ref())
+            .flat_map(|it| it.syntax().descendants().filter_map(ast::NameRef::cast))
+            .collect::<Vec<_>>();
+        let unqualified_usages = |name: &str| {
+            name_refs
+                .iter()
+                .chain(&param_type_refs)
+                .filter(|it| it.text() == name && is_unqualified(it))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        let mut type_params = Vec::new();
+        let impl_params = impl_block.as_ref().and_then(|it| it.type_param_list());
+        for type_param_list in impl_params.into_iter().chain(fn_def.type_param_list()) {
+            for param in type_param_list.type_params() {
+                let name = param.name()?.text().to_string();
+                if in_macro(&name) {
+                    return None;
+                }
+                let usages = unqualified_usages(&name);
+                type_params.push((name, usages));
+            }
+        }
+        let self_type_usages = unqualified_usages("Self");
+
+        let locals = body
+            .syntax()
+            .descendants()
+            .filter_map(ast::BindPat::cast)
+            .filter_map(|it| it.name())
+            .map(|it| it.text().to_string())
+            .collect();
+
+        Some(Callee {
+            fn_def: fn_def.clone(),
+            body,
+            params,
+            self_param,
+            type_params,
+            in_trait_impl: impl_block.as_ref().and_then(|it| it.target_trait()).is_some(),
+            impl_block,
+            self_type_usages,
+            locals,
+        })
+    }
+
+    /// The code which replaces `call`.
+    fn inline(
+        &self,
+        db: &impl HirDatabase,
+        analyzer: &hir::SourceAnalyzer,
+        call: &CallSite,
+    ) -> Option<String> {
+        let mut args = call.args();
+        let mut receiver = match call {
+            CallSite::MethodCall(it) => Some(it.expr()?),
+            CallSite::Call(_) => None,
+        };
+        let is_ufcs = receiver.is_none() && self.self_param.is_some();
+        if is_ufcs && !args.is_empty() {
+            receiver = Some(args.remove(0));
+        }
+        if args.len() != self.params.len() {
+            return None;
+        }
+
+        let mut edits: Vec<(TextRange, String)> = Vec::new();
+        self.type_edits(db, analyzer, call, &mut edits)?;
+        let mut lets = Vec::new();
+        // Arguments which replace a usage of their parameter directly
+        let mut inlined: Vec<(&ast::NameRef, &ast::Expr)> = Vec::new();
+
+        if let Some((kind, usages)) = &self.self_param {
+            let receiver = receiver?;
+            let by_ref = match kind {
+                ast::SelfParamKind::Owned => None,
+                _ if is_ufcs => match &receiver {
+                    ast::Expr::RefExpr(_) => Some(*kind),
+                    _ => None,
+                },
+                _ if analyzer.type_of(db, &receiver).map_or(false, |it| is_reference(&it)) => None,
+                _ => Some(*kind),
+            };
+            // `S::method(&s)` borrows `s` like `s.method()`
+            let receiver = match receiver {
+                ast::Expr::RefExpr(it) => {
+                    if is_ufcs && by_ref.is_some() {
+                        it.expr()?
+                    } else {
+                        it.into()
+                    }
+                }
+                it => it,
+            };
+            let prefix = match by_ref {
+                Some(ast::SelfParamKind::MutRef) => "&mut ",
+                Some(_) => "&",
+                None => "",
+            };
+            let receiver_text = receiver.syntax().text().to_string();
+            let is_inlined = match by_ref {
+                Some(_) => is_place(&receiver),
+                None => can_inline(&receiver, usages.len()),
+            };
+            if is_inlined {
+                for usage in usages {
+                    let text = if by_ref.is_some() && !is_auto_deref(usage) {
+                        format!("{}{}", prefix, receiver_text)
+                    } else if needs_parens(&receiver, usage.syntax()) {
+                        format!("({})", receiver_text)
+                    } else {
+                        receiver_text.clone()
+                    };
+                    edits.push((usage.syntax().text_range(), text));
+                }
+            } else {
+                lets.push(format!("let this = {}{};", prefix, receiver_text));
+                for usage in usages {
+                    edits.push((usage.syntax().text_range(), "this".to_string()));
+                }
+            }
+        }
+
+        // Arguments are bound with `let` if they have side effects, are used
+        // several times, or would be captured by a name of the body.
+        let mut is_bound = self
+            .params
+            .iter()
+            .zip(&args)
+            .map(|(param, arg)| match &param.usages {
+                Some(usages) => !can_inline(arg, usages.len()),
+                None => true,
+            })
+            .collect::<Vec<_>>();
+        loop {
+            let bound_names = self
+                .params
+                .iter()
+                .zip(&is_bound)
+                .filter(|(_, bound)| **bound)
+                .map(|(param, _)| param.pat.syntax().text().to_string())
+                .chain(self.locals.iter().cloned())
+                .collect::<Vec<_>>();
+            let mut changed = false;
+            for (arg, bound) in args.iter().zip(is_bound.iter_mut()) {
+                let is_captured = arg
+                    .syntax()
+                    .descendants()
+                    .filter_map(ast::NameRef::cast)
+                    .any(|it| bound_names.iter().any(|name| *name == it.text().as_str()));
+                if !*bound && is_captured {
+                    *bound = true;
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        for ((param, arg), bound) in self.params.iter().zip(&args).zip(is_bound) {
+            let arg_text = arg.syntax().text().to_string();
+            // The edits of the generic types of the parameter are not in the body
+            let ty = match &param.ty {
+                Some(ty) => {
+                    let range = ty.syntax().text_range();
+                    let (mut ty_edits, body_edits): (Vec<_>, Vec<_>) =
+                        edits.drain(..).partition(|(it, _)| it.is_subrange(&range));
+                    edits = body_edits;
+                    format!(": {}", apply_edits(ty.syntax(), range, &mut ty_edits))
+                }
+                None => String::new(),
+            };
+            match &param.usages {
+                Some(usages) if !bound => {
+                    for usage in usages {
+                        inlined.push((usage, arg));
+                    }
+                }
+                Some(usages) if usages.is_empty() => {
+                    lets.push(format!("let _{} = {};", ty, arg_text))
+                }
+                _ => lets.push(format!("let {}{} = {};", param.pat.syntax().text(), ty, arg_text)),
+            }
+        }
+        for (usage, arg) in &inlined {
+            let arg_text = arg.syntax().text().to_string();
+            if let Some(field) = usage.syntax().parent().and_then(ast::RecordField::cast) {
+                edits
+                    .push((field.syntax().text_range(), format!("{}: {}", usage.text(), arg_text)));
+                continue;
+            }
+            let path_expr = usage.syntax().ancestors().find_map(ast::PathExpr::cast)?;
+            let text = if needs_parens(arg, path_expr.syntax()) {
+                format!("({})", arg_text)
+            } else {
+                arg_text
+            };
+            edits.push((path_expr.syntax().text_range(), text));
+        }
+
+        let body_range = self.body_range();
+        let body_text = apply_edits(self.body.syntax(), body_range, &mut edits);
+        let has_statements = self.body.statements().next().is_some();
+        if lets.is_empty() && !has_statements {
+            let tail = match self.body.expr() {
+                Some(it) => it,
+                None => return Some("()".to_string()),
+            };
+            // The tail expression might have been replaced by an argument
+            let tail = match tail {
+                ast::Expr::PathExpr(path_expr) => inlined
+                    .iter()
+                    .find(|(usage, _)| {
+                        usage.syntax().ancestors().find_map(ast::PathExpr::cast).as_ref()
+                            == Some(&path_expr)
+                    })
+                    .map(|(_, arg)| (*arg).clone())
+                    .unwrap_or_else(|| path_expr.into()),
+                it => it,
+            };
+            if needs_parens(&tail, call.syntax()) {
+                return Some(format!("({})", body_text));
+            }
+            return Some(body_text);
+        }
+
+        let indent = leading_indent(call.syntax()).unwrap_or_default();
+        let old_indent = self
+            .body
+            .statements()
+            .next()
+            .and_then(|it| leading_indent(it.syntax()))
+            .unwrap_or_default();
+        let mut buf = String::from("{");
+        let lines = body_text.lines().map(|line| {
+            if line.starts_with(old_indent.as_str()) {
+                &line[old_indent.len()..]
+            } else {
+                line.trim_start()
+            }
+        });
+        for line in lets.iter().map(String::as_str).chain(lines) {
+            if line.is_empty() {
+                buf.push_str("\n");
+            } else {
+                format!(buf, "\n{}    {}", indent, line);
+            }
+        }
+        format!(buf, "\n{}}}", indent);
+        Some(buf)
+    }
+
+    /// Spells out the generic arguments of the call in the body.
+    fn type_edits(
+        &self,
+        db: &impl HirDatabase,
+        analyzer: &hir::SourceAnalyzer,
+        call: &CallSite,
+        edits: &mut Vec<(TextRange, String)>,
+    ) -> Option<()> {
+        let type_args = self.type_args(db, analyzer, call);
+        let impl_params = self.type_params.len() - self.fn_type_params();
+        let target = self.impl_block.as_ref().and_then(|it| it.target_type());
+        let mut impl_edits = Vec::new();
+        for (i, ((name, usages), arg)) in self.type_params.iter().zip(&type_args).enumerate() {
+            let arg = match arg {
+                Some(it) => it,
+                None if usages.is_empty() => continue,
+                None => return None,
+            };
+            for usage in usages {
+                edits.push((usage.syntax().text_range(), type_text(usage, arg)?));
+            }
+            if let (Some(target), true) = (&target, i < impl_params) {
+                let usages = target
+                    .syntax()
+                    .descendants()
+                    .filter_map(ast::NameRef::cast)
+                    .filter(|it| it.text() == name.as_str() && is_unqualified(it));
+                for usage in usages {
+                    impl_edits.push((usage.syntax().text_range(), arg.clone()));
+                }
+            }
+        }
+        if !self.self_type_usages.is_empty() {
+            let target = target?;
+            let needs_impl_args =
+                self.type_params[..impl_params].iter().zip(&type_args).any(|(_, it)| it.is_none());
+            if needs_impl_args && impl_params > 0 {
+                return None;
+            }
+            let self_ty =
+                apply_edits(target.syntax(), target.syntax().text_range(), &mut impl_edits);
+            for usage in &self.self_type_usages {
+                edits.push((usage.syntax().text_range(), type_text(usage, &self_ty)?));
+            }
+        }
+        Some(())
+    }
+
+    fn fn_type_params(&self) -> usize {
+        self.fn_def.type_param_list().map_or(0, |it| it.type_params().count())
+    }
+
+    /// The generic arguments of the call, if they are known.
+    fn type_args(
+        &self,
+        db: &impl HirDatabase,
+        analyzer: &hir::SourceAnalyzer,
+        call: &CallSite,
+    ) -> Vec<Option<String>> {
+        let display = |ty: &Ty| {
+            let text = ty.display(db).to_string();
+            if text.contains('{') {
+                None
+            } else {
+                Some(text)
+            }
+        };
+        let unknown = vec![None; self.type_params.len()];
+        match call {
+            CallSite::Call(call) => {
+                let substs = match call.expr().and_then(|it| analyzer.type_of(db, &it)) {
+                    Some(Ty::Apply(a_ty)) => match a_ty.ctor {
+                        TypeCtor::FnDef(_) => a_ty.parameters,
+                        _ => return unknown,
+                    },
+                    _ => return unknown,
+                };
+                if substs.len() != self.type_params.len() {
+                    return unknown;
+                }
+                substs.iter().map(display).collect()
+            }
+            CallSite::MethodCall(call) => {
+                // The arguments of the impl are the ones of the receiver if
+                // the impl is for `Type<T, U>`, and the ones of the method are
+                // given with a turbofish.
+                let fn_params = self.fn_type_params();
+                let impl_params = self.type_params.len() - fn_params;
+                let mut res = unknown;
+                let target_args = self
+                    .impl_block
+                    .as_ref()
+                    .and_then(|it| it.target_type())
+                    .and_then(|it| match it {
+                        ast::TypeRef::PathType(it) => it.path()?.segment()?.type_arg_list(),
+                        _ => None,
+                    })
+                    .map(|it| {
+                        it.type_args().map(|it| it.syntax().text().to_string()).collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default();
+                let is_forwarded = target_args.len() == impl_params
+                    && target_args
+                        .iter()
+                        .zip(&self.type_params)
+                        .all(|(arg, (name, _))| arg == name);
+                let receiver_ty = call.expr().and_then(|it| analyzer.type_of(db, &it));
+                if let (true, Some(mut ty)) = (is_forwarded, receiver_ty) {
+                    while is_reference(&ty) {
+                        ty = match ty {
+                            Ty::Apply(a_ty) => a_ty.parameters.as_single().clone(),
+                            _ => unreachable!(),
+                        };
+                    }
+                    if let Ty::Apply(a_ty) = ty {
+                        if let (TypeCtor::Adt(_), true) =
+                            (a_ty.ctor, a_ty.parameters.len() == impl_params)
+                        {
+                            for (i, ty) in a_ty.parameters.iter().enumerate() {
+                                res[i] = display(ty);
+                            }
+                        }
+                    }
+                }
+                if let Some(type_args) = call.type_arg_list() {
+                    let type_args = type_args
+                        .type_args()
+                        .map(|it| it.syntax().text().to_string())
+                        .collect::<Vec<_>>();
+                    if type_args.len() == fn_params {
+                        for (i, arg) in type_args.into_iter().enumerate() {
+                            res[impl_params + i] = Some(arg);
+                        }
+                    }
+                }
+                res
+            }
+        }
+    }
+
+    /// The statements and the tail expression of the body.
+    fn body_range(&self) -> TextRange {
+        let elements = self
+            .body
+            .syntax()
+            .children_with_tokens()
+            .filter(|it| it.kind() != WHITESPACE && it.kind() != T!['{'] && it.kind() != T!['}'])
+            .map(|it| it.text_range())
+            .collect::<Vec<_>>();
+        match (elements.first(), elements.last()) {
+            (Some(first), Some(last)) => TextRange::from_to(first.start(), last.end()),
+            _ => TextRange::offset_len(self.body.syntax().text_range().end(), 0.into()),
+        }
+    }
+}
+
+/// The text of `range` in `node`, with `edits` applied.
+fn apply_edits(
+    node: &SyntaxNode,
+    range: TextRange,
+    edits: &mut Vec<(TextRange, String)>,
+) -> String {
+    edits.sort_by_key(|(range, _)| (range.start(), range.end()));
+    let text = node.text().to_string();
+    let base = node.text_range().start();
+    let slice =
+        |start: TextUnit, end: TextUnit| &text[(start - base).to_usize()..(end - base).to_usize()];
+    let mut res = String::new();
+    let mut last = range.start();
+    for (range, edit) in edits.iter() {
+        res.push_str(slice(last, range.start()));
+        res.push_str(edit);
+        last = range.end();
+    }
+    res.push_str(slice(last, range.end()));
+    res
+}
+
+/// The text replacing the type `usage` with `ty`. In expressions, generic
+/// arguments need a turbofish, and other types angle brackets.
+fn type_text(usage: &ast::NameRef, ty: &str) -> Option<String> {
+    let path = usage.syntax().ancestors().find_map(ast::Path::cast)?;
+    let top_path = path.syntax().ancestors().take_while(|it| it.kind() == PATH).last()?;
+    if top_path.parent().map_or(false, |it| it.kind() == PATH_TYPE) {
+        return Some(ty.to_string());
+    }
+    let is_path = |text: &str| text.chars().all(|c| c.is_alphanumeric() || c == '_' || c == ':');
+    let generic_path = match ty.find('<') {
+        Some(idx) if ty.ends_with('>') && is_path(&ty[..idx]) => Some(idx),
+        _ => None,
+    };
+    if is_path(ty) {
+        Some(ty.to_string())
+    } else if let Some(idx) = generic_path {
+        Some(format!("{}::{}", &ty[..idx], &ty[idx..]))
+    } else if path.syntax().parent().map_or(false, |it| it.kind() == PATH) {
+        Some(format!("<{}>", ty))
+    } else {
+        None
+    }
+}
+
+/// Whether `name_ref` is a local used as a value, and not the name of a
+/// field.
+fn is_value_usage(name_ref: &ast::NameRef) -> bool {
+    match name_ref.syntax().parent().and_then(ast::RecordField::cast) {
+        Some(field) => field.expr().is_none(),
+        None => true,
+    }
+}
+
+fn is_unqualified(name_ref: &ast::NameRef) -> bool {
+    name_ref
+        .syntax()
+        .parent()
+        .and_then(ast::PathSegment::cast)
+        .map_or(false, |it| it.parent_path().qualifier().is_none())
+}
+
+/// Whether an argument can replace `usages` usages of its parameter without
+/// changing when or how often it is evaluated.
+fn can_inline(arg: &ast::Expr, usages: usize) -> bool {
+    match usages {
+        0 | 1 => is_pure(arg),
+        _ => match arg {
+            ast::Expr::Literal(_) | ast::Expr::PathExpr(_) => true,
+            _ => false,
+        },
+    }
+}
+
+fn is_pure(expr: &ast::Expr) -> bool {
+    let inner = match expr {
+        ast::Expr::Literal(_) | ast::Expr::PathExpr(_) => return true,
+        ast::Expr::ParenExpr(it) => it.expr(),
+        ast::Expr::RefExpr(it) => it.expr(),
+        ast::Expr::FieldExpr(it) => it.expr(),
+        ast::Expr::PrefixExpr(it) => it.expr(),
+        _ => return false,
+    };
+    inner.map_or(false, |it| is_pure(&it))
+}
+
+fn is_place(expr: &ast::Expr) -> bool {
+    match expr {
+        ast::Expr::PathExpr(_) => true,
+        ast::Expr::FieldExpr(it) => it.expr().map_or(false, |it| is_place(&it)),
+        ast::Expr::ParenExpr(it) => it.expr().map_or(false, |it| is_place(&it)),
+        _ => false,
+    }
+}
+
+fn is_reference(ty: &Ty) -> bool {
+    match ty {
+        Ty::Apply(a_ty) => match a_ty.ctor {
+            TypeCtor::Ref(_) => true,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Whether `self` is used as a receiver or a base, where it is dereferenced
+/// automatically.
+fn is_auto_deref(usage: &ast::PathExpr) -> bool {
+    let parent = match usage.syntax().parent() {
+        Some(it) => it,
+        None => return false,
+    };
+    let base = if let Some(it) = ast::MethodCallExpr::cast(parent.clone()) {
+        it.expr()
+    } else if let Some(it) = ast::FieldExpr::cast(parent.clone()) {
+        it.expr()
+    } else if let Some(it) = ast::IndexExpr::cast(parent) {
+        it.base()
+    } else {
+        None
+    };
+    base.map(|it| it.syntax().clone()) == Some(usage.syntax().clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use hir::mock::MockDatabase;
+    use ra_db::FileRange;
+    use test_utils::{covers, extract_offset};
+
+    use crate::helpers::{check_assist, check_assist_not_applicable, check_assist_nth_action};
+
+    use super::*;
+
+    #[test]
+    fn inline_simple_call() {
+        check_assist(
+            inline_call,
+            "
+fn add(a: u32, b: u32) -> u32 {
+    a + b
+}
+fn main() {
+    let x = 2 * ad<|>d(1, 2);
+}",
+            "
+fn add(a: u32, b: u32) -> u32 {
+    a + b
+}
+fn main() {
+    let x = 2 * <|>(1 + 2);
+}",
+        );
+    }
+
+    #[test]
+    fn inline_binds_arguments_with_side_effects() {
+        check_assist(
+            inline_call,
+            "
+fn make() -> u32 { 92 }
+fn double(x: u32) -> u32 {
+    x + x
+}
+fn main() {
+    let y = dou<|>ble(make());
+}",
+            "
+fn make() -> u32 { 92 }
+fn double(x: u32) -> u32 {
+    x + x
+}
+fn main() {
+    let y = <|>{
+        let x: u32 = make();
+        x + x
+    };
+}",
+        );
+    }
+
+    #[test]
+    fn inline_avoids_capturing_arguments() {
+        check_assist(
+            inline_call,
+            "
+fn foo(a: u32, b: u32) -> u32 {
+    let a2 = a * 2;
+    a2 + b
+}
+fn main() {
+    let a2 = 1;
+    let x = fo<|>o(3, a2);
+}",
+            "
+fn foo(a: u32, b: u32) -> u32 {
+    let a2 = a * 2;
+    a2 + b
+}
+fn main() {
+    let a2 = 1;
+    let x = <|>{
+        let b: u32 = a2;
+        let a2 = 3 * 2;
+        a2 + b
+    };
+}",
+        );
+    }
+
+    #[test]
+    fn inline_substitutes_generic_parameter_types() {
+        check_assist(
+            inline_call,
+            "
+fn make() -> u8 { 1 }
+fn twice<T: Clone>(x: T) -> (T, T) {
+    (x.clone(), x)
+}
+fn main() {
+    let p = twi<|>ce(make());
+}",
+            "
+fn make() -> u8 { 1 }
+fn twice<T: Clone>(x: T) -> (T, T) {
+    (x.clone(), x)
+}
+fn main() {
+    let p = <|>{
+        let x: u8 = make();
+        (x.clone(), x)
+    };
+}",
+        );
+    }
+
+    #[test]
+    fn inline_method_call() {
+        check_assist(
+            inline_call,
+            "
+struct Counter { n: u32 }
+impl Counter {
+    fn bump(&mut self, by: u32) {
+        self.n += by;
+        log(self);
+    }
+}
+fn log(c: &mut Counter) {}
+fn main() {
+    let mut c = Counter { n: 0 };
+    c.bu<|>mp(2);
+}",
+            "
+struct Counter { n: u32 }
+impl Counter {
+    fn bump(&mut self, by: u32) {
+        self.n += by;
+        log(self);
+    }
+}
+fn log(c: &mut Counter) {}
+fn main() {
+    let mut c = Counter { n: 0 };
+    <|>{
+        c.n += 2;
+        log(&mut c);
+    };
+}",
+        );
+    }
+
+    #[test]
+    fn inline_method_call_on_temporary() {
+        check_assist(
+            inline_call,
+            "
+struct S { a: u32 }
+impl S {
+    fn get(&self) -> u32 { self.a }
+}
+fn make() -> S { S { a: 1 } }
+fn main() {
+    let x = make().g<|>et();
+}",
+            "
+struct S { a: u32 }
+impl S {
+    fn get(&self) -> u32 { self.a }
+}
+fn make() -> S { S { a: 1 } }
+fn main() {
+    let x = <|>{
+        let this = &make();
+        this.a
+    };
+}",
+        );
+    }
+
+    #[test]
+    fn inline_ufcs_call() {
+        check_assist(
+            inline_call,
+            "
+struct S { a: u32 }
+impl S {
+    fn get(&self) -> u32 { self.a }
+}
+fn main() {
+    let s = S { a: 1 };
+    let x = S::g<|>et(&s);
+}",
+            "
+struct S { a: u32 }
+impl S {
+    fn get(&self) -> u32 { self.a }
+}
+fn main() {
+    let s = S { a: 1 };
+    let x = <|>s.a;
+}",
+        );
+    }
+
+    #[test]
+    fn inline_substitutes_generics() {
+        check_assist(
+            inline_call,
+            "
+struct Wrapper<T> { value: T }
+impl<T> Wrapper<T> {
+    fn new(value: T) -> Self {
+        let w: Self = Self { value };
+        w
+    }
+}
+fn main() {
+    let w = Wrapper::n<|>ew(1u8);
+}",
+            "
+struct Wrapper<T> { value: T }
+impl<T> Wrapper<T> {
+    fn new(value: T) -> Self {
+        let w: Self = Self { value };
+        w
+    }
+}
+fn main() {
+    let w = <|>{
+        let w: Wrapper<u8> = Wrapper::<u8> { value: 1u8 };
+        w
+    };
+}",
+        );
+    }
+
+    #[test]
+    fn inline_record_field_shorthand() {
+        check_assist(
+            inline_call,
+            "
+struct P { x: u32 }
+fn p(x: u32) -> P { P { x } }
+fn main() {
+    let v = <|>p(3);
+}",
+            "
+struct P { x: u32 }
+fn p(x: u32) -> P { P { x } }
+fn main() {
+    let v = <|>P { x: 3 };
+}",
+        );
+    }
+
+    #[test]
+    fn inline_all_calls_deletes_function() {
+        check_assist_nth_action(
+            inline_call,
+            "
+fn main() {
+    let a = inc(1);
+    let b = in<|>c(a);
+}
+
+fn inc(x: u32) -> u32 {
+    x + 1
+}",
+            "
+fn main() {
+    let a = 1 + 1;
+    let b = <|>a + 1;
+}",
+            1,
+        );
+    }
+
+    #[test]
+    fn inline_all_calls_not_offered_for_other_uses() {
+        check_only_inline_call(
+            "
+fn apply(f: fn(u32) -> u32) {}
+fn main() {
+    let a = in<|>c(1);
+    apply(inc);
+}
+fn inc(x: u32) -> u32 { x + 1 }",
+        );
+    }
+
+    #[test]
+    fn inline_all_calls_not_offered_with_child_module_files() {
+        check_only_inline_call(
+            "
+mod child;
+fn main() {
+    let a = in<|>c(1);
+}
+fn inc(x: u32) -> u32 { x + 1 }",
+        );
+    }
+
+    #[test]
+    fn inline_all_calls_not_offered_with_calls_in_other_modules() {
+        check_only_inline_call(
+            "
+mod a {
+    pub(super) fn call_inc() -> u32 {
+        super::inc(1)
+    }
+}
+fn main() {
+    let a = in<|>c(1);
+}
+fn inc(x: u32) -> u32 { x + 1 }",
+        );
+    }
+
+    fn check_only_inline_call(before: &str) {
+        let (before_cursor_pos, before) = extract_offset(before);
+        let (db, _source_root, file_id) = MockDatabase::with_single_file(&before);
+        let frange =
+            FileRange { file_id, range: TextRange::offset_len(before_cursor_pos, 0.into()) };
+        let assist = AssistCtx::with_ctx(&db, frange, true, inline_call).unwrap();
+        match assist {
+            Assist::Resolved(actions) => assert_eq!(actions.len(), 1),
+            Assist::Unresolved(_) => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn inline_not_applicable_with_early_return() {
+        covers!(inline_call_early_return);
+        check_assist_not_applicable(
+            inline_call,
+            "
+fn foo(x: u32) -> u32 {
+    if x > 1 {
+        return 0;
+    }
+    x
+}
+fn main() {
+    fo<|>o(1);
+}",
+        );
+    }
+
+    #[test]
+    fn inline_not_applicable_from_other_module() {
+        check_assist_not_applicable(
+            inline_call,
+            "
+mod a {
+    fn helper() -> u32 { 1 }
+    pub fn foo() -> u32 {
+        helper()
+    }
+}
+fn main() {
+    a::fo<|>o();
+}",
+        );
+    }
+
+    #[test]
+    fn inline_not_applicable_to_trait_method() {
+        check_assist_not_applicable(
+            inline_call,
+            "
+trait T {
+    fn foo(&self) -> u32 { 1 }
+}
+fn bar(t: &impl T) {
+    t.fo<|>o();
+}",
+        );
+    }
+}
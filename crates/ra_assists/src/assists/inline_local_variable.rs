@@ -3,7 +3,7 @@
 use hir::db::HirDatabase;
 use ra_syntax::{
     ast::{self, AstNode, AstToken},
-    TextRange,
+    SyntaxNode, TextRange,
 };
 
 use crate::assist_ctx::AssistBuilder;
@@ -41,39 +41,7 @@ pub(crate) fn inline_local_varialbe(mut ctx: AssistCtx<impl HirDatabase>) -> Opt
             .covering_node_for_range(desc.range)
             .ancestors()
             .find_map(|node| ast::PathExpr::cast(node))?;
-        let usage_parent_option = usage_node.syntax().parent().and_then(ast::Expr::cast);
-        let usage_parent = match usage_parent_option {
-            Some(u) => u,
-            None => {
-                wrap_in_parens[i] = false;
-                continue;
-            }
-        };
-
-        wrap_in_parens[i] = match (&initializer_expr, usage_parent) {
-            (ast::Expr::CallExpr(_), _)
-            | (ast::Expr::IndexExpr(_), _)
-            | (ast::Expr::MethodCallExpr(_), _)
-            | (ast::Expr::FieldExpr(_), _)
-            | (ast::Expr::TryExpr(_), _)
-            | (ast::Expr::RefExpr(_), _)
-            | (ast::Expr::Literal(_), _)
-            | (ast::Expr::TupleExpr(_), _)
-            | (ast::Expr::ArrayExpr(_), _)
-            | (ast::Expr::ParenExpr(_), _)
-            | (ast::Expr::PathExpr(_), _)
-            | (ast::Expr::BlockExpr(_), _)
-            | (_, ast::Expr::CallExpr(_))
-            | (_, ast::Expr::TupleExpr(_))
-            | (_, ast::Expr::ArrayExpr(_))
-            | (_, ast::Expr::ParenExpr(_))
-            | (_, ast::Expr::ForExpr(_))
-            | (_, ast::Expr::WhileExpr(_))
-            | (_, ast::Expr::BreakExpr(_))
-            | (_, ast::Expr::ReturnExpr(_))
-            | (_, ast::Expr::MatchExpr(_)) => false,
-            _ => true,
-        };
+        wrap_in_parens[i] = needs_parens(&initializer_expr, usage_node.syntax());
     }
 
     let init_str = initializer_expr.syntax().text().to_string();
@@ -98,6 +66,43 @@ pub(crate) fn inline_local_varialbe(mut ctx: AssistCtx<impl HirDatabase>) -> Opt
     ctx.build()
 }
 
+/// Whether `expr` has to be wrapped in parentheses when it replaces the
+/// expression `usage`, so that it keeps binding the same way.
+pub(crate) fn needs_parens(expr: &ast::Expr, usage: &SyntaxNode) -> bool {
+    let usage_parent = match usage.parent().and_then(ast::Expr::cast) {
+        Some(u) => u,
+        None => return false,
+    };
+    match (expr, usage_parent) {
+        (ast::Expr::RefExpr(_), ast::Expr::MethodCallExpr(_))
+        | (ast::Expr::RefExpr(_), ast::Expr::FieldExpr(_))
+        | (ast::Expr::RefExpr(_), ast::Expr::IndexExpr(_))
+        | (ast::Expr::RefExpr(_), ast::Expr::TryExpr(_)) => true,
+        (ast::Expr::CallExpr(_), _)
+        | (ast::Expr::IndexExpr(_), _)
+        | (ast::Expr::MethodCallExpr(_), _)
+        | (ast::Expr::FieldExpr(_), _)
+        | (ast::Expr::TryExpr(_), _)
+        | (ast::Expr::RefExpr(_), _)
+        | (ast::Expr::Literal(_), _)
+        | (ast::Expr::TupleExpr(_), _)
+        | (ast::Expr::ArrayExpr(_), _)
+        | (ast::Expr::ParenExpr(_), _)
+        | (ast::Expr::PathExpr(_), _)
+        | (ast::Expr::BlockExpr(_), _)
+        | (_, ast::Expr::CallExpr(_))
+        | (_, ast::Expr::TupleExpr(_))
+        | (_, ast::Expr::ArrayExpr(_))
+        | (_, ast::Expr::ParenExpr(_))
+        | (_, ast::Expr::ForExpr(_))
+        | (_, ast::Expr::WhileExpr(_))
+        | (_, ast::Expr::BreakExpr(_))
+        | (_, ast::Expr::ReturnExpr(_))
+        | (_, ast::Expr::MatchExpr(_)) => false,
+        _ => true,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::helpers::{check_assist, check_assist_not_applicable};
@@ -632,6 +637,22 @@ fn foo() {
             "
 fn foo() {
     <|>match 1 > 0 {}
+}",
+        );
+    }
+
+    #[test]
+    fn test_ref_used_as_receiver() {
+        check_assist(
+            inline_local_varialbe,
+            "
+fn foo(v: Vec<u32>) {
+    let a<|> = &v;
+    a.len();
+}",
+            "
+fn foo(v: Vec<u32>) {
+    <|>(&v).len();
 }",
         );
     }
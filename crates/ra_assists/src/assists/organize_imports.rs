@@ -0,0 +1,818 @@
+//! Assist organizing the imports of a file: merging the `use` items sharing a
+//! prefix, removing the unused ones and sorting them into groups.
+
+use std::{collections::BTreeMap, mem};
+
+use hir::{db::HirDatabase, PathResolution};
+use itertools::Itertools;
+use ra_db::FileId;
+use ra_fmt::leading_indent;
+use ra_syntax::{
+    ast::{self, make, AstNode, AttrsOwner, NameOwner, VisibilityOwner},
+    SyntaxKind::*,
+    SyntaxNode, SyntaxToken, TextRange, T,
+};
+use rustc_hash::FxHashSet;
+
+use crate::{Assist, AssistCtx, AssistId};
+
+/// rustfmt's default maximum width.
+const MAX_WIDTH: usize = 100;
+
+/// The crates distributed with the compiler, whose imports come first.
+const STD_CRATES: &[&str] = &["std", "core", "alloc", "proc_macro", "test"];
+
+/// Organizes the runs of consecutive `use` items touched by the selection.
+/// The imports of each run are merged into one tree per crate, the unused
+/// ones are removed, and the rest are sorted into `std`, external crate and
+/// local crate groups, separated by blank lines.
+///
+/// Selecting the whole file organizes all of its imports, which is how the
+/// action runs on save.
+pub(crate) fn organize_imports(mut ctx: AssistCtx<impl HirDatabase>) -> Option<Assist> {
+    let file_id = ctx.frange.file_id;
+    let file = ctx.db.parse(file_id).tree();
+    let selection = ctx.frange.range;
+    let edits = file
+        .syntax()
+        .descendants()
+        .filter(is_import_scope)
+        .flat_map(|scope| import_runs(&scope))
+        .filter(|run| {
+            let range = run_range(run);
+            if selection.is_empty() {
+                range.contains_inclusive(selection.start())
+            } else {
+                range.intersection(&selection).is_some()
+            }
+        })
+        .filter_map(|run| {
+            let (range, text) = organize(ctx.db, file_id, &run)?;
+            if file.syntax().text().slice(range) == text.as_str() {
+                return None;
+            }
+            Some((range, text))
+        })
+        .collect::<Vec<_>>();
+    let start = edits.first()?.0.start();
+
+    ctx.add_action(AssistId("organize_imports"), "organize imports", |edit| {
+        edit.target(TextRange::from_to(start, edits.last().unwrap().0.end()));
+        for (range, text) in edits {
+            edit.replace(range, text);
+        }
+        edit.set_cursor(start);
+    });
+    ctx.build()
+}
+
+/// A path brought into scope by a `use` item.
+struct Import {
+    visibility: String,
+    path: Vec<String>,
+    kind: ImportKind,
+    /// The range of the `use` item this import comes from.
+    source: TextRange,
+}
+
+enum ImportKind {
+    Name,
+    Alias(String),
+    Glob,
+}
+
+impl Import {
+    /// The name this import is referred to by, if it can be referred to.
+    fn local_name(&self) -> Option<&str> {
+        match &self.kind {
+            ImportKind::Name => self.path.last().map(String::as_str),
+            ImportKind::Alias(alias) if alias != "_" => Some(alias.as_str()),
+            _ => None,
+        }
+    }
+}
+
+/// The imports of a run, merged by common prefix.
+#[derive(Default)]
+struct ImportTree {
+    imported: bool,
+    aliases: Vec<String>,
+    glob: bool,
+    children: BTreeMap<String, ImportTree>,
+}
+
+impl ImportTree {
+    fn insert(&mut self, import: &Import) {
+        let node = import
+            .path
+            .iter()
+            .fold(self, |node, segment| node.children.entry(segment.clone()).or_default());
+        match &import.kind {
+            ImportKind::Name => node.imported = true,
+            ImportKind::Alias(alias) => {
+                if !node.aliases.contains(alias) {
+                    node.aliases.push(alias.clone());
+                    node.aliases.sort();
+                }
+            }
+            ImportKind::Glob => node.glob = true,
+        }
+    }
+
+    fn sorted_children(&self) -> impl Iterator<Item = (&String, &ImportTree)> {
+        self.children.iter().sorted_by(|(a, _), (b, _)| sort_key(a).cmp(&sort_key(b)))
+    }
+
+    /// The use trees importing `name` and the items below it.
+    fn use_trees(&self, name: &str) -> Vec<String> {
+        match self.list() {
+            None => {
+                let own = if self.imported { Some(name.to_string()) } else { None };
+                own.into_iter()
+                    .chain(self.aliases.iter().map(|alias| format!("{} as {}", name, alias)))
+                    .collect()
+            }
+            Some(items) => match items.as_slice() {
+                [item] => vec![format!("{}::{}", name, item)],
+                _ => vec![format!("{}::{{{}}}", name, items.join(", "))],
+            },
+        }
+    }
+
+    /// The items of the use tree list below this node, if it needs one.
+    fn list(&self) -> Option<Vec<String>> {
+        if self.children.is_empty() && !self.glob {
+            return None;
+        }
+        let mut items = Vec::new();
+        if self.imported {
+            items.push("self".to_string());
+        }
+        items.extend(self.aliases.iter().map(|alias| format!("self as {}", alias)));
+        for (name, child) in self.sorted_children() {
+            items.extend(child.use_trees(name));
+        }
+        if self.glob {
+            items.push("*".to_string());
+        }
+        Some(items)
+    }
+
+    /// One `use` item per crate, wrapping the lists which don't fit in a line.
+    fn use_items(&self, visibility: &str, indent: &str) -> Vec<String> {
+        let mut res = Vec::new();
+        for (name, child) in self.sorted_children() {
+            let items = match child.list() {
+                Some(items) if items.len() > 1 => items,
+                _ => {
+                    let trees = child.use_trees(name).into_iter();
+                    res.extend(trees.map(|tree| format!("use {}{};", visibility, tree)));
+                    continue;
+                }
+            };
+            let line = format!("use {}{}::{{{}}};", visibility, name, items.join(", "));
+            if indent.len() + line.len() <= MAX_WIDTH {
+                res.push(line);
+                continue;
+            }
+            let mut text = format!("use {}{}::{{", visibility, name);
+            let mut line = String::new();
+            for item in items {
+                if !line.is_empty() && indent.len() + 4 + line.len() + item.len() + 2 > MAX_WIDTH {
+                    text += &format!("\n{}    {}", indent, line);
+                    line.clear();
+                }
+                if !line.is_empty() {
+                    line.push(' ');
+                }
+                line += &item;
+                line.push(',');
+            }
+            text += &format!("\n{}    {}\n{}}};", indent, line, indent);
+            res.push(text);
+        }
+        res
+    }
+}
+
+/// The order of the segments of a use tree: `self`, `super` and `crate`
+/// first, then modules, types and constants, like rustfmt.
+fn sort_key(name: &str) -> (u8, &str) {
+    let rank = match name {
+        "" | "self" => 0,
+        "super" => 1,
+        "crate" => 2,
+        _ if name.starts_with(char::is_uppercase) && name.chars().any(char::is_lowercase) => 4,
+        _ if name.starts_with(char::is_uppercase) => 5,
+        _ => 3,
+    };
+    (rank, name)
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum Group {
+    Std,
+    External,
+    Local,
+}
+
+fn group(first_segment: &str, dependencies: &[String]) -> Group {
+    match first_segment {
+        "crate" | "self" | "super" => Group::Local,
+        _ if STD_CRATES.contains(&first_segment) => Group::Std,
+        "" => Group::External,
+        _ if dependencies.iter().any(|it| it == first_segment) => Group::External,
+        _ => Group::Local,
+    }
+}
+
+/// Whether `use` items in `node` are organized together.
+fn is_import_scope(node: &SyntaxNode) -> bool {
+    match node.kind() {
+        SOURCE_FILE | BLOCK => true,
+        ITEM_LIST => node.parent().map(|it| it.kind()) == Some(MODULE),
+        _ => false,
+    }
+}
+
+/// The `use` items of `scope` separated only by whitespace. Items with
+/// attributes or comments are left alone, and end the run.
+fn import_runs(scope: &SyntaxNode) -> Vec<Vec<ast::UseItem>> {
+    let mut runs = Vec::new();
+    let mut run = Vec::new();
+    for element in scope.children_with_tokens() {
+        if element.kind() == WHITESPACE {
+            continue;
+        }
+        match element.into_node().and_then(ast::UseItem::cast).filter(is_simple) {
+            Some(item) => run.push(item),
+            None if !run.is_empty() => runs.push(mem::replace(&mut run, Vec::new())),
+            None => (),
+        }
+    }
+    if !run.is_empty() {
+        runs.push(run);
+    }
+    runs
+}
+
+fn is_simple(item: &ast::UseItem) -> bool {
+    item.use_tree().is_some()
+        && item.attrs().next().is_none()
+        && !item.syntax().descendants_with_tokens().any(|it| it.kind() == COMMENT)
+        && item.syntax().last_token().map(|it| it.kind()) == Some(T![;])
+}
+
+fn run_range(run: &[ast::UseItem]) -> TextRange {
+    TextRange::from_to(
+        run[0].syntax().text_range().start(),
+        run[run.len() - 1].syntax().text_range().end(),
+    )
+}
+
+/// Computes the organized text of a run, and the range it replaces.
+fn organize(
+    db: &impl HirDatabase,
+    file_id: FileId,
+    run: &[ast::UseItem],
+) -> Option<(TextRange, String)> {
+    let mut imports = Vec::new();
+    for item in run {
+        let visibility =
+            item.visibility().map(|it| format!("{} ", it.syntax().text())).unwrap_or_default();
+        let source = item.syntax().text_range();
+        flatten(item.use_tree()?, &[], &mut |path: Vec<String>, kind: ImportKind| {
+            imports.push(Import { visibility: visibility.clone(), path, kind, source })
+        })?;
+    }
+    let module = hir::Module::from_definition(
+        db,
+        hir::Source {
+            file_id: file_id.into(),
+            ast: hir::ModuleSource::from_child_node(db, file_id, run[0].syntax()),
+        },
+    );
+    let usages = Usages::new(db, file_id, module, &run[0]);
+    imports.retain(|import| !usages.is_unused(db, import));
+
+    let dependencies = module
+        .and_then(|it| it.krate(db))
+        .map(|krate| krate.dependencies(db).into_iter().map(|dep| dep.name.to_string()).collect())
+        .unwrap_or_else(Vec::new);
+    let indent = leading_indent(run[0].syntax()).unwrap_or_default();
+    let visibilities =
+        imports.iter().map(|import| import.visibility.as_str()).sorted().dedup().collect_vec();
+    let mut groups = Vec::new();
+    for visibility in visibilities {
+        let mut trees = BTreeMap::<Group, ImportTree>::new();
+        for import in imports.iter().filter(|it| it.visibility == visibility) {
+            let group = group(&import.path[0], &dependencies);
+            trees.entry(group).or_default().insert(import);
+        }
+        groups.extend(
+            trees
+                .values()
+                .map(|tree| tree.use_items(visibility, &indent).join(&format!("\n{}", indent))),
+        );
+    }
+    let text = groups.join(&format!("\n\n{}", indent));
+
+    let mut range = run_range(run);
+    if text.is_empty() {
+        // Don't leave the whitespace after the removed run behind.
+        let last = run[run.len() - 1].syntax();
+        if let Some(ws) = last.next_sibling_or_token().filter(|it| it.kind() == WHITESPACE) {
+            range = TextRange::from_to(range.start(), ws.text_range().end());
+        }
+    }
+    Some((range, text))
+}
+
+/// Calls `f` with the path and kind of each import of `tree`, bailing out on
+/// trees which can't be reformatted without losing something.
+fn flatten(
+    tree: ast::UseTree,
+    prefix: &[String],
+    f: &mut dyn FnMut(Vec<String>, ImportKind),
+) -> Option<()> {
+    let mut path = prefix.to_vec();
+    if let Some(p) = tree.path() {
+        let text = p.syntax().text().to_string();
+        if text.contains("//") || text.contains("/*") {
+            return None;
+        }
+        let text = text.split_whitespace().collect::<String>();
+        path.extend(text.split("::").map(|it| it.to_string()));
+    }
+    if let Some(list) = tree.use_tree_list() {
+        for tree in list.use_trees() {
+            flatten(tree, &path, f)?;
+        }
+        return Some(());
+    }
+    if path.last().map(String::as_str) == Some("self") && path.len() > 1 {
+        path.pop();
+    }
+    if path.is_empty() {
+        return None;
+    }
+    let kind = if tree.has_star() {
+        ImportKind::Glob
+    } else if let Some(alias) = tree.alias() {
+        ImportKind::Alias(alias.name().map_or("_".to_string(), |it| it.text().to_string()))
+    } else {
+        ImportKind::Name
+    };
+    f(path, kind);
+    Some(())
+}
+
+/// The identifiers which could refer to the imports of a run: those of its
+/// scope, and of the files of the child modules, which see private imports
+/// as well.
+struct Usages {
+    file_id: FileId,
+    analyzer: hir::SourceAnalyzer,
+    roots: Vec<(FileId, SyntaxNode)>,
+    idents: Vec<Ident>,
+}
+
+struct Ident {
+    file_id: FileId,
+    token: SyntaxToken,
+    /// Whether the identifier is in a child module, where any path can refer
+    /// to the import, rather than just those starting with its name.
+    in_child_module: bool,
+}
+
+impl Usages {
+    fn new(
+        db: &impl HirDatabase,
+        file_id: FileId,
+        module: Option<hir::Module>,
+        first: &ast::UseItem,
+    ) -> Usages {
+        let analyzer = hir::SourceAnalyzer::new(db, file_id, first.syntax(), None);
+        let scope = first.syntax().parent().unwrap();
+        let mut roots = vec![(file_id, scope.clone())];
+        if scope.kind() != BLOCK {
+            let mut modules = module.into_iter().flat_map(|it| it.children(db)).collect_vec();
+            while let Some(module) = modules.pop() {
+                let src = module.definition_source(db);
+                if let hir::ModuleSource::SourceFile(file) = src.ast {
+                    roots.push((src.file_id.original_file(db), file.syntax().clone()));
+                }
+                modules.extend(module.children(db));
+            }
+        }
+        let idents = roots
+            .iter()
+            .flat_map(|(root_file_id, root)| {
+                let is_scope = root == &scope;
+                root.descendants_with_tokens()
+                    .filter_map(|it| it.into_token())
+                    .filter(|it| it.kind() == IDENT)
+                    .map(move |token| {
+                        let in_child_module = !is_scope
+                            || token
+                                .parent()
+                                .ancestors()
+                                .take_while(|it| it != root)
+                                .any(|it| it.kind() == MODULE);
+                        Ident { file_id: *root_file_id, token, in_child_module }
+                    })
+            })
+            .collect();
+        Usages { file_id, analyzer, roots, idents }
+    }
+
+    /// The identifiers outside of the `use` item of `import`.
+    fn idents<'a>(&'a self, import: &'a Import) -> impl Iterator<Item = &'a Ident> + 'a {
+        self.idents.iter().filter(move |ident| {
+            ident.file_id != self.file_id || !ident.token.text_range().is_subrange(&import.source)
+        })
+    }
+
+    /// Whether nothing refers to a private, resolved import. Trait imports
+    /// are used by calls to the methods of the trait as well.
+    fn is_unused(&self, db: &impl HirDatabase, import: &Import) -> bool {
+        if !import.visibility.is_empty() {
+            return false;
+        }
+        let name = match import.local_name() {
+            Some(it) => it,
+            None => return false,
+        };
+        let path = make::path_from_name_ref(make::name_ref(name));
+        let resolution = match self.analyzer.resolve_path(db, &path) {
+            Some(it) => it,
+            None => return false,
+        };
+        if self.idents(import).any(|ident| refers_to(db, ident, name, &resolution)) {
+            return false;
+        }
+        match resolution {
+            PathResolution::Def(hir::ModuleDef::Trait(trait_)) => {
+                !self.uses_trait_items(db, import, trait_)
+            }
+            _ => true,
+        }
+    }
+
+    fn uses_trait_items(&self, db: &impl HirDatabase, import: &Import, trait_: hir::Trait) -> bool {
+        let names = trait_
+            .items(db)
+            .into_iter()
+            .filter_map(|item| match item {
+                hir::AssocItem::Function(it) => Some(it.name(db)),
+                hir::AssocItem::Const(it) => it.name(db),
+                hir::AssocItem::TypeAlias(it) => Some(it.name(db)),
+            })
+            .map(|it| it.to_string())
+            .collect::<FxHashSet<_>>();
+        let used_directly = self
+            .idents(import)
+            .filter(|ident| names.contains(ident.token.text().as_str()))
+            .any(|ident| {
+                let parent = ident.token.parent();
+                if parent.kind() == TOKEN_TREE {
+                    return true;
+                }
+                if parent.kind() != NAME_REF {
+                    return false;
+                }
+                let call = match parent.parent().and_then(ast::MethodCallExpr::cast) {
+                    Some(it) => it,
+                    None => return true,
+                };
+                let analyzer = hir::SourceAnalyzer::new(db, ident.file_id, call.syntax(), None);
+                match analyzer.resolve_method_call(&call).and_then(|it| it.container(db)) {
+                    Some(hir::Container::Trait(it)) => it == trait_,
+                    Some(hir::Container::ImplBlock(it)) => {
+                        it.target_trait_ref(db).map(|it| it.trait_) == Some(trait_)
+                    }
+                    None => true,
+                }
+            });
+        used_directly || self.macros_call(db, &names)
+    }
+
+    /// Whether the expansion of a macro call calls one of `names`, like
+    /// `write!` calls `write_fmt`.
+    fn macros_call(&self, db: &impl HirDatabase, names: &FxHashSet<String>) -> bool {
+        self.roots.iter().any(|(file_id, root)| {
+            root.descendants().filter_map(ast::MacroCall::cast).any(|call| {
+                let analyzer = hir::SourceAnalyzer::new(db, *file_id, call.syntax(), None);
+                let expansion = analyzer
+                    .expand(db, &call)
+                    .and_then(|expansion| db.parse_or_expand(expansion.file_id()));
+                expansion.map_or(false, |node| {
+                    node.descendants()
+                        .filter_map(ast::NameRef::cast)
+                        .any(|it| names.contains(it.text().as_str()))
+                })
+            })
+        })
+    }
+}
+
+/// Whether `ident` may refer to the import named `name`, which resolves to
+/// `resolution`. Identifiers in macro calls can't be resolved, so they are
+/// assumed to refer to it.
+fn refers_to(
+    db: &impl HirDatabase,
+    ident: &Ident,
+    name: &str,
+    resolution: &PathResolution,
+) -> bool {
+    if ident.token.text().as_str() != name {
+        return false;
+    }
+    let parent = ident.token.parent();
+    match parent.kind() {
+        TOKEN_TREE => true,
+        NAME_REF => {
+            let path = match parent.parent().and_then(ast::PathSegment::cast) {
+                Some(segment) => segment.parent_path(),
+                None => return false,
+            };
+            if !ident.in_child_module && path.qualifier().is_some() {
+                return false;
+            }
+            if path.syntax().ancestors().any(|it| it.kind() == USE_ITEM) {
+                // Paths in use tree lists are relative to the list's prefix.
+                let in_list = path
+                    .syntax()
+                    .parent()
+                    .and_then(|it| it.parent())
+                    .map_or(false, |it| it.kind() == USE_TREE_LIST);
+                return ident.in_child_module || !in_list;
+            }
+            let analyzer = hir::SourceAnalyzer::new(db, ident.file_id, &parent, None);
+            analyzer.resolve_path(db, &path).as_ref() == Some(resolution)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::{check_assist, check_assist_not_applicable, check_assist_range};
+
+    #[test]
+    fn merges_imports_with_common_prefix() {
+        check_assist(
+            organize_imports,
+            "
+use std::fmt<|>::Display;
+use std::fmt::Debug;
+use std::{collections::HashMap, fmt};
+
+fn f(_: &dyn Display, _: &dyn Debug, _: HashMap<(), ()>, _: fmt::Error) {}
+",
+            "
+<|>use std::{collections::HashMap, fmt::{self, Debug, Display}};
+
+fn f(_: &dyn Display, _: &dyn Debug, _: HashMap<(), ()>, _: fmt::Error) {}
+",
+        );
+    }
+
+    #[test]
+    fn sorts_imports_into_groups() {
+        check_assist(
+            organize_imports,
+            "
+use crate::foo::<|>Foo;
+pub use crate::foo::Bar;
+use std::mem;
+use self::foo::baz;
+
+mod foo {
+    pub struct Foo;
+    pub struct Bar;
+    pub fn baz() {}
+}
+
+fn f(_: Foo) {
+    baz();
+    mem::drop(());
+}
+",
+            "
+<|>use std::mem;
+
+use self::foo::baz;
+use crate::foo::Foo;
+
+pub use crate::foo::Bar;
+
+mod foo {
+    pub struct Foo;
+    pub struct Bar;
+    pub fn baz() {}
+}
+
+fn f(_: Foo) {
+    baz();
+    mem::drop(());
+}
+",
+        );
+    }
+
+    #[test]
+    fn removes_unused_imports() {
+        check_assist(
+            organize_imports,
+            "
+use crate::foo::{Bar<|>, Baz, baz};
+
+mod foo {
+    pub struct Bar;
+    pub struct Baz;
+    pub fn baz() {}
+}
+
+fn f() -> Bar {
+    Bar
+}
+",
+            "
+<|>use crate::foo::Bar;
+
+mod foo {
+    pub struct Bar;
+    pub struct Baz;
+    pub fn baz() {}
+}
+
+fn f() -> Bar {
+    Bar
+}
+",
+        );
+    }
+
+    #[test]
+    fn removes_imports_shadowed_at_every_usage() {
+        check_assist(
+            organize_imports,
+            "
+use crate::foo::{Bar<|>, Baz};
+
+mod foo {
+    pub struct Bar;
+    pub struct Baz;
+}
+
+fn f<Bar>(_: Bar, _: Baz) {}
+",
+            "
+<|>use crate::foo::Baz;
+
+mod foo {
+    pub struct Bar;
+    pub struct Baz;
+}
+
+fn f<Bar>(_: Bar, _: Baz) {}
+",
+        );
+    }
+
+    #[test]
+    fn keeps_imports_used_by_macros_and_child_modules() {
+        check_assist(
+            organize_imports,
+            "
+use crate::foo::{<|>Foo, Bar, foo};
+
+mod foo {
+    pub struct Foo;
+    pub struct Bar;
+    pub fn foo() {}
+}
+
+mod bar {
+    fn f() -> super::Foo {
+        super::Foo
+    }
+}
+
+fn f() {
+    m!(Bar);
+}
+",
+            "
+<|>use crate::foo::{Bar, Foo};
+
+mod foo {
+    pub struct Foo;
+    pub struct Bar;
+    pub fn foo() {}
+}
+
+mod bar {
+    fn f() -> super::Foo {
+        super::Foo
+    }
+}
+
+fn f() {
+    m!(Bar);
+}
+",
+        );
+    }
+
+    #[test]
+    fn keeps_trait_imports_used_by_method_calls() {
+        check_assist(
+            organize_imports,
+            "
+use crate::tr::<|>{Tr, Unused};
+
+mod tr {
+    pub trait Tr { fn method(&self); }
+    pub trait Unused { fn other(&self); }
+}
+
+impl tr::Tr for () {
+    fn method(&self) {}
+}
+
+fn f() {
+    ().method();
+}
+",
+            "
+<|>use crate::tr::Tr;
+
+mod tr {
+    pub trait Tr { fn method(&self); }
+    pub trait Unused { fn other(&self); }
+}
+
+impl tr::Tr for () {
+    fn method(&self) {}
+}
+
+fn f() {
+    ().method();
+}
+",
+        );
+    }
+
+    #[test]
+    fn organizes_every_selected_run() {
+        check_assist_range(
+            organize_imports,
+            "
+<|>use b::C;
+use a::B;
+
+mod m {
+    use b::C;
+    use a::{b, a};
+}<|>
+",
+            "
+<|>use a::B;
+use b::C;
+
+mod m {
+    use a::{a, b};
+    use b::C;
+}
+",
+        );
+    }
+
+    #[test]
+    fn organize_imports_not_applicable_to_organized_imports() {
+        check_assist_not_applicable(
+            organize_imports,
+            "
+use std::fmt;
+
+use crate::<|>foo::{Bar, Baz};
+
+fn f(_: Bar, _: Baz, _: fmt::Error) {}
+",
+        );
+    }
+
+    #[test]
+    fn organize_imports_leaves_imports_with_attributes_alone() {
+        check_assist_not_applicable(
+            organize_imports,
+            "
+#[cfg(test)]
+use b::<|>{C, B};
+",
+        );
+    }
+}
@@ -92,6 +92,7 @@ mod assists {
     mod add_derive;
     mod add_explicit_type;
     mod add_impl;
+    mod generate_struct_items;
     mod flip_comma;
     mod flip_binexpr;
     mod change_visibility;
@@ -99,11 +100,14 @@ mod assists {
     mod merge_match_arms;
     mod introduce_variable;
     mod inline_local_variable;
+    mod inline_call;
+    mod extract_function;
     mod raw_string;
     mod replace_if_let_with_match;
     mod split_import;
     mod remove_dbg;
     pub(crate) mod auto_import;
+    mod organize_imports;
     mod add_missing_impl_members;
     mod move_guard;
     mod move_bounds;
@@ -113,6 +117,10 @@ mod assists {
             add_derive::add_derive,
             add_explicit_type::add_explicit_type,
             add_impl::add_impl,
+            generate_struct_items::add_new,
+            generate_struct_items::add_getters,
+            generate_struct_items::add_setters,
+            generate_struct_items::add_default_impl,
             change_visibility::change_visibility,
             fill_match_arms::fill_match_arms,
             merge_match_arms::merge_match_arms,
@@ -123,9 +131,12 @@ mod assists {
             split_import::split_import,
             remove_dbg::remove_dbg,
             auto_import::auto_import,
+            organize_imports::organize_imports,
             add_missing_impl_members::add_missing_impl_members,
             add_missing_impl_members::add_missing_default_members,
             inline_local_variable::inline_local_varialbe,
+            inline_call::inline_call,
+            extract_function::extract_function,
             move_guard::move_guard_to_arm_body,
             move_guard::move_arm_cond_to_match_guard,
             move_bounds::move_bounds_to_where_clause,
@@ -4,4 +4,6 @@ test_utils::marks!(
     introduce_var_in_comment_is_not_applicable
     test_introduce_var_expr_stmt
     test_introduce_var_last_expr
+    extract_function_complex_control_flow
+    inline_call_early_return
 );
@@ -5,9 +5,9 @@ use std::{collections::HashSet, error::Error, path::Path};
 use rustc_hash::FxHashMap;
 
 use crossbeam_channel::{unbounded, Receiver};
-use ra_db::{CrateGraph, FileId, SourceRootId};
+use ra_db::{CrateGraph, FileId, IncludeDir, SourceRootId};
 use ra_ide_api::{AnalysisChange, AnalysisHost, FeatureFlags};
-use ra_project_model::{PackageRoot, ProjectWorkspace};
+use ra_project_model::{get_rustc_cfg_options, PackageRoot, ProjectWorkspace};
 use ra_vfs::{RootEntry, Vfs, VfsChange, VfsTask, Watch};
 use ra_vfs_glob::RustPackageFilterBuilder;
 
@@ -22,7 +22,7 @@ fn vfs_root_to_id(r: ra_vfs::VfsRoot) -> SourceRootId {
 
 pub fn load_cargo(root: &Path) -> Result<(AnalysisHost, FxHashMap<SourceRootId, PackageRoot>)> {
     let root = std::env::current_dir()?.join(root);
-    let ws = ProjectWorkspace::discover(root.as_ref())?;
+    let ws = ProjectWorkspace::discover(root.as_ref(), &Default::default())?;
     let project_roots = ws.to_roots();
     let (sender, receiver) = unbounded();
     let sender = Box::new(move |t| sender.send(t).unwrap());
@@ -41,11 +41,35 @@ pub fn load_cargo(root: &Path) -> Result<(AnalysisHost, FxHashMap<SourceRootId,
         sender,
         Watch(false),
     );
-    let (crate_graph, _crate_names) = ws.to_crate_graph(&mut |path: &Path| {
-        let vfs_file = vfs.load(path);
-        log::debug!("vfs file {:?} -> {:?}", path, vfs_file);
-        vfs_file.map(vfs_file_to_id)
-    });
+
+    let default_cfg_options = {
+        let mut opts = get_rustc_cfg_options();
+        opts.insert_atom("test".into());
+        opts.insert_atom("debug_assertions".into());
+        opts
+    };
+
+    let root_paths = roots
+        .iter()
+        .map(|&vfs_root| (vfs.root2path(vfs_root), vfs_root_to_id(vfs_root)))
+        .collect::<Vec<_>>();
+    let (crate_graph, _crate_names) = ws.to_crate_graph(
+        &default_cfg_options,
+        &mut |path: &Path| {
+            let vfs_file = vfs.load(path);
+            log::debug!("vfs file {:?} -> {:?}", path, vfs_file);
+            vfs_file.map(vfs_file_to_id)
+        },
+        &mut |path: &Path| {
+            let (root_path, source_root) = root_paths
+                .iter()
+                .filter(|(root_path, _)| path.starts_with(root_path))
+                .max_by_key(|(root_path, _)| root_path.components().count())?;
+            IncludeDir::new(path, *source_root, root_path)
+        },
+        // Batch analysis doesn't build the workspace, so there are no proc macros.
+        &mut |_: &Path| Vec::new(),
+    );
     log::debug!("crate graph: {:?}", crate_graph);
 
     let source_roots = roots
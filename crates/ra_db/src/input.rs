@@ -6,9 +6,16 @@
 //! actual IO. See `vfs` and `project_model` in the `ra_lsp_server` crate for how
 //! actual IO is done and lowered to input.
 
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
 use relative_path::{RelativePath, RelativePathBuf};
 use rustc_hash::FxHashMap;
 
+use ra_cfg::CfgOptions;
 use ra_syntax::SmolStr;
 use rustc_hash::FxHashSet;
 
@@ -63,7 +70,7 @@ impl SourceRoot {
 
 /// `CrateGraph` is a bit of information which turns a set of text files into a
 /// number of Rust crates. Each crate is defined by the `FileId` of its root module,
-/// the set of cfg flags (not yet implemented) and the set of dependencies. Note
+/// the set of cfg flags, the environment variables and the set of dependencies. Note
 /// that, due to cfg's, there might be several crates for a single `FileId`! As
 /// in the rust-lang proper, a crate does not have a name. Instead, names are
 /// specified on dependency edges. That is, a crate might be known under
@@ -108,12 +115,109 @@ impl Edition {
 struct CrateData {
     file_id: FileId,
     edition: Edition,
+    cfg_options: CfgOptions,
+    env: Env,
     dependencies: Vec<Dependency>,
+    proc_macros: Vec<ProcMacro>,
+    include_dirs: Vec<IncludeDir>,
+}
+
+/// Environment variables visible to a crate at compile time, as read by `env!`.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct Env {
+    entries: FxHashMap<String, String>,
+}
+
+impl Env {
+    pub fn set(&mut self, env: &str, value: String) {
+        self.entries.insert(env.to_owned(), value);
+    }
+
+    pub fn get(&self, env: &str) -> Option<&str> {
+        self.entries.get(env).map(String::as_str)
+    }
+}
+
+/// A directory whose files a crate can `include!` by absolute path, like the
+/// package root or the `OUT_DIR` of a build script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncludeDir {
+    /// The absolute path of the directory.
+    pub path: PathBuf,
+    /// The source root containing the directory.
+    pub source_root: SourceRootId,
+    /// The path of the directory relative to `source_root`.
+    pub relative_path: RelativePathBuf,
+}
+
+impl IncludeDir {
+    /// Creates the include directory `path`, which is in the source root
+    /// `source_root` with the absolute path `root_path`.
+    pub fn new(path: &Path, source_root: SourceRootId, root_path: &Path) -> Option<IncludeDir> {
+        let relative_path = RelativePathBuf::from_path(path.strip_prefix(root_path).ok()?).ok()?;
+        Some(IncludeDir { path: path.to_path_buf(), source_root, relative_path })
+    }
+
+    /// Returns the path of `path` relative to `source_root`, if it is in this
+    /// directory.
+    pub fn relative_path_of(&self, path: &Path) -> Option<RelativePathBuf> {
+        let rest = path.strip_prefix(&self.path).ok()?;
+        let rest = RelativePathBuf::from_path(rest).ok()?;
+        Some(self.relative_path.join(rest).normalize())
+    }
+}
+
+/// Identifies a procedural macro among the ones exported by a crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ProcMacroId(pub u32);
+
+/// A procedural macro exported by a `proc-macro` crate. We can't run the macro
+/// ourselves, so it is expanded by an opaque `expander`, which typically talks
+/// to a separate process.
+#[derive(Clone)]
+pub struct ProcMacro {
+    pub name: SmolStr,
+    pub kind: ProcMacroKind,
+    pub expander: Arc<dyn tt::TokenExpander>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProcMacroKind {
+    /// `#[proc_macro_derive(Name)]`
+    CustomDerive,
+    /// `#[proc_macro]`
+    FuncLike,
+    /// `#[proc_macro_attribute]`
+    Attr,
 }
 
+impl fmt::Debug for ProcMacro {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProcMacro").field("name", &self.name).field("kind", &self.kind).finish()
+    }
+}
+
+impl PartialEq for ProcMacro {
+    fn eq(&self, other: &ProcMacro) -> bool {
+        self.name == other.name
+            && self.kind == other.kind
+            && Arc::ptr_eq(&self.expander, &other.expander)
+    }
+}
+
+impl Eq for ProcMacro {}
+
 impl CrateData {
-    fn new(file_id: FileId, edition: Edition) -> CrateData {
-        CrateData { file_id, edition, dependencies: Vec::new() }
+    fn new(file_id: FileId, edition: Edition, cfg_options: CfgOptions, env: Env) -> CrateData {
+        CrateData {
+            file_id,
+            edition,
+            cfg_options,
+            env,
+            dependencies: Vec::new(),
+            proc_macros: Vec::new(),
+            include_dirs: Vec::new(),
+        }
     }
 
     fn add_dep(&mut self, name: SmolStr, crate_id: CrateId) {
@@ -134,9 +238,15 @@ impl Dependency {
 }
 
 impl CrateGraph {
-    pub fn add_crate_root(&mut self, file_id: FileId, edition: Edition) -> CrateId {
+    pub fn add_crate_root(
+        &mut self,
+        file_id: FileId,
+        edition: Edition,
+        cfg_options: CfgOptions,
+        env: Env,
+    ) -> CrateId {
         let crate_id = CrateId(self.arena.len() as u32);
-        let prev = self.arena.insert(crate_id, CrateData::new(file_id, edition));
+        let prev = self.arena.insert(crate_id, CrateData::new(file_id, edition, cfg_options, env));
         assert!(prev.is_none());
         crate_id
     }
@@ -154,6 +264,17 @@ impl CrateGraph {
         Ok(())
     }
 
+    /// Sets the procedural macros exported by a `proc-macro` crate.
+    pub fn set_proc_macros(&mut self, crate_id: CrateId, proc_macros: Vec<ProcMacro>) {
+        self.arena.get_mut(&crate_id).unwrap().proc_macros = proc_macros;
+    }
+
+    /// Sets the directories outside of the crate's source root that it can
+    /// `include!` from.
+    pub fn set_include_dirs(&mut self, crate_id: CrateId, include_dirs: Vec<IncludeDir>) {
+        self.arena.get_mut(&crate_id).unwrap().include_dirs = include_dirs;
+    }
+
     pub fn is_empty(&self) -> bool {
         self.arena.is_empty()
     }
@@ -170,6 +291,26 @@ impl CrateGraph {
         self.arena[&crate_id].edition
     }
 
+    pub fn cfg_options(&self, crate_id: CrateId) -> &CfgOptions {
+        &self.arena[&crate_id].cfg_options
+    }
+
+    pub fn env(&self, crate_id: CrateId) -> &Env {
+        &self.arena[&crate_id].env
+    }
+
+    pub fn proc_macros(&self, crate_id: CrateId) -> &[ProcMacro] {
+        &self.arena[&crate_id].proc_macros
+    }
+
+    pub fn include_dirs(&self, crate_id: CrateId) -> &[IncludeDir] {
+        &self.arena[&crate_id].include_dirs
+    }
+
+    pub fn proc_macro(&self, crate_id: CrateId, id: ProcMacroId) -> Option<&ProcMacro> {
+        self.proc_macros(crate_id).get(id.0 as usize)
+    }
+
     // FIXME: this only finds one crate with the given root; we could have multiple
     pub fn crate_id_for_crate_root(&self, file_id: FileId) -> Option<CrateId> {
         let (&crate_id, _) = self.arena.iter().find(|(_crate_id, data)| data.file_id == file_id)?;
@@ -221,14 +362,17 @@ impl CrateGraph {
 
 #[cfg(test)]
 mod tests {
-    use super::{CrateGraph, Edition::Edition2018, FileId, SmolStr};
+    use super::{CfgOptions, CrateGraph, Edition::Edition2018, Env, FileId, SmolStr};
 
     #[test]
     fn it_should_panic_because_of_cycle_dependencies() {
         let mut graph = CrateGraph::default();
-        let crate1 = graph.add_crate_root(FileId(1u32), Edition2018);
-        let crate2 = graph.add_crate_root(FileId(2u32), Edition2018);
-        let crate3 = graph.add_crate_root(FileId(3u32), Edition2018);
+        let crate1 =
+            graph.add_crate_root(FileId(1u32), Edition2018, CfgOptions::default(), Env::default());
+        let crate2 =
+            graph.add_crate_root(FileId(2u32), Edition2018, CfgOptions::default(), Env::default());
+        let crate3 =
+            graph.add_crate_root(FileId(3u32), Edition2018, CfgOptions::default(), Env::default());
         assert!(graph.add_dep(crate1, SmolStr::new("crate2"), crate2).is_ok());
         assert!(graph.add_dep(crate2, SmolStr::new("crate3"), crate3).is_ok());
         assert!(graph.add_dep(crate3, SmolStr::new("crate1"), crate1).is_err());
@@ -237,9 +381,12 @@ mod tests {
     #[test]
     fn it_works() {
         let mut graph = CrateGraph::default();
-        let crate1 = graph.add_crate_root(FileId(1u32), Edition2018);
-        let crate2 = graph.add_crate_root(FileId(2u32), Edition2018);
-        let crate3 = graph.add_crate_root(FileId(3u32), Edition2018);
+        let crate1 =
+            graph.add_crate_root(FileId(1u32), Edition2018, CfgOptions::default(), Env::default());
+        let crate2 =
+            graph.add_crate_root(FileId(2u32), Edition2018, CfgOptions::default(), Env::default());
+        let crate3 =
+            graph.add_crate_root(FileId(3u32), Edition2018, CfgOptions::default(), Env::default());
         assert!(graph.add_dep(crate1, SmolStr::new("crate2"), crate2).is_ok());
         assert!(graph.add_dep(crate2, SmolStr::new("crate3"), crate3).is_ok());
     }
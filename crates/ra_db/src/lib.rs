@@ -10,7 +10,10 @@ use relative_path::RelativePathBuf;
 
 pub use crate::{
     cancellation::Canceled,
-    input::{CrateGraph, CrateId, Dependency, Edition, FileId, SourceRoot, SourceRootId},
+    input::{
+        CrateGraph, CrateId, Dependency, Edition, Env, FileId, IncludeDir, ProcMacro, ProcMacroId,
+        ProcMacroKind, SourceRoot, SourceRootId,
+    },
 };
 pub use salsa;
 
@@ -50,6 +53,17 @@ impl<T: salsa::Database> CheckCanceled for T {
     }
 }
 
+/// Gives the `parse` query the syntax trees obtained by incrementally
+/// reparsing the previous ones after an edit. The trees are kept outside of
+/// salsa, so that each of them is only kept until it is used once: the `parse`
+/// query doesn't depend on them, as it computes the same tree without them,
+/// only slower.
+pub trait FileReparses {
+    /// Takes the reparsed syntax tree of the file, if it was reparsed into
+    /// `text`.
+    fn take_reparse(&self, file_id: FileId, text: &Arc<String>) -> Option<Parse<SourceFile>>;
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct FilePosition {
     pub file_id: FileId,
@@ -67,7 +81,7 @@ pub const DEFAULT_LRU_CAP: usize = 128;
 /// Database which stores all significant input facts: source code and project
 /// model. Everything else in rust-analyzer is derived from these queries.
 #[salsa::query_group(SourceDatabaseStorage)]
-pub trait SourceDatabase: CheckCanceled + std::fmt::Debug {
+pub trait SourceDatabase: CheckCanceled + FileReparses + std::fmt::Debug {
     /// Text of the file.
     #[salsa::input]
     fn file_text(&self, file_id: FileId) -> Arc<String>;
@@ -99,5 +113,8 @@ fn source_root_crates(db: &impl SourceDatabase, id: SourceRootId) -> Arc<Vec<Cra
 fn parse_query(db: &impl SourceDatabase, file_id: FileId) -> Parse<ast::SourceFile> {
     let _p = profile("parse_query");
     let text = db.file_text(file_id);
+    if let Some(parse) = db.take_reparse(file_id, &text) {
+        return parse;
+    }
     SourceFile::parse(&*text)
 }
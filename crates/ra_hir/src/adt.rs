@@ -1,5 +1,5 @@
 //! This module contains the implementation details of the HIR for ADTs, i.e.
-//! structs and enums (and unions).
+//! structs, unions and enums.
 
 use std::sync::Arc;
 
@@ -7,9 +7,11 @@ use ra_arena::{impl_arena_id, Arena, RawId};
 use ra_syntax::ast::{self, NameOwner, StructKind, TypeAscriptionOwner};
 
 use crate::{
+    attr::cfg_filter,
     db::{AstDatabase, DefDatabase, HirDatabase},
+    hygiene::Hygiene,
     type_ref::TypeRef,
-    AsName, Enum, EnumVariant, FieldSource, HasSource, Name, Source, Struct, StructField,
+    AsName, Enum, EnumVariant, FieldSource, HasSource, Name, Source, Struct, StructField, Union,
 };
 
 impl Struct {
@@ -18,6 +20,12 @@ impl Struct {
     }
 }
 
+impl Union {
+    pub(crate) fn variant_data(self, db: &impl DefDatabase) -> Arc<VariantData> {
+        db.struct_data(Struct { id: self.id }).variant_data.clone()
+    }
+}
+
 /// Note that we use `StructData` for unions as well!
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct StructData {
@@ -26,9 +34,13 @@ pub struct StructData {
 }
 
 impl StructData {
-    fn new(struct_def: &ast::StructDef) -> StructData {
+    fn new(
+        struct_def: &ast::StructDef,
+        is_cfg_enabled: &dyn Fn(&dyn ast::AttrsOwner) -> bool,
+        hygiene: &Hygiene,
+    ) -> StructData {
         let name = struct_def.name().map(|n| n.as_name());
-        let variant_data = VariantData::new(struct_def.kind());
+        let variant_data = VariantData::new(struct_def.kind(), is_cfg_enabled, hygiene);
         let variant_data = Arc::new(variant_data);
         StructData { name, variant_data }
     }
@@ -38,12 +50,21 @@ impl StructData {
         struct_: Struct,
     ) -> Arc<StructData> {
         let src = struct_.source(db);
-        Arc::new(StructData::new(&src.ast))
+        let is_cfg_enabled = cfg_filter(db, src.file_id, struct_.module(db).krate);
+        let hygiene = Hygiene::new(db, src.file_id);
+        Arc::new(StructData::new(&src.ast, &is_cfg_enabled, &hygiene))
     }
 }
 
-fn variants(enum_def: &ast::EnumDef) -> impl Iterator<Item = ast::EnumVariant> {
-    enum_def.variant_list().into_iter().flat_map(|it| it.variants())
+fn variants<'a>(
+    enum_def: &ast::EnumDef,
+    is_cfg_enabled: &'a dyn Fn(&dyn ast::AttrsOwner) -> bool,
+) -> impl Iterator<Item = ast::EnumVariant> + 'a {
+    enum_def
+        .variant_list()
+        .into_iter()
+        .flat_map(|it| it.variants())
+        .filter(move |it| is_cfg_enabled(it))
 }
 
 impl EnumVariant {
@@ -52,7 +73,8 @@ impl EnumVariant {
         db: &(impl DefDatabase + AstDatabase),
     ) -> Source<ast::EnumVariant> {
         let src = self.parent.source(db);
-        let ast = variants(&src.ast)
+        let is_cfg_enabled = cfg_filter(db, src.file_id, self.parent.module(db).krate);
+        let ast = variants(&src.ast, &is_cfg_enabled)
             .zip(db.enum_data(self.parent).variants.iter())
             .find(|(_syntax, (id, _))| *id == self.id)
             .unwrap()
@@ -73,11 +95,13 @@ pub struct EnumData {
 impl EnumData {
     pub(crate) fn enum_data_query(db: &(impl DefDatabase + AstDatabase), e: Enum) -> Arc<EnumData> {
         let src = e.source(db);
+        let is_cfg_enabled = cfg_filter(db, src.file_id, e.module(db).krate);
+        let hygiene = Hygiene::new(db, src.file_id);
         let name = src.ast.name().map(|n| n.as_name());
-        let variants = variants(&src.ast)
+        let variants = variants(&src.ast, &is_cfg_enabled)
             .map(|var| EnumVariantData {
                 name: var.name().map(|it| it.as_name()),
-                variant_data: Arc::new(VariantData::new(var.kind())),
+                variant_data: Arc::new(VariantData::new(var.kind(), &is_cfg_enabled, &hygiene)),
             })
             .collect();
         Arc::new(EnumData { name, variants })
@@ -123,18 +147,37 @@ impl VariantData {
             _ => None,
         }
     }
+
+    pub(crate) fn is_unit(&self) -> bool {
+        match &self.0 {
+            VariantDataInner::Unit => true,
+            _ => false,
+        }
+    }
+
+    pub(crate) fn is_tuple(&self) -> bool {
+        match &self.0 {
+            VariantDataInner::Tuple(_) => true,
+            _ => false,
+        }
+    }
 }
 
 impl VariantData {
-    fn new(flavor: StructKind) -> Self {
+    fn new(
+        flavor: StructKind,
+        is_cfg_enabled: &dyn Fn(&dyn ast::AttrsOwner) -> bool,
+        hygiene: &Hygiene,
+    ) -> Self {
         let inner = match flavor {
             ast::StructKind::Tuple(fl) => {
                 let fields = fl
                     .fields()
+                    .filter(|fd| is_cfg_enabled(fd))
                     .enumerate()
                     .map(|(i, fd)| StructFieldData {
                         name: Name::new_tuple_field(i),
-                        type_ref: TypeRef::from_ast_opt(fd.type_ref()),
+                        type_ref: TypeRef::from_ast_opt(fd.type_ref(), hygiene),
                     })
                     .collect();
                 VariantDataInner::Tuple(fields)
@@ -142,9 +185,10 @@ impl VariantData {
             ast::StructKind::Named(fl) => {
                 let fields = fl
                     .fields()
+                    .filter(|fd| is_cfg_enabled(fd))
                     .map(|fd| StructFieldData {
                         name: fd.name().map(|n| n.as_name()).unwrap_or_else(Name::missing),
-                        type_ref: TypeRef::from_ast_opt(fd.ascribed_type()),
+                        type_ref: TypeRef::from_ast_opt(fd.ascribed_type(), hygiene),
                     })
                     .collect();
                 VariantDataInner::Struct(fields)
@@ -158,14 +202,16 @@ impl VariantData {
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum VariantDef {
     Struct(Struct),
+    Union(Union),
     EnumVariant(EnumVariant),
 }
-impl_froms!(VariantDef: Struct, EnumVariant);
+impl_froms!(VariantDef: Struct, Union, EnumVariant);
 
 impl VariantDef {
     pub fn fields(self, db: &impl HirDatabase) -> Vec<StructField> {
         match self {
             VariantDef::Struct(it) => it.fields(db),
+            VariantDef::Union(it) => it.fields(db),
             VariantDef::EnumVariant(it) => it.fields(db),
         }
     }
@@ -173,12 +219,14 @@ impl VariantDef {
     pub(crate) fn field(self, db: &impl HirDatabase, name: &Name) -> Option<StructField> {
         match self {
             VariantDef::Struct(it) => it.field(db, name),
+            VariantDef::Union(it) => it.field(db, name),
             VariantDef::EnumVariant(it) => it.field(db, name),
         }
     }
     pub(crate) fn variant_data(self, db: &impl DefDatabase) -> Arc<VariantData> {
         match self {
             VariantDef::Struct(it) => it.variant_data(db),
+            VariantDef::Union(it) => it.variant_data(db),
             VariantDef::EnumVariant(it) => it.variant_data(db),
         }
     }
@@ -189,21 +237,33 @@ impl StructField {
         let var_data = self.parent.variant_data(db);
         let fields = var_data.fields().unwrap();
         let ss;
+        let us;
         let es;
-        let (file_id, struct_kind) = match self.parent {
+        let (file_id, struct_kind, krate) = match self.parent {
             VariantDef::Struct(s) => {
                 ss = s.source(db);
-                (ss.file_id, ss.ast.kind())
+                (ss.file_id, ss.ast.kind(), s.module(db).krate)
+            }
+            VariantDef::Union(u) => {
+                us = u.source(db);
+                (us.file_id, us.ast.kind(), u.module(db).krate)
             }
             VariantDef::EnumVariant(e) => {
                 es = e.source(db);
-                (es.file_id, es.ast.kind())
+                (es.file_id, es.ast.kind(), e.parent.module(db).krate)
             }
         };
+        let is_cfg_enabled = cfg_filter(db, file_id, krate);
 
         let field_sources = match struct_kind {
-            ast::StructKind::Tuple(fl) => fl.fields().map(|it| FieldSource::Pos(it)).collect(),
-            ast::StructKind::Named(fl) => fl.fields().map(|it| FieldSource::Named(it)).collect(),
+            ast::StructKind::Tuple(fl) => {
+                fl.fields().filter(|it| is_cfg_enabled(it)).map(|it| FieldSource::Pos(it)).collect()
+            }
+            ast::StructKind::Named(fl) => fl
+                .fields()
+                .filter(|it| is_cfg_enabled(it))
+                .map(|it| FieldSource::Named(it))
+                .collect(),
             ast::StructKind::Unit => Vec::new(),
         };
         let ast = field_sources
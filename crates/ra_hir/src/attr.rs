@@ -0,0 +1,285 @@
+//! A higher level attributes based on TokenTree, with also some shortcuts.
+
+use std::sync::Arc;
+
+use mbe::ast_to_token_tree;
+use ra_cfg::CfgOptions;
+use ra_syntax::{
+    ast::{self, AstNode, AttrsOwner},
+    SmolStr,
+};
+use tt::{Leaf, Subtree, TokenTree};
+
+use crate::{
+    db::AstDatabase, hygiene::Hygiene, path::Path, AsName, Crate, HirFileId, PathKind, Source,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Attr {
+    pub(crate) path: Path,
+    pub(crate) input: Option<AttrInput>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttrInput {
+    Literal(SmolStr),
+    TokenTree(Subtree),
+}
+
+impl Attr {
+    pub(crate) fn from_src(
+        Source { file_id, ast }: Source<ast::Attr>,
+        db: &impl AstDatabase,
+    ) -> Option<Attr> {
+        let path = Path::from_src(ast.path()?, &Hygiene::new(db, file_id))?;
+        let input = match ast.input() {
+            None => None,
+            Some(ast::AttrInput::Literal(lit)) => {
+                // FIXME: escape? raw string?
+                let value = lit.syntax().first_token()?.text().trim_matches('"').into();
+                Some(AttrInput::Literal(value))
+            }
+            Some(ast::AttrInput::TokenTree(tt)) => {
+                Some(AttrInput::TokenTree(ast_to_token_tree(&tt)?.0))
+            }
+        };
+
+        Some(Attr { path, input })
+    }
+
+    pub(crate) fn from_attrs_owner(
+        file_id: HirFileId,
+        owner: &dyn AttrsOwner,
+        db: &impl AstDatabase,
+    ) -> Option<Arc<[Attr]>> {
+        let mut attrs = owner.attrs().peekable();
+        if attrs.peek().is_none() {
+            // Avoid heap allocation
+            return None;
+        }
+        Some(attrs.flat_map(|ast| Attr::from_src(Source { file_id, ast }, db)).collect())
+    }
+
+    /// Parses an attribute from the tokens of an attribute listed inside of
+    /// `#[cfg_attr(predicate, attr1, attr2)]`.
+    fn from_tt(tokens: &[TokenTree]) -> Option<Attr> {
+        let (path, rest) = path_from_tt(tokens)?;
+
+        let input = match rest {
+            [] => None,
+            [TokenTree::Subtree(subtree)] => Some(AttrInput::TokenTree(subtree.clone())),
+            [TokenTree::Leaf(Leaf::Punct(eq)), TokenTree::Leaf(Leaf::Literal(lit))]
+                if eq.char == '=' =>
+            {
+                Some(AttrInput::Literal(lit.text.trim_matches('"').into()))
+            }
+            _ => return None,
+        };
+
+        Some(Attr { path, input })
+    }
+
+    /// Returns the paths of the macros listed in `#[derive(A, b::B)]`.
+    pub(crate) fn as_derive(&self) -> Option<Vec<Path>> {
+        if !self.is_simple_atom("derive") {
+            return None;
+        }
+        let subtree = match &self.input {
+            Some(AttrInput::TokenTree(subtree)) => subtree,
+            _ => return None,
+        };
+        let paths = subtree
+            .token_trees
+            .split(|tt| match tt {
+                TokenTree::Leaf(Leaf::Punct(punct)) => punct.char == ',',
+                _ => false,
+            })
+            .filter(|tokens| !tokens.is_empty())
+            .filter_map(|tokens| match path_from_tt(tokens)? {
+                (path, []) => Some(path),
+                _ => None,
+            })
+            .collect();
+        Some(paths)
+    }
+
+    /// Returns `true` for attributes which are known to the compiler, as
+    /// opposed to attribute macros.
+    pub(crate) fn is_builtin(&self) -> bool {
+        match self.path.segments.first() {
+            Some(segment) if self.path.segments.len() > 1 => {
+                TOOL_MODULES.iter().any(|it| segment.name.to_string() == *it)
+            }
+            Some(segment) => BUILTIN_ATTRS.iter().any(|it| segment.name.to_string() == *it),
+            None => false,
+        }
+    }
+
+    pub(crate) fn is_simple_atom(&self, name: &str) -> bool {
+        // FIXME: Avoid cloning
+        self.path.as_ident().map_or(false, |s| s.to_string() == name)
+    }
+
+    pub(crate) fn as_simple_key_value(&self, name: &str) -> Option<&SmolStr> {
+        if !self.is_simple_atom(name) {
+            return None;
+        }
+        match &self.input {
+            Some(AttrInput::Literal(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_cfg(&self) -> Option<&Subtree> {
+        if !self.is_simple_atom("cfg") {
+            return None;
+        }
+        match &self.input {
+            Some(AttrInput::TokenTree(subtree)) => Some(subtree),
+            _ => None,
+        }
+    }
+
+    /// Expands `#[cfg_attr(predicate, attr1, attr2)]` into `attr1` and `attr2`
+    /// if `predicate` holds, and into nothing otherwise.
+    ///
+    /// Returns `None` if this is not a well-formed `cfg_attr`.
+    pub(crate) fn expand_cfg_attr(&self, cfg_options: &CfgOptions) -> Option<Vec<Attr>> {
+        if !self.is_simple_atom("cfg_attr") {
+            return None;
+        }
+        let subtree = match &self.input {
+            Some(AttrInput::TokenTree(subtree)) => subtree,
+            _ => return None,
+        };
+        // The predicate is the first comma separated part, `parse_cfg` stops
+        // right after it.
+        if !cfg_options.is_cfg_enabled(subtree)? {
+            return Some(Vec::new());
+        }
+        let attrs = subtree
+            .token_trees
+            .split(|tt| match tt {
+                TokenTree::Leaf(Leaf::Punct(punct)) => punct.char == ',',
+                _ => false,
+            })
+            .skip(1)
+            .filter(|tokens| !tokens.is_empty())
+            .filter_map(Attr::from_tt)
+            .collect();
+        Some(attrs)
+    }
+
+    pub(crate) fn is_cfg_enabled(&self, cfg_options: &CfgOptions) -> Option<bool> {
+        if let Some(cfg) = self.as_cfg() {
+            return cfg_options.is_cfg_enabled(cfg);
+        }
+        // `cfg_attr` might expand to `cfg`
+        let mut res = None;
+        for attr in self.expand_cfg_attr(cfg_options)? {
+            match attr.is_cfg_enabled(cfg_options) {
+                Some(false) => return Some(false),
+                Some(true) => res = Some(true),
+                None => (),
+            }
+        }
+        res
+    }
+}
+
+/// Attributes of the compiler which may appear on items. Unlike attribute
+/// macros, they don't need to be resolved.
+const BUILTIN_ATTRS: &[&str] = &[
+    "allow",
+    "automatically_derived",
+    "cfg",
+    "cfg_attr",
+    "cold",
+    "deny",
+    "deprecated",
+    "derive",
+    "doc",
+    "export_name",
+    "forbid",
+    "global_allocator",
+    "ignore",
+    "inline",
+    "link",
+    "link_name",
+    "link_section",
+    "macro_export",
+    "macro_use",
+    "must_use",
+    "no_mangle",
+    "non_exhaustive",
+    "panic_handler",
+    "path",
+    "proc_macro",
+    "proc_macro_attribute",
+    "proc_macro_derive",
+    "repr",
+    "should_panic",
+    "stable",
+    "target_feature",
+    "test",
+    "unstable",
+    "used",
+    "warn",
+];
+
+/// Tools whose attributes, like `#[rustfmt::skip]`, are known to the compiler.
+const TOOL_MODULES: &[&str] = &["rustfmt", "clippy"];
+
+/// Parses a simple path like `a::b` from the start of `tokens`, returning the
+/// rest of the tokens.
+fn path_from_tt(tokens: &[TokenTree]) -> Option<(Path, &[TokenTree])> {
+    let mut segments = Vec::new();
+    let mut idx = 0;
+    loop {
+        match tokens.get(idx) {
+            Some(TokenTree::Leaf(Leaf::Ident(ident))) => segments.push(ident.as_name()),
+            _ => return None,
+        }
+        idx += 1;
+        match (tokens.get(idx), tokens.get(idx + 1)) {
+            (
+                Some(TokenTree::Leaf(Leaf::Punct(first))),
+                Some(TokenTree::Leaf(Leaf::Punct(second))),
+            ) if first.char == ':' && second.char == ':' => idx += 2,
+            _ => break,
+        }
+    }
+    Some((Path::from_simple_segments(PathKind::Plain, segments), &tokens[idx..]))
+}
+
+/// Returns `false` if any of `attrs` is a `cfg` (possibly produced by a
+/// `cfg_attr`) which doesn't hold for `cfg_options`.
+pub(crate) fn is_cfg_enabled(attrs: &[Attr], cfg_options: &CfgOptions) -> bool {
+    attrs.iter().all(|attr| attr.is_cfg_enabled(cfg_options) != Some(false))
+}
+
+/// Returns a predicate which checks `#[cfg]` attributes of nodes from `file_id`
+/// against the cfg options of `krate`.
+pub(crate) fn cfg_filter<'a>(
+    db: &'a impl AstDatabase,
+    file_id: HirFileId,
+    krate: Crate,
+) -> impl Fn(&dyn AttrsOwner) -> bool + 'a {
+    let crate_graph = db.crate_graph();
+    move |owner: &dyn AttrsOwner| match Attr::from_attrs_owner(file_id, owner, db) {
+        Some(attrs) => is_cfg_enabled(&attrs, crate_graph.cfg_options(krate.crate_id())),
+        None => true,
+    }
+}
+
+/// Replaces every `cfg_attr` in `attrs` with the attributes it expands to.
+pub(crate) fn expand_cfg_attrs(attrs: &[Attr], cfg_options: &CfgOptions) -> Vec<Attr> {
+    let mut res = Vec::new();
+    for attr in attrs {
+        match attr.expand_cfg_attr(cfg_options) {
+            Some(expanded) => res.extend(expand_cfg_attrs(&expanded, cfg_options)),
+            None => res.push(attr.clone()),
+        }
+    }
+    res
+}
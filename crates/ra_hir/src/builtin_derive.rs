@@ -0,0 +1,146 @@
+//! Builtin derives, like `#[derive(Clone)]`, are implemented by the compiler.
+//!
+//! We expand them to impls without any items: the trait's methods are declared
+//! by the trait itself, so an impl with the right generic bounds is all type
+//! inference needs.
+
+use ra_syntax::ast::{self, make, AstNode};
+
+use crate::{
+    builtin_macro::text_to_tt,
+    db::AstDatabase,
+    ids::{MacroCallId, MacroDefId, MacroDefKind},
+    name, Crate, Name,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BuiltinDeriveExpander {
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Hash,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+}
+
+// FIXME: std declares the builtin derives as `#[rustc_builtin_macro] pub macro
+// Clone(..)`, which we can't parse yet, so they are matched by name instead of
+// being resolved.
+pub(crate) fn find_builtin_derive(ident: &Name, krate: Crate) -> Option<MacroDefId> {
+    let expander = BuiltinDeriveExpander::by_name(ident)?;
+    Some(MacroDefId { ast_id: None, krate, kind: MacroDefKind::BuiltInDerive(expander) })
+}
+
+impl BuiltinDeriveExpander {
+    fn by_name(ident: &Name) -> Option<BuiltinDeriveExpander> {
+        let expander = if *ident == name::CLONE_TRAIT {
+            BuiltinDeriveExpander::Clone
+        } else if *ident == name::COPY_TRAIT {
+            BuiltinDeriveExpander::Copy
+        } else if *ident == name::DEBUG_TRAIT {
+            BuiltinDeriveExpander::Debug
+        } else if *ident == name::DEFAULT_TRAIT {
+            BuiltinDeriveExpander::Default
+        } else if *ident == name::HASH_TRAIT {
+            BuiltinDeriveExpander::Hash
+        } else if *ident == name::EQ_TRAIT {
+            BuiltinDeriveExpander::Eq
+        } else if *ident == name::PARTIAL_EQ_TRAIT {
+            BuiltinDeriveExpander::PartialEq
+        } else if *ident == name::ORD_TRAIT {
+            BuiltinDeriveExpander::Ord
+        } else if *ident == name::PARTIAL_ORD_TRAIT {
+            BuiltinDeriveExpander::PartialOrd
+        } else {
+            return None;
+        };
+        Some(expander)
+    }
+
+    /// The path of the trait in `core`.
+    fn trait_path(self) -> &'static str {
+        match self {
+            BuiltinDeriveExpander::Clone => "clone::Clone",
+            BuiltinDeriveExpander::Copy => "marker::Copy",
+            BuiltinDeriveExpander::Debug => "fmt::Debug",
+            BuiltinDeriveExpander::Default => "default::Default",
+            BuiltinDeriveExpander::Hash => "hash::Hash",
+            BuiltinDeriveExpander::Eq => "cmp::Eq",
+            BuiltinDeriveExpander::PartialEq => "cmp::PartialEq",
+            BuiltinDeriveExpander::Ord => "cmp::Ord",
+            BuiltinDeriveExpander::PartialOrd => "cmp::PartialOrd",
+        }
+    }
+
+    pub(crate) fn expand(
+        self,
+        db: &impl AstDatabase,
+        id: MacroCallId,
+    ) -> Result<tt::Subtree, String> {
+        let loc = id.loc(db);
+        let item = loc.kind.node(db);
+        let def = ast::NominalDef::cast(item).ok_or("Fail to find the derived struct or enum")?;
+        let trait_path = format!("{}::{}", core_path(db, loc.def.krate), self.trait_path());
+        let text = derive_impl(&def, &trait_path).ok_or("Fail to derive impl")?;
+        text_to_tt(&text).ok_or_else(|| "Fail to tokenize derived impl".into())
+    }
+}
+
+/// The path of `core` from `krate`, which works in `no_std` crates too. `std`
+/// reexports the traits, so it is used when `core` isn't a dependency, and a
+/// crate which depends on neither is `core` itself.
+fn core_path(db: &impl AstDatabase, krate: Crate) -> &'static str {
+    let crate_graph = db.crate_graph();
+    let has_dep = |name: &str| {
+        crate_graph.dependencies(krate.crate_id()).any(|dep| dep.name.as_str() == name)
+    };
+    if has_dep("core") {
+        "core"
+    } else if has_dep("std") {
+        "std"
+    } else {
+        "crate"
+    }
+}
+
+/// Builds the impl of `trait_path` for `def`, like
+///
+/// ```text
+/// impl<'a, T: Bound + Trait> Trait for Foo<'a, T> where T: 'a {}
+/// ```
+///
+/// Like rustc, we require every type parameter to implement the trait, even
+/// if it isn't used in a field.
+fn derive_impl(def: &ast::NominalDef, trait_path: &str) -> Option<String> {
+    Some(format!("{} {{}}", make::impl_header(def, Some(trait_path))?))
+}
+
+#[cfg(test)]
+mod tests {
+    use ra_syntax::SourceFile;
+
+    use super::*;
+
+    fn check_derive(item: &str, expected: &str) {
+        let file = SourceFile::parse(item).ok().unwrap();
+        let def = file.syntax().descendants().find_map(ast::NominalDef::cast).unwrap();
+        assert_eq!(derive_impl(&def, "core::clone::Clone").unwrap(), expected);
+    }
+
+    #[test]
+    fn derive_impl_without_generics() {
+        check_derive("struct Foo;", "impl core::clone::Clone for Foo {}");
+    }
+
+    #[test]
+    fn derive_impl_bounds_every_type_param() {
+        check_derive(
+            "enum Foo<'a, T: Copy = u32, U> where T: 'a { A(&'a T), B(U) }",
+            "impl<'a, T: Copy + core::clone::Clone, U: core::clone::Clone> core::clone::Clone \
+             for Foo<'a, T, U> where T: 'a {}",
+        );
+    }
+}
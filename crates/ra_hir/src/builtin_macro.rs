@@ -0,0 +1,302 @@
+//! Builtin macros, like `line!` or `include!`, are implemented by the compiler
+//! itself. In the standard library, they are declared as
+//! `#[rustc_builtin_macro] macro_rules! line { () => {} }`, and we expand them
+//! here instead of via `ra_mbe`.
+
+use std::path::Path;
+
+use ra_db::{CrateId, FileId};
+use ra_syntax::{ast, AstNode, SmolStr, TextUnit};
+use relative_path::RelativePathBuf;
+use tt::{Leaf, TokenTree};
+
+use crate::{
+    db::AstDatabase,
+    ids::{MacroCallId, MacroDefId, MacroDefKind},
+    name, AsName, AstId, Crate, Name,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BuiltinExpander {
+    Column,
+    Concat,
+    Env,
+    File,
+    FormatArgs,
+    Include,
+    Line,
+    Stringify,
+}
+
+pub(crate) fn find_builtin_macro(
+    ident: &Name,
+    krate: Crate,
+    ast_id: AstId<ast::MacroCall>,
+) -> Option<MacroDefId> {
+    let expander = BuiltinExpander::by_name(ident)?;
+    Some(MacroDefId { ast_id: Some(ast_id), krate, kind: MacroDefKind::BuiltIn(expander) })
+}
+
+impl BuiltinExpander {
+    fn by_name(ident: &Name) -> Option<BuiltinExpander> {
+        let expander = if *ident == name::COLUMN_MACRO {
+            BuiltinExpander::Column
+        } else if *ident == name::CONCAT_MACRO {
+            BuiltinExpander::Concat
+        } else if *ident == name::ENV_MACRO {
+            BuiltinExpander::Env
+        } else if *ident == name::FILE_MACRO {
+            BuiltinExpander::File
+        } else if *ident == name::FORMAT_ARGS_MACRO {
+            BuiltinExpander::FormatArgs
+        } else if *ident == name::INCLUDE_MACRO {
+            BuiltinExpander::Include
+        } else if *ident == name::LINE_MACRO {
+            BuiltinExpander::Line
+        } else if *ident == name::STRINGIFY_MACRO {
+            BuiltinExpander::Stringify
+        } else {
+            return None;
+        };
+        Some(expander)
+    }
+
+    pub(crate) fn expand(
+        self,
+        db: &impl AstDatabase,
+        id: MacroCallId,
+        tt: &tt::Subtree,
+    ) -> Result<tt::Subtree, String> {
+        let call_site = CallSite::new(db, id);
+        match self {
+            BuiltinExpander::Column | BuiltinExpander::Line => {
+                let value = self.eval(db, &call_site, tt).ok_or("Fail to expand builtin macro")?;
+                Ok(literal(format!("{}u32", value).into()))
+            }
+            BuiltinExpander::Concat
+            | BuiltinExpander::Env
+            | BuiltinExpander::File
+            | BuiltinExpander::Stringify => {
+                let value = self.eval(db, &call_site, tt).ok_or("Fail to expand builtin macro")?;
+                Ok(literal(format!("{:?}", value).into()))
+            }
+            BuiltinExpander::Include => {
+                let path = split_args(&tt.token_trees)
+                    .next()
+                    .and_then(|arg| eval_arg(db, &call_site, arg))
+                    .ok_or("Fail to evaluate included path")?;
+                let file_id = resolve_include(db, &call_site, &path)
+                    .ok_or_else(|| format!("Fail to find included file: {}", path))?;
+                text_to_tt(&db.file_text(file_id))
+                    .ok_or_else(|| "Fail to tokenize included file".into())
+            }
+            BuiltinExpander::FormatArgs => {
+                expand_format_args(tt).ok_or_else(|| "Fail to expand format_args".into())
+            }
+        }
+    }
+
+    /// Evaluates a builtin macro which produces a literal to the value of that
+    /// literal. This is used to eagerly expand nested calls, like
+    /// `include!(concat!(env!("OUT_DIR"), "/gen.rs"))`.
+    fn eval(self, db: &impl AstDatabase, call_site: &CallSite, tt: &tt::Subtree) -> Option<String> {
+        match self {
+            BuiltinExpander::Column => Some(call_site.line_col(db).1.to_string()),
+            BuiltinExpander::Line => Some(call_site.line_col(db).0.to_string()),
+            BuiltinExpander::File => Some(db.file_relative_path(call_site.file_id).to_string()),
+            BuiltinExpander::Stringify => {
+                let tokens = tt::Subtree {
+                    delimiter: tt::Delimiter::None,
+                    token_trees: tt.token_trees.clone(),
+                };
+                Some(tokens.to_string())
+            }
+            BuiltinExpander::Concat => split_args(&tt.token_trees)
+                .map(|arg| eval_arg(db, call_site, arg))
+                .collect::<Option<Vec<_>>>()
+                .map(|parts| parts.concat()),
+            BuiltinExpander::Env => {
+                let key = split_args(&tt.token_trees)
+                    .next()
+                    .and_then(|arg| eval_arg(db, call_site, arg))?;
+                let krate = call_site.krate(db)?;
+                let crate_graph = db.crate_graph();
+                crate_graph.env(krate).get(&key).map(String::from)
+            }
+            BuiltinExpander::FormatArgs | BuiltinExpander::Include => None,
+        }
+    }
+}
+
+/// The place in the original source a builtin macro is called from.
+struct CallSite {
+    file_id: FileId,
+    offset: TextUnit,
+}
+
+impl CallSite {
+    fn new(db: &impl AstDatabase, id: MacroCallId) -> CallSite {
+        let (file_id, offset) = id.original_call_site(db);
+        CallSite { file_id, offset }
+    }
+
+    /// One-based line and column of the call.
+    fn line_col(&self, db: &impl AstDatabase) -> (usize, usize) {
+        let text = db.file_text(self.file_id);
+        let before = &text[..self.offset.to_usize()];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().map_or(0, |it| it.chars().count()) + 1;
+        (line, column)
+    }
+
+    // FIXME: a file may belong to several crates, we just pick the first one
+    fn krate(&self, db: &impl AstDatabase) -> Option<CrateId> {
+        db.source_root_crates(db.file_source_root(self.file_id)).first().copied()
+    }
+}
+
+fn literal(text: SmolStr) -> tt::Subtree {
+    let leaf = Leaf::from(tt::Literal { text });
+    tt::Subtree { delimiter: tt::Delimiter::None, token_trees: vec![leaf.into()] }
+}
+
+/// Splits macro arguments on top-level commas, ignoring a trailing one.
+fn split_args(tokens: &[TokenTree]) -> impl Iterator<Item = &[TokenTree]> + '_ {
+    tokens.split(|tt| is_punct(tt, ',')).filter(|arg| !arg.is_empty())
+}
+
+/// Evaluates a literal or a nested builtin macro call to its value.
+fn eval_arg(db: &impl AstDatabase, call_site: &CallSite, tokens: &[TokenTree]) -> Option<String> {
+    match tokens {
+        [TokenTree::Leaf(Leaf::Literal(lit))] => literal_value(&lit.text),
+        [TokenTree::Leaf(Leaf::Punct(minus)), TokenTree::Leaf(Leaf::Literal(lit))]
+            if minus.char == '-' =>
+        {
+            Some(format!("-{}", literal_value(&lit.text)?))
+        }
+        // FIXME: nested calls are matched by name, not resolved
+        [TokenTree::Leaf(Leaf::Ident(ident)), bang, TokenTree::Subtree(args)]
+            if is_punct(bang, '!') =>
+        {
+            BuiltinExpander::by_name(&ident.as_name())?.eval(db, call_site, args)
+        }
+        _ => None,
+    }
+}
+
+/// Returns the value of a literal the way `concat!` sees it.
+fn literal_value(text: &str) -> Option<String> {
+    if text.starts_with('r') {
+        let text = text[1..].trim_matches('#');
+        return Some(text.get(1..text.len().checked_sub(1)?)?.to_string());
+    }
+    if text.starts_with('b') {
+        // Byte literals are not allowed in `concat!`
+        return None;
+    }
+    if !(text.starts_with('"') || text.starts_with('\'')) {
+        // Numbers and booleans
+        return Some(text.to_string());
+    }
+    let mut res = String::new();
+    let mut chars = text[1..text.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            res.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => res.push('\n'),
+            't' => res.push('\t'),
+            'r' => res.push('\r'),
+            '0' => res.push('\0'),
+            '\n' => {
+                // Line continuation skips the leading whitespace of the next line
+                chars = chars.as_str().trim_start().chars();
+            }
+            // FIXME: `\x` and `\u` escapes
+            c => res.push(c),
+        }
+    }
+    Some(res)
+}
+
+/// Resolves the path of an `include!` relative to the including file, or, for
+/// absolute paths like the ones built from `OUT_DIR`, in one of the include
+/// directories of the crate.
+fn resolve_include(db: &impl AstDatabase, call_site: &CallSite, path: &str) -> Option<FileId> {
+    if Path::new(path).is_absolute() {
+        let crate_graph = db.crate_graph();
+        return crate_graph.include_dirs(call_site.krate(db)?).iter().find_map(|dir| {
+            let relative_path = dir.relative_path_of(Path::new(path))?;
+            db.source_root(dir.source_root).file_by_relative_path(&relative_path)
+        });
+    }
+    let source_root = db.source_root(db.file_source_root(call_site.file_id));
+    let file_path = db.file_relative_path(call_site.file_id);
+    let root = RelativePathBuf::default();
+    let dir_path = file_path.parent().unwrap_or(&root);
+    source_root.file_by_relative_path(&dir_path.join(path).normalize())
+}
+
+/// Lexes `text` into a flat token tree, the same way a macro sees its input.
+pub(crate) fn text_to_tt(text: &str) -> Option<tt::Subtree> {
+    // The newline guards against a trailing line comment
+    let wrapped = format!("__ra_tokens!{{{}\n}}", text);
+    let file = ast::SourceFile::parse(&wrapped).tree();
+    let macro_call = file.syntax().descendants().find_map(ast::MacroCall::cast)?;
+    let (tt, _) = mbe::ast_to_token_tree(&macro_call.token_tree()?)?;
+    Some(tt::Subtree { delimiter: tt::Delimiter::None, token_trees: tt.token_trees })
+}
+
+/// Expands `format_args!("{} {}", a, b)` to
+///
+/// ```text
+/// std::fmt::Arguments::new_v1(&[], &[
+///     std::fmt::ArgumentV1::new(&(a), std::fmt::Display::fmt),
+///     std::fmt::ArgumentV1::new(&(b), std::fmt::Display::fmt),
+/// ])
+/// ```
+///
+/// which is enough to type check the arguments.
+fn expand_format_args(tt: &tt::Subtree) -> Option<tt::Subtree> {
+    let mut args = split_args(&tt.token_trees);
+    let _format_string = args.next()?;
+
+    let mut elements = Vec::new();
+    for arg in args {
+        // Strip the name of named arguments, `name = expr`
+        let is_named = match arg.get(..3) {
+            Some([TokenTree::Leaf(Leaf::Ident(_)), eq, next]) => {
+                is_punct(eq, '=') && !is_punct(next, '=')
+            }
+            _ => false,
+        };
+        let arg = if is_named { &arg[2..] } else { arg };
+        let mut new_args = text_to_tt("&")?.token_trees;
+        new_args.push(subtree(tt::Delimiter::Parenthesis, arg.to_vec()));
+        new_args.extend(text_to_tt(", std::fmt::Display::fmt")?.token_trees);
+
+        elements.extend(text_to_tt("std::fmt::ArgumentV1::new")?.token_trees);
+        elements.push(subtree(tt::Delimiter::Parenthesis, new_args));
+        elements.extend(text_to_tt(",")?.token_trees);
+    }
+
+    let mut new_v1_args = text_to_tt("&[], &")?.token_trees;
+    new_v1_args.push(subtree(tt::Delimiter::Bracket, elements));
+
+    let mut res = text_to_tt("std::fmt::Arguments::new_v1")?;
+    res.token_trees.push(subtree(tt::Delimiter::Parenthesis, new_v1_args));
+    Some(res)
+}
+
+fn subtree(delimiter: tt::Delimiter, token_trees: Vec<TokenTree>) -> TokenTree {
+    tt::Subtree { delimiter, token_trees }.into()
+}
+
+fn is_punct(tt: &TokenTree, c: char) -> bool {
+    match tt {
+        TokenTree::Leaf(Leaf::Punct(punct)) => punct.char == c,
+        _ => false,
+    }
+}
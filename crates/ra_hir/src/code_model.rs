@@ -10,10 +10,12 @@ use ra_syntax::ast::{self, NameOwner, TypeAscriptionOwner};
 
 use crate::{
     adt::{EnumVariantId, StructFieldId, VariantDef},
+    const_eval::{self, ConstExpr, ConstValue},
     db::{AstDatabase, DefDatabase, HirDatabase},
     diagnostics::DiagnosticSink,
     expr::{validation::ExprValidator, Body, BodySourceMap},
     generics::HasGenericParams,
+    hygiene::Hygiene,
     ids::{
         AstItemDef, ConstId, EnumId, FunctionId, MacroDefId, StaticId, StructId, TraitId,
         TypeAliasId,
@@ -403,10 +405,32 @@ impl Union {
         db.struct_data(Struct { id: self.id }).name.clone()
     }
 
-    pub fn module(self, db: &impl HirDatabase) -> Module {
+    pub fn module(self, db: &impl DefDatabase) -> Module {
         self.id.module(db)
     }
 
+    pub fn krate(self, db: &impl DefDatabase) -> Option<Crate> {
+        self.module(db).krate(db)
+    }
+
+    pub fn fields(self, db: &impl HirDatabase) -> Vec<StructField> {
+        self.variant_data(db)
+            .fields()
+            .into_iter()
+            .flat_map(|it| it.iter())
+            .map(|(id, _)| StructField { parent: self.into(), id })
+            .collect()
+    }
+
+    pub fn field(self, db: &impl HirDatabase, name: &Name) -> Option<StructField> {
+        self.variant_data(db)
+            .fields()
+            .into_iter()
+            .flat_map(|it| it.iter())
+            .find(|(_id, data)| data.name == *name)
+            .map(|(id, _)| StructField { parent: self.into(), id })
+    }
+
     pub fn ty(self, db: &impl HirDatabase) -> Ty {
         db.type_for_def(self.into(), Namespace::Types)
     }
@@ -629,13 +653,14 @@ impl FnData {
         func: Function,
     ) -> Arc<FnData> {
         let src = func.source(db);
+        let hygiene = Hygiene::new(db, src.file_id);
         let name = src.ast.name().map(|n| n.as_name()).unwrap_or_else(Name::missing);
         let mut params = Vec::new();
         let mut has_self_param = false;
         if let Some(param_list) = src.ast.param_list() {
             if let Some(self_param) = param_list.self_param() {
                 let self_type = if let Some(type_ref) = self_param.ascribed_type() {
-                    TypeRef::from_ast(type_ref)
+                    TypeRef::from_ast(type_ref, &hygiene)
                 } else {
                     let self_type = TypeRef::Path(SELF_TYPE.into());
                     match self_param.kind() {
@@ -652,12 +677,12 @@ impl FnData {
                 has_self_param = true;
             }
             for param in param_list.params() {
-                let type_ref = TypeRef::from_ast_opt(param.ascribed_type());
+                let type_ref = TypeRef::from_ast_opt(param.ascribed_type(), &hygiene);
                 params.push(type_ref);
             }
         }
         let ret_type = if let Some(type_ref) = src.ast.ret_type().and_then(|rt| rt.type_ref()) {
-            TypeRef::from_ast(type_ref)
+            TypeRef::from_ast(type_ref, &hygiene)
         } else {
             TypeRef::unit()
         };
@@ -799,12 +824,18 @@ impl Const {
             .unwrap_or_else(|| self.module(db).resolver(db));
         r
     }
+
+    /// The value of this const, if it can be evaluated.
+    pub fn value(self, db: &impl HirDatabase) -> Option<ConstValue> {
+        const_eval::eval_const(db, self, 0)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ConstData {
     pub(crate) name: Option<Name>,
     pub(crate) type_ref: TypeRef,
+    pub(crate) value: ConstExpr,
 }
 
 impl ConstData {
@@ -820,23 +851,30 @@ impl ConstData {
         db: &(impl DefDatabase + AstDatabase),
         konst: Const,
     ) -> Arc<ConstData> {
-        let node = konst.source(db).ast;
-        const_data_for(&node)
+        let src = konst.source(db);
+        let hygiene = Hygiene::new(db, src.file_id);
+        const_data_for(&src.ast, src.ast.body(), &hygiene)
     }
 
     pub(crate) fn static_data_query(
         db: &(impl DefDatabase + AstDatabase),
         konst: Static,
     ) -> Arc<ConstData> {
-        let node = konst.source(db).ast;
-        const_data_for(&node)
+        let src = konst.source(db);
+        let hygiene = Hygiene::new(db, src.file_id);
+        const_data_for(&src.ast, src.ast.body(), &hygiene)
     }
 }
 
-fn const_data_for<N: NameOwner + TypeAscriptionOwner>(node: &N) -> Arc<ConstData> {
+fn const_data_for<N: NameOwner + TypeAscriptionOwner>(
+    node: &N,
+    body: Option<ast::Expr>,
+    hygiene: &Hygiene,
+) -> Arc<ConstData> {
     let name = node.name().map(|n| n.as_name());
-    let type_ref = TypeRef::from_ast_opt(node.ascribed_type());
-    let sig = ConstData { name, type_ref };
+    let type_ref = TypeRef::from_ast_opt(node.ascribed_type(), hygiene);
+    let value = ConstExpr::from_ast_opt(body);
+    let sig = ConstData { name, type_ref, value };
     Arc::new(sig)
 }
 
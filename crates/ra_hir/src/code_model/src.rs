@@ -12,7 +12,7 @@ use crate::{
     ModuleSource, Static, Struct, StructField, Trait, TypeAlias, Union,
 };
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
 pub struct Source<T> {
     pub file_id: HirFileId,
     pub ast: T,
@@ -24,6 +24,12 @@ pub trait HasSource {
 }
 
 impl<T> Source<T> {
+    pub fn as_ref(&self) -> Source<&T> {
+        Source { file_id: self.file_id, ast: &self.ast }
+    }
+    pub fn with_ast<U>(&self, ast: U) -> Source<U> {
+        Source { file_id: self.file_id, ast }
+    }
     pub(crate) fn map<F: FnOnce(T) -> U, U>(self, f: F) -> Source<U> {
         Source { file_id: self.file_id, ast: f(self.ast) }
     }
@@ -118,10 +124,12 @@ impl HasSource for TypeAlias {
         self.id.source(db)
     }
 }
-impl HasSource for MacroDef {
-    type Ast = ast::MacroCall;
-    fn source(self, db: &(impl DefDatabase + AstDatabase)) -> Source<ast::MacroCall> {
-        Source { file_id: self.id.ast_id.file_id(), ast: self.id.ast_id.to_node(db) }
+impl MacroDef {
+    /// Returns the `macro_rules!` definition. Procedural macros don't have
+    /// one.
+    pub fn source(self, db: &(impl DefDatabase + AstDatabase)) -> Option<Source<ast::MacroCall>> {
+        let ast_id = self.id.ast_id?;
+        Some(Source { file_id: ast_id.file_id(), ast: ast_id.to_node(db) })
     }
 }
 
@@ -0,0 +1,246 @@
+//! A small evaluator for constant expressions, like array lengths and the
+//! values of `const` items.
+//!
+//! It understands literals, arithmetic and references to other consts, which
+//! covers most of the constants found in the wild. Everything else evaluates
+//! to `None`.
+
+use std::fmt;
+
+use ra_syntax::{
+    ast::{self, LiteralKind},
+    SmolStr, T,
+};
+
+use crate::{
+    db::HirDatabase,
+    expr::{ArithOp, BinaryOp, Body, CmpOp, Expr, ExprId, Literal, LogicOp, Ordering, UnaryOp},
+    resolve::{Resolver, ValueNs},
+    Const, Path,
+};
+
+/// Consts referring to other consts are followed at most this many times, to
+/// protect against cycles like `const A: usize = B; const B: usize = A;`.
+const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConstValue {
+    Bool(bool),
+    Int(i128),
+}
+
+impl ConstValue {
+    pub fn as_u64(self) -> Option<u64> {
+        match self {
+            ConstValue::Int(it) if it >= 0 && it <= i128::from(u64::max_value()) => Some(it as u64),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ConstValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConstValue::Bool(it) => write!(f, "{}", it),
+            ConstValue::Int(it) => write!(f, "{}", it),
+        }
+    }
+}
+
+/// An unevaluated constant expression. Paths in these are not yet resolved.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ConstExpr {
+    Literal(ConstValue),
+    Path(Path),
+    UnaryOp {
+        expr: Box<ConstExpr>,
+        op: UnaryOp,
+    },
+    BinaryOp {
+        lhs: Box<ConstExpr>,
+        rhs: Box<ConstExpr>,
+        op: BinaryOp,
+    },
+    /// `expr as T`; the value is kept as is.
+    // FIXME: truncate the value to the target type
+    Cast(Box<ConstExpr>),
+    Unknown,
+}
+
+impl ConstExpr {
+    pub(crate) fn from_ast(expr: ast::Expr) -> ConstExpr {
+        match expr {
+            ast::Expr::Literal(lit) => match lit.kind() {
+                LiteralKind::IntNumber { suffix } => int_literal_value(&lit, suffix.as_ref())
+                    .map_or(ConstExpr::Unknown, |it| ConstExpr::Literal(ConstValue::Int(it))),
+                LiteralKind::Bool => {
+                    ConstExpr::Literal(ConstValue::Bool(lit.token().kind() == T![true]))
+                }
+                _ => ConstExpr::Unknown,
+            },
+            ast::Expr::ParenExpr(e) => ConstExpr::from_ast_opt(e.expr()),
+            ast::Expr::PathExpr(e) => {
+                e.path().and_then(Path::from_ast).map_or(ConstExpr::Unknown, ConstExpr::Path)
+            }
+            ast::Expr::PrefixExpr(e) => match e.op_kind() {
+                Some(op) => {
+                    ConstExpr::UnaryOp { expr: Box::new(ConstExpr::from_ast_opt(e.expr())), op }
+                }
+                None => ConstExpr::Unknown,
+            },
+            ast::Expr::BinExpr(e) => match e.op_kind() {
+                Some(op) => ConstExpr::BinaryOp {
+                    lhs: Box::new(ConstExpr::from_ast_opt(e.lhs())),
+                    rhs: Box::new(ConstExpr::from_ast_opt(e.rhs())),
+                    op: op.into(),
+                },
+                None => ConstExpr::Unknown,
+            },
+            ast::Expr::CastExpr(e) => ConstExpr::Cast(Box::new(ConstExpr::from_ast_opt(e.expr()))),
+            _ => ConstExpr::Unknown,
+        }
+    }
+
+    pub(crate) fn from_ast_opt(expr: Option<ast::Expr>) -> ConstExpr {
+        expr.map_or(ConstExpr::Unknown, ConstExpr::from_ast)
+    }
+
+    /// Converts an expression in a body, like the length of `[0; N]`.
+    pub(crate) fn from_body(body: &Body, expr: ExprId) -> ConstExpr {
+        match &body[expr] {
+            Expr::Literal(Literal::Int(value, _)) => {
+                ConstExpr::Literal(ConstValue::Int(i128::from(*value)))
+            }
+            Expr::Literal(Literal::Bool(value)) => ConstExpr::Literal(ConstValue::Bool(*value)),
+            Expr::Path(path) => ConstExpr::Path(path.clone()),
+            Expr::UnaryOp { expr, op } => {
+                ConstExpr::UnaryOp { expr: Box::new(ConstExpr::from_body(body, *expr)), op: *op }
+            }
+            Expr::BinaryOp { lhs, rhs, op: Some(op) } => ConstExpr::BinaryOp {
+                lhs: Box::new(ConstExpr::from_body(body, *lhs)),
+                rhs: Box::new(ConstExpr::from_body(body, *rhs)),
+                op: *op,
+            },
+            Expr::Cast { expr, .. } => ConstExpr::Cast(Box::new(ConstExpr::from_body(body, *expr))),
+            _ => ConstExpr::Unknown,
+        }
+    }
+
+    pub(crate) fn eval(&self, db: &impl HirDatabase, resolver: &Resolver) -> Option<ConstValue> {
+        self.eval_inner(db, resolver, 0)
+    }
+
+    fn eval_inner(
+        &self,
+        db: &impl HirDatabase,
+        resolver: &Resolver,
+        depth: usize,
+    ) -> Option<ConstValue> {
+        match self {
+            ConstExpr::Literal(value) => Some(*value),
+            ConstExpr::Path(path) => match resolver.resolve_path_in_value_ns_fully(db, path)? {
+                ValueNs::Const(konst) => eval_const(db, konst, depth + 1),
+                _ => None,
+            },
+            ConstExpr::UnaryOp { expr, op } => {
+                let value = expr.eval_inner(db, resolver, depth)?;
+                match (op, value) {
+                    (UnaryOp::Neg, ConstValue::Int(it)) => it.checked_neg().map(ConstValue::Int),
+                    (UnaryOp::Not, ConstValue::Bool(it)) => Some(ConstValue::Bool(!it)),
+                    // FIXME: `!` on integers depends on the type
+                    _ => None,
+                }
+            }
+            ConstExpr::BinaryOp { lhs, rhs, op } => {
+                let lhs = lhs.eval_inner(db, resolver, depth)?;
+                let rhs = rhs.eval_inner(db, resolver, depth)?;
+                eval_binary_op(*op, lhs, rhs)
+            }
+            ConstExpr::Cast(expr) => expr.eval_inner(db, resolver, depth),
+            ConstExpr::Unknown => None,
+        }
+    }
+}
+
+pub(crate) fn eval_const(db: &impl HirDatabase, konst: Const, depth: usize) -> Option<ConstValue> {
+    if depth > MAX_DEPTH {
+        return None;
+    }
+    let data = konst.data(db);
+    data.value.eval_inner(db, &konst.resolver(db), depth)
+}
+
+// FIXME: overflow depends on the type of the operands
+fn eval_binary_op(op: BinaryOp, lhs: ConstValue, rhs: ConstValue) -> Option<ConstValue> {
+    let value = match (op, lhs, rhs) {
+        (BinaryOp::ArithOp(op), ConstValue::Int(lhs), ConstValue::Int(rhs)) => {
+            let value = match op {
+                ArithOp::Add => lhs.checked_add(rhs)?,
+                ArithOp::Sub => lhs.checked_sub(rhs)?,
+                ArithOp::Mul => lhs.checked_mul(rhs)?,
+                ArithOp::Div => lhs.checked_div(rhs)?,
+                ArithOp::Rem => lhs.checked_rem(rhs)?,
+                ArithOp::Shl => lhs.checked_shl(shift_amount(rhs)?)?,
+                ArithOp::Shr => lhs.checked_shr(shift_amount(rhs)?)?,
+                ArithOp::BitXor => lhs ^ rhs,
+                ArithOp::BitOr => lhs | rhs,
+                ArithOp::BitAnd => lhs & rhs,
+            };
+            ConstValue::Int(value)
+        }
+        (BinaryOp::ArithOp(op), ConstValue::Bool(lhs), ConstValue::Bool(rhs)) => {
+            let value = match op {
+                ArithOp::BitXor => lhs ^ rhs,
+                ArithOp::BitOr => lhs | rhs,
+                ArithOp::BitAnd => lhs & rhs,
+                _ => return None,
+            };
+            ConstValue::Bool(value)
+        }
+        (BinaryOp::LogicOp(op), ConstValue::Bool(lhs), ConstValue::Bool(rhs)) => {
+            let value = match op {
+                LogicOp::And => lhs && rhs,
+                LogicOp::Or => lhs || rhs,
+            };
+            ConstValue::Bool(value)
+        }
+        (BinaryOp::CmpOp(op), ConstValue::Int(lhs), ConstValue::Int(rhs)) => {
+            ConstValue::Bool(compare(op, lhs, rhs))
+        }
+        (BinaryOp::CmpOp(op), ConstValue::Bool(lhs), ConstValue::Bool(rhs)) => {
+            ConstValue::Bool(compare(op, lhs, rhs))
+        }
+        _ => return None,
+    };
+    Some(value)
+}
+
+fn compare<T: Ord>(op: CmpOp, lhs: T, rhs: T) -> bool {
+    match op {
+        CmpOp::Eq { negated } => (lhs == rhs) != negated,
+        CmpOp::Ord { ordering: Ordering::Less, strict: true } => lhs < rhs,
+        CmpOp::Ord { ordering: Ordering::Less, strict: false } => lhs <= rhs,
+        CmpOp::Ord { ordering: Ordering::Greater, strict: true } => lhs > rhs,
+        CmpOp::Ord { ordering: Ordering::Greater, strict: false } => lhs >= rhs,
+    }
+}
+
+fn shift_amount(rhs: i128) -> Option<u32> {
+    if rhs < 0 || rhs > i128::from(u32::max_value()) {
+        return None;
+    }
+    Some(rhs as u32)
+}
+
+/// Parses the value of an integer literal, like `0xFF_u8`.
+pub(crate) fn int_literal_value(lit: &ast::Literal, suffix: Option<&SmolStr>) -> Option<i128> {
+    let text = lit.token().text().clone();
+    let text = text[..text.len() - suffix.map_or(0, |it| it.len())].replace('_', "");
+    let (text, radix) = match text.get(..2) {
+        Some("0x") => (&text[2..], 16),
+        Some("0o") => (&text[2..], 8),
+        Some("0b") => (&text[2..], 2),
+        _ => (&text[..], 10),
+    };
+    i128::from_str_radix(text, radix).ok()
+}
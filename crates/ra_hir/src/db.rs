@@ -71,13 +71,16 @@ pub trait AstDatabase: InternDatabase {
     fn parse_or_expand(&self, file_id: HirFileId) -> Option<SyntaxNode>;
 
     #[salsa::invoke(crate::ids::HirFileId::parse_macro_query)]
-    fn parse_macro(&self, macro_file: ids::MacroFile) -> Option<Parse<SyntaxNode>>;
+    fn parse_macro(
+        &self,
+        macro_file: ids::MacroFile,
+    ) -> Option<(Parse<SyntaxNode>, Arc<mbe::RevTokenMap>)>;
 
     #[salsa::invoke(crate::ids::macro_def_query)]
-    fn macro_def(&self, macro_id: MacroDefId) -> Option<Arc<mbe::MacroRules>>;
+    fn macro_def(&self, macro_id: MacroDefId) -> Option<Arc<(mbe::MacroRules, mbe::TokenMap)>>;
 
     #[salsa::invoke(crate::ids::macro_arg_query)]
-    fn macro_arg(&self, macro_call: ids::MacroCallId) -> Option<Arc<tt::Subtree>>;
+    fn macro_arg(&self, macro_call: ids::MacroCallId) -> Option<Arc<(tt::Subtree, mbe::TokenMap)>>;
 
     #[salsa::invoke(crate::ids::macro_expand_query)]
     fn macro_expand(&self, macro_call: ids::MacroCallId) -> Result<Arc<tt::Subtree>, String>;
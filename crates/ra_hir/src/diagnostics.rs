@@ -5,7 +5,7 @@ use std::{any::Any, fmt};
 use ra_syntax::{ast, AstNode, AstPtr, SyntaxNode, SyntaxNodePtr, TextRange};
 use relative_path::RelativePathBuf;
 
-use crate::{db::HirDatabase, HirFileId, Name, Source};
+use crate::{db::HirDatabase, HirFileId, MissingPattern, Name, Source};
 
 /// Diagnostic defines hir API for errors and warnings.
 ///
@@ -146,6 +146,49 @@ impl AstDiagnostic for MissingFields {
     }
 }
 
+#[derive(Debug)]
+pub struct UnionExprFieldCount {
+    pub file: HirFileId,
+    pub field_list: AstPtr<ast::RecordFieldList>,
+}
+
+impl Diagnostic for UnionExprFieldCount {
+    fn message(&self) -> String {
+        "union expressions should have exactly one field".to_string()
+    }
+    fn source(&self) -> Source<SyntaxNodePtr> {
+        Source { file_id: self.file, ast: self.field_list.into() }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct MissingMatchArms {
+    pub file: HirFileId,
+    pub match_expr: AstPtr<ast::Expr>,
+    pub arms: AstPtr<ast::MatchArmList>,
+    pub missing_patterns: Vec<MissingPattern>,
+}
+
+impl Diagnostic for MissingMatchArms {
+    fn message(&self) -> String {
+        let mut patterns: Vec<String> =
+            self.missing_patterns.iter().take(3).map(|pat| format!("`{}`", pat)).collect();
+        if self.missing_patterns.len() > 3 {
+            patterns.push(format!("{} more", self.missing_patterns.len() - 3));
+        }
+        format!("missing match arms: {} not covered", patterns.join(", "))
+    }
+    fn source(&self) -> Source<SyntaxNodePtr> {
+        Source { file_id: self.file, ast: self.match_expr.into() }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
 #[derive(Debug)]
 pub struct MissingOkInTailExpr {
     pub file: HirFileId,
@@ -173,3 +216,52 @@ impl AstDiagnostic for MissingOkInTailExpr {
         ast::Expr::cast(node).unwrap()
     }
 }
+
+#[derive(Debug)]
+pub struct InvalidCast {
+    pub file: HirFileId,
+    pub expr: AstPtr<ast::Expr>,
+    pub from_ty: String,
+    pub to_ty: String,
+}
+
+impl Diagnostic for InvalidCast {
+    fn message(&self) -> String {
+        format!("invalid cast: `{}` as `{}`", self.from_ty, self.to_ty)
+    }
+    fn source(&self) -> Source<SyntaxNodePtr> {
+        Source { file_id: self.file, ast: self.expr.into() }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct TrivialCast {
+    pub file: HirFileId,
+    pub expr: AstPtr<ast::Expr>,
+    pub ty: String,
+}
+
+impl Diagnostic for TrivialCast {
+    fn message(&self) -> String {
+        format!("trivial cast: `{}` as `{}`", self.ty, self.ty)
+    }
+    fn source(&self) -> Source<SyntaxNodePtr> {
+        Source { file_id: self.file, ast: self.expr.into() }
+    }
+    fn as_any(&self) -> &(dyn Any + Send + 'static) {
+        self
+    }
+}
+
+impl AstDiagnostic for TrivialCast {
+    type AST = ast::CastExpr;
+
+    fn ast(&self, db: &impl HirDatabase) -> Self::AST {
+        let root = db.parse_or_expand(self.file).unwrap();
+        let node = self.source().ast.to_node(&root);
+        ast::CastExpr::cast(node).unwrap()
+    }
+}
@@ -1,6 +1,7 @@
 //! FIXME: write short doc here
 
 pub(crate) mod lower;
+pub(crate) mod match_check;
 pub(crate) mod scope;
 pub(crate) mod validation;
 
@@ -12,6 +13,7 @@ use rustc_hash::FxHashMap;
 
 use crate::{
     db::HirDatabase,
+    hygiene::SyntaxContext,
     path::GenericArgs,
     ty::primitive::{UncertainFloatTy, UncertainIntTy},
     type_ref::{Mutability, TypeRef},
@@ -44,6 +46,10 @@ pub struct Body {
     params: Vec<PatId>,
     /// The `ExprId` of the actual body expression.
     body_expr: ExprId,
+    /// Expansion contexts of the paths and bindings which come from macro
+    /// expansions. Everything else is in the root context.
+    expr_contexts: FxHashMap<ExprId, SyntaxContext>,
+    pat_contexts: FxHashMap<PatId, SyntaxContext>,
 }
 
 type ExprPtr = Either<AstPtr<ast::Expr>, AstPtr<ast::RecordField>>;
@@ -60,14 +66,13 @@ type PatSource = Source<PatPtr>;
 ///
 /// One complication here is that, due to macro expansion, a single `Body` might
 /// be spread across several files. So, for each ExprId and PatId, we record
-/// both the HirFileId and the position inside the file. However, we only store
-/// AST -> ExprId mapping for non-macro files, as it is not clear how to handle
-/// this properly for macros.
+/// both the HirFileId and the position inside the file, and key the AST ->
+/// ExprId mapping by the file as well.
 #[derive(Default, Debug, Eq, PartialEq)]
 pub struct BodySourceMap {
-    expr_map: FxHashMap<ExprPtr, ExprId>,
+    expr_map: FxHashMap<ExprSource, ExprId>,
     expr_map_back: ArenaMap<ExprId, ExprSource>,
-    pat_map: FxHashMap<PatPtr, PatId>,
+    pat_map: FxHashMap<PatSource, PatId>,
     pat_map_back: ArenaMap<PatId, PatSource>,
     field_map: FxHashMap<(ExprId, usize), AstPtr<ast::RecordField>>,
 }
@@ -92,6 +97,14 @@ impl Body {
     pub fn pats(&self) -> impl Iterator<Item = (PatId, &Pat)> {
         self.pats.iter()
     }
+
+    pub(crate) fn expr_context(&self, expr: ExprId) -> SyntaxContext {
+        self.expr_contexts.get(&expr).copied().unwrap_or(SyntaxContext::ROOT)
+    }
+
+    pub(crate) fn pat_context(&self, pat: PatId) -> SyntaxContext {
+        self.pat_contexts.get(&pat).copied().unwrap_or(SyntaxContext::ROOT)
+    }
 }
 
 // needs arbitrary_self_types to be a method... or maybe move to the def?
@@ -101,19 +114,23 @@ pub(crate) fn resolver_for_expr(
     expr_id: ExprId,
 ) -> Resolver {
     let scopes = db.expr_scopes(body.owner);
-    resolver_for_scope(body, db, scopes.scope_for(expr_id))
+    let ctx = body.expr_context(expr_id);
+    resolver_for_scope(body, db, scopes.scope_for(expr_id), ctx)
 }
 
+/// Only the local bindings of the given expansion context are visible to the
+/// resulting resolver.
 pub(crate) fn resolver_for_scope(
     body: Arc<Body>,
     db: &impl HirDatabase,
     scope_id: Option<scope::ScopeId>,
+    ctx: SyntaxContext,
 ) -> Resolver {
     let mut r = body.owner.resolver(db);
     let scopes = db.expr_scopes(body.owner);
     let scope_chain = scopes.scope_chain(scope_id).collect::<Vec<_>>();
     for scope in scope_chain.into_iter().rev() {
-        r = r.push_expr_scope(Arc::clone(&scopes), scope);
+        r = r.push_expr_scope(Arc::clone(&scopes), scope, ctx);
     }
     r
 }
@@ -139,16 +156,18 @@ impl BodySourceMap {
         self.expr_map_back.get(expr).copied()
     }
 
-    pub(crate) fn node_expr(&self, node: &ast::Expr) -> Option<ExprId> {
-        self.expr_map.get(&Either::A(AstPtr::new(node))).cloned()
+    pub(crate) fn node_expr(&self, node: Source<&ast::Expr>) -> Option<ExprId> {
+        let src = node.map(|it| Either::A(AstPtr::new(it)));
+        self.expr_map.get(&src).cloned()
     }
 
     pub(crate) fn pat_syntax(&self, pat: PatId) -> Option<PatSource> {
         self.pat_map_back.get(pat).copied()
     }
 
-    pub(crate) fn node_pat(&self, node: &ast::Pat) -> Option<PatId> {
-        self.pat_map.get(&Either::A(AstPtr::new(node))).cloned()
+    pub(crate) fn node_pat(&self, node: Source<&ast::Pat>) -> Option<PatId> {
+        let src = node.map(|it| Either::A(AstPtr::new(it)));
+        self.pat_map.get(&src).cloned()
     }
 
     pub(crate) fn field_syntax(&self, expr: ExprId, field: usize) -> AstPtr<ast::RecordField> {
@@ -487,7 +506,11 @@ pub struct RecordFieldPat {
 pub enum Pat {
     Missing,
     Wild,
-    Tuple(Vec<PatId>),
+    Tuple {
+        args: Vec<PatId>,
+        /// The position of `..` in the pattern, if any.
+        ellipsis: Option<usize>,
+    },
     Record {
         path: Option<Path>,
         args: Vec<RecordFieldPat>,
@@ -496,6 +519,8 @@ pub enum Pat {
     Range {
         start: ExprId,
         end: ExprId,
+        /// Whether the range is `start..=end` rather than `start..end`.
+        inclusive: bool,
     },
     Slice {
         prefix: Vec<PatId>,
@@ -512,6 +537,7 @@ pub enum Pat {
     TupleStruct {
         path: Option<Path>,
         args: Vec<PatId>,
+        ellipsis: Option<usize>,
     },
     Ref {
         pat: PatId,
@@ -526,7 +552,7 @@ impl Pat {
             Pat::Bind { subpat, .. } => {
                 subpat.iter().copied().for_each(f);
             }
-            Pat::Tuple(args) | Pat::TupleStruct { args, .. } => {
+            Pat::Tuple { args, .. } | Pat::TupleStruct { args, .. } => {
                 args.iter().copied().for_each(f);
             }
             Pat::Ref { pat, .. } => f(*pat),
@@ -541,6 +567,20 @@ impl Pat {
     }
 }
 
+/// Maps the position of a subpattern in a tuple or tuple struct pattern to the
+/// index of the field it matches, skipping the fields covered by `..`.
+pub(crate) fn tuple_field_index(
+    pos: usize,
+    ellipsis: Option<usize>,
+    arg_count: usize,
+    field_count: usize,
+) -> usize {
+    match ellipsis {
+        Some(ellipsis) if pos >= ellipsis => pos + field_count.saturating_sub(arg_count),
+        _ => pos,
+    }
+}
+
 // Queries
 pub(crate) fn body_with_source_map_query(
     db: &impl HirDatabase,
@@ -1,17 +1,23 @@
 //! FIXME: write short doc here
 
 use ra_arena::Arena;
+use ra_cfg::CfgOptions;
 use ra_syntax::{
     ast::{
-        self, ArgListOwner, ArrayExprKind, LiteralKind, LoopBodyOwner, NameOwner,
+        self, ArgListOwner, ArrayExprKind, AstChildren, LiteralKind, LoopBodyOwner, NameOwner,
         TypeAscriptionOwner,
     },
-    AstNode, AstPtr,
+    AstNode, AstPtr, SyntaxNode, T,
 };
+use rustc_hash::FxHashMap;
 use test_utils::tested_by;
 
 use crate::{
+    attr::{self, Attr},
+    const_eval::int_literal_value,
     db::HirDatabase,
+    hygiene::{Hygiene, SyntaxContext, SyntaxContexts},
+    ids::MacroCallKind,
     name::{AsName, Name, SELF_PARAM},
     path::GenericArgs,
     ty::primitive::{FloatTy, IntTy, UncertainFloatTy, UncertainIntTy},
@@ -23,6 +29,7 @@ use crate::{
 use super::{
     ArithOp, Array, BinaryOp, BindingAnnotation, Body, BodySourceMap, CmpOp, Expr, ExprId, Literal,
     LogicOp, MatchArm, Ordering, Pat, PatId, PatPtr, RecordFieldPat, RecordLitField, Statement,
+    UnaryOp,
 };
 
 pub(super) fn lower(
@@ -33,11 +40,17 @@ pub(super) fn lower(
     params: Option<ast::ParamList>,
     body: Option<ast::Expr>,
 ) -> (Body, BodySourceMap) {
+    let cfg_options = match owner.krate(db) {
+        Some(krate) => db.crate_graph().cfg_options(krate.crate_id()).clone(),
+        None => CfgOptions::default(),
+    };
     ExprCollector {
         resolver,
         db,
-        original_file_id: file_id,
+        cfg_options,
         current_file_id: file_id,
+        hygiene: Hygiene::new(db, file_id),
+        syntax_contexts: SyntaxContexts::default(),
         source_map: BodySourceMap::default(),
         body: Body {
             owner,
@@ -45,6 +58,8 @@ pub(super) fn lower(
             pats: Arena::default(),
             params: Vec::new(),
             body_expr: ExprId((!0).into()),
+            expr_contexts: FxHashMap::default(),
+            pat_contexts: FxHashMap::default(),
         },
     }
     .collect(params, body)
@@ -53,12 +68,13 @@ pub(super) fn lower(
 struct ExprCollector<DB> {
     db: DB,
     resolver: Resolver,
-    // Expr collector expands macros along the way. original points to the file
-    // we started with, current points to the current macro expansion. source
-    // maps don't support macros yet, so we only record info into source map if
-    // current == original (see #1196)
-    original_file_id: HirFileId,
+    cfg_options: CfgOptions,
+    // Expr collector expands macros along the way, current points to the
+    // current macro expansion. Source map entries are keyed by the file they
+    // come from, so nodes inside of expansions can be mapped as well.
     current_file_id: HirFileId,
+    hygiene: Hygiene,
+    syntax_contexts: SyntaxContexts,
 
     body: Body,
     source_map: BodySourceMap,
@@ -104,12 +120,9 @@ where
     fn alloc_expr(&mut self, expr: Expr, ptr: AstPtr<ast::Expr>) -> ExprId {
         let ptr = Either::A(ptr);
         let id = self.body.exprs.alloc(expr);
-        if self.current_file_id == self.original_file_id {
-            self.source_map.expr_map.insert(ptr, id);
-        }
-        self.source_map
-            .expr_map_back
-            .insert(id, Source { file_id: self.current_file_id, ast: ptr });
+        let src = Source { file_id: self.current_file_id, ast: ptr };
+        self.source_map.expr_map.insert(src, id);
+        self.source_map.expr_map_back.insert(id, src);
         id
     }
     // desugared exprs don't have ptr, that's wrong and should be fixed
@@ -120,23 +133,48 @@ where
     fn alloc_expr_field_shorthand(&mut self, expr: Expr, ptr: AstPtr<ast::RecordField>) -> ExprId {
         let ptr = Either::B(ptr);
         let id = self.body.exprs.alloc(expr);
-        if self.current_file_id == self.original_file_id {
-            self.source_map.expr_map.insert(ptr, id);
-        }
-        self.source_map
-            .expr_map_back
-            .insert(id, Source { file_id: self.current_file_id, ast: ptr });
+        let src = Source { file_id: self.current_file_id, ast: ptr };
+        self.source_map.expr_map.insert(src, id);
+        self.source_map.expr_map_back.insert(id, src);
         id
     }
     fn alloc_pat(&mut self, pat: Pat, ptr: PatPtr) -> PatId {
         let id = self.body.pats.alloc(pat);
-        if self.current_file_id == self.original_file_id {
-            self.source_map.pat_map.insert(ptr, id);
-        }
-        self.source_map.pat_map_back.insert(id, Source { file_id: self.current_file_id, ast: ptr });
+        let src = Source { file_id: self.current_file_id, ast: ptr };
+        self.source_map.pat_map.insert(src, id);
+        self.source_map.pat_map_back.insert(id, src);
         id
     }
 
+    /// Records the expansion context of the identifier starting at `node` for
+    /// the path `expr`.
+    fn set_expr_context(&mut self, expr: ExprId, node: &SyntaxNode) {
+        if let Some(ctx) = self.syntax_context(node) {
+            self.body.expr_contexts.insert(expr, ctx);
+        }
+    }
+
+    /// Records the expansion context of the name of the binding `pat`.
+    fn set_pat_context(&mut self, pat: PatId, node: &SyntaxNode) {
+        if let Some(ctx) = self.syntax_context(node) {
+            self.body.pat_contexts.insert(pat, ctx);
+        }
+    }
+
+    fn syntax_context(&mut self, node: &SyntaxNode) -> Option<SyntaxContext> {
+        if !self.current_file_id.is_macro_file() {
+            return None;
+        }
+        let token = node.first_token()?;
+        let src = Source { file_id: self.current_file_id, ast: token };
+        let ctx = self.syntax_contexts.of_token(self.db, src);
+        if ctx == SyntaxContext::ROOT {
+            None
+        } else {
+            Some(ctx)
+        }
+    }
+
     fn empty_block(&mut self) -> ExprId {
         let block = Expr::Block { statements: Vec::new(), tail: None };
         self.body.exprs.alloc(block)
@@ -248,7 +286,8 @@ where
                     Vec::new()
                 };
                 let method_name = e.name_ref().map(|nr| nr.as_name()).unwrap_or_else(Name::missing);
-                let generic_args = e.type_arg_list().and_then(GenericArgs::from_ast);
+                let generic_args =
+                    e.type_arg_list().and_then(|it| GenericArgs::from_ast(it, &self.hygiene));
                 self.alloc_expr(
                     Expr::MethodCall { receiver, method_name, args, generic_args },
                     syntax_ptr,
@@ -259,13 +298,18 @@ where
                 let arms = if let Some(match_arm_list) = e.match_arm_list() {
                     match_arm_list
                         .arms()
-                        .map(|arm| MatchArm {
-                            pats: arm.pats().map(|p| self.collect_pat(p)).collect(),
-                            expr: self.collect_expr_opt(arm.expr()),
-                            guard: arm
-                                .guard()
-                                .and_then(|guard| guard.expr())
-                                .map(|e| self.collect_expr(e)),
+                        .filter_map(|arm| {
+                            if !self.is_cfg_enabled(&arm) {
+                                return None;
+                            }
+                            Some(MatchArm {
+                                pats: arm.pats().map(|p| self.collect_pat(p)).collect(),
+                                expr: self.collect_expr_opt(arm.expr()),
+                                guard: arm
+                                    .guard()
+                                    .and_then(|guard| guard.expr())
+                                    .map(|e| self.collect_expr(e)),
+                            })
                         })
                         .collect()
                 } else {
@@ -279,7 +323,9 @@ where
                     .and_then(|path| self.parse_path(path))
                     .map(Expr::Path)
                     .unwrap_or(Expr::Missing);
-                self.alloc_expr(path, syntax_ptr)
+                let id = self.alloc_expr(path, syntax_ptr);
+                self.set_expr_context(id, e.syntax());
+                id
             }
             ast::Expr::ContinueExpr(_e) => {
                 // FIXME: labels
@@ -292,7 +338,8 @@ where
             ast::Expr::ParenExpr(e) => {
                 let inner = self.collect_expr_opt(e.expr());
                 // make the paren expr point to the inner expression as well
-                self.source_map.expr_map.insert(Either::A(syntax_ptr), inner);
+                let src = Source { file_id: self.current_file_id, ast: Either::A(syntax_ptr) };
+                self.source_map.expr_map.insert(src, inner);
                 inner
             }
             ast::Expr::ReturnExpr(e) => {
@@ -305,23 +352,30 @@ where
                 let record_lit = if let Some(nfl) = e.record_field_list() {
                     let fields = nfl
                         .fields()
-                        .inspect(|field| field_ptrs.push(AstPtr::new(field)))
-                        .map(|field| RecordLitField {
-                            name: field
-                                .name_ref()
-                                .map(|nr| nr.as_name())
-                                .unwrap_or_else(Name::missing),
-                            expr: if let Some(e) = field.expr() {
-                                self.collect_expr(e)
-                            } else if let Some(nr) = field.name_ref() {
-                                // field shorthand
-                                self.alloc_expr_field_shorthand(
-                                    Expr::Path(Path::from_name_ref(&nr)),
-                                    AstPtr::new(&field),
-                                )
-                            } else {
-                                self.missing_expr()
-                            },
+                        .filter_map(|field| {
+                            if !self.is_cfg_enabled(&field) {
+                                return None;
+                            }
+                            field_ptrs.push(AstPtr::new(&field));
+                            Some(RecordLitField {
+                                name: field
+                                    .name_ref()
+                                    .map(|nr| nr.as_name())
+                                    .unwrap_or_else(Name::missing),
+                                expr: if let Some(e) = field.expr() {
+                                    self.collect_expr(e)
+                                } else if let Some(nr) = field.name_ref() {
+                                    // field shorthand
+                                    let id = self.alloc_expr_field_shorthand(
+                                        Expr::Path(Path::from_name_ref(&nr)),
+                                        AstPtr::new(&field),
+                                    );
+                                    self.set_expr_context(id, nr.syntax());
+                                    id
+                                } else {
+                                    self.missing_expr()
+                                },
+                            })
                         })
                         .collect();
                     let spread = nfl.spread().map(|s| self.collect_expr(s));
@@ -354,7 +408,7 @@ where
             }
             ast::Expr::CastExpr(e) => {
                 let expr = self.collect_expr_opt(e.expr());
-                let type_ref = TypeRef::from_ast_opt(e.type_ref());
+                let type_ref = TypeRef::from_ast_opt(e.type_ref(), &self.hygiene);
                 self.alloc_expr(Expr::Cast { expr, type_ref }, syntax_ptr)
             }
             ast::Expr::RefExpr(e) => {
@@ -376,7 +430,8 @@ where
                 if let Some(pl) = e.param_list() {
                     for param in pl.params() {
                         let pat = self.collect_pat_opt(param.pat());
-                        let type_ref = param.ascribed_type().map(TypeRef::from_ast);
+                        let type_ref =
+                            param.ascribed_type().map(|it| TypeRef::from_ast(it, &self.hygiene));
                         args.push(pat);
                         arg_types.push(type_ref);
                     }
@@ -419,33 +474,7 @@ where
             }
 
             ast::Expr::Literal(e) => {
-                let lit = match e.kind() {
-                    LiteralKind::IntNumber { suffix } => {
-                        let known_name = suffix
-                            .and_then(|it| IntTy::from_suffix(&it).map(UncertainIntTy::Known));
-
-                        Literal::Int(
-                            Default::default(),
-                            known_name.unwrap_or(UncertainIntTy::Unknown),
-                        )
-                    }
-                    LiteralKind::FloatNumber { suffix } => {
-                        let known_name = suffix
-                            .and_then(|it| FloatTy::from_suffix(&it).map(UncertainFloatTy::Known));
-
-                        Literal::Float(
-                            Default::default(),
-                            known_name.unwrap_or(UncertainFloatTy::Unknown),
-                        )
-                    }
-                    LiteralKind::ByteString => Literal::ByteString(Default::default()),
-                    LiteralKind::String => Literal::String(Default::default()),
-                    LiteralKind::Byte => {
-                        Literal::Int(Default::default(), UncertainIntTy::Known(IntTy::u8()))
-                    }
-                    LiteralKind::Bool => Literal::Bool(Default::default()),
-                    LiteralKind::Char => Literal::Char(Default::default()),
-                };
+                let lit = self.collect_literal(&e);
                 self.alloc_expr(Expr::Literal(lit), syntax_ptr)
             }
             ast::Expr::IndexExpr(e) => {
@@ -466,15 +495,26 @@ where
 
                 if let Some(path) = e.path().and_then(|path| self.parse_path(path)) {
                     if let Some(def) = self.resolver.resolve_path_as_macro(self.db, &path) {
-                        let call_id = MacroCallLoc { def: def.id, ast_id }.id(self.db);
+                        let call_id =
+                            MacroCallLoc { def: def.id, kind: MacroCallKind::FnLike(ast_id) }
+                                .id(self.db);
                         let file_id = call_id.as_file(MacroFileKind::Expr);
                         if let Some(node) = self.db.parse_or_expand(file_id) {
                             if let Some(expr) = ast::Expr::cast(node) {
                                 log::debug!("macro expansion {:#?}", expr.syntax());
                                 let old_file_id =
                                     std::mem::replace(&mut self.current_file_id, file_id);
+                                let old_hygiene = std::mem::replace(
+                                    &mut self.hygiene,
+                                    Hygiene::new(self.db, file_id),
+                                );
                                 let id = self.collect_expr(expr);
                                 self.current_file_id = old_file_id;
+                                self.hygiene = old_hygiene;
+                                // make the macro call point to the expansion as well
+                                let src =
+                                    Source { file_id: old_file_id, ast: Either::A(syntax_ptr) };
+                                self.source_map.expr_map.insert(src, id);
                                 return id;
                             }
                         }
@@ -506,7 +546,8 @@ where
             .map(|s| match s {
                 ast::Stmt::LetStmt(stmt) => {
                     let pat = self.collect_pat_opt(stmt.pat());
-                    let type_ref = stmt.ascribed_type().map(TypeRef::from_ast);
+                    let type_ref =
+                        stmt.ascribed_type().map(|it| TypeRef::from_ast(it, &self.hygiene));
                     let initializer = stmt.initializer().map(|e| self.collect_expr(e));
                     Statement::Let { pat, type_ref, initializer }
                 }
@@ -535,8 +576,8 @@ where
             }
             ast::Pat::TupleStructPat(p) => {
                 let path = p.path().and_then(|path| self.parse_path(path));
-                let args = p.args().map(|p| self.collect_pat(p)).collect();
-                Pat::TupleStruct { path, args }
+                let (args, ellipsis) = self.collect_tuple_pat(p.args());
+                Pat::TupleStruct { path, args, ellipsis }
             }
             ast::Pat::RefPat(p) => {
                 let pat = self.collect_pat_opt(p.pat());
@@ -548,8 +589,8 @@ where
                 path.map(Pat::Path).unwrap_or(Pat::Missing)
             }
             ast::Pat::TuplePat(p) => {
-                let args = p.args().map(|p| self.collect_pat(p)).collect();
-                Pat::Tuple(args)
+                let (args, ellipsis) = self.collect_tuple_pat(p.args());
+                Pat::Tuple { args, ellipsis }
             }
             ast::Pat::PlaceholderPat(_) => Pat::Wild,
             ast::Pat::RecordPat(p) => {
@@ -577,14 +618,116 @@ where
                 Pat::Record { path, args: fields }
             }
 
+            ast::Pat::LiteralPat(p) => Pat::Lit(self.collect_literal_pat(p)),
+            ast::Pat::RangePat(p) => {
+                let start = self.collect_range_pat_bound(p.start());
+                let end = self.collect_range_pat_bound(p.end());
+                Pat::Range { start, end, inclusive: p.is_inclusive() }
+            }
+            ast::Pat::SlicePat(p) => {
+                let mut prefix = Vec::new();
+                let mut rest = None;
+                let mut suffix = Vec::new();
+                for arg in p.args() {
+                    let is_rest = match &arg {
+                        ast::Pat::DotDotPat(_) => true,
+                        ast::Pat::BindPat(bp) => match bp.pat() {
+                            Some(ast::Pat::DotDotPat(_)) => true,
+                            _ => false,
+                        },
+                        _ => false,
+                    };
+                    let pat = self.collect_pat(arg);
+                    if is_rest && rest.is_none() {
+                        rest = Some(pat);
+                    } else if rest.is_none() {
+                        prefix.push(pat);
+                    } else {
+                        suffix.push(pat);
+                    }
+                }
+                Pat::Slice { prefix, rest, suffix }
+            }
+            // `..` only appears in tuples and slices, where it matches anything.
+            ast::Pat::DotDotPat(_) => Pat::Wild,
+
             // FIXME: implement
-            ast::Pat::DotDotPat(_) => Pat::Missing,
             ast::Pat::BoxPat(_) => Pat::Missing,
-            ast::Pat::LiteralPat(_) => Pat::Missing,
-            ast::Pat::SlicePat(_) | ast::Pat::RangePat(_) => Pat::Missing,
         };
         let ptr = AstPtr::new(&pat);
-        self.alloc_pat(pattern, Either::A(ptr))
+        let id = self.alloc_pat(pattern, Either::A(ptr));
+        if let ast::Pat::BindPat(bp) = &pat {
+            if let Some(name) = bp.name() {
+                self.set_pat_context(id, name.syntax());
+            }
+        }
+        id
+    }
+
+    fn collect_tuple_pat(&mut self, args: AstChildren<ast::Pat>) -> (Vec<PatId>, Option<usize>) {
+        let mut ellipsis = None;
+        let mut pats = Vec::new();
+        for arg in args {
+            match arg {
+                ast::Pat::DotDotPat(_) if ellipsis.is_none() => ellipsis = Some(pats.len()),
+                _ => pats.push(self.collect_pat(arg)),
+            }
+        }
+        (pats, ellipsis)
+    }
+
+    fn collect_literal(&self, e: &ast::Literal) -> Literal {
+        match e.kind() {
+            LiteralKind::IntNumber { suffix } => {
+                let value = int_literal_value(e, suffix.as_ref())
+                    .filter(|&it| it >= 0 && it <= i128::from(u64::max_value()))
+                    .map_or(0, |it| it as u64);
+                let known_name =
+                    suffix.and_then(|it| IntTy::from_suffix(&it).map(UncertainIntTy::Known));
+
+                Literal::Int(value, known_name.unwrap_or(UncertainIntTy::Unknown))
+            }
+            LiteralKind::FloatNumber { suffix } => {
+                let known_name =
+                    suffix.and_then(|it| FloatTy::from_suffix(&it).map(UncertainFloatTy::Known));
+
+                Literal::Float(Default::default(), known_name.unwrap_or(UncertainFloatTy::Unknown))
+            }
+            LiteralKind::ByteString => Literal::ByteString(Default::default()),
+            LiteralKind::String => Literal::String(Default::default()),
+            LiteralKind::Byte => {
+                Literal::Int(Default::default(), UncertainIntTy::Known(IntTy::u8()))
+            }
+            LiteralKind::Bool => Literal::Bool(e.token().kind() == T![true]),
+            LiteralKind::Char => Literal::Char(Default::default()),
+        }
+    }
+
+    fn collect_literal_pat(&mut self, pat: &ast::LiteralPat) -> ExprId {
+        // The literal is only mapped to the pattern, so that it's not typed twice
+        let lit = match pat.literal() {
+            Some(it) => {
+                let lit = self.collect_literal(&it);
+                self.alloc_expr_desugared(Expr::Literal(lit))
+            }
+            None => return self.missing_expr(),
+        };
+        if pat.is_negative() {
+            self.alloc_expr_desugared(Expr::UnaryOp { expr: lit, op: UnaryOp::Neg })
+        } else {
+            lit
+        }
+    }
+
+    fn collect_range_pat_bound(&mut self, pat: Option<ast::Pat>) -> ExprId {
+        match pat {
+            Some(ast::Pat::LiteralPat(p)) => self.collect_literal_pat(&p),
+            Some(ast::Pat::PathPat(p)) => match p.path().and_then(|path| self.parse_path(path)) {
+                Some(path) => self.alloc_expr_desugared(Expr::Path(path)),
+                None => self.missing_expr(),
+            },
+            _ => self.missing_expr(),
+        }
     }
 
     fn collect_pat_opt(&mut self, pat: Option<ast::Pat>) -> PatId {
@@ -596,7 +739,14 @@ where
     }
 
     fn parse_path(&mut self, path: ast::Path) -> Option<Path> {
-        Path::from_src(Source { ast: path, file_id: self.current_file_id }, self.db)
+        Path::from_src(path, &self.hygiene)
+    }
+
+    fn is_cfg_enabled(&self, owner: &impl ast::AttrsOwner) -> bool {
+        match Attr::from_attrs_owner(self.current_file_id, owner, self.db) {
+            Some(attrs) => attr::is_cfg_enabled(&attrs, &self.cfg_options),
+            None => true,
+        }
     }
 }
 
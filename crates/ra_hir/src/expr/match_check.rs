@@ -0,0 +1,713 @@
+//! Checks that `match` expressions are exhaustive.
+//!
+//! This is the usefulness algorithm from "Warnings for pattern matching" by Luc
+//! Maranget: a match is exhaustive iff a wildcard arm added at the end would
+//! not be useful. Patterns are first lowered, driven by the type of the
+//! scrutinee, to a small language of constructors: enum variants, `true` and
+//! `false`, integer ranges, slice lengths and the single constructor of
+//! structs, unions, tuples and references. Everything else, like string
+//! literals, is opaque: such patterns match some values, but never cover a
+//! whole constructor.
+
+use std::{fmt, iter};
+
+use crate::{
+    adt::VariantDef,
+    const_eval::{ConstExpr, ConstValue},
+    db::HirDatabase,
+    expr::{
+        tuple_field_index, BindingAnnotation, Body, Expr, ExprId, Literal, MatchArm, Pat, PatId,
+    },
+    resolve::{Resolver, ValueNs},
+    ty::{
+        primitive::{IntBitness, IntTy, Signedness, UncertainIntTy},
+        ApplicationTy, InferenceResult, Substs, Ty, TypeCtor, TypeWalk,
+    },
+    Adt, EnumVariant, Path,
+};
+
+/// A pattern that is not covered by the arms of a `match` expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingPattern {
+    text: String,
+    enum_variant: Option<EnumVariant>,
+}
+
+impl MissingPattern {
+    /// The enum variant this pattern matches, looking through references.
+    pub fn enum_variant(&self) -> Option<EnumVariant> {
+        self.enum_variant
+    }
+}
+
+impl fmt::Display for MissingPattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// Computes the patterns not covered by `arms`. Returns `None` if the match
+/// can't be checked, for example because of unknown types or unresolved paths.
+pub(crate) fn missing_patterns(
+    db: &impl HirDatabase,
+    body: &Body,
+    infer: &InferenceResult,
+    resolver: &Resolver,
+    scrutinee: ExprId,
+    arms: &[MatchArm],
+) -> Option<Vec<MissingPattern>> {
+    let ty = &infer[scrutinee];
+    if *ty == Ty::Unknown {
+        return None;
+    }
+    let cx = MatchCheckCtx { db, body, infer, resolver };
+
+    let mut rows = Vec::new();
+    for arm in arms {
+        // A guard may fail, so guarded arms don't cover anything.
+        if arm.guard.is_some() {
+            continue;
+        }
+        for &pat in arm.pats.iter() {
+            rows.push(vec![cx.lower_pat(pat, ty)?]);
+        }
+    }
+
+    let missing = cx
+        .missing(&rows, &[ty.clone()])
+        .into_iter()
+        .filter_map(|mut witness| {
+            let pattern = witness.pop()?;
+            let text = cx.render(&pattern, ty);
+            let enum_variant = cx.enum_variant(&pattern, ty);
+            Some(MissingPattern { text, enum_variant })
+        })
+        .collect();
+    Some(missing)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Constructor {
+    /// The only constructor of structs, unions, tuples and references.
+    Single,
+    Variant(EnumVariant),
+    Bool(bool),
+    IntRange(IntRange),
+    /// Slices of length `len`, or of at least that length if `var` is set.
+    Slice {
+        len: u64,
+        var: bool,
+    },
+}
+
+/// A pattern in the lowered form the algorithm works with. Missing patterns
+/// are expressed in the same form.
+#[derive(Debug, Clone)]
+enum Pattern {
+    Wild,
+    /// A pattern we can't reason about, like a string literal.
+    Opaque,
+    Ctor(Constructor, Vec<Pattern>),
+    Slice {
+        prefix: Vec<Pattern>,
+        rest: bool,
+        suffix: Vec<Pattern>,
+    },
+}
+
+type Row = Vec<Pattern>;
+
+/// An inclusive range of integers. Values are stored as `u128`, with the sign
+/// bit of signed values flipped, so that ranges of all integer types can be
+/// compared in the same way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct IntRange {
+    lo: u128,
+    hi: u128,
+    signed: bool,
+}
+
+impl IntRange {
+    fn of_type(ty: IntTy) -> IntRange {
+        let bits = match ty.bitness {
+            IntBitness::X8 => 8,
+            IntBitness::X16 => 16,
+            IntBitness::X32 => 32,
+            // FIXME: the size of `usize` depends on the target
+            IntBitness::X64 | IntBitness::Xsize => 64,
+            IntBitness::X128 => 128,
+        };
+        match ty.signedness {
+            Signedness::Signed => IntRange {
+                lo: encode_signed(i128::min_value() >> (128 - bits)),
+                hi: encode_signed(i128::max_value() >> (128 - bits)),
+                signed: true,
+            },
+            Signedness::Unsigned => {
+                IntRange { lo: 0, hi: u128::max_value() >> (128 - bits), signed: false }
+            }
+        }
+    }
+
+    /// The range `lo..=hi` of values of type `ty`, if it is not empty and
+    /// fits the type.
+    fn new(ty: IntTy, lo: i128, hi: i128) -> Option<IntRange> {
+        let full = IntRange::of_type(ty);
+        let encode = |value: i128| {
+            if full.signed {
+                Some(encode_signed(value))
+            } else if value >= 0 {
+                Some(value as u128)
+            } else {
+                None
+            }
+        };
+        let range = IntRange { lo: encode(lo)?, hi: encode(hi)?, signed: full.signed };
+        if range.lo > range.hi || !full.contains(&range) {
+            return None;
+        }
+        Some(range)
+    }
+
+    fn contains(&self, other: &IntRange) -> bool {
+        self.lo <= other.lo && other.hi <= self.hi
+    }
+
+    /// Splits this range into subranges, such that each of `ranges` either
+    /// contains a subrange or is disjoint from it.
+    fn split(self, ranges: impl Iterator<Item = IntRange>) -> Vec<IntRange> {
+        let mut borders = vec![self.lo];
+        for range in ranges {
+            borders.push(range.lo);
+            if range.hi < self.hi {
+                borders.push(range.hi + 1);
+            }
+        }
+        borders.sort();
+        borders.dedup();
+        let ends = borders.iter().skip(1).map(|&border| border - 1).chain(iter::once(self.hi));
+        borders.iter().zip(ends).map(|(&lo, hi)| IntRange { lo, hi, signed: self.signed }).collect()
+    }
+
+    fn display_value(&self, value: u128) -> String {
+        if self.signed {
+            ((value ^ SIGN_BIT) as i128).to_string()
+        } else {
+            value.to_string()
+        }
+    }
+}
+
+impl fmt::Display for IntRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.lo == self.hi {
+            write!(f, "{}", self.display_value(self.lo))
+        } else {
+            write!(f, "{}..={}", self.display_value(self.lo), self.display_value(self.hi))
+        }
+    }
+}
+
+const SIGN_BIT: u128 = 1 << 127;
+
+fn encode_signed(value: i128) -> u128 {
+    (value as u128) ^ SIGN_BIT
+}
+
+struct MatchCheckCtx<'a, DB> {
+    db: &'a DB,
+    body: &'a Body,
+    infer: &'a InferenceResult,
+    resolver: &'a Resolver,
+}
+
+impl<'a, DB: HirDatabase> MatchCheckCtx<'a, DB> {
+    fn lower_pat(&self, pat: PatId, ty: &Ty) -> Option<Pattern> {
+        let pattern = match &self.body[pat] {
+            Pat::Wild => Pattern::Wild,
+            Pat::Bind { subpat: Some(subpat), .. } => self.lower_pat(*subpat, ty)?,
+            Pat::Bind { mode: BindingAnnotation::Unannotated, name, subpat: None } => {
+                // A plain identifier refers to a unit variant, a unit struct
+                // or a const if there is one with that name in scope.
+                let path = Path::from(name.clone());
+                match self.resolver.resolve_path_in_value_ns_fully(self.db, &path) {
+                    Some(ValueNs::EnumVariant(_))
+                    | Some(ValueNs::Struct(_))
+                    | Some(ValueNs::Const(_)) => self.lower_path_pat(&path, ty)?,
+                    _ => Pattern::Wild,
+                }
+            }
+            Pat::Bind { .. } => Pattern::Wild,
+            _ if *ty == Ty::Unknown => return None,
+            Pat::Ref { pat, .. } => {
+                let (inner, _) = ty.as_reference()?;
+                Pattern::Ctor(Constructor::Single, vec![self.lower_pat(*pat, inner)?])
+            }
+            _ if self.is_string_literal(pat) => Pattern::Opaque,
+            _ if ty.as_reference().is_some() => {
+                // With default binding modes, `Some(x)` can match a `&Option<T>`.
+                let (inner, _) = ty.as_reference()?;
+                Pattern::Ctor(Constructor::Single, vec![self.lower_pat(pat, inner)?])
+            }
+            Pat::Tuple { args, ellipsis } => {
+                let tys = ty.as_tuple()?;
+                let fields = self.lower_tuple_fields(args, *ellipsis, &tys)?;
+                Pattern::Ctor(Constructor::Single, fields)
+            }
+            Pat::TupleStruct { args, ellipsis, .. } => {
+                let variant = self.infer.variant_resolution_for_pat(pat)?;
+                let ctor = self.variant_constructor(variant, ty)?;
+                let field_tys = self.field_tys(&ctor, ty);
+                let fields = self.lower_tuple_fields(args, *ellipsis, &field_tys)?;
+                Pattern::Ctor(ctor, fields)
+            }
+            Pat::Record { args, .. } => {
+                let variant = self.infer.variant_resolution_for_pat(pat)?;
+                let ctor = self.variant_constructor(variant, ty)?;
+                let field_tys = self.field_tys(&ctor, ty);
+                let variant_fields = variant.fields(self.db);
+                let mut fields = vec![Pattern::Wild; field_tys.len()];
+                for arg in args {
+                    let idx = variant_fields.iter().position(|f| f.name(self.db) == arg.name)?;
+                    fields[idx] = self.lower_pat(arg.pat, field_tys.get(idx)?)?;
+                }
+                Pattern::Ctor(ctor, fields)
+            }
+            Pat::Path(path) => self.lower_path_pat(path, ty)?,
+            Pat::Lit(expr) => self.lower_const(&ConstExpr::from_body(self.body, *expr), ty),
+            Pat::Range { start, end, inclusive } => {
+                let int_ty = match int_ty(ty) {
+                    Some(it) => it,
+                    None => return Some(Pattern::Opaque),
+                };
+                let start = ConstExpr::from_body(self.body, *start).eval(self.db, self.resolver);
+                let end = ConstExpr::from_body(self.body, *end).eval(self.db, self.resolver);
+                match (start, end) {
+                    (Some(ConstValue::Int(start)), Some(ConstValue::Int(end))) => {
+                        let end = if *inclusive { end } else { end.checked_sub(1)? };
+                        let range = IntRange::new(int_ty, start, end)?;
+                        Pattern::Ctor(Constructor::IntRange(range), Vec::new())
+                    }
+                    _ => Pattern::Opaque,
+                }
+            }
+            Pat::Slice { prefix, rest, suffix } => {
+                let elem_ty = elem_ty(ty)?;
+                let lower_all = |pats: &[PatId]| -> Option<Vec<Pattern>> {
+                    pats.iter().map(|&pat| self.lower_pat(pat, elem_ty)).collect()
+                };
+                Pattern::Slice {
+                    prefix: lower_all(prefix)?,
+                    rest: rest.is_some(),
+                    suffix: lower_all(suffix)?,
+                }
+            }
+            Pat::Missing => return None,
+        };
+        Some(pattern)
+    }
+
+    fn lower_tuple_fields(
+        &self,
+        args: &[PatId],
+        ellipsis: Option<usize>,
+        tys: &[Ty],
+    ) -> Option<Vec<Pattern>> {
+        if args.len() > tys.len() || (ellipsis.is_none() && args.len() != tys.len()) {
+            return None;
+        }
+        let mut fields = vec![Pattern::Wild; tys.len()];
+        for (i, &arg) in args.iter().enumerate() {
+            let idx = tuple_field_index(i, ellipsis, args.len(), tys.len());
+            fields[idx] = self.lower_pat(arg, &tys[idx])?;
+        }
+        Some(fields)
+    }
+
+    fn lower_path_pat(&self, path: &Path, ty: &Ty) -> Option<Pattern> {
+        let variant: VariantDef = match self
+            .resolver
+            .resolve_path_in_value_ns_fully(self.db, path)?
+        {
+            ValueNs::EnumVariant(it) => it.into(),
+            ValueNs::Struct(it) => it.into(),
+            ValueNs::Const(_) => return Some(self.lower_const(&ConstExpr::Path(path.clone()), ty)),
+            _ => return None,
+        };
+        let ctor = self.variant_constructor(variant, ty)?;
+        let fields = vec![Pattern::Wild; self.field_tys(&ctor, ty).len()];
+        Some(Pattern::Ctor(ctor, fields))
+    }
+
+    fn lower_const(&self, expr: &ConstExpr, ty: &Ty) -> Pattern {
+        let ctor = match (expr.eval(self.db, self.resolver), ty) {
+            (
+                Some(ConstValue::Bool(value)),
+                Ty::Apply(ApplicationTy { ctor: TypeCtor::Bool, .. }),
+            ) => Constructor::Bool(value),
+            (Some(ConstValue::Int(value)), _) => {
+                match int_ty(ty).and_then(|int_ty| IntRange::new(int_ty, value, value)) {
+                    Some(range) => Constructor::IntRange(range),
+                    None => return Pattern::Opaque,
+                }
+            }
+            _ => return Pattern::Opaque,
+        };
+        Pattern::Ctor(ctor, Vec::new())
+    }
+
+    fn is_string_literal(&self, pat: PatId) -> bool {
+        match &self.body[pat] {
+            Pat::Lit(expr) => match &self.body[*expr] {
+                Expr::Literal(Literal::String(..)) | Expr::Literal(Literal::ByteString(..)) => true,
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn variant_constructor(&self, variant: VariantDef, ty: &Ty) -> Option<Constructor> {
+        let (adt, _) = ty.as_adt()?;
+        let ctor = match (variant, adt) {
+            (VariantDef::EnumVariant(v), Adt::Enum(e)) if v.parent_enum(self.db) == e => {
+                Constructor::Variant(v)
+            }
+            (VariantDef::Struct(s), Adt::Struct(t)) if s == t => Constructor::Single,
+            (VariantDef::Union(u), Adt::Union(t)) if u == t => Constructor::Single,
+            _ => return None,
+        };
+        Some(ctor)
+    }
+
+    /// The types of the subpatterns of `ctor`.
+    fn field_tys(&self, ctor: &Constructor, ty: &Ty) -> Vec<Ty> {
+        match ctor {
+            Constructor::Single => {
+                if let Some((inner, _)) = ty.as_reference() {
+                    vec![inner.clone()]
+                } else if let Some(tys) = ty.as_tuple() {
+                    tys.to_vec()
+                } else {
+                    match ty.as_adt() {
+                        Some((Adt::Struct(s), substs)) => self.variant_field_tys(s.into(), substs),
+                        Some((Adt::Union(u), substs)) => self.variant_field_tys(u.into(), substs),
+                        _ => Vec::new(),
+                    }
+                }
+            }
+            Constructor::Variant(v) => match ty.as_adt() {
+                Some((_, substs)) => self.variant_field_tys((*v).into(), substs),
+                None => Vec::new(),
+            },
+            Constructor::Slice { len, .. } => match elem_ty(ty) {
+                Some(elem_ty) => iter::repeat(elem_ty.clone()).take(*len as usize).collect(),
+                None => Vec::new(),
+            },
+            Constructor::Bool(_) | Constructor::IntRange(_) => Vec::new(),
+        }
+    }
+
+    fn variant_field_tys(&self, variant: VariantDef, substs: &Substs) -> Vec<Ty> {
+        variant.fields(self.db).iter().map(|field| field.ty(self.db).subst(substs)).collect()
+    }
+
+    /// All constructors of `ty`, split such that each of them is either fully
+    /// covered by a head pattern or disjoint from it. Returns `None` for types
+    /// whose values can't be enumerated, like `str` or floats.
+    fn constructors(&self, ty: &Ty, heads: &[&Pattern]) -> Option<Vec<Constructor>> {
+        let a_ty = match ty {
+            Ty::Apply(a_ty) => a_ty,
+            _ => return None,
+        };
+        let ctors = match a_ty.ctor {
+            TypeCtor::Bool => vec![Constructor::Bool(false), Constructor::Bool(true)],
+            TypeCtor::Int(UncertainIntTy::Known(int_ty)) => {
+                let ranges = heads.iter().filter_map(|head| match head {
+                    Pattern::Ctor(Constructor::IntRange(range), _) => Some(*range),
+                    _ => None,
+                });
+                IntRange::of_type(int_ty)
+                    .split(ranges)
+                    .into_iter()
+                    .map(Constructor::IntRange)
+                    .collect()
+            }
+            TypeCtor::Never => Vec::new(),
+            TypeCtor::Adt(Adt::Enum(e)) => {
+                e.variants(self.db).into_iter().map(Constructor::Variant).collect()
+            }
+            TypeCtor::Adt(_) | TypeCtor::Tuple { .. } | TypeCtor::Ref(_) => {
+                vec![Constructor::Single]
+            }
+            TypeCtor::Array { len: Some(len) } => vec![Constructor::Slice { len, var: false }],
+            TypeCtor::Slice => {
+                // Slices longer than all patterns behave the same, so they are
+                // represented by a single constructor for "at least `max_len`".
+                let mut max_fixed_len = None;
+                let mut max_var_len = 0;
+                for head in heads {
+                    if let Pattern::Slice { prefix, rest, suffix } = head {
+                        let len = (prefix.len() + suffix.len()) as u64;
+                        if *rest {
+                            max_var_len = max_var_len.max(len);
+                        } else {
+                            max_fixed_len = Some(max_fixed_len.unwrap_or(0).max(len));
+                        }
+                    }
+                }
+                let max_len = max_fixed_len.map_or(0, |it| it + 1).max(max_var_len);
+                (0..max_len)
+                    .map(|len| Constructor::Slice { len, var: false })
+                    .chain(iter::once(Constructor::Slice { len: max_len, var: true }))
+                    .collect()
+            }
+            _ => return None,
+        };
+        Some(ctors)
+    }
+
+    /// Computes the vectors of patterns that match values not matched by any
+    /// of `rows`. `tys` are the types of the columns.
+    fn missing(&self, rows: &[Row], tys: &[Ty]) -> Vec<Row> {
+        let ty = match tys.first() {
+            Some(it) => it,
+            None => return if rows.is_empty() { vec![Vec::new()] } else { Vec::new() },
+        };
+        // Any slice pattern might match all arrays of an unknown length, so
+        // we rather don't check arms matching on them.
+        if let Ty::Apply(ApplicationTy { ctor: TypeCtor::Array { len: None }, .. }) = ty {
+            if !rows.is_empty() {
+                return Vec::new();
+            }
+        }
+        let heads: Vec<&Pattern> = rows.iter().map(|row| &row[0]).collect();
+
+        let (covered, uncovered) = match self.constructors(ty, &heads) {
+            Some(ctors) => {
+                let (covered, uncovered): (Vec<_>, Vec<_>) =
+                    ctors.into_iter().partition(|ctor| heads.iter().any(|head| covers(head, ctor)));
+                (covered, Some(uncovered))
+            }
+            None => (Vec::new(), None),
+        };
+
+        let mut res = Vec::new();
+        for ctor in covered {
+            let field_tys = self.field_tys(&ctor, ty);
+            let arity = field_tys.len();
+            let specialized: Vec<Row> =
+                rows.iter().filter_map(|row| specialize(row, &ctor, arity)).collect();
+            let tys: Vec<Ty> = field_tys.into_iter().chain(tys[1..].iter().cloned()).collect();
+            for mut witness in self.missing(&specialized, &tys) {
+                let rest = witness.split_off(arity);
+                res.push(iter::once(Pattern::Ctor(ctor.clone(), witness)).chain(rest).collect());
+            }
+        }
+        if uncovered.as_ref().map_or(false, |it| it.is_empty()) {
+            return res;
+        }
+
+        // Values built with the remaining constructors are only matched by
+        // the rows starting with a wildcard.
+        let default: Vec<Row> = rows
+            .iter()
+            .filter_map(|row| match row[0] {
+                Pattern::Wild => Some(row[1..].to_vec()),
+                _ => None,
+            })
+            .collect();
+        let witnesses = self.missing(&default, &tys[1..]);
+        if witnesses.is_empty() {
+            return res;
+        }
+
+        // If no arm mentions a constructor, it's nicer to report `_` than to
+        // list all of them.
+        let any_ctor = heads.iter().any(|head| match head {
+            Pattern::Ctor(..) | Pattern::Slice { .. } => true,
+            Pattern::Wild | Pattern::Opaque => false,
+        });
+        let missing_heads = match uncovered.filter(|_| any_ctor) {
+            Some(ctors) => merge_int_ranges(ctors)
+                .into_iter()
+                .map(|ctor| {
+                    let fields = vec![Pattern::Wild; self.field_tys(&ctor, ty).len()];
+                    Pattern::Ctor(ctor, fields)
+                })
+                .collect(),
+            None => vec![Pattern::Wild],
+        };
+        for head in missing_heads {
+            for witness in witnesses.iter() {
+                res.push(iter::once(head.clone()).chain(witness.iter().cloned()).collect());
+            }
+        }
+        res
+    }
+
+    fn enum_variant(&self, pattern: &Pattern, ty: &Ty) -> Option<EnumVariant> {
+        match pattern {
+            Pattern::Ctor(Constructor::Variant(v), _) => Some(*v),
+            Pattern::Ctor(Constructor::Single, fields) => {
+                let (inner, _) = ty.as_reference()?;
+                self.enum_variant(fields.first()?, inner)
+            }
+            _ => None,
+        }
+    }
+
+    fn render(&self, pattern: &Pattern, ty: &Ty) -> String {
+        let (ctor, fields) = match pattern {
+            Pattern::Wild | Pattern::Opaque | Pattern::Slice { .. } => return "_".to_string(),
+            Pattern::Ctor(ctor, fields) => (ctor, fields),
+        };
+        let field_tys = self.field_tys(ctor, ty);
+        let render_fields = |fields: &[Pattern]| -> Vec<String> {
+            fields
+                .iter()
+                .zip(field_tys.iter())
+                .map(|(field, field_ty)| self.render(field, field_ty))
+                .collect()
+        };
+        match ctor {
+            Constructor::Bool(value) => value.to_string(),
+            Constructor::IntRange(range) => range.to_string(),
+            Constructor::Slice { var, .. } => {
+                let mut fields = render_fields(fields);
+                if *var {
+                    fields.push("..".to_string());
+                }
+                format!("[{}]", fields.join(", "))
+            }
+            Constructor::Single if ty.as_reference().is_some() => {
+                format!("&{}", render_fields(fields).join(""))
+            }
+            Constructor::Single if ty.as_tuple().is_some() => {
+                let fields = render_fields(fields);
+                if fields.len() == 1 {
+                    format!("({},)", fields[0])
+                } else {
+                    format!("({})", fields.join(", "))
+                }
+            }
+            Constructor::Single => match ty.as_adt() {
+                Some((Adt::Struct(s), _)) => {
+                    let name = s.name(self.db).map(|it| it.to_string()).unwrap_or_default();
+                    self.render_variant(name, s.into(), &render_fields(fields))
+                }
+                Some((Adt::Union(u), _)) => {
+                    let name = u.name(self.db).map(|it| it.to_string()).unwrap_or_default();
+                    self.render_variant(name, u.into(), &render_fields(fields))
+                }
+                _ => "_".to_string(),
+            },
+            Constructor::Variant(v) => {
+                let enum_name = v.parent_enum(self.db).name(self.db);
+                let name = match (enum_name, v.name(self.db)) {
+                    (Some(enum_name), Some(name)) => format!("{}::{}", enum_name, name),
+                    (None, Some(name)) => name.to_string(),
+                    (_, None) => String::new(),
+                };
+                self.render_variant(name, (*v).into(), &render_fields(fields))
+            }
+        }
+    }
+
+    fn render_variant(&self, name: String, variant: VariantDef, fields: &[String]) -> String {
+        let variant_data = variant.variant_data(self.db);
+        if variant_data.is_unit() {
+            name
+        } else if variant_data.is_tuple() {
+            format!("{}({})", name, fields.join(", "))
+        } else {
+            let mut named_fields: Vec<String> = variant
+                .fields(self.db)
+                .iter()
+                .zip(fields)
+                .filter(|(_, field)| *field != "_")
+                .map(|(f, field)| format!("{}: {}", f.name(self.db), field))
+                .collect();
+            if named_fields.len() < fields.len() {
+                named_fields.push("..".to_string());
+            }
+            format!("{} {{ {} }}", name, named_fields.join(", "))
+        }
+    }
+}
+
+/// Whether `pattern` matches all values built with `ctor`.
+fn covers(pattern: &Pattern, ctor: &Constructor) -> bool {
+    match (pattern, ctor) {
+        (Pattern::Ctor(Constructor::IntRange(range), _), Constructor::IntRange(other)) => {
+            range.contains(other)
+        }
+        (Pattern::Ctor(pattern_ctor, _), _) => pattern_ctor == ctor,
+        (Pattern::Slice { prefix, rest, suffix }, Constructor::Slice { len, .. }) => {
+            let pattern_len = (prefix.len() + suffix.len()) as u64;
+            if *rest {
+                pattern_len <= *len
+            } else {
+                pattern_len == *len
+            }
+        }
+        _ => false,
+    }
+}
+
+/// The row matching the fields of values built with `ctor`, if `row` matches
+/// such values at all.
+fn specialize(row: &[Pattern], ctor: &Constructor, arity: usize) -> Option<Row> {
+    let fields = match &row[0] {
+        Pattern::Wild => vec![Pattern::Wild; arity],
+        head if !covers(head, ctor) => return None,
+        Pattern::Ctor(_, fields) => fields.clone(),
+        Pattern::Slice { prefix, suffix, .. } => {
+            let wild_count = arity - prefix.len() - suffix.len();
+            prefix
+                .iter()
+                .cloned()
+                .chain(iter::repeat(Pattern::Wild).take(wild_count))
+                .chain(suffix.iter().cloned())
+                .collect()
+        }
+        Pattern::Opaque => return None,
+    };
+    Some(fields.into_iter().chain(row[1..].iter().cloned()).collect())
+}
+
+/// Merges adjacent integer ranges, so that missing values are reported as a
+/// single range.
+fn merge_int_ranges(ctors: Vec<Constructor>) -> Vec<Constructor> {
+    let mut res: Vec<Constructor> = Vec::with_capacity(ctors.len());
+    for ctor in ctors {
+        if let (Some(Constructor::IntRange(last)), Constructor::IntRange(range)) =
+            (res.last_mut(), &ctor)
+        {
+            if last.hi.checked_add(1) == Some(range.lo) {
+                last.hi = range.hi;
+                continue;
+            }
+        }
+        res.push(ctor);
+    }
+    res
+}
+
+fn int_ty(ty: &Ty) -> Option<IntTy> {
+    match ty {
+        Ty::Apply(ApplicationTy { ctor: TypeCtor::Int(UncertainIntTy::Known(int_ty)), .. }) => {
+            Some(*int_ty)
+        }
+        _ => None,
+    }
+}
+
+fn elem_ty(ty: &Ty) -> Option<&Ty> {
+    match ty {
+        Ty::Apply(ApplicationTy { ctor: TypeCtor::Array { .. }, parameters })
+        | Ty::Apply(ApplicationTy { ctor: TypeCtor::Slice, parameters }) => {
+            Some(parameters.as_single())
+        }
+        _ => None,
+    }
+}
@@ -8,6 +8,7 @@ use rustc_hash::FxHashMap;
 use crate::{
     db::HirDatabase,
     expr::{Body, Expr, ExprId, Pat, PatId, Statement},
+    hygiene::SyntaxContext,
     DefWithBody, Name,
 };
 
@@ -26,6 +27,9 @@ pub struct ExprScopes {
 pub(crate) struct ScopeEntry {
     name: Name,
     pat: PatId,
+    /// Bindings are only visible to identifiers from the same expansion
+    /// context, so macros can't accidentally capture or shadow variables.
+    ctx: SyntaxContext,
 }
 
 impl ScopeEntry {
@@ -36,6 +40,10 @@ impl ScopeEntry {
     pub(crate) fn pat(&self) -> PatId {
         self.pat
     }
+
+    pub(crate) fn ctx(&self) -> SyntaxContext {
+        self.ctx
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -95,7 +103,7 @@ impl ExprScopes {
             Pat::Bind { name, .. } => {
                 // bind can have a sub pattern, but it's actually not allowed
                 // to bind to things in there
-                let entry = ScopeEntry { name: name.clone(), pat };
+                let entry = ScopeEntry { name: name.clone(), pat, ctx: body.pat_context(pat) };
                 self.scopes[scope].entries.push(entry)
             }
             p => p.walk_child_pats(|pat| self.add_bindings(body, scope, pat)),
@@ -178,7 +186,7 @@ mod tests {
     use ra_syntax::{algo::find_node_at_offset, ast, AstNode};
     use test_utils::{assert_eq_text, extract_offset};
 
-    use crate::{mock::MockDatabase, source_binder::SourceAnalyzer};
+    use crate::{mock::MockDatabase, source_binder::SourceAnalyzer, Source};
 
     fn do_check(code: &str, expected: &[&str]) {
         let (off, code) = extract_offset(code);
@@ -197,7 +205,9 @@ mod tests {
         let analyzer = SourceAnalyzer::new(&db, file_id, marker.syntax(), None);
 
         let scopes = analyzer.scopes();
-        let expr_id = analyzer.body_source_map().node_expr(&marker.into()).unwrap();
+        let marker: ast::Expr = marker.into();
+        let marker_src = Source { file_id: file_id.into(), ast: &marker };
+        let expr_id = analyzer.body_source_map().node_expr(marker_src).unwrap();
         let scope = scopes.scope_for(expr_id);
 
         let actual = scopes
@@ -7,14 +7,16 @@ use rustc_hash::FxHashSet;
 
 use crate::{
     db::HirDatabase,
-    diagnostics::{DiagnosticSink, MissingFields, MissingOkInTailExpr},
+    diagnostics::{
+        DiagnosticSink, MissingFields, MissingMatchArms, MissingOkInTailExpr, UnionExprFieldCount,
+    },
     expr::AstPtr,
     path::known,
     ty::{ApplicationTy, InferenceResult, Ty, TypeCtor},
-    Adt, Function, Name, Path,
+    Adt, Function, HirFileId, Name, Path,
 };
 
-use super::{Expr, ExprId, RecordLitField};
+use super::{match_check, Body, Expr, ExprId, MatchArm, RecordLitField};
 
 pub(crate) struct ExprValidator<'a, 'b: 'a> {
     func: Function,
@@ -35,8 +37,14 @@ impl<'a, 'b> ExprValidator<'a, 'b> {
         let body = self.func.body(db);
 
         for e in body.exprs() {
-            if let (id, Expr::RecordLit { path, fields, spread }) = e {
-                self.validate_record_literal(id, path, fields, *spread, db);
+            match e {
+                (id, Expr::RecordLit { path, fields, spread }) => {
+                    self.validate_record_literal(id, path, fields, *spread, db);
+                }
+                (id, Expr::Match { expr, arms }) => {
+                    self.validate_match(id, *expr, arms, &body, db);
+                }
+                _ => {}
             }
         }
 
@@ -60,6 +68,14 @@ impl<'a, 'b> ExprValidator<'a, 'b> {
 
         let struct_def = match self.infer[id].as_adt() {
             Some((Adt::Struct(s), _)) => s,
+            Some((Adt::Union(_), _)) => {
+                if fields.len() != 1 {
+                    if let Some((file, field_list)) = self.record_field_list(id, db) {
+                        self.sink.push(UnionExprFieldCount { file, field_list });
+                    }
+                }
+                return;
+            }
             _ => return,
         };
 
@@ -79,21 +95,67 @@ impl<'a, 'b> ExprValidator<'a, 'b> {
         if missed_fields.is_empty() {
             return;
         }
+        if let Some((file, field_list)) = self.record_field_list(id, db) {
+            self.sink.push(MissingFields { file, field_list, missed_fields })
+        }
+    }
+
+    fn validate_match(
+        &mut self,
+        id: ExprId,
+        expr: ExprId,
+        arms: &[MatchArm],
+        body: &Body,
+        db: &impl HirDatabase,
+    ) {
+        let resolver = self.func.resolver(db);
+        let missing_patterns =
+            match match_check::missing_patterns(db, body, &self.infer, &resolver, expr, arms) {
+                Some(it) => it,
+                None => return,
+            };
+        if missing_patterns.is_empty() {
+            return;
+        }
+
         let source_map = self.func.body_source_map(db);
+        let source_ptr = match source_map.expr_syntax(id) {
+            Some(it) => it,
+            None => return,
+        };
+        let root = source_ptr.file_syntax(db);
+        // `if let` and `while let` are desugared to matches, which are
+        // exhaustive by construction.
+        let match_expr = match source_ptr.ast.a().map(|ptr| ptr.to_node(&root)) {
+            Some(ast::Expr::MatchExpr(it)) => it,
+            _ => return,
+        };
+        if let (Some(scrutinee), Some(arm_list)) = (match_expr.expr(), match_expr.match_arm_list())
+        {
+            self.sink.push(MissingMatchArms {
+                file: source_ptr.file_id,
+                match_expr: AstPtr::new(&scrutinee),
+                arms: AstPtr::new(&arm_list),
+                missing_patterns,
+            });
+        }
+    }
 
-        if let Some(source_ptr) = source_map.expr_syntax(id) {
-            if let Some(expr) = source_ptr.ast.a() {
-                let root = source_ptr.file_syntax(db);
-                if let ast::Expr::RecordLit(record_lit) = expr.to_node(&root) {
-                    if let Some(field_list) = record_lit.record_field_list() {
-                        self.sink.push(MissingFields {
-                            file: source_ptr.file_id,
-                            field_list: AstPtr::new(&field_list),
-                            missed_fields,
-                        })
-                    }
-                }
+    fn record_field_list(
+        &self,
+        id: ExprId,
+        db: &impl HirDatabase,
+    ) -> Option<(HirFileId, AstPtr<ast::RecordFieldList>)> {
+        let source_map = self.func.body_source_map(db);
+        let source_ptr = source_map.expr_syntax(id)?;
+        let expr = source_ptr.ast.a()?;
+        let root = source_ptr.file_syntax(db);
+        match expr.to_node(&root) {
+            ast::Expr::RecordLit(record_lit) => {
+                let field_list = record_lit.record_field_list()?;
+                Some((source_ptr.file_id, AstPtr::new(&field_list)))
             }
+            _ => None,
         }
     }
 
@@ -108,9 +108,13 @@ impl FromSource for StructField {
         let variant_def: VariantDef = match src.ast {
             FieldSource::Named(ref field) => {
                 let ast = field.syntax().ancestors().find_map(ast::StructDef::cast)?;
+                let is_union = ast.is_union();
                 let src = Source { file_id: src.file_id, ast };
-                let def = Struct::from_source(db, src)?;
-                VariantDef::from(def)
+                if is_union {
+                    VariantDef::from(Union::from_source(db, src)?)
+                } else {
+                    VariantDef::from(Struct::from_source(db, src)?)
+                }
             }
             FieldSource::Pos(ref field) => {
                 let ast = field.syntax().ancestors().find_map(ast::EnumVariant::cast)?;
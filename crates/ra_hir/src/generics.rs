@@ -9,11 +9,12 @@ use ra_syntax::ast::{self, DefaultTypeParamOwner, NameOwner, TypeBoundsOwner, Ty
 
 use crate::{
     db::{AstDatabase, DefDatabase, HirDatabase},
+    hygiene::Hygiene,
     name::SELF_TYPE,
     path::Path,
     type_ref::{TypeBound, TypeRef},
-    Adt, AsName, Const, Container, Enum, EnumVariant, Function, HasSource, ImplBlock, Name, Struct,
-    Trait, TypeAlias, Union,
+    Adt, AsName, Const, Container, Enum, EnumVariant, Function, HasSource, ImplBlock, Name, Source,
+    Struct, Trait, TypeAlias, Union,
 };
 
 /// Data about a generic parameter (to a function, struct, impl, ...).
@@ -88,80 +89,95 @@ impl GenericParams {
         let start = generics.parent_params.as_ref().map(|p| p.params.len()).unwrap_or(0) as u32;
         // FIXME: add `: Sized` bound for everything except for `Self` in traits
         match def {
-            GenericDef::Function(it) => generics.fill(&it.source(db).ast, start),
-            GenericDef::Adt(Adt::Struct(it)) => generics.fill(&it.source(db).ast, start),
-            GenericDef::Adt(Adt::Union(it)) => generics.fill(&it.source(db).ast, start),
-            GenericDef::Adt(Adt::Enum(it)) => generics.fill(&it.source(db).ast, start),
+            GenericDef::Function(it) => generics.fill(db, it.source(db), start),
+            GenericDef::Adt(Adt::Struct(it)) => generics.fill(db, it.source(db), start),
+            GenericDef::Adt(Adt::Union(it)) => generics.fill(db, it.source(db), start),
+            GenericDef::Adt(Adt::Enum(it)) => generics.fill(db, it.source(db), start),
             GenericDef::Trait(it) => {
+                let src = it.source(db);
                 // traits get the Self type as an implicit first type parameter
                 generics.params.push(GenericParam { idx: start, name: SELF_TYPE, default: None });
-                generics.fill(&it.source(db).ast, start + 1);
+                generics.fill(db, src.clone(), start + 1);
                 // add super traits as bounds on Self
                 // i.e., trait Foo: Bar is equivalent to trait Foo where Self: Bar
                 let self_param = TypeRef::Path(SELF_TYPE.into());
-                generics.fill_bounds(&it.source(db).ast, self_param);
+                let hygiene = Hygiene::new(db, src.file_id);
+                generics.fill_bounds(&src.ast, self_param, &hygiene);
             }
-            GenericDef::TypeAlias(it) => generics.fill(&it.source(db).ast, start),
+            GenericDef::TypeAlias(it) => generics.fill(db, it.source(db), start),
             // Note that we don't add `Self` here: in `impl`s, `Self` is not a
             // type-parameter, but rather is a type-alias for impl's target
             // type, so this is handled by the resolver.
-            GenericDef::ImplBlock(it) => generics.fill(&it.source(db).ast, start),
+            GenericDef::ImplBlock(it) => generics.fill(db, it.source(db), start),
             GenericDef::EnumVariant(_) | GenericDef::Const(_) => {}
         }
 
         Arc::new(generics)
     }
 
-    fn fill(&mut self, node: &impl TypeParamsOwner, start: u32) {
-        if let Some(params) = node.type_param_list() {
-            self.fill_params(params, start)
+    fn fill(&mut self, db: &impl AstDatabase, src: Source<impl TypeParamsOwner>, start: u32) {
+        let hygiene = Hygiene::new(db, src.file_id);
+        if let Some(params) = src.ast.type_param_list() {
+            self.fill_params(params, start, &hygiene)
         }
-        if let Some(where_clause) = node.where_clause() {
-            self.fill_where_predicates(where_clause);
+        if let Some(where_clause) = src.ast.where_clause() {
+            self.fill_where_predicates(where_clause, &hygiene);
         }
     }
 
-    fn fill_bounds(&mut self, node: &impl ast::TypeBoundsOwner, type_ref: TypeRef) {
+    fn fill_bounds(
+        &mut self,
+        node: &impl ast::TypeBoundsOwner,
+        type_ref: TypeRef,
+        hygiene: &Hygiene,
+    ) {
         for bound in
             node.type_bound_list().iter().flat_map(|type_bound_list| type_bound_list.bounds())
         {
-            self.add_where_predicate_from_bound(bound, type_ref.clone());
+            self.add_where_predicate_from_bound(bound, type_ref.clone(), hygiene);
         }
     }
 
-    fn fill_params(&mut self, params: ast::TypeParamList, start: u32) {
+    fn fill_params(&mut self, params: ast::TypeParamList, start: u32, hygiene: &Hygiene) {
         for (idx, type_param) in params.type_params().enumerate() {
             let name = type_param.name().map_or_else(Name::missing, |it| it.as_name());
-            // FIXME: Use `Path::from_src`
-            let default = type_param.default_type().and_then(|t| t.path()).and_then(Path::from_ast);
+            let default = type_param
+                .default_type()
+                .and_then(|t| t.path())
+                .and_then(|it| Path::from_src(it, hygiene));
 
             let param = GenericParam { idx: idx as u32 + start, name: name.clone(), default };
             self.params.push(param);
 
             let type_ref = TypeRef::Path(name.into());
-            self.fill_bounds(&type_param, type_ref);
+            self.fill_bounds(&type_param, type_ref, hygiene);
         }
     }
 
-    fn fill_where_predicates(&mut self, where_clause: ast::WhereClause) {
+    fn fill_where_predicates(&mut self, where_clause: ast::WhereClause, hygiene: &Hygiene) {
         for pred in where_clause.predicates() {
             let type_ref = match pred.type_ref() {
                 Some(type_ref) => type_ref,
                 None => continue,
             };
-            let type_ref = TypeRef::from_ast(type_ref);
+            let type_ref = TypeRef::from_ast(type_ref, hygiene);
             for bound in pred.type_bound_list().iter().flat_map(|l| l.bounds()) {
-                self.add_where_predicate_from_bound(bound, type_ref.clone());
+                self.add_where_predicate_from_bound(bound, type_ref.clone(), hygiene);
             }
         }
     }
 
-    fn add_where_predicate_from_bound(&mut self, bound: ast::TypeBound, type_ref: TypeRef) {
+    fn add_where_predicate_from_bound(
+        &mut self,
+        bound: ast::TypeBound,
+        type_ref: TypeRef,
+        hygiene: &Hygiene,
+    ) {
         if bound.has_question_mark() {
             // FIXME: remove this bound
             return;
         }
-        let bound = TypeBound::from_ast(bound);
+        let bound = TypeBound::from_ast(bound, hygiene);
         self.where_predicates.push(WherePredicate { type_ref, bound });
     }
 
@@ -0,0 +1,117 @@
+//! Hygiene of macro expansions.
+//!
+//! `macro_rules` are only partially hygienic: items defined by a macro are
+//! visible everywhere, but local variables (and `$crate`) are resolved relative
+//! to the place where the identifier was written, which might be the macro
+//! definition rather than the macro call.
+
+use mbe::Origin;
+use ra_syntax::SyntaxToken;
+use rustc_hash::FxHashMap;
+
+use crate::{
+    db::AstDatabase,
+    ids::{ExpansionInfo, MacroDefKind},
+    Crate, HirFileId, MacroCallId, Source,
+};
+
+/// Knows how to lower paths of a particular file: in macro expansions, `$crate`
+/// refers to the crate the macro is defined in.
+#[derive(Debug, Clone, Copy)]
+pub struct Hygiene {
+    def_crate: Option<Crate>,
+}
+
+impl Hygiene {
+    pub fn new(db: &impl AstDatabase, file_id: HirFileId) -> Hygiene {
+        Hygiene { def_crate: def_crate(db, file_id) }
+    }
+
+    /// Hygiene for code which is known not to come from a macro expansion.
+    pub fn new_unhygienic() -> Hygiene {
+        Hygiene { def_crate: None }
+    }
+
+    /// The crate `$crate` refers to, if any.
+    pub(crate) fn dollar_crate(&self) -> Option<Crate> {
+        self.def_crate
+    }
+}
+
+/// The crate of the macro which produced `file_id`. If the macro itself was
+/// defined by another macro, `$crate` in its body was written in the definition
+/// of that outer macro.
+fn def_crate(db: &impl AstDatabase, file_id: HirFileId) -> Option<Crate> {
+    let def = file_id.macro_call_id()?.loc(db).def;
+    def.ast_id.and_then(|it| def_crate(db, it.file_id())).or(Some(def.krate))
+}
+
+/// The expansion an identifier was written in. A local binding is only visible
+/// to identifiers from the same context, so a `let` inside of a macro body
+/// doesn't shadow a variable passed to the macro and vice versa.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct SyntaxContext(Option<MacroCallId>);
+
+impl SyntaxContext {
+    /// The context of code written outside of any macro definition.
+    pub(crate) const ROOT: SyntaxContext = SyntaxContext(None);
+}
+
+/// Computes the `SyntaxContext`s of tokens. The `ExpansionInfo` of every macro
+/// file is kept, as the tokens of a body mostly come from the same few
+/// expansions.
+#[derive(Debug, Default)]
+pub(crate) struct SyntaxContexts {
+    expansion_infos: FxHashMap<HirFileId, Option<ExpansionInfo>>,
+}
+
+impl SyntaxContexts {
+    /// Computes the context of `token` by mapping it up through the macro calls
+    /// which produced it, until it ends up either in the macro definition or
+    /// in a real file.
+    pub(crate) fn of_token(
+        &mut self,
+        db: &impl AstDatabase,
+        token: Source<SyntaxToken>,
+    ) -> SyntaxContext {
+        let mut token = token;
+        loop {
+            let macro_call_id = match token.file_id.macro_call_id() {
+                Some(it) => it,
+                None => return SyntaxContext::ROOT,
+            };
+            let file_id = token.file_id;
+            let mapped = self
+                .expansion_infos
+                .entry(file_id)
+                .or_insert_with(|| file_id.expansion_info(db))
+                .as_ref()
+                .and_then(|info| info.map_token_up(token.as_ref()));
+            token = match mapped {
+                Some((token, Origin::Call)) => token,
+                // Procedural macros are unhygienic: everything they produce
+                // behaves as if it was written at the call site.
+                None if is_proc_macro(db, macro_call_id) => {
+                    match token.file_id.call_node(db).and_then(|call| {
+                        Some(Source { file_id: call.file_id, ast: call.ast.first_token()? })
+                    }) {
+                        Some(it) => it,
+                        None => return SyntaxContext::ROOT,
+                    }
+                }
+                // Tokens of the macro definition and tokens made up by the
+                // expander itself, like `$crate`, belong to this expansion.
+                Some((_, Origin::Def)) | None => return SyntaxContext(Some(macro_call_id)),
+            };
+        }
+    }
+}
+
+fn is_proc_macro(db: &impl AstDatabase, id: MacroCallId) -> bool {
+    match id.loc(db).def.kind {
+        MacroDefKind::ProcMacro(_) => true,
+        MacroDefKind::Declarative | MacroDefKind::BuiltIn(_) | MacroDefKind::BuiltInDerive(_) => {
+            false
+        }
+    }
+}
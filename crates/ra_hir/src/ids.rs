@@ -8,10 +8,15 @@ use std::{
 use mbe::MacroRules;
 use ra_db::{salsa, FileId};
 use ra_prof::profile;
-use ra_syntax::{ast, AstNode, Parse, SyntaxNode};
+use ra_syntax::{
+    algo::find_covering_element, ast, AstNode, Parse, SyntaxNode, SyntaxToken, TextUnit,
+};
 
 use crate::{
+    builtin_derive::BuiltinDeriveExpander,
+    builtin_macro::BuiltinExpander,
     db::{AstDatabase, DefDatabase, InternDatabase},
+    proc_macro::ProcMacroExpander,
     AstId, Crate, FileAstId, Module, Source,
 };
 
@@ -45,7 +50,7 @@ impl HirFileId {
             HirFileIdRepr::File(file_id) => file_id,
             HirFileIdRepr::Macro(macro_file) => {
                 let loc = macro_file.macro_call_id.loc(db);
-                loc.ast_id.file_id().original_file(db)
+                loc.kind.file_id().original_file(db)
             }
         }
     }
@@ -60,13 +65,61 @@ impl HirFileId {
         }
     }
 
-    /// Get the crate which the macro lives in, if it is a macro file.
-    pub(crate) fn macro_crate(self, db: &impl AstDatabase) -> Option<Crate> {
+    pub(crate) fn is_macro_file(self) -> bool {
+        match self.0 {
+            HirFileIdRepr::File(_) => false,
+            HirFileIdRepr::Macro(_) => true,
+        }
+    }
+
+    /// For macro-expansion files, returns the id of the macro call which
+    /// produced the file.
+    pub(crate) fn macro_call_id(self) -> Option<MacroCallId> {
+        match self.0 {
+            HirFileIdRepr::File(_) => None,
+            HirFileIdRepr::Macro(macro_file) => Some(macro_file.macro_call_id),
+        }
+    }
+
+    /// For macro-expansion files, returns the macro call which produced the
+    /// file. For derives and attribute macros, this is the annotated item.
+    pub(crate) fn call_node(self, db: &impl AstDatabase) -> Option<Source<SyntaxNode>> {
         match self.0 {
             HirFileIdRepr::File(_) => None,
             HirFileIdRepr::Macro(macro_file) => {
                 let loc = macro_file.macro_call_id.loc(db);
-                Some(loc.def.krate)
+                Some(Source { file_id: loc.kind.file_id(), ast: loc.kind.node(db) })
+            }
+        }
+    }
+
+    /// Returns the information needed to map tokens between a macro call and
+    /// this file, if it is a macro file.
+    pub fn expansion_info(self, db: &impl AstDatabase) -> Option<ExpansionInfo> {
+        match self.0 {
+            HirFileIdRepr::File(_) => None,
+            HirFileIdRepr::Macro(macro_file) => {
+                let loc = macro_file.macro_call_id.loc(db);
+                // Only `macro_rules!` keep track of where the expanded tokens
+                // come from.
+                let call_ast_id = match loc.kind {
+                    MacroCallKind::FnLike(ast_id) => ast_id,
+                    MacroCallKind::Attr(_) => return None,
+                };
+                let def_ast_id = loc.def.ast_id?;
+                let arg = call_ast_id.to_node(db).token_tree()?;
+                let def = def_ast_id.to_node(db).token_tree()?;
+                let macro_def = db.macro_def(loc.def)?;
+                let macro_arg = db.macro_arg(macro_file.macro_call_id)?;
+                let (parse, exp_map) = db.parse_macro(macro_file)?;
+                Some(ExpansionInfo {
+                    expanded: Source { file_id: self, ast: parse.syntax_node() },
+                    arg: Source { file_id: call_ast_id.file_id(), ast: arg },
+                    def: Source { file_id: def_ast_id.file_id(), ast: def },
+                    macro_def,
+                    macro_arg,
+                    exp_map,
+                })
             }
         }
     }
@@ -78,7 +131,7 @@ impl HirFileId {
         match file_id.0 {
             HirFileIdRepr::File(file_id) => Some(db.parse(file_id).tree().syntax().clone()),
             HirFileIdRepr::Macro(macro_file) => {
-                db.parse_macro(macro_file).map(|it| it.syntax_node())
+                db.parse_macro(macro_file).map(|(it, _)| it.syntax_node())
             }
         }
     }
@@ -86,7 +139,7 @@ impl HirFileId {
     pub(crate) fn parse_macro_query(
         db: &impl AstDatabase,
         macro_file: MacroFile,
-    ) -> Option<Parse<SyntaxNode>> {
+    ) -> Option<(Parse<SyntaxNode>, Arc<mbe::RevTokenMap>)> {
         let _p = profile("parse_macro_query");
         let macro_call_id = macro_file.macro_call_id;
         let tt = db
@@ -102,10 +155,69 @@ impl HirFileId {
                 );
             })
             .ok()?;
-        match macro_file.macro_file_kind {
-            MacroFileKind::Items => mbe::token_tree_to_items(&tt).ok().map(Parse::to_syntax),
-            MacroFileKind::Expr => mbe::token_tree_to_expr(&tt).ok().map(Parse::to_syntax),
+        let fragment_kind = match macro_file.macro_file_kind {
+            MacroFileKind::Items => mbe::FragmentKind::Items,
+            MacroFileKind::Expr => mbe::FragmentKind::Expr,
+        };
+        let (parse, rev_token_map) = mbe::token_tree_to_syntax_node(&tt, fragment_kind).ok()?;
+        Some((parse, Arc::new(rev_token_map)))
+    }
+}
+
+/// `ExpansionInfo` maps tokens between a macro call (its argument and the
+/// definition of the macro) and the syntax tree of its expansion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpansionInfo {
+    expanded: Source<SyntaxNode>,
+    arg: Source<ast::TokenTree>,
+    def: Source<ast::TokenTree>,
+
+    macro_def: Arc<(MacroRules, mbe::TokenMap)>,
+    macro_arg: Arc<(tt::Subtree, mbe::TokenMap)>,
+    exp_map: Arc<mbe::RevTokenMap>,
+}
+
+impl ExpansionInfo {
+    /// Maps a token of the macro call argument to the token it was expanded
+    /// to. If the token is used several times, the first use wins. Tokens of
+    /// other files map to nothing.
+    pub fn map_token_down(&self, token: Source<&SyntaxToken>) -> Option<Source<SyntaxToken>> {
+        if token.file_id != self.arg.file_id {
+            return None;
+        }
+        let arg_range = self.arg.ast.syntax().text_range();
+        if !token.ast.text_range().is_subrange(&arg_range) {
+            return None;
+        }
+        let relative_range = token.ast.text_range() - arg_range.start();
+        let token_id = self.macro_arg.1.token_by_relative_range(relative_range)?;
+        let token_id = self.macro_def.0.map_id_down(token_id);
+
+        let range = self.exp_map.range_of(token_id)?;
+        let token = find_covering_element(&self.expanded.ast, range).into_token()?;
+        Some(Source { file_id: self.expanded.file_id, ast: token })
+    }
+
+    /// Maps a token of the expansion back to the token of either the macro
+    /// call argument or the macro definition it came from. Tokens of other
+    /// files map to nothing.
+    pub fn map_token_up(
+        &self,
+        token: Source<&SyntaxToken>,
+    ) -> Option<(Source<SyntaxToken>, mbe::Origin)> {
+        if token.file_id != self.expanded.file_id {
+            return None;
         }
+        let token_id = self.exp_map.token_by_range(token.ast.text_range())?;
+
+        let (token_id, origin) = self.macro_def.0.map_id_up(token_id);
+        let (token_map, tt) = match origin {
+            mbe::Origin::Call => (&self.macro_arg.1, &self.arg),
+            mbe::Origin::Def => (&self.macro_def.1, &self.def),
+        };
+        let range = token_map.relative_range_of(token_id)? + tt.ast.syntax().text_range().start();
+        let token = find_covering_element(tt.ast.syntax(), range).into_token()?;
+        Some((Source { file_id: tt.file_id, ast: token }, origin))
     }
 }
 
@@ -135,14 +247,38 @@ impl From<FileId> for HirFileId {
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct MacroDefId {
-    pub(crate) ast_id: AstId<ast::MacroCall>,
+    /// The `macro_rules!` definition. Procedural macros are defined by
+    /// compiled code, so they don't have one.
+    pub(crate) ast_id: Option<AstId<ast::MacroCall>>,
     pub(crate) krate: Crate,
+    pub(crate) kind: MacroDefKind,
 }
 
-pub(crate) fn macro_def_query(db: &impl AstDatabase, id: MacroDefId) -> Option<Arc<MacroRules>> {
-    let macro_call = id.ast_id.to_node(db);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum MacroDefKind {
+    /// A `macro_rules!` definition, expanded by `ra_mbe`.
+    Declarative,
+    /// A `#[rustc_builtin_macro]`, expanded by rust-analyzer itself.
+    BuiltIn(BuiltinExpander),
+    /// A builtin derive, like `#[derive(Clone)]`.
+    BuiltInDerive(BuiltinDeriveExpander),
+    /// A procedural macro, expanded by an external expander.
+    ProcMacro(ProcMacroExpander),
+}
+
+pub(crate) fn macro_def_query(
+    db: &impl AstDatabase,
+    id: MacroDefId,
+) -> Option<Arc<(MacroRules, mbe::TokenMap)>> {
+    match id.kind {
+        MacroDefKind::Declarative => (),
+        MacroDefKind::BuiltIn(_) | MacroDefKind::BuiltInDerive(_) | MacroDefKind::ProcMacro(_) => {
+            return None
+        }
+    }
+    let macro_call = id.ast_id?.to_node(db);
     let arg = macro_call.token_tree()?;
-    let (tt, _) = mbe::ast_to_token_tree(&arg).or_else(|| {
+    let (tt, tmap) = mbe::ast_to_token_tree(&arg).or_else(|| {
         log::warn!("fail on macro_def to token tree: {:#?}", arg);
         None
     })?;
@@ -150,15 +286,17 @@ pub(crate) fn macro_def_query(db: &impl AstDatabase, id: MacroDefId) -> Option<A
         log::warn!("fail on macro_def parse: {:#?}", tt);
         None
     })?;
-    Some(Arc::new(rules))
+    Some(Arc::new((rules, tmap)))
 }
 
-pub(crate) fn macro_arg_query(db: &impl AstDatabase, id: MacroCallId) -> Option<Arc<tt::Subtree>> {
+pub(crate) fn macro_arg_query(
+    db: &impl AstDatabase,
+    id: MacroCallId,
+) -> Option<Arc<(tt::Subtree, mbe::TokenMap)>> {
     let loc = id.loc(db);
-    let macro_call = loc.ast_id.to_node(db);
-    let arg = macro_call.token_tree()?;
-    let (tt, _) = mbe::ast_to_token_tree(&arg)?;
-    Some(Arc::new(tt))
+    let arg = loc.kind.arg(db)?;
+    let (tt, tmap) = mbe::syntax_node_to_token_tree(&arg)?;
+    Some(Arc::new((tt, tmap)))
 }
 
 pub(crate) fn macro_expand_query(
@@ -168,8 +306,15 @@ pub(crate) fn macro_expand_query(
     let loc = id.loc(db);
     let macro_arg = db.macro_arg(id).ok_or("Fail to args in to tt::TokenTree")?;
 
-    let macro_rules = db.macro_def(loc.def).ok_or("Fail to find macro definition")?;
-    let tt = macro_rules.expand(&macro_arg).map_err(|err| format!("{:?}", err))?;
+    let tt = match loc.def.kind {
+        MacroDefKind::Declarative => {
+            let macro_rules = db.macro_def(loc.def).ok_or("Fail to find macro definition")?;
+            macro_rules.0.expand(&macro_arg.0).map_err(|err| format!("{:?}", err))?
+        }
+        MacroDefKind::BuiltIn(expander) => expander.expand(db, id, &macro_arg.0)?,
+        MacroDefKind::BuiltInDerive(expander) => expander.expand(db, id)?,
+        MacroDefKind::ProcMacro(expander) => expander.expand(db, &macro_arg.0)?,
+    };
     // Set a hard limit for the expanded tt
     let count = tt.count();
     if count > 65536 {
@@ -200,7 +345,42 @@ impl_intern_key!(MacroCallId);
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct MacroCallLoc {
     pub(crate) def: MacroDefId,
-    pub(crate) ast_id: AstId<ast::MacroCall>,
+    pub(crate) kind: MacroCallKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum MacroCallKind {
+    /// A function-like macro call, like `foo!(...)`.
+    FnLike(AstId<ast::MacroCall>),
+    /// A derive or an attribute macro, applied to the item.
+    Attr(AstId<ast::ModuleItem>),
+}
+
+impl MacroCallKind {
+    pub(crate) fn file_id(self) -> HirFileId {
+        match self {
+            MacroCallKind::FnLike(ast_id) => ast_id.file_id(),
+            MacroCallKind::Attr(ast_id) => ast_id.file_id(),
+        }
+    }
+
+    pub(crate) fn node(self, db: &impl AstDatabase) -> SyntaxNode {
+        match self {
+            MacroCallKind::FnLike(ast_id) => ast_id.to_node(db).syntax().clone(),
+            MacroCallKind::Attr(ast_id) => ast_id.to_node(db).syntax().clone(),
+        }
+    }
+
+    /// The input of the macro: the token tree of a function-like macro call,
+    /// or the whole item for derives and attribute macros.
+    fn arg(self, db: &impl AstDatabase) -> Option<SyntaxNode> {
+        match self {
+            MacroCallKind::FnLike(ast_id) => {
+                Some(ast_id.to_node(db).token_tree()?.syntax().clone())
+            }
+            MacroCallKind::Attr(ast_id) => Some(ast_id.to_node(db).syntax().clone()),
+        }
+    }
 }
 
 impl MacroCallId {
@@ -212,6 +392,16 @@ impl MacroCallId {
         let macro_file = MacroFile { macro_call_id: self, macro_file_kind: kind };
         HirFileId(HirFileIdRepr::Macro(macro_file))
     }
+
+    /// Returns the original file and the offset of the macro call in it. For
+    /// calls produced by other macros, this is the outermost call.
+    pub(crate) fn original_call_site(self, db: &impl AstDatabase) -> (FileId, TextUnit) {
+        let loc = self.loc(db);
+        match loc.kind.file_id().0 {
+            HirFileIdRepr::File(file_id) => (file_id, loc.kind.node(db).text_range().start()),
+            HirFileIdRepr::Macro(macro_file) => macro_file.macro_call_id.original_call_site(db),
+        }
+    }
 }
 
 impl MacroCallLoc {
@@ -380,10 +570,10 @@ impl AstItemDef<ast::TypeAliasDef> for TypeAliasId {
 impl MacroCallId {
     pub fn debug_dump(self, db: &impl AstDatabase) -> String {
         let loc = self.loc(db);
-        let node = loc.ast_id.to_node(db);
+        let node = loc.kind.node(db);
         let syntax_str = {
             let mut res = String::new();
-            node.syntax().text().for_each_chunk(|chunk| {
+            node.text().for_each_chunk(|chunk| {
                 if !res.is_empty() {
                     res.push(' ')
                 }
@@ -393,7 +583,7 @@ impl MacroCallId {
         };
 
         // dump the file name
-        let file_id: HirFileId = self.loc(db).ast_id.file_id();
+        let file_id: HirFileId = loc.kind.file_id();
         let original = file_id.original_file(db);
         let macro_rules = db.macro_def(loc.def);
 
@@ -5,16 +5,18 @@ use std::sync::Arc;
 
 use ra_arena::{impl_arena_id, map::ArenaMap, Arena, RawId};
 use ra_syntax::{
-    ast::{self, AstNode},
+    ast::{self, AstNode, AttrsOwner},
     AstPtr,
 };
 
 use crate::{
+    attr::cfg_filter,
     code_model::{Module, ModuleSource},
     db::{AstDatabase, DefDatabase, HirDatabase},
     generics::HasGenericParams,
+    hygiene::Hygiene,
     ids::LocationCtx,
-    ids::MacroCallLoc,
+    ids::{MacroCallKind, MacroCallLoc},
     resolve::Resolver,
     ty::Ty,
     type_ref::TypeRef,
@@ -125,13 +127,16 @@ impl ImplData {
         module: Module,
         node: &ast::ImplBlock,
     ) -> Self {
-        let target_trait = node.target_trait().map(TypeRef::from_ast);
-        let target_type = TypeRef::from_ast_opt(node.target_type());
+        let hygiene = Hygiene::new(db, file_id);
+        let target_trait = node.target_trait().map(|it| TypeRef::from_ast(it, &hygiene));
+        let target_type = TypeRef::from_ast_opt(node.target_type(), &hygiene);
         let ctx = LocationCtx::new(db, module, file_id);
         let negative = node.is_negative();
+        let is_cfg_enabled = cfg_filter(db, file_id, module.krate);
         let items = if let Some(item_list) = node.item_list() {
             item_list
                 .impl_items()
+                .filter(|item_node| is_cfg_enabled(item_node))
                 .map(|item_node| match item_node {
                     ast::ImplItem::FnDef(it) => Function { id: ctx.to_def(&it) }.into(),
                     ast::ImplItem::ConstDef(it) => Const { id: ctx.to_def(&it) }.into(),
@@ -205,9 +210,13 @@ impl ModuleImplBlocks {
         owner: &dyn ast::ModuleItemOwner,
         file_id: HirFileId,
     ) {
+        let is_cfg_enabled = cfg_filter(db, file_id, self.module.krate);
         for item in owner.items_with_macros() {
             match item {
                 ast::ItemOrMacro::Item(ast::ModuleItem::ImplBlock(impl_block_ast)) => {
+                    if !is_cfg_enabled(&impl_block_ast) {
+                        continue;
+                    }
                     let impl_block = ImplData::from_ast(db, file_id, self.module, &impl_block_ast);
                     let id = self.impls.alloc(impl_block);
                     for &impl_item in &self.impls[id].items {
@@ -216,17 +225,34 @@ impl ModuleImplBlocks {
 
                     source_map.insert(id, file_id, &impl_block_ast);
                 }
-                ast::ItemOrMacro::Item(_) => (),
+                ast::ItemOrMacro::Item(item) => {
+                    // Derives and attribute macros mostly produce impls.
+                    if item.attrs().next().is_none() {
+                        continue;
+                    }
+                    let ast_id = db.ast_id_map(file_id).ast_id(&item).with_file_id(file_id);
+                    let def_map = db.crate_def_map(self.module.krate);
+                    for &call_id in def_map.attr_macro_calls(ast_id) {
+                        let file_id = call_id.as_file(MacroFileKind::Items);
+                        if let Some(item_list) =
+                            db.parse_or_expand(file_id).and_then(ast::MacroItems::cast)
+                        {
+                            self.collect_from_item_owner(db, source_map, &item_list, file_id)
+                        }
+                    }
+                }
                 ast::ItemOrMacro::Macro(macro_call) => {
                     //FIXME: we should really cut down on the boilerplate required to process a macro
                     let ast_id = db.ast_id_map(file_id).ast_id(&macro_call).with_file_id(file_id);
                     if let Some(path) = macro_call
                         .path()
-                        .and_then(|path| Path::from_src(Source { ast: path, file_id }, db))
+                        .and_then(|path| Path::from_src(path, &Hygiene::new(db, file_id)))
                     {
                         if let Some(def) = self.module.resolver(db).resolve_path_as_macro(db, &path)
                         {
-                            let call_id = MacroCallLoc { def: def.id, ast_id }.id(db);
+                            let call_id =
+                                MacroCallLoc { def: def.id, kind: MacroCallKind::FnLike(ast_id) }
+                                    .id(db);
                             let file_id = call_id.as_file(MacroFileKind::Items);
                             if let Some(item_list) =
                                 db.parse_or_expand(file_id).and_then(ast::MacroItems::cast)
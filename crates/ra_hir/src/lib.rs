@@ -35,14 +35,21 @@ pub mod mock;
 mod path;
 pub mod source_binder;
 
+mod attr;
+
 mod source_id;
 mod ids;
+mod hygiene;
+mod builtin_derive;
+mod builtin_macro;
+mod proc_macro;
 mod name;
 mod nameres;
 mod adt;
 mod traits;
 mod type_alias;
 mod type_ref;
+mod const_eval;
 mod ty;
 mod impl_block;
 mod expr;
@@ -67,17 +74,19 @@ use crate::{
 
 pub use self::{
     adt::VariantDef,
+    const_eval::ConstValue,
     either::Either,
-    expr::ExprScopes,
+    expr::{match_check::MissingPattern, ExprScopes},
     from_source::FromSource,
     generics::{GenericParam, GenericParams, HasGenericParams},
-    ids::{HirFileId, MacroCallId, MacroCallLoc, MacroDefId, MacroFile},
+    hygiene::Hygiene,
+    ids::{ExpansionInfo, HirFileId, MacroCallId, MacroCallLoc, MacroDefId, MacroFile},
     impl_block::ImplBlock,
     name::Name,
     nameres::{ImportId, Namespace, PerNs},
     path::{Path, PathKind},
     resolve::ScopeDef,
-    source_binder::{PathResolution, ScopeEntryWithSyntax, SourceAnalyzer},
+    source_binder::{Expansion, PathResolution, ScopeEntryWithSyntax, SourceAnalyzer},
     source_id::{AstIdMap, ErasedFileAstId},
     ty::{
         display::HirDisplay, ApplicationTy, CallableDef, Substs, TraitRef, Ty, TypeCtor, TypeWalk,
@@ -3,10 +3,12 @@
 use std::{panic, sync::Arc};
 
 use parking_lot::Mutex;
+use ra_cfg::CfgOptions;
 use ra_db::{
-    salsa, CrateGraph, CrateId, Edition, FileId, FilePosition, SourceDatabase, SourceRoot,
-    SourceRootId,
+    salsa, CrateGraph, CrateId, Edition, Env, FileId, FilePosition, FileReparses, IncludeDir,
+    ProcMacro, SourceDatabase, SourceRoot, SourceRootId,
 };
+use ra_syntax::{Parse, SourceFile};
 use relative_path::RelativePathBuf;
 use rustc_hash::FxHashMap;
 use test_utils::{extract_offset, parse_fixture, CURSOR_MARKER};
@@ -43,6 +45,12 @@ impl HirDebugHelper for MockDatabase {
     }
 }
 
+impl FileReparses for MockDatabase {
+    fn take_reparse(&self, _file_id: FileId, _text: &Arc<String>) -> Option<Parse<SourceFile>> {
+        None
+    }
+}
+
 impl MockDatabase {
     pub fn with_files(fixture: &str) -> MockDatabase {
         let (db, position) = MockDatabase::from_fixture(fixture);
@@ -74,13 +82,14 @@ impl MockDatabase {
     pub fn set_crate_graph_from_fixture(&mut self, graph: CrateGraphFixture) {
         let mut ids = FxHashMap::default();
         let mut crate_graph = CrateGraph::default();
-        for (crate_name, (crate_root, edition, _)) in graph.0.iter() {
+        for (crate_name, (crate_root, edition, cfg_options, env, _)) in graph.0.iter() {
             let crate_root = self.file_id_of(&crate_root);
-            let crate_id = crate_graph.add_crate_root(crate_root, *edition);
+            let crate_id =
+                crate_graph.add_crate_root(crate_root, *edition, cfg_options.clone(), env.clone());
             Arc::make_mut(&mut self.crate_names).insert(crate_id, crate_name.clone());
             ids.insert(crate_name, crate_id);
         }
-        for (crate_name, (_, _, deps)) in graph.0.iter() {
+        for (crate_name, (_, _, _, _, deps)) in graph.0.iter() {
             let from = ids[crate_name];
             for dep in deps {
                 let to = ids[dep];
@@ -90,6 +99,26 @@ impl MockDatabase {
         self.set_crate_graph(Arc::new(crate_graph))
     }
 
+    /// Makes the crate with the root `crate_root` a `proc-macro` crate
+    /// exporting `proc_macros`.
+    pub fn set_proc_macros(&mut self, crate_root: &str, proc_macros: Vec<ProcMacro>) {
+        let crate_root = self.file_id_of(crate_root);
+        let mut crate_graph = CrateGraph::clone(&self.crate_graph());
+        let crate_id = crate_graph.crate_id_for_crate_root(crate_root).unwrap();
+        crate_graph.set_proc_macros(crate_id, proc_macros);
+        self.set_crate_graph(Arc::new(crate_graph))
+    }
+
+    /// Sets the directories the crate with the root `crate_root` can
+    /// `include!` from by absolute path.
+    pub fn set_include_dirs(&mut self, crate_root: &str, include_dirs: Vec<IncludeDir>) {
+        let crate_root = self.file_id_of(crate_root);
+        let mut crate_graph = CrateGraph::clone(&self.crate_graph());
+        let crate_id = crate_graph.crate_id_for_crate_root(crate_root).unwrap();
+        crate_graph.set_include_dirs(crate_id, include_dirs);
+        self.set_crate_graph(Arc::new(crate_graph))
+    }
+
     pub fn diagnostics(&self) -> String {
         let mut buf = String::new();
         let mut files: Vec<FileId> = self.files.values().copied().collect();
@@ -184,7 +213,12 @@ impl MockDatabase {
 
         if is_crate_root {
             let mut crate_graph = CrateGraph::default();
-            crate_graph.add_crate_root(file_id, Edition::Edition2018);
+            crate_graph.add_crate_root(
+                file_id,
+                Edition::Edition2018,
+                CfgOptions::default(),
+                Env::default(),
+            );
             self.set_crate_graph(Arc::new(crate_graph));
         }
         file_id
@@ -268,19 +302,57 @@ impl MockDatabase {
 }
 
 #[derive(Default)]
-pub struct CrateGraphFixture(pub Vec<(String, (String, Edition, Vec<String>))>);
+pub struct CrateGraphFixture(pub Vec<(String, (String, Edition, CfgOptions, Env, Vec<String>))>);
 
 #[macro_export]
 macro_rules! crate_graph {
-    ($($crate_name:literal: ($crate_path:literal, $($edition:literal,)? [$($dep:literal),*]),)*) => {{
+    ($(
+        $crate_name:literal: (
+            $crate_path:literal,
+            $($edition:literal,)?
+            [$($dep:literal),*]
+            $(, cfg = {
+                $($key:literal $(= $value:literal)?),*
+                $(,)?
+            })?
+            $(, env = {
+                $($env_key:literal = $env_value:literal),*
+                $(,)?
+            })?
+        ),
+    )*) => {{
         let mut res = $crate::mock::CrateGraphFixture::default();
         $(
             #[allow(unused_mut, unused_assignments)]
             let mut edition = ra_db::Edition::Edition2018;
             $(edition = ra_db::Edition::from_string($edition);)?
+            let cfg_options = {
+                #[allow(unused_mut)]
+                let mut cfg = ra_cfg::CfgOptions::default();
+                $(
+                    $(
+                        let value: Option<&str> = None $(.or(Some($value)))?;
+                        match value {
+                            None => cfg.insert_atom($key.into()),
+                            Some(value) => cfg.insert_key_value($key.into(), value.into()),
+                        }
+                    )*
+                )?
+                cfg
+            };
+            let env = {
+                #[allow(unused_mut)]
+                let mut env = ra_db::Env::default();
+                $(
+                    $(
+                        env.set($env_key, $env_value.to_string());
+                    )*
+                )?
+                env
+            };
             res.0.push((
                 $crate_name.to_string(),
-                ($crate_path.to_string(), edition, vec![$($dep.to_string()),*])
+                ($crate_path.to_string(), edition, cfg_options, env, vec![$($dep.to_string()),*])
             ));
         )*
         res
@@ -92,12 +92,24 @@ impl AsName for ast::FieldKind {
     }
 }
 
+impl AsName for tt::Ident {
+    fn as_name(&self) -> Name {
+        Name::resolve(&self.text)
+    }
+}
+
 impl AsName for ra_db::Dependency {
     fn as_name(&self) -> Name {
         Name::new_text(self.name.clone())
     }
 }
 
+impl AsName for ra_db::ProcMacro {
+    fn as_name(&self) -> Name {
+        Name::new_text(self.name.clone())
+    }
+}
+
 // Primitives
 pub(crate) const ISIZE: Name = Name::new_inline_ascii(5, b"isize");
 pub(crate) const I8: Name = Name::new_inline_ascii(2, b"i8");
@@ -140,3 +152,30 @@ pub(crate) const RESULT_TYPE: Name = Name::new_inline_ascii(6, b"Result");
 pub(crate) const OUTPUT_TYPE: Name = Name::new_inline_ascii(6, b"Output");
 pub(crate) const TARGET_TYPE: Name = Name::new_inline_ascii(6, b"Target");
 pub(crate) const BOX_TYPE: Name = Name::new_inline_ascii(3, b"Box");
+pub(crate) const INDEX_TYPE: Name = Name::new_inline_ascii(5, b"Index");
+pub(crate) const INDEX_MUT_TYPE: Name = Name::new_inline_ascii(8, b"IndexMut");
+
+// Builtin macros
+pub(crate) const COLUMN_MACRO: Name = Name::new_inline_ascii(6, b"column");
+pub(crate) const CONCAT_MACRO: Name = Name::new_inline_ascii(6, b"concat");
+pub(crate) const ENV_MACRO: Name = Name::new_inline_ascii(3, b"env");
+pub(crate) const FILE_MACRO: Name = Name::new_inline_ascii(4, b"file");
+pub(crate) const FORMAT_ARGS_MACRO: Name = Name::new_inline_ascii(11, b"format_args");
+pub(crate) const INCLUDE_MACRO: Name = Name::new_inline_ascii(7, b"include");
+pub(crate) const LINE_MACRO: Name = Name::new_inline_ascii(4, b"line");
+pub(crate) const STRINGIFY_MACRO: Name = Name::new_inline_ascii(9, b"stringify");
+
+// Builtin derives
+pub(crate) const CLONE_TRAIT: Name = Name::new_inline_ascii(5, b"Clone");
+pub(crate) const COPY_TRAIT: Name = Name::new_inline_ascii(4, b"Copy");
+pub(crate) const DEBUG_TRAIT: Name = Name::new_inline_ascii(5, b"Debug");
+pub(crate) const DEFAULT_TRAIT: Name = Name::new_inline_ascii(7, b"Default");
+pub(crate) const HASH_TRAIT: Name = Name::new_inline_ascii(4, b"Hash");
+pub(crate) const EQ_TRAIT: Name = Name::new_inline_ascii(2, b"Eq");
+pub(crate) const PARTIAL_EQ_TRAIT: Name = Name::new_inline_ascii(9, b"PartialEq");
+pub(crate) const ORD_TRAIT: Name = Name::new_inline_ascii(3, b"Ord");
+pub(crate) const PARTIAL_ORD_TRAIT: Name = Name::new_inline_ascii(10, b"PartialOrd");
+
+// Methods of known traits
+pub(crate) const INDEX_FN: Name = Name::new_inline_ascii(5, b"index");
+pub(crate) const INDEX_MUT_FN: Name = Name::new_inline_ascii(9, b"index_mut");
@@ -46,6 +46,9 @@
 //! unexpanded macros. On every iteration, we try to resolve each macro call
 //! path and, upon success, we run macro expansion and "collect module" phase
 //! on the result
+//!
+//! Derives and attribute macros on items are handled the same way, except that
+//! the input of the macro is the item itself.
 
 mod per_ns;
 mod raw;
@@ -67,7 +70,7 @@ use test_utils::tested_by;
 use crate::{
     db::{AstDatabase, DefDatabase},
     diagnostics::DiagnosticSink,
-    ids::MacroDefId,
+    ids::{MacroCallId, MacroDefId},
     nameres::diagnostics::DefDiagnostic,
     Adt, AstId, BuiltinType, Crate, HirFileId, MacroDef, Module, ModuleDef, Name, Path, PathKind,
     Trait,
@@ -105,6 +108,9 @@ pub struct CrateDefMap {
     /// However, do we want to put it as a global variable?
     poison_macros: FxHashSet<MacroDefId>,
 
+    /// Expanded derives and attribute macros, by the item they are applied to.
+    attr_macro_calls: FxHashMap<AstId<ast::ModuleItem>, Vec<MacroCallId>>,
+
     diagnostics: Vec<DefDiagnostic>,
 }
 
@@ -264,6 +270,7 @@ impl CrateDefMap {
                 root,
                 modules,
                 poison_macros: FxHashSet::default(),
+                attr_macro_calls: FxHashMap::default(),
                 diagnostics: Vec::new(),
             }
         };
@@ -287,6 +294,11 @@ impl CrateDefMap {
         &self.extern_prelude
     }
 
+    /// Returns the expansions of derives and attribute macros of `item`.
+    pub(crate) fn attr_macro_calls(&self, item: AstId<ast::ModuleItem>) -> &[MacroCallId] {
+        self.attr_macro_calls.get(&item).map_or(&[], |it| it.as_slice())
+    }
+
     pub(crate) fn add_diagnostics(
         &self,
         db: &(impl DefDatabase + AstDatabase),
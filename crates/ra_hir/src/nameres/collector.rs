@@ -1,13 +1,20 @@
 //! FIXME: write short doc here
 
-use ra_db::FileId;
+use ra_cfg::CfgOptions;
+use ra_db::{FileId, ProcMacroKind};
 use ra_syntax::{ast, SmolStr};
 use rustc_hash::FxHashMap;
 use test_utils::tested_by;
 
 use crate::{
+    attr::{self, Attr},
+    builtin_derive::find_builtin_derive,
+    builtin_macro::find_builtin_macro,
     db::DefDatabase,
-    ids::{AstItemDef, LocationCtx, MacroCallId, MacroCallLoc, MacroDefId, MacroFileKind},
+    ids::{
+        AstItemDef, LocationCtx, MacroCallId, MacroCallKind, MacroCallLoc, MacroDefId,
+        MacroDefKind, MacroFileKind,
+    },
     name::MACRO_RULES,
     nameres::{
         diagnostics::DefDiagnostic,
@@ -15,6 +22,7 @@ use crate::{
         raw, Crate, CrateDefMap, CrateModuleId, ModuleData, ModuleDef, PerNs, ReachedFixedPoint,
         Resolution, ResolveMode,
     },
+    proc_macro::proc_macros,
     Adt, AstId, Const, Enum, Function, HirFileId, MacroDef, Module, Name, Path, PathKind, Static,
     Struct, Trait, TypeAlias, Union,
 };
@@ -35,13 +43,18 @@ pub(super) fn collect_defs(db: &impl DefDatabase, mut def_map: CrateDefMap) -> C
         }
     }
 
+    let crate_graph = db.crate_graph();
+    let cfg_options = crate_graph.cfg_options(def_map.krate.crate_id());
+
     let mut collector = DefCollector {
         db,
         def_map,
         glob_imports: FxHashMap::default(),
         unresolved_imports: Vec::new(),
         unexpanded_macros: Vec::new(),
+        unexpanded_attrs: Vec::new(),
         macro_stack_monitor: MacroStackMonitor::default(),
+        cfg_options,
     };
     collector.collect();
     collector.finish()
@@ -76,19 +89,24 @@ impl MacroStackMonitor {
 }
 
 /// Walks the tree of module recursively
-struct DefCollector<DB> {
+struct DefCollector<'a, DB> {
     db: DB,
     def_map: CrateDefMap,
     glob_imports: FxHashMap<CrateModuleId, Vec<(CrateModuleId, raw::ImportId)>>,
     unresolved_imports: Vec<(CrateModuleId, raw::ImportId, raw::ImportData)>,
     unexpanded_macros: Vec<(CrateModuleId, AstId<ast::MacroCall>, Path)>,
+    /// Derives (`ProcMacroKind::CustomDerive`) and attribute macros
+    /// (`ProcMacroKind::Attr`) applied to items.
+    unexpanded_attrs: Vec<(CrateModuleId, AstId<ast::ModuleItem>, Path, ProcMacroKind)>,
 
     /// Some macro use `$tt:tt which mean we have to handle the macro perfectly
     /// To prevent stack overflow, we add a deep counter here for prevent that.
     macro_stack_monitor: MacroStackMonitor,
+
+    cfg_options: &'a CfgOptions,
 }
 
-impl<'a, DB> DefCollector<&'a DB>
+impl<DB> DefCollector<'_, &'_ DB>
 where
     DB: DefDatabase,
 {
@@ -98,6 +116,12 @@ where
         let raw_items = self.db.raw_items(file_id.into());
         let module_id = self.def_map.root;
         self.def_map.modules[module_id].definition = Some(file_id);
+
+        // Procedural macros are exported from the root of a `proc-macro` crate.
+        for (name, macro_) in proc_macros(self.db, self.def_map.krate) {
+            self.define_macro(module_id, name, macro_, true);
+        }
+
         ModCollector {
             def_collector: &mut *self,
             attr_path: None,
@@ -441,7 +465,8 @@ where
             );
 
             if let Some(def) = resolved_res.resolved_def.get_macros() {
-                let call_id = MacroCallLoc { def: def.id, ast_id: *ast_id }.id(self.db);
+                let call_id =
+                    MacroCallLoc { def: def.id, kind: MacroCallKind::FnLike(*ast_id) }.id(self.db);
                 resolved.push((*module_id, call_id, def.id));
                 res = ReachedFixedPoint::No;
                 return false;
@@ -452,6 +477,48 @@ where
 
         self.unexpanded_macros = macros;
 
+        let mut attrs = std::mem::replace(&mut self.unexpanded_attrs, Vec::new());
+        attrs.retain(|(module_id, ast_id, path, kind)| {
+            let resolved_res = self.def_map.resolve_path_fp_with_macro(
+                self.db,
+                ResolveMode::Other,
+                *module_id,
+                path,
+            );
+
+            let def = match resolved_res.resolved_def.get_macros() {
+                Some(it) => it.id,
+                None => {
+                    let builtin_derive = match kind {
+                        ProcMacroKind::CustomDerive => path
+                            .segments
+                            .last()
+                            .and_then(|it| find_builtin_derive(&it.name, self.def_map.krate)),
+                        ProcMacroKind::FuncLike | ProcMacroKind::Attr => None,
+                    };
+                    match builtin_derive {
+                        Some(it) => it,
+                        None => return true,
+                    }
+                }
+            };
+            match def.kind {
+                MacroDefKind::ProcMacro(expander) if expander.kind() != *kind => (),
+                MacroDefKind::ProcMacro(_) | MacroDefKind::BuiltInDerive(_) => {
+                    let call_id =
+                        MacroCallLoc { def, kind: MacroCallKind::Attr(*ast_id) }.id(self.db);
+                    self.def_map.attr_macro_calls.entry(*ast_id).or_default().push(call_id);
+                    resolved.push((*module_id, call_id, def));
+                    res = ReachedFixedPoint::No;
+                }
+                // Something else with the same name, like a `macro_rules!`,
+                // which can't be used as an attribute.
+                _ => (),
+            }
+            false
+        });
+        self.unexpanded_attrs = attrs;
+
         for (module_id, macro_call_id, macro_def_id) in resolved {
             self.collect_macro_expansion(module_id, macro_call_id, macro_def_id);
         }
@@ -506,7 +573,7 @@ struct ModCollector<'a, D> {
     parent_module: Option<ParentModule<'a>>,
 }
 
-impl<DB> ModCollector<'_, &'_ mut DefCollector<&'_ DB>>
+impl<DB> ModCollector<'_, &'_ mut DefCollector<'_, &'_ DB>>
 where
     DB: DefDatabase,
 {
@@ -523,52 +590,66 @@ where
         // `#[macro_use] extern crate` is hoisted to imports macros before collecting
         // any other items.
         for item in items {
-            if let raw::RawItem::Import(import_id) = *item {
-                let import = self.raw_items[import_id].clone();
-                if import.is_extern_crate && import.is_macro_use {
-                    self.def_collector.import_macros_from_extern_crate(self.module_id, &import);
+            if self.is_cfg_enabled(item.attrs()) {
+                if let raw::RawItemKind::Import(import_id) = item.kind {
+                    let import = self.raw_items[import_id].clone();
+                    if import.is_extern_crate && import.is_macro_use {
+                        self.def_collector.import_macros_from_extern_crate(self.module_id, &import);
+                    }
                 }
             }
         }
 
         for item in items {
-            match *item {
-                raw::RawItem::Module(m) => self.collect_module(&self.raw_items[m]),
-                raw::RawItem::Import(import_id) => self.def_collector.unresolved_imports.push((
-                    self.module_id,
-                    import_id,
-                    self.raw_items[import_id].clone(),
-                )),
-                raw::RawItem::Def(def) => self.define_def(&self.raw_items[def]),
-                raw::RawItem::Macro(mac) => self.collect_macro(&self.raw_items[mac]),
+            if self.is_cfg_enabled(item.attrs()) {
+                match item.kind {
+                    raw::RawItemKind::Module(m) => {
+                        self.collect_module(&self.raw_items[m], item.attrs())
+                    }
+                    raw::RawItemKind::Import(import_id) => self
+                        .def_collector
+                        .unresolved_imports
+                        .push((self.module_id, import_id, self.raw_items[import_id].clone())),
+                    raw::RawItemKind::Def(def) => {
+                        self.define_def(&self.raw_items[def]);
+                        self.collect_attr_macros(&self.raw_items[def], item.attrs());
+                    }
+                    raw::RawItemKind::Macro(mac) => self.collect_macro(&self.raw_items[mac]),
+                }
             }
         }
     }
 
-    fn collect_module(&mut self, module: &raw::ModuleData) {
+    fn collect_module(&mut self, module: &raw::ModuleData, attrs: &[Attr]) {
+        // `cfg_attr` may produce `path` and `macro_use` attributes as well
+        let expanded_attrs = attr::expand_cfg_attrs(attrs, self.def_collector.cfg_options);
+        let expanded_path = expanded_attrs.iter().find_map(|it| it.as_simple_key_value("path"));
+        let expanded_macro_use = expanded_attrs.iter().any(|it| it.is_simple_atom("macro_use"));
         match module {
             // inline module, just recurse
             raw::ModuleData::Definition { name, items, ast_id, attr_path, is_macro_use } => {
+                let attr_path = attr_path.as_ref().or(expanded_path);
                 let module_id =
                     self.push_child_module(name.clone(), ast_id.with_file_id(self.file_id), None);
-                let parent_module = ParentModule { name, attr_path: attr_path.as_ref() };
+                let parent_module = ParentModule { name, attr_path };
 
                 ModCollector {
                     def_collector: &mut *self.def_collector,
                     module_id,
-                    attr_path: attr_path.as_ref(),
+                    attr_path,
                     file_id: self.file_id,
                     raw_items: self.raw_items,
                     parent_module: Some(parent_module),
                 }
                 .collect(&*items);
-                if *is_macro_use {
+                if *is_macro_use || expanded_macro_use {
                     self.import_all_legacy_macros(module_id);
                 }
             }
             // out of line module, resolve, parse and recurse
             raw::ModuleData::Declaration { name, ast_id, attr_path, is_macro_use } => {
                 let ast_id = ast_id.with_file_id(self.file_id);
+                let attr_path = attr_path.as_ref().or(expanded_path);
                 let is_root = self.def_collector.def_map.modules[self.module_id].parent.is_none();
                 match resolve_submodule(
                     self.def_collector.db,
@@ -576,7 +657,7 @@ where
                     self.attr_path,
                     name,
                     is_root,
-                    attr_path.as_ref(),
+                    attr_path,
                     self.parent_module,
                 ) {
                     Ok(file_id) => {
@@ -585,13 +666,13 @@ where
                         ModCollector {
                             def_collector: &mut *self.def_collector,
                             module_id,
-                            attr_path: attr_path.as_ref(),
+                            attr_path,
                             file_id: file_id.into(),
                             raw_items: &raw_items,
                             parent_module: None,
                         }
                         .collect(raw_items.items());
-                        if *is_macro_use {
+                        if *is_macro_use || expanded_macro_use {
                             self.import_all_legacy_macros(module_id);
                         }
                     }
@@ -660,14 +741,52 @@ where
         self.def_collector.update(self.module_id, None, &[(name, resolution)])
     }
 
+    /// Records the derives and attribute macros applied to `def`. They are
+    /// expanded during name resolution, as their paths might refer to imports.
+    ///
+    /// FIXME: an attribute macro replaces the item, but we keep the original
+    /// item defined as well.
+    fn collect_attr_macros(&mut self, def: &raw::DefData, attrs: &[Attr]) {
+        if attrs.is_empty() {
+            return;
+        }
+        let ast_id = def.kind.item_ast_id().with_file_id(self.file_id);
+        for attr in attr::expand_cfg_attrs(attrs, self.def_collector.cfg_options) {
+            if let Some(derives) = attr.as_derive() {
+                for path in derives {
+                    self.def_collector.unexpanded_attrs.push((
+                        self.module_id,
+                        ast_id,
+                        path,
+                        ProcMacroKind::CustomDerive,
+                    ));
+                }
+            } else if !attr.is_builtin() {
+                self.def_collector.unexpanded_attrs.push((
+                    self.module_id,
+                    ast_id,
+                    attr.path,
+                    ProcMacroKind::Attr,
+                ));
+            }
+        }
+    }
+
     fn collect_macro(&mut self, mac: &raw::MacroData) {
         // Case 1: macro rules, define a macro in crate-global mutable scope
         if is_macro_rules(&mac.path) {
             if let Some(name) = &mac.name {
-                let macro_id = MacroDefId {
-                    ast_id: mac.ast_id.with_file_id(self.file_id),
-                    krate: self.def_collector.def_map.krate,
-                };
+                let ast_id = mac.ast_id.with_file_id(self.file_id);
+                let krate = self.def_collector.def_map.krate;
+                // Builtin macros we don't know about are treated like the
+                // empty `macro_rules!` they are declared as.
+                let builtin_id =
+                    if mac.builtin { find_builtin_macro(name, krate, ast_id) } else { None };
+                let macro_id = builtin_id.unwrap_or(MacroDefId {
+                    ast_id: Some(ast_id),
+                    krate,
+                    kind: MacroDefKind::Declarative,
+                });
                 let macro_ = MacroDef { id: macro_id };
                 self.def_collector.define_macro(self.module_id, name.clone(), macro_, mac.export);
             }
@@ -682,7 +801,8 @@ where
             self.def_collector.def_map[self.module_id].scope.get_legacy_macro(&name)
         }) {
             let def = macro_def.id;
-            let macro_call_id = MacroCallLoc { def, ast_id }.id(self.def_collector.db);
+            let macro_call_id =
+                MacroCallLoc { def, kind: MacroCallKind::FnLike(ast_id) }.id(self.def_collector.db);
 
             self.def_collector.collect_macro_expansion(self.module_id, macro_call_id, def);
             return;
@@ -703,6 +823,10 @@ where
             self.def_collector.define_legacy_macro(self.module_id, name.clone(), macro_);
         }
     }
+
+    fn is_cfg_enabled(&self, attrs: &[Attr]) -> bool {
+        attr::is_cfg_enabled(attrs, self.def_collector.cfg_options)
+    }
 }
 
 fn is_macro_rules(path: &Path) -> bool {
@@ -729,7 +853,9 @@ mod tests {
             glob_imports: FxHashMap::default(),
             unresolved_imports: Vec::new(),
             unexpanded_macros: Vec::new(),
+            unexpanded_attrs: Vec::new(),
             macro_stack_monitor: monitor,
+            cfg_options: &CfgOptions::default(),
         };
         collector.collect();
         collector.finish()
@@ -752,6 +878,7 @@ mod tests {
                 root,
                 modules,
                 poison_macros: FxHashSet::default(),
+                attr_macro_calls: FxHashMap::default(),
                 diagnostics: Vec::new(),
             }
         };
@@ -1,3 +1,6 @@
+use ra_db::{IncludeDir, SourceRootId};
+use relative_path::RelativePathBuf;
+
 use super::*;
 
 #[test]
@@ -620,3 +623,70 @@ fn macro_dollar_crate_is_correct_in_indirect_deps() {
         ⋮bar: t v
     "###);
 }
+
+#[test]
+fn builtin_include_brings_file_into_scope() {
+    let map = def_map_with_crate_graph(
+        r#"
+        //- /main.rs
+        #[rustc_builtin_macro]
+        macro_rules! include {() => {}}
+        #[rustc_builtin_macro]
+        macro_rules! concat {() => {}}
+        #[rustc_builtin_macro]
+        macro_rules! env {() => {}}
+
+        include!(concat!(env!("GEN_DIR"), "/gen.rs"));
+
+        //- /gen/gen.rs
+        pub struct Generated;
+        fn generated() {}
+        "#,
+        crate_graph! {
+            "main": ("/main.rs", [], env = { "GEN_DIR" = "gen" }),
+        },
+    );
+    assert_snapshot!(map, @r###"
+        ⋮crate
+        ⋮Generated: t v
+        ⋮generated: v
+    "###);
+}
+
+#[test]
+fn builtin_include_resolves_absolute_paths_in_include_dirs() {
+    let mut db = MockDatabase::with_files(
+        r#"
+        //- /main.rs
+        #[rustc_builtin_macro]
+        macro_rules! include {() => {}}
+        #[rustc_builtin_macro]
+        macro_rules! concat {() => {}}
+        #[rustc_builtin_macro]
+        macro_rules! env {() => {}}
+
+        include!(concat!(env!("OUT_DIR"), "/gen.rs"));
+
+        //- root /out/
+        //- /out/gen.rs
+        pub struct Generated;
+        "#,
+    );
+    db.set_crate_graph_from_fixture(crate_graph! {
+        "main": ("/main.rs", [], env = { "OUT_DIR" = "/target/debug/build/main/out" }),
+    });
+    db.set_include_dirs(
+        "/main.rs",
+        vec![IncludeDir {
+            path: "/target/debug/build/main/out".into(),
+            source_root: SourceRootId(1),
+            relative_path: RelativePathBuf::default(),
+        }],
+    );
+    let krate = Crate { crate_id: db.crate_graph().iter().next().unwrap() };
+    let map = render_crate_def_map(&db.crate_def_map(krate));
+    assert_snapshot!(map, @r###"
+        ⋮crate
+        ⋮Generated: t v
+    "###);
+}
@@ -7,7 +7,9 @@ use ra_syntax::{
     AstNode,
 };
 
-use crate::{db::AstDatabase, name, type_ref::TypeRef, AsName, Crate, Name, Source};
+use crate::{
+    db::AstDatabase, hygiene::Hygiene, name, type_ref::TypeRef, AsName, Crate, Name, Source,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Path {
@@ -66,7 +68,8 @@ impl Path {
         mut cb: impl FnMut(Path, &ast::UseTree, bool, Option<Name>),
     ) {
         if let Some(tree) = item_src.ast.use_tree() {
-            expand_use_tree(None, tree, &|| item_src.file_id.macro_crate(db), &mut cb);
+            let hygiene = Hygiene::new(db, item_src.file_id);
+            expand_use_tree(None, tree, &hygiene, &mut cb);
         }
     }
 
@@ -83,17 +86,12 @@ impl Path {
     /// Converts an `ast::Path` to `Path`. Works with use trees.
     /// DEPRECATED: It does not handle `$crate` from macro call.
     pub fn from_ast(path: ast::Path) -> Option<Path> {
-        Path::parse(path, &|| None)
+        Path::from_src(path, &Hygiene::new_unhygienic())
     }
 
     /// Converts an `ast::Path` to `Path`. Works with use trees.
     /// It correctly handles `$crate` based path from macro call.
-    pub fn from_src(source: Source<ast::Path>, db: &impl AstDatabase) -> Option<Path> {
-        let file_id = source.file_id;
-        Path::parse(source.ast, &|| file_id.macro_crate(db))
-    }
-
-    fn parse(mut path: ast::Path, macro_crate: &impl Fn() -> Option<Crate>) -> Option<Path> {
+    pub fn from_src(mut path: ast::Path, hygiene: &Hygiene) -> Option<Path> {
         let mut kind = PathKind::Plain;
         let mut segments = Vec::new();
         loop {
@@ -106,19 +104,20 @@ impl Path {
             match segment.kind()? {
                 ast::PathSegmentKind::Name(name) => {
                     if name.text() == "$crate" {
-                        if let Some(macro_crate) = macro_crate() {
-                            kind = PathKind::DollarCrate(macro_crate);
+                        if let Some(krate) = hygiene.dollar_crate() {
+                            kind = PathKind::DollarCrate(krate);
                             break;
                         }
                     }
 
                     let args = segment
                         .type_arg_list()
-                        .and_then(GenericArgs::from_ast)
+                        .and_then(|it| GenericArgs::from_ast(it, hygiene))
                         .or_else(|| {
                             GenericArgs::from_fn_like_path_ast(
                                 segment.param_list(),
                                 segment.ret_type(),
+                                hygiene,
                             )
                         })
                         .map(Arc::new);
@@ -128,7 +127,7 @@ impl Path {
                 ast::PathSegmentKind::Type { type_ref, trait_ref } => {
                     assert!(path.qualifier().is_none()); // this can only occur at the first segment
 
-                    let self_type = TypeRef::from_ast(type_ref?);
+                    let self_type = TypeRef::from_ast(type_ref?, hygiene);
 
                     match trait_ref {
                         // <T>::foo
@@ -137,7 +136,7 @@ impl Path {
                         }
                         // <T as Trait<A>>::Foo desugars to Trait<Self=T, A>::Foo
                         Some(trait_ref) => {
-                            let path = Path::parse(trait_ref.path()?, macro_crate)?;
+                            let path = Path::from_src(trait_ref.path()?, hygiene)?;
                             kind = path.kind;
                             let mut prefix_segments = path.segments;
                             prefix_segments.reverse();
@@ -224,10 +223,10 @@ impl Path {
 }
 
 impl GenericArgs {
-    pub(crate) fn from_ast(node: ast::TypeArgList) -> Option<GenericArgs> {
+    pub(crate) fn from_ast(node: ast::TypeArgList, hygiene: &Hygiene) -> Option<GenericArgs> {
         let mut args = Vec::new();
         for type_arg in node.type_args() {
-            let type_ref = TypeRef::from_ast_opt(type_arg.type_ref());
+            let type_ref = TypeRef::from_ast_opt(type_arg.type_ref(), hygiene);
             args.push(GenericArg::Type(type_ref));
         }
         // lifetimes ignored for now
@@ -235,7 +234,7 @@ impl GenericArgs {
         for assoc_type_arg in node.assoc_type_args() {
             if let Some(name_ref) = assoc_type_arg.name_ref() {
                 let name = name_ref.as_name();
-                let type_ref = TypeRef::from_ast_opt(assoc_type_arg.type_ref());
+                let type_ref = TypeRef::from_ast_opt(assoc_type_arg.type_ref(), hygiene);
                 bindings.push((name, type_ref));
             }
         }
@@ -251,20 +250,21 @@ impl GenericArgs {
     pub(crate) fn from_fn_like_path_ast(
         params: Option<ast::ParamList>,
         ret_type: Option<ast::RetType>,
+        hygiene: &Hygiene,
     ) -> Option<GenericArgs> {
         let mut args = Vec::new();
         let mut bindings = Vec::new();
         if let Some(params) = params {
             let mut param_types = Vec::new();
             for param in params.params() {
-                let type_ref = TypeRef::from_ast_opt(param.ascribed_type());
+                let type_ref = TypeRef::from_ast_opt(param.ascribed_type(), hygiene);
                 param_types.push(type_ref);
             }
             let arg = GenericArg::Type(TypeRef::Tuple(param_types));
             args.push(arg);
         }
         if let Some(ret_type) = ret_type {
-            let type_ref = TypeRef::from_ast_opt(ret_type.type_ref());
+            let type_ref = TypeRef::from_ast_opt(ret_type.type_ref(), hygiene);
             bindings.push((name::OUTPUT_TYPE, type_ref))
         }
         if args.is_empty() && bindings.is_empty() {
@@ -288,7 +288,7 @@ impl From<Name> for Path {
 fn expand_use_tree(
     prefix: Option<Path>,
     tree: ast::UseTree,
-    macro_crate: &impl Fn() -> Option<Crate>,
+    hygiene: &Hygiene,
     cb: &mut impl FnMut(Path, &ast::UseTree, bool, Option<Name>),
 ) {
     if let Some(use_tree_list) = tree.use_tree_list() {
@@ -297,13 +297,13 @@ fn expand_use_tree(
             None => prefix,
             // E.g. `use something::{inner}` (prefix is `None`, path is `something`)
             // or `use something::{path::{inner::{innerer}}}` (prefix is `something::path`, path is `inner`)
-            Some(path) => match convert_path(prefix, path, macro_crate) {
+            Some(path) => match convert_path(prefix, path, hygiene) {
                 Some(it) => Some(it),
                 None => return, // FIXME: report errors somewhere
             },
         };
         for child_tree in use_tree_list.use_trees() {
-            expand_use_tree(prefix.clone(), child_tree, macro_crate, cb);
+            expand_use_tree(prefix.clone(), child_tree, hygiene, cb);
         }
     } else {
         let alias = tree.alias().and_then(|a| a.name()).map(|a| a.as_name());
@@ -320,7 +320,7 @@ fn expand_use_tree(
                     }
                 }
             }
-            if let Some(path) = convert_path(prefix, ast_path, macro_crate) {
+            if let Some(path) = convert_path(prefix, ast_path, hygiene) {
                 let is_glob = tree.has_star();
                 cb(path, &tree, is_glob, alias)
             }
@@ -330,13 +330,9 @@ fn expand_use_tree(
     }
 }
 
-fn convert_path(
-    prefix: Option<Path>,
-    path: ast::Path,
-    macro_crate: &impl Fn() -> Option<Crate>,
-) -> Option<Path> {
+fn convert_path(prefix: Option<Path>, path: ast::Path, hygiene: &Hygiene) -> Option<Path> {
     let prefix = if let Some(qual) = path.qualifier() {
-        Some(convert_path(prefix, qual, macro_crate)?)
+        Some(convert_path(prefix, qual, hygiene)?)
     } else {
         prefix
     };
@@ -345,7 +341,7 @@ fn convert_path(
     let res = match segment.kind()? {
         ast::PathSegmentKind::Name(name) => {
             if name.text() == "$crate" {
-                if let Some(krate) = macro_crate() {
+                if let Some(krate) = hygiene.dollar_crate() {
                     return Some(Path::from_simple_segments(
                         PathKind::DollarCrate(krate),
                         iter::empty(),
@@ -403,6 +399,14 @@ pub mod known {
         Path::from_simple_segments(PathKind::Abs, vec![name::STD, name::OPS, name::TRY_TYPE])
     }
 
+    pub fn std_ops_index() -> Path {
+        Path::from_simple_segments(PathKind::Abs, vec![name::STD, name::OPS, name::INDEX_TYPE])
+    }
+
+    pub fn std_ops_index_mut() -> Path {
+        Path::from_simple_segments(PathKind::Abs, vec![name::STD, name::OPS, name::INDEX_MUT_TYPE])
+    }
+
     pub fn std_result_result() -> Path {
         Path::from_simple_segments(PathKind::Abs, vec![name::STD, name::RESULT, name::RESULT_TYPE])
     }
@@ -0,0 +1,137 @@
+//! Procedural macros are compiled Rust code, so we can't expand them ourselves.
+//! Instead, the crate graph provides an expander for each proc macro of a
+//! `proc-macro` crate, which usually runs the macro in a separate process.
+
+use ra_db::{CrateId, ProcMacroId, ProcMacroKind};
+use tt::{Leaf, TokenTree};
+
+use crate::{
+    db::{AstDatabase, DefDatabase},
+    ids::{MacroDefId, MacroDefKind},
+    AsName, Crate, MacroDef, Name,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ProcMacroExpander {
+    krate: CrateId,
+    proc_macro_id: ProcMacroId,
+    kind: ProcMacroKind,
+}
+
+/// Returns the procedural macros exported by `krate`, which is empty unless
+/// `krate` is a `proc-macro` crate.
+pub(crate) fn proc_macros(db: &impl DefDatabase, krate: Crate) -> Vec<(Name, MacroDef)> {
+    let crate_graph = db.crate_graph();
+    crate_graph
+        .proc_macros(krate.crate_id())
+        .iter()
+        .enumerate()
+        .map(|(idx, proc_macro)| {
+            let expander = ProcMacroExpander {
+                krate: krate.crate_id(),
+                proc_macro_id: ProcMacroId(idx as u32),
+                kind: proc_macro.kind,
+            };
+            let id = MacroDefId { ast_id: None, krate, kind: MacroDefKind::ProcMacro(expander) };
+            (proc_macro.as_name(), MacroDef { id })
+        })
+        .collect()
+}
+
+impl ProcMacroExpander {
+    pub(crate) fn kind(self) -> ProcMacroKind {
+        self.kind
+    }
+
+    /// Expands the macro call argument, or the annotated item for derives and
+    /// attribute macros.
+    pub(crate) fn expand(
+        self,
+        db: &impl AstDatabase,
+        tt: &tt::Subtree,
+    ) -> Result<tt::Subtree, String> {
+        let crate_graph = db.crate_graph();
+        let proc_macro = crate_graph
+            .proc_macro(self.krate, self.proc_macro_id)
+            .ok_or("Fail to find proc macro")?;
+        let res = match self.kind {
+            ProcMacroKind::Attr => {
+                let (item, attr) = split_attr(tt, &proc_macro.name)
+                    .ok_or("Fail to find the attribute of the attribute macro")?;
+                proc_macro.expander.expand(&item, Some(&attr))
+            }
+            ProcMacroKind::CustomDerive | ProcMacroKind::FuncLike => {
+                proc_macro.expander.expand(tt, None)
+            }
+        };
+        res.map_err(|err| err.to_string())
+    }
+}
+
+/// Splits an item annotated with `#[name(args)]` into the item without the
+/// attribute and `args`, which is what an attribute macro gets as its input.
+fn split_attr(item: &tt::Subtree, name: &str) -> Option<(tt::Subtree, tt::Subtree)> {
+    let tokens = &item.token_trees;
+    let idx = (0..tokens.len().saturating_sub(1)).find(|&idx| {
+        match (&tokens[idx], &tokens[idx + 1]) {
+            (TokenTree::Leaf(Leaf::Punct(punct)), TokenTree::Subtree(attr)) => {
+                punct.char == '#'
+                    && attr.delimiter == tt::Delimiter::Bracket
+                    && attr_name(attr) == Some(name)
+            }
+            _ => false,
+        }
+    })?;
+
+    let attr = match &tokens[idx + 1] {
+        TokenTree::Subtree(attr) => attr,
+        _ => unreachable!(),
+    };
+    let args = match attr.token_trees.last() {
+        Some(TokenTree::Subtree(args)) if args.delimiter == tt::Delimiter::Parenthesis => {
+            tt::Subtree { delimiter: tt::Delimiter::None, token_trees: args.token_trees.clone() }
+        }
+        _ => tt::Subtree { delimiter: tt::Delimiter::None, token_trees: Vec::new() },
+    };
+
+    let mut item = item.clone();
+    item.token_trees.drain(idx..idx + 2);
+    Some((item, args))
+}
+
+/// The last segment of the path of the attribute `attr`, like `main` for
+/// `#[tokio::main]`.
+fn attr_name(attr: &tt::Subtree) -> Option<&str> {
+    attr.token_trees
+        .iter()
+        .take_while(|tt| match tt {
+            TokenTree::Subtree(_) => false,
+            TokenTree::Leaf(_) => true,
+        })
+        .filter_map(|tt| match tt {
+            TokenTree::Leaf(Leaf::Ident(ident)) => Some(ident.text.as_str()),
+            _ => None,
+        })
+        .last()
+}
+
+#[cfg(test)]
+mod tests {
+    use ra_syntax::{AstNode, SourceFile};
+
+    use super::*;
+
+    fn to_subtree(text: &str) -> tt::Subtree {
+        let source_file = SourceFile::parse(text).ok().unwrap();
+        mbe::syntax_node_to_token_tree(source_file.syntax()).unwrap().0
+    }
+
+    #[test]
+    fn split_attr_removes_the_invoking_attribute() {
+        let item = to_subtree("#[inline] #[tokio::main(basic_scheduler)] async fn main() {}");
+        let (item, args) = split_attr(&item, "main").unwrap();
+        let item = item.to_string();
+        assert!(item.contains("inline") && !item.contains("tokio"), "{}", item);
+        assert_eq!(args.to_string(), "basic_scheduler");
+    }
+}
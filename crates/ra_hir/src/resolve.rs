@@ -11,6 +11,7 @@ use crate::{
         PatId,
     },
     generics::GenericParams,
+    hygiene::SyntaxContext,
     impl_block::ImplBlock,
     name::{Name, SELF_PARAM, SELF_TYPE},
     nameres::{CrateDefMap, CrateModuleId, PerNs},
@@ -35,6 +36,8 @@ pub(crate) struct ModuleItemMap {
 pub(crate) struct ExprScope {
     expr_scopes: Arc<ExprScopes>,
     scope_id: ScopeId,
+    /// Only bindings from this expansion context are visible.
+    ctx: SyntaxContext,
 }
 
 #[derive(Debug, Clone)]
@@ -211,7 +214,7 @@ impl Resolver {
                         .expr_scopes
                         .entries(scope.scope_id)
                         .iter()
-                        .find(|entry| entry.name() == first_name);
+                        .find(|entry| entry.name() == first_name && entry.ctx() == scope.ctx);
 
                     if let Some(e) = entry {
                         return Some(ResolveValueResult::ValueNs(ValueNs::LocalBinding(e.pat())));
@@ -379,8 +382,9 @@ impl Resolver {
         self,
         expr_scopes: Arc<ExprScopes>,
         scope_id: ScopeId,
+        ctx: SyntaxContext,
     ) -> Resolver {
-        self.push_scope(Scope::ExprScope(ExprScope { expr_scopes, scope_id }))
+        self.push_scope(Scope::ExprScope(ExprScope { expr_scopes, scope_id, ctx }))
     }
 }
 
@@ -439,10 +443,15 @@ impl Scope {
             Scope::ImplBlockScope(i) => {
                 f(SELF_TYPE, ScopeDef::SelfType(*i));
             }
-            Scope::ExprScope(e) => {
-                e.expr_scopes.entries(e.scope_id).iter().for_each(|e| {
-                    f(e.name().clone(), ScopeDef::LocalBinding(e.pat()));
-                });
+            Scope::ExprScope(scope) => {
+                scope
+                    .expr_scopes
+                    .entries(scope.scope_id)
+                    .iter()
+                    .filter(|e| e.ctx() == scope.ctx)
+                    .for_each(|e| {
+                        f(e.name().clone(), ScopeDef::LocalBinding(e.pat()));
+                    });
             }
         }
     }
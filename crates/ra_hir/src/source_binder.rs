@@ -12,23 +12,24 @@ use ra_syntax::{
     ast::{self, AstNode},
     AstPtr,
     SyntaxKind::*,
-    SyntaxNode, SyntaxNodePtr, TextRange, TextUnit,
+    SyntaxNode, SyntaxNodePtr, SyntaxToken, TextRange, TextUnit,
 };
 use rustc_hash::FxHashSet;
 
 use crate::{
     db::HirDatabase,
     expr::{
-        self,
+        self, match_check,
         scope::{ExprScopes, ScopeId},
-        BodySourceMap,
+        Body, BodySourceMap,
     },
-    ids::LocationCtx,
+    hygiene::{Hygiene, SyntaxContext, SyntaxContexts},
+    ids::{LocationCtx, MacroCallId, MacroCallKind, MacroCallLoc, MacroFileKind},
     path::known,
     resolve::{ScopeDef, TypeNs, ValueNs},
     ty::method_resolution::implements_trait,
     AsName, Const, DefWithBody, Either, Enum, FromSource, Function, HasBody, HirFileId, MacroDef,
-    Module, Name, Path, Resolver, Static, Struct, Ty,
+    MissingPattern, Module, Name, Path, Resolver, Source, Static, Struct, Ty,
 };
 
 fn try_get_resolver_for_node(
@@ -84,7 +85,12 @@ fn def_with_body_from_child_node(
 /// original source files. It should not be used inside the HIR itself.
 #[derive(Debug)]
 pub struct SourceAnalyzer {
+    file_id: HirFileId,
+    /// The expansion context of the node the analyzer was created for: only
+    /// local bindings of this context are visible.
+    ctx: SyntaxContext,
     resolver: Resolver,
+    body: Option<Arc<Body>>,
     body_source_map: Option<Arc<BodySourceMap>>,
     infer: Option<Arc<crate::ty::InferenceResult>>,
     scopes: Option<Arc<crate::expr::ExprScopes>>,
@@ -132,27 +138,49 @@ impl SourceAnalyzer {
         node: &SyntaxNode,
         offset: Option<TextUnit>,
     ) -> SourceAnalyzer {
-        let def_with_body = def_with_body_from_child_node(db, file_id, node);
+        SourceAnalyzer::new_for_source(db, Source { file_id: file_id.into(), ast: node }, offset)
+    }
+
+    /// Like `new`, but `node` may also come from a macro expansion, see
+    /// `SourceAnalyzer::expand`.
+    pub fn new_for_source(
+        db: &impl HirDatabase,
+        src: Source<&SyntaxNode>,
+        offset: Option<TextUnit>,
+    ) -> SourceAnalyzer {
+        let (file_id, node) = original_node(db, src);
+        let def_with_body = def_with_body_from_child_node(db, file_id, &node);
         if let Some(def) = def_with_body {
             let source_map = def.body_source_map(db);
             let scopes = db.expr_scopes(def);
             let scope = match offset {
-                None => scope_for(&scopes, &source_map, &node),
-                Some(offset) => scope_for_offset(&scopes, &source_map, file_id.into(), offset),
+                None => scope_for(&scopes, &source_map, src),
+                Some(offset) => scope_for_offset(&scopes, &source_map, src.file_id, offset),
+            };
+            let body = def.body(db);
+            let ctx = match src.ast.first_token() {
+                Some(token) => SyntaxContexts::default().of_token(db, src.with_ast(token)),
+                None => SyntaxContext::ROOT,
             };
-            let resolver = expr::resolver_for_scope(def.body(db), db, scope);
+            let resolver = expr::resolver_for_scope(body.clone(), db, scope, ctx);
             SourceAnalyzer {
+                file_id: src.file_id,
+                ctx,
                 resolver,
+                body: Some(body),
                 body_source_map: Some(source_map),
                 infer: Some(def.infer(db)),
                 scopes: Some(scopes),
             }
         } else {
             SourceAnalyzer {
+                file_id: src.file_id,
+                ctx: SyntaxContext::ROOT,
                 resolver: node
                     .ancestors()
                     .find_map(|node| try_get_resolver_for_node(db, file_id, &node))
                     .unwrap_or_default(),
+                body: None,
                 body_source_map: None,
                 infer: None,
                 scopes: None,
@@ -161,12 +189,12 @@ impl SourceAnalyzer {
     }
 
     pub fn type_of(&self, _db: &impl HirDatabase, expr: &ast::Expr) -> Option<crate::Ty> {
-        let expr_id = self.body_source_map.as_ref()?.node_expr(expr)?;
+        let expr_id = self.expr_id(expr)?;
         Some(self.infer.as_ref()?[expr_id].clone())
     }
 
     pub fn type_of_pat(&self, _db: &impl HirDatabase, pat: &ast::Pat) -> Option<crate::Ty> {
-        let pat_id = self.body_source_map.as_ref()?.node_pat(pat)?;
+        let pat_id = self.pat_id(pat)?;
         Some(self.infer.as_ref()?[pat_id].clone())
     }
 
@@ -179,35 +207,66 @@ impl SourceAnalyzer {
     }
 
     pub fn resolve_method_call(&self, call: &ast::MethodCallExpr) -> Option<Function> {
-        let expr_id = self.body_source_map.as_ref()?.node_expr(&call.clone().into())?;
+        let expr_id = self.expr_id(&call.clone().into())?;
         self.infer.as_ref()?.method_resolution(expr_id)
     }
 
     pub fn resolve_field(&self, field: &ast::FieldExpr) -> Option<crate::StructField> {
-        let expr_id = self.body_source_map.as_ref()?.node_expr(&field.clone().into())?;
+        let expr_id = self.expr_id(&field.clone().into())?;
         self.infer.as_ref()?.field_resolution(expr_id)
     }
 
     pub fn resolve_record_literal(&self, record_lit: &ast::RecordLit) -> Option<crate::VariantDef> {
-        let expr_id = self.body_source_map.as_ref()?.node_expr(&record_lit.clone().into())?;
+        let expr_id = self.expr_id(&record_lit.clone().into())?;
         self.infer.as_ref()?.variant_resolution_for_expr(expr_id)
     }
 
     pub fn resolve_record_pattern(&self, record_pat: &ast::RecordPat) -> Option<crate::VariantDef> {
-        let pat_id = self.body_source_map.as_ref()?.node_pat(&record_pat.clone().into())?;
+        let pat_id = self.pat_id(&record_pat.clone().into())?;
         self.infer.as_ref()?.variant_resolution_for_pat(pat_id)
     }
 
+    /// The patterns not covered by the arms of `match_expr`, or `None` if the
+    /// match can't be checked.
+    pub fn missing_match_arms(
+        &self,
+        db: &impl HirDatabase,
+        match_expr: &ast::MatchExpr,
+    ) -> Option<Vec<MissingPattern>> {
+        let expr_id = self.expr_id(&match_expr.clone().into())?;
+        let body = self.body.as_ref()?;
+        match &body[expr_id] {
+            expr::Expr::Match { expr, arms } => match_check::missing_patterns(
+                db,
+                body,
+                self.infer.as_ref()?,
+                &self.resolver,
+                *expr,
+                arms,
+            ),
+            _ => None,
+        }
+    }
+
     pub fn resolve_macro_call(
         &self,
         db: &impl HirDatabase,
         macro_call: &ast::MacroCall,
     ) -> Option<MacroDef> {
-        // This must be a normal source file rather than macro file.
-        let path = macro_call.path().and_then(Path::from_ast)?;
+        let hygiene = Hygiene::new(db, self.file_id);
+        let path = macro_call.path().and_then(|it| Path::from_src(it, &hygiene))?;
         self.resolver.resolve_path_as_macro(db, &path)
     }
 
+    /// Returns the expansion of `macro_call`, which must come from the same
+    /// file as the node this analyzer was created for.
+    pub fn expand(&self, db: &impl HirDatabase, macro_call: &ast::MacroCall) -> Option<Expansion> {
+        let def = self.resolve_macro_call(db, macro_call)?.id;
+        let ast_id = db.ast_id_map(self.file_id).ast_id(macro_call).with_file_id(self.file_id);
+        let macro_call_id = MacroCallLoc { def, kind: MacroCallKind::FnLike(ast_id) }.id(db);
+        Some(Expansion { macro_call_id, macro_file_kind: to_macro_file_kind(macro_call) })
+    }
+
     pub fn resolve_hir_path(
         &self,
         db: &impl HirDatabase,
@@ -227,12 +286,12 @@ impl SourceAnalyzer {
                 ValueNs::LocalBinding(it) => {
                     // We get a `PatId` from resolver, but it actually can only
                     // point at `BindPat`, and not at the arbitrary pattern.
-                    let pat_ptr = self
-                        .body_source_map
-                        .as_ref()?
-                        .pat_syntax(it)?
-                        .ast // FIXME: ignoring file_id here is definitelly wrong
-                        .map_a(|ptr| ptr.cast::<ast::BindPat>().unwrap());
+                    let pat_src = self.body_source_map.as_ref()?.pat_syntax(it)?;
+                    // FIXME: bindings from macro expansions can't be represented yet
+                    if pat_src.file_id.is_macro_file() {
+                        return None;
+                    }
+                    let pat_ptr = pat_src.ast.map_a(|ptr| ptr.cast::<ast::BindPat>().unwrap());
                     PathResolution::LocalBinding(pat_ptr)
                 }
                 ValueNs::Function(it) => PathResolution::Def(it.into()),
@@ -251,19 +310,19 @@ impl SourceAnalyzer {
 
     pub fn resolve_path(&self, db: &impl HirDatabase, path: &ast::Path) -> Option<PathResolution> {
         if let Some(path_expr) = path.syntax().parent().and_then(ast::PathExpr::cast) {
-            let expr_id = self.body_source_map.as_ref()?.node_expr(&path_expr.into())?;
+            let expr_id = self.expr_id(&path_expr.into())?;
             if let Some(assoc) = self.infer.as_ref()?.assoc_resolutions_for_expr(expr_id) {
                 return Some(PathResolution::AssocItem(assoc));
             }
         }
         if let Some(path_pat) = path.syntax().parent().and_then(ast::PathPat::cast) {
-            let pat_id = self.body_source_map.as_ref()?.node_pat(&path_pat.into())?;
+            let pat_id = self.pat_id(&path_pat.into())?;
             if let Some(assoc) = self.infer.as_ref()?.assoc_resolutions_for_pat(pat_id) {
                 return Some(PathResolution::AssocItem(assoc));
             }
         }
-        // This must be a normal source file rather than macro file.
-        let hir_path = crate::Path::from_ast(path.clone())?;
+        let hygiene = Hygiene::new(db, self.file_id);
+        let hir_path = crate::Path::from_src(path.clone(), &hygiene)?;
         self.resolve_hir_path(db, &hir_path)
     }
 
@@ -272,18 +331,22 @@ impl SourceAnalyzer {
         let name = name_ref.as_name();
         let source_map = self.body_source_map.as_ref()?;
         let scopes = self.scopes.as_ref()?;
-        let scope = scope_for(scopes, source_map, name_ref.syntax());
+        let src = Source { file_id: self.file_id, ast: name_ref.syntax() };
+        let scope = scope_for(scopes, source_map, src);
         let ret = scopes
             .scope_chain(scope)
             .flat_map(|scope| scopes.entries(scope).iter())
+            .filter(|entry| entry.ctx() == self.ctx)
             .filter(|entry| shadowed.insert(entry.name()))
             .filter(|entry| entry.name() == &name)
             .nth(0);
         ret.and_then(|entry| {
-            Some(ScopeEntryWithSyntax {
-                name: entry.name().clone(),
-                ptr: source_map.pat_syntax(entry.pat())?.ast,
-            })
+            let pat_src = source_map.pat_syntax(entry.pat())?;
+            // FIXME: bindings from macro expansions can't be represented yet
+            if pat_src.file_id.is_macro_file() {
+                return None;
+            }
+            Some(ScopeEntryWithSyntax { name: entry.name().clone(), ptr: pat_src.ast })
         })
     }
 
@@ -360,6 +423,16 @@ impl SourceAnalyzer {
         implements_trait(&canonical_ty, db, &self.resolver, krate, std_future_trait)
     }
 
+    fn expr_id(&self, expr: &ast::Expr) -> Option<expr::ExprId> {
+        let src = Source { file_id: self.file_id, ast: expr };
+        self.body_source_map.as_ref()?.node_expr(src)
+    }
+
+    fn pat_id(&self, pat: &ast::Pat) -> Option<expr::PatId> {
+        let src = Source { file_id: self.file_id, ast: pat };
+        self.body_source_map.as_ref()?.node_pat(src)
+    }
+
     #[cfg(test)]
     pub(crate) fn body_source_map(&self) -> Arc<BodySourceMap> {
         self.body_source_map.clone().unwrap()
@@ -379,11 +452,12 @@ impl SourceAnalyzer {
 fn scope_for(
     scopes: &ExprScopes,
     source_map: &BodySourceMap,
-    node: &SyntaxNode,
+    node: Source<&SyntaxNode>,
 ) -> Option<ScopeId> {
-    node.ancestors()
+    node.ast
+        .ancestors()
         .filter_map(ast::Expr::cast)
-        .filter_map(|it| source_map.node_expr(&it))
+        .filter_map(|it| source_map.node_expr(Source { file_id: node.file_id, ast: &it }))
         .find_map(|it| scopes.scope_for(it))
 }
 
@@ -398,7 +472,6 @@ fn scope_for_offset(
         .iter()
         .filter_map(|(id, scope)| {
             let source = source_map.expr_syntax(*id)?;
-            // FIXME: correctly handle macro expansion
             if source.file_id != file_id {
                 return None;
             }
@@ -428,7 +501,6 @@ fn adjust(
         .iter()
         .filter_map(|(id, scope)| {
             let source = source_map.expr_syntax(*id)?;
-            // FIXME: correctly handle macro expansion
             if source.file_id != file_id {
                 return None;
             }
@@ -451,3 +523,46 @@ fn adjust(
         })
         .map(|(_ptr, scope)| *scope)
 }
+
+/// Walks up the macro call sites of `src` until a node in a real file is found.
+fn original_node(db: &impl HirDatabase, src: Source<&SyntaxNode>) -> (FileId, SyntaxNode) {
+    let mut file_id = src.file_id;
+    let mut node = src.ast.clone();
+    while let Some(call_node) = file_id.call_node(db) {
+        file_id = call_node.file_id;
+        node = call_node.ast;
+    }
+    (file_id.original_file(db), node)
+}
+
+/// The expansion of a particular macro call, see `SourceAnalyzer::expand`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Expansion {
+    macro_call_id: MacroCallId,
+    macro_file_kind: MacroFileKind,
+}
+
+impl Expansion {
+    /// The id of the file holding the expanded code.
+    pub fn file_id(&self) -> HirFileId {
+        self.macro_call_id.as_file(self.macro_file_kind)
+    }
+
+    /// Maps a token of the macro call argument to the corresponding token of
+    /// the expansion.
+    pub fn map_token_down(
+        &self,
+        db: &impl HirDatabase,
+        token: Source<&SyntaxToken>,
+    ) -> Option<Source<SyntaxToken>> {
+        let exp_info = self.file_id().expansion_info(db)?;
+        exp_info.map_token_down(token)
+    }
+}
+
+fn to_macro_file_kind(macro_call: &ast::MacroCall) -> MacroFileKind {
+    match macro_call.syntax().parent().map(|it| it.kind()) {
+        Some(SOURCE_FILE) | Some(MACRO_ITEMS) | Some(ITEM_LIST) => MacroFileKind::Items,
+        _ => MacroFileKind::Expr,
+    }
+}
@@ -80,6 +80,15 @@ impl<N: AstNode> FileAstId<N> {
     pub(crate) fn with_file_id(self, file_id: HirFileId) -> AstId<N> {
         AstId { file_id, file_ast_id: self }
     }
+
+    /// Converts this id to the id of the same node, viewed as a more general
+    /// kind of node, like an `ast::ModuleItem` instead of an `ast::FnDef`.
+    pub(crate) fn upcast<M: AstNode>(self) -> FileAstId<M>
+    where
+        M: From<N>,
+    {
+        FileAstId { raw: self.raw, _ty: PhantomData }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
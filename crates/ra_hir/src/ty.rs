@@ -58,8 +58,9 @@ pub enum TypeCtor {
     /// The pointee of an array slice.  Written as `[T]`.
     Slice,
 
-    /// An array with the given length. Written as `[T; n]`.
-    Array,
+    /// An array with the given length. Written as `[T; n]`. The length is
+    /// `None` if it couldn't be evaluated.
+    Array { len: Option<u64> },
 
     /// A raw pointer. Written as `*mut T` or `*const T`
     RawPtr(Mutability),
@@ -121,7 +122,7 @@ impl TypeCtor {
             | TypeCtor::Str
             | TypeCtor::Never => 0,
             TypeCtor::Slice
-            | TypeCtor::Array
+            | TypeCtor::Array { .. }
             | TypeCtor::RawPtr(_)
             | TypeCtor::Ref(_)
             | TypeCtor::Closure { .. } // 1 param representing the signature of the closure
@@ -152,7 +153,7 @@ impl TypeCtor {
             | TypeCtor::Str
             | TypeCtor::Never
             | TypeCtor::Slice
-            | TypeCtor::Array
+            | TypeCtor::Array { .. }
             | TypeCtor::RawPtr(_)
             | TypeCtor::Ref(_)
             | TypeCtor::FnPtr { .. }
@@ -173,7 +174,7 @@ impl TypeCtor {
             | TypeCtor::Str
             | TypeCtor::Never
             | TypeCtor::Slice
-            | TypeCtor::Array
+            | TypeCtor::Array { .. }
             | TypeCtor::RawPtr(_)
             | TypeCtor::Ref(_)
             | TypeCtor::FnPtr { .. }
@@ -786,9 +787,12 @@ impl HirDisplay for ApplicationTy {
                 let t = self.parameters.as_single();
                 write!(f, "[{}]", t.display(f.db))?;
             }
-            TypeCtor::Array => {
+            TypeCtor::Array { len } => {
                 let t = self.parameters.as_single();
-                write!(f, "[{};_]", t.display(f.db))?;
+                match len {
+                    Some(len) => write!(f, "[{}; {}]", t.display(f.db), len)?,
+                    None => write!(f, "[{};_]", t.display(f.db))?,
+                }
             }
             TypeCtor::RawPtr(m) => {
                 let t = self.parameters.as_single();
@@ -14,13 +14,13 @@
 //! the `ena` crate, which is extracted from rustc.
 
 use std::borrow::Cow;
-use std::iter::{repeat, repeat_with};
+use std::iter::{self, repeat, repeat_with};
 use std::mem;
 use std::ops::Index;
 use std::sync::Arc;
 
 use ena::unify::{InPlaceUnificationTable, NoError, UnifyKey, UnifyValue};
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 
 use ra_arena::map::ArenaMap;
 use ra_prof::profile;
@@ -29,17 +29,18 @@ use test_utils::tested_by;
 use super::{
     autoderef, lower, method_resolution, op, primitive,
     traits::{Guidance, Obligation, ProjectionPredicate, Solution},
-    ApplicationTy, CallableDef, InEnvironment, ProjectionTy, Substs, TraitEnvironment, TraitRef,
-    Ty, TypableDef, TypeCtor, TypeWalk,
+    ApplicationTy, CallableDef, Canonical, InEnvironment, ProjectionTy, Substs, TraitEnvironment,
+    TraitRef, Ty, TypableDef, TypeCtor, TypeWalk,
 };
 use crate::{
     adt::VariantDef,
     code_model::TypeAlias,
+    const_eval::ConstExpr,
     db::HirDatabase,
     diagnostics::DiagnosticSink,
     expr::{
-        self, Array, BinaryOp, BindingAnnotation, Body, Expr, ExprId, Literal, Pat, PatId,
-        RecordFieldPat, Statement, UnaryOp,
+        self, tuple_field_index, Array, BinaryOp, BindingAnnotation, Body, Expr, ExprId, Literal,
+        Pat, PatId, RecordFieldPat, Statement, UnaryOp,
     },
     generics::{GenericParams, HasGenericParams},
     lang_item::LangItemTarget,
@@ -50,10 +51,12 @@ use crate::{
     ty::infer::diagnostics::InferenceDiagnostic,
     type_ref::{Mutability, TypeRef},
     Adt, AssocItem, ConstData, DefWithBody, FnData, Function, HasBody, Name, Path, StructField,
+    Trait,
 };
 
 mod unify;
 mod path;
+mod cast;
 
 /// The entry point of type inference.
 pub fn infer_query(db: &impl HirDatabase, def: DefWithBody) -> Arc<InferenceResult> {
@@ -189,6 +192,10 @@ struct InferenceContext<'a, D: HirDatabase> {
     result: InferenceResult,
     /// The return type of the function being inferred.
     return_ty: Ty,
+    /// Index expressions used as mutable places, which go through `IndexMut`.
+    mutable_places: FxHashSet<ExprId>,
+    /// Casts are checked once inference is done and the types are known.
+    deferred_cast_checks: Vec<cast::DeferredCastCheck>,
 
     /// Impls of `CoerceUnsized` used in coercion.
     /// (from_ty_ctor, to_ty_ctor) => coerce_generic_index
@@ -213,6 +220,8 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
             var_unification_table: InPlaceUnificationTable::new(),
             obligations: Vec::default(),
             return_ty: Ty::Unknown, // set in collect_fn_signature
+            mutable_places: FxHashSet::default(),
+            deferred_cast_checks: Vec::new(),
             trait_env: lower::trait_env(db, &resolver),
             coerce_unsized_map: Self::init_coerce_unsized_map(db, &resolver),
             db,
@@ -262,6 +271,7 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
 
     fn resolve_all(mut self) -> InferenceResult {
         // FIXME resolve obligations as well (use Guidance if necessary)
+        self.check_casts();
         let mut result = mem::replace(&mut self.result, InferenceResult::default());
         let mut tv_stack = Vec::new();
         for ty in result.type_of_expr.values_mut() {
@@ -337,6 +347,11 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
             (Ty::Apply(a_ty1), Ty::Apply(a_ty2)) if a_ty1.ctor == a_ty2.ctor => {
                 self.unify_substs(&a_ty1.parameters, &a_ty2.parameters, depth + 1)
             }
+            // An array length we couldn't evaluate is compatible with any length
+            (
+                ty_app!(TypeCtor::Array { len: len1 }, st1),
+                ty_app!(TypeCtor::Array { len: len2 }, st2),
+            ) if len1.is_none() || len2.is_none() => self.unify_substs(st1, st2, depth + 1),
             _ => self.unify_inner_trivial(&ty1, &ty2),
         }
     }
@@ -579,13 +594,17 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                 let ty = self.insert_type_vars(ty.apply_substs(substs));
                 (ty, Some(s.into()))
             }
+            TypableDef::Adt(Adt::Union(u)) => {
+                let ty = u.ty(self.db);
+                let ty = self.insert_type_vars(ty.apply_substs(substs));
+                (ty, Some(u.into()))
+            }
             TypableDef::EnumVariant(var) => {
                 let ty = var.parent_enum(self.db).ty(self.db);
                 let ty = self.insert_type_vars(ty.apply_substs(substs));
                 (ty, Some(var.into()))
             }
             TypableDef::Adt(Adt::Enum(_))
-            | TypableDef::Adt(Adt::Union(_))
             | TypableDef::TypeAlias(_)
             | TypableDef::Function(_)
             | TypableDef::Const(_)
@@ -598,16 +617,23 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         &mut self,
         path: Option<&Path>,
         subpats: &[PatId],
+        ellipsis: Option<usize>,
         expected: &Ty,
         default_bm: BindingMode,
+        id: PatId,
     ) -> Ty {
         let (ty, def) = self.resolve_variant(path);
+        if let Some(variant) = def {
+            self.write_variant_resolution(id.into(), variant);
+        }
 
         self.unify(&ty, expected);
 
         let substs = ty.substs().unwrap_or_else(Substs::empty);
+        let field_count = def.map_or(subpats.len(), |d| d.fields(self.db).len());
 
         for (i, &subpat) in subpats.iter().enumerate() {
+            let i = tuple_field_index(i, ellipsis, subpats.len(), field_count);
             let expected_ty = def
                 .and_then(|d| d.field(self.db, &Name::new_tuple_field(i)))
                 .map_or(Ty::Unknown, |field| field.ty(self.db))
@@ -651,13 +677,19 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         let body = Arc::clone(&self.body); // avoid borrow checker problem
 
         let is_non_ref_pat = match &body[pat] {
-            Pat::Tuple(..)
+            Pat::Tuple { .. }
             | Pat::TupleStruct { .. }
             | Pat::Record { .. }
             | Pat::Range { .. }
             | Pat::Slice { .. } => true,
-            // FIXME: Path/Lit might actually evaluate to ref, but inference is unimplemented.
-            Pat::Path(..) | Pat::Lit(..) => true,
+            // FIXME: Path might actually evaluate to ref, but inference is unimplemented.
+            Pat::Path(..) => true,
+            Pat::Lit(expr) => match &body[*expr] {
+                Expr::Literal(Literal::String(..)) | Expr::Literal(Literal::ByteString(..)) => {
+                    false
+                }
+                _ => true,
+            },
             Pat::Wild | Pat::Bind { .. } | Pat::Ref { .. } | Pat::Missing => false,
         };
         if is_non_ref_pat {
@@ -681,20 +713,28 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         let expected = expected;
 
         let ty = match &body[pat] {
-            Pat::Tuple(ref args) => {
+            Pat::Tuple { args, ellipsis } => {
                 let expectations = match expected.as_tuple() {
                     Some(parameters) => &*parameters.0,
                     _ => &[],
                 };
-                let expectations_iter = expectations.iter().chain(repeat(&Ty::Unknown));
-
-                let inner_tys = args
-                    .iter()
-                    .zip(expectations_iter)
-                    .map(|(&pat, ty)| self.infer_pat(pat, ty, default_bm))
+                let cardinality = match ellipsis {
+                    Some(_) => expectations.len().max(args.len()),
+                    None => args.len(),
+                };
+                let mut inner_tys: Vec<_> = (0..cardinality)
+                    .map(|i| expectations.get(i).cloned().unwrap_or(Ty::Unknown))
                     .collect();
 
-                Ty::apply(TypeCtor::Tuple { cardinality: args.len() as u16 }, Substs(inner_tys))
+                for (i, &pat) in args.iter().enumerate() {
+                    let idx = tuple_field_index(i, *ellipsis, args.len(), cardinality);
+                    inner_tys[idx] = self.infer_pat(pat, &inner_tys[idx].clone(), default_bm);
+                }
+
+                Ty::apply(
+                    TypeCtor::Tuple { cardinality: cardinality as u16 },
+                    Substs(inner_tys.into()),
+                )
             }
             Pat::Ref { pat, mutability } => {
                 let expectation = match expected.as_reference() {
@@ -709,9 +749,14 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                 let subty = self.infer_pat(*pat, expectation, default_bm);
                 Ty::apply_one(TypeCtor::Ref(*mutability), subty)
             }
-            Pat::TupleStruct { path: p, args: subpats } => {
-                self.infer_tuple_struct_pat(p.as_ref(), subpats, expected, default_bm)
-            }
+            Pat::TupleStruct { path: p, args: subpats, ellipsis } => self.infer_tuple_struct_pat(
+                p.as_ref(),
+                subpats,
+                *ellipsis,
+                expected,
+                default_bm,
+                pat,
+            ),
             Pat::Record { path: p, args: fields } => {
                 self.infer_record_pat(p.as_ref(), fields, expected, default_bm, pat)
             }
@@ -743,6 +788,35 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                 self.write_pat_ty(pat, bound_ty);
                 return inner_ty;
             }
+            Pat::Lit(expr) => self.infer_expr(*expr, &Expectation::has_type(expected.clone())),
+            Pat::Range { start, end, .. } => {
+                let ty = self.infer_expr(*start, &Expectation::has_type(expected.clone()));
+                self.infer_expr(*end, &Expectation::has_type(ty))
+            }
+            Pat::Slice { prefix, rest, suffix } => {
+                let (container, elem_ty) = match expected {
+                    ty_app!(TypeCtor::Array { len }, st) => {
+                        (TypeCtor::Array { len: *len }, st.as_single().clone())
+                    }
+                    ty_app!(TypeCtor::Slice, st) => (TypeCtor::Slice, st.as_single().clone()),
+                    _ => (TypeCtor::Slice, Ty::Unknown),
+                };
+                for &pat in prefix.iter().chain(suffix.iter()) {
+                    self.infer_pat(pat, &elem_ty, default_bm);
+                }
+                if let Some(rest) = rest {
+                    let rest_ctor = match container {
+                        TypeCtor::Array { len } => TypeCtor::Array {
+                            len: len.and_then(|it| {
+                                it.checked_sub((prefix.len() + suffix.len()) as u64)
+                            }),
+                        },
+                        _ => container,
+                    };
+                    self.infer_pat(*rest, &Ty::apply_one(rest_ctor, elem_ty.clone()), default_bm);
+                }
+                Ty::apply_one(container, elem_ty)
+            }
             _ => Ty::Unknown,
         };
         // use a new type variable if we got Ty::Unknown here
@@ -874,6 +948,121 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         ret_ty
     }
 
+    /// Infers the type of `base[index]`. Arrays and slices indexed by an integer
+    /// use builtin indexing, anything else has to implement `std::ops::Index`
+    /// (or `IndexMut` in a mutable place) after autoderef.
+    fn infer_index_expr(&mut self, tgt_expr: ExprId, base_ty: Ty, index_ty: Ty) -> Ty {
+        let usize_ty =
+            Ty::simple(TypeCtor::Int(primitive::UncertainIntTy::Known(primitive::IntTy::usize())));
+        let is_builtin_index = match &*self.resolve_ty_shallow(&index_ty) {
+            ty_app!(TypeCtor::Int(primitive::UncertainIntTy::Known(int_ty))) => {
+                *int_ty == primitive::IntTy::usize()
+            }
+            ty_app!(TypeCtor::Int(primitive::UncertainIntTy::Unknown))
+            | Ty::Infer(InferTy::IntVar(..)) => true,
+            _ => false,
+        };
+        // In a mutable place, fall back to `Index` for types which don't
+        // implement `IndexMut`, so that the element type is still known.
+        let mut index_traits = Vec::with_capacity(2);
+        if self.mutable_places.contains(&tgt_expr) {
+            index_traits.extend(self.resolve_ops_index_mut());
+        }
+        index_traits.extend(self.resolve_ops_index());
+
+        let db = self.db;
+        let resolver = self.resolver.clone();
+        let canonicalized = self.canonicalizer().canonicalize_ty(base_ty);
+        // `None` as the trait means builtin indexing.
+        let resolved = autoderef::autoderef(db, &resolver, canonicalized.value.clone())
+            .flat_map(|derefed_ty| {
+                // Arrays can also be indexed through the slice they unsize to.
+                let unsized_ty = match &derefed_ty.value {
+                    ty_app!(TypeCtor::Array { .. }, st) => Some(Canonical {
+                        value: Ty::apply_one(TypeCtor::Slice, st.as_single().clone()),
+                        num_vars: derefed_ty.num_vars,
+                    }),
+                    _ => None,
+                };
+                iter::once(derefed_ty).chain(unsized_ty)
+            })
+            .find_map(|derefed_ty| match &derefed_ty.value {
+                ty_app!(TypeCtor::Array { .. }, st) | ty_app!(TypeCtor::Slice, st)
+                    if is_builtin_index =>
+                {
+                    Some((st.as_single().clone(), None))
+                }
+                _ => {
+                    let krate = resolver.krate()?;
+                    let trait_ = index_traits.iter().copied().find(|&trait_| {
+                        method_resolution::implements_trait(
+                            &derefed_ty,
+                            db,
+                            &resolver,
+                            krate,
+                            trait_,
+                        )
+                    })?;
+                    Some((derefed_ty.value.clone(), Some(trait_)))
+                }
+            });
+
+        match resolved {
+            Some((elem_ty, None)) => {
+                self.unify(&index_ty, &usize_ty);
+                canonicalized.decanonicalize_ty(elem_ty)
+            }
+            Some((self_ty, Some(trait_))) => {
+                let self_ty = canonicalized.decanonicalize_ty(self_ty);
+                let method_name = if Some(trait_) == self.resolve_ops_index_mut() {
+                    &name::INDEX_MUT_FN
+                } else {
+                    &name::INDEX_FN
+                };
+                let method = trait_.items(db).into_iter().find_map(|item| match item {
+                    AssocItem::Function(f) if f.name(db) == *method_name => Some(f),
+                    _ => None,
+                });
+                if let Some(method) = method {
+                    self.write_method_resolution(tgt_expr, method);
+                }
+                match self.resolve_ops_index_output() {
+                    Some(output_alias) => {
+                        let ty = self.new_type_var();
+                        let projection = ProjectionPredicate {
+                            ty: ty.clone(),
+                            projection_ty: ProjectionTy {
+                                associated_ty: output_alias,
+                                parameters: Substs(vec![self_ty, index_ty].into()),
+                            },
+                        };
+                        self.obligations.push(Obligation::Projection(projection));
+                        self.resolve_ty_as_possible(&mut vec![], ty)
+                    }
+                    None => Ty::Unknown,
+                }
+            }
+            None => Ty::Unknown,
+        }
+    }
+
+    /// Records that `expr` is used as a mutable place, so that the index
+    /// expressions it consists of resolve to `IndexMut` instead of `Index`.
+    fn mark_mutable_place(&mut self, expr: ExprId) {
+        let body = Arc::clone(&self.body); // avoid borrow checker problem
+        let mut expr = expr;
+        loop {
+            match &body[expr] {
+                Expr::Index { base, .. } => {
+                    self.mutable_places.insert(expr);
+                    expr = *base;
+                }
+                Expr::Field { expr: inner, .. } => expr = *inner,
+                _ => break,
+            }
+        }
+    }
+
     /// Infer type of expression with possibly implicit coerce to the expected type.
     /// Return the type after possible coercion.
     fn infer_expr_coerce(&mut self, expr: ExprId, expected: &Expectation) -> Ty {
@@ -1037,7 +1226,7 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
 
         match (&from_ty, &to_ty) {
             // `[T; N]` -> `[T]`
-            (ty_app!(TypeCtor::Array, st1), ty_app!(TypeCtor::Slice, st2)) => {
+            (ty_app!(TypeCtor::Array { .. }, st1), ty_app!(TypeCtor::Slice, st2)) => {
                 Some(self.unify(&st1[0], &st2[0]))
             }
 
@@ -1381,6 +1570,10 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                             self.write_field_resolution(tgt_expr, field);
                             field.ty(self.db).subst(&a_ty.parameters)
                         }),
+                        TypeCtor::Adt(Adt::Union(u)) => u.field(self.db, name).map(|field| {
+                            self.write_field_resolution(tgt_expr, field);
+                            field.ty(self.db).subst(&a_ty.parameters)
+                        }),
                         _ => None,
                     },
                     _ => None,
@@ -1428,9 +1621,32 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                 ty
             }
             Expr::Cast { expr, type_ref } => {
-                let _inner_ty = self.infer_expr(*expr, &Expectation::none());
                 let cast_ty = self.make_ty(type_ref);
-                // FIXME check the cast...
+                // Like in rustc, the cast picks the type of an unsuffixed integer
+                // literal, so that `97 as char` casts a `u8`
+                let expectation = match &body[*expr] {
+                    Expr::Literal(Literal::Int(_, primitive::UncertainIntTy::Unknown)) => {
+                        match self.resolve_ty_shallow(&cast_ty).as_ref() {
+                            Ty::Apply(ApplicationTy { ctor: TypeCtor::Int(_), .. }) => {
+                                Expectation::has_type(cast_ty.clone())
+                            }
+                            Ty::Apply(ApplicationTy { ctor: TypeCtor::Char, .. }) => {
+                                Expectation::has_type(Ty::simple(TypeCtor::Int(
+                                    primitive::UncertainIntTy::Known(primitive::IntTy::u8()),
+                                )))
+                            }
+                            _ => Expectation::none(),
+                        }
+                    }
+                    _ => Expectation::none(),
+                };
+                let inner_ty = self.infer_expr(*expr, &expectation);
+                self.deferred_cast_checks.push(cast::DeferredCastCheck {
+                    expr: tgt_expr,
+                    inner_expr: *expr,
+                    from_ty: inner_ty,
+                    to_ty: cast_ty.clone(),
+                });
                 cast_ty
             }
             Expr::Ref { expr, mutability } => {
@@ -1444,6 +1660,9 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                     } else {
                         Expectation::none()
                     };
+                if *mutability == Mutability::Mut {
+                    self.mark_mutable_place(*expr);
+                }
                 // FIXME reference coercions etc.
                 let inner_ty = self.infer_expr(*expr, &expectation);
                 Ty::apply_one(TypeCtor::Ref(*mutability), inner_ty)
@@ -1508,6 +1727,9 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                         BinaryOp::LogicOp(..) => Expectation::has_type(Ty::simple(TypeCtor::Bool)),
                         _ => Expectation::none(),
                     };
+                    if let BinaryOp::Assignment { .. } = op {
+                        self.mark_mutable_place(*lhs);
+                    }
                     let lhs_ty = self.infer_expr(*lhs, &lhs_expectation);
                     // FIXME: find implementation of trait corresponding to operation
                     // symbol and resolve associated `Output` type
@@ -1520,10 +1742,9 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                 _ => Ty::Unknown,
             },
             Expr::Index { base, index } => {
-                let _base_ty = self.infer_expr(*base, &Expectation::none());
-                let _index_ty = self.infer_expr(*index, &Expectation::none());
-                // FIXME: use `std::ops::Index::Output` to figure out the real return type
-                Ty::Unknown
+                let base_ty = self.infer_expr(*base, &Expectation::none());
+                let index_ty = self.infer_expr(*index, &Expectation::none());
+                self.infer_index_expr(tgt_expr, base_ty, index_ty)
             }
             Expr::Tuple { exprs } => {
                 let mut tys = match &expected.ty {
@@ -1544,17 +1765,18 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
             }
             Expr::Array(array) => {
                 let elem_ty = match &expected.ty {
-                    ty_app!(TypeCtor::Array, st) | ty_app!(TypeCtor::Slice, st) => {
+                    ty_app!(TypeCtor::Array { .. }, st) | ty_app!(TypeCtor::Slice, st) => {
                         st.as_single().clone()
                     }
                     _ => self.new_type_var(),
                 };
 
-                match array {
+                let len = match array {
                     Array::ElementList(items) => {
                         for expr in items.iter() {
                             self.infer_expr_coerce(*expr, &Expectation::has_type(elem_ty.clone()));
                         }
+                        Some(items.len() as u64)
                     }
                     Array::Repeat { initializer, repeat } => {
                         self.infer_expr_coerce(
@@ -1567,10 +1789,13 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
                                 primitive::UncertainIntTy::Known(primitive::IntTy::usize()),
                             ))),
                         );
+                        ConstExpr::from_body(&self.body, *repeat)
+                            .eval(self.db, &self.resolver)
+                            .and_then(|it| it.as_u64())
                     }
-                }
+                };
 
-                Ty::apply_one(TypeCtor::Array, elem_ty)
+                Ty::apply_one(TypeCtor::Array { len }, elem_ty)
             }
             Expr::Literal(lit) => match lit {
                 Literal::Bool(..) => Ty::simple(TypeCtor::Bool),
@@ -1698,6 +1923,21 @@ impl<'a, D: HirDatabase> InferenceContext<'a, D> {
         trait_.associated_type_by_name(self.db, &name::OK_TYPE)
     }
 
+    fn resolve_ops_index(&self) -> Option<Trait> {
+        let path = known::std_ops_index();
+        self.resolver.resolve_known_trait(self.db, &path)
+    }
+
+    fn resolve_ops_index_mut(&self) -> Option<Trait> {
+        let path = known::std_ops_index_mut();
+        self.resolver.resolve_known_trait(self.db, &path)
+    }
+
+    fn resolve_ops_index_output(&self) -> Option<TypeAlias> {
+        let trait_ = self.resolve_ops_index()?;
+        trait_.associated_type_by_name(self.db, &name::OUTPUT_TYPE)
+    }
+
     fn resolve_future_future_output(&self) -> Option<TypeAlias> {
         let path = known::std_future_future();
         let trait_ = self.resolver.resolve_known_trait(self.db, &path)?;
@@ -1831,14 +2071,17 @@ impl Expectation {
 mod diagnostics {
     use crate::{
         db::HirDatabase,
-        diagnostics::{DiagnosticSink, NoSuchField},
+        diagnostics::{DiagnosticSink, InvalidCast, NoSuchField, TrivialCast},
         expr::ExprId,
+        ty::{display::HirDisplay, Ty},
         Function, HasSource,
     };
 
     #[derive(Debug, PartialEq, Eq, Clone)]
     pub(super) enum InferenceDiagnostic {
         NoSuchField { expr: ExprId, field: usize },
+        InvalidCast { expr: ExprId, from_ty: Ty, to_ty: Ty },
+        TrivialCast { expr: ExprId, ty: Ty },
     }
 
     impl InferenceDiagnostic {
@@ -1854,6 +2097,29 @@ mod diagnostics {
                     let field = owner.body_source_map(db).field_syntax(*expr, *field);
                     sink.push(NoSuchField { file, field })
                 }
+                InferenceDiagnostic::InvalidCast { expr, from_ty, to_ty } => {
+                    if let Some(source) = owner.body_source_map(db).expr_syntax(*expr) {
+                        if let Some(expr) = source.ast.a() {
+                            sink.push(InvalidCast {
+                                file: source.file_id,
+                                expr,
+                                from_ty: from_ty.display(db).to_string(),
+                                to_ty: to_ty.display(db).to_string(),
+                            })
+                        }
+                    }
+                }
+                InferenceDiagnostic::TrivialCast { expr, ty } => {
+                    if let Some(source) = owner.body_source_map(db).expr_syntax(*expr) {
+                        if let Some(expr) = source.ast.a() {
+                            sink.push(TrivialCast {
+                                file: source.file_id,
+                                expr,
+                                ty: ty.display(db).to_string(),
+                            })
+                        }
+                    }
+                }
             }
         }
     }
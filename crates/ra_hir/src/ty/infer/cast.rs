@@ -0,0 +1,175 @@
+//! Checking of `as` casts.
+//!
+//! Casts are checked at the end of inference, once the types of both sides are
+//! known. Compare with `librustc_typeck/check/cast.rs` in rustc.
+
+use std::mem;
+
+use super::{InferenceContext, InferenceDiagnostic};
+use crate::{
+    db::HirDatabase,
+    expr::{Expr, ExprId},
+    ty::{
+        primitive::{IntTy, UncertainIntTy},
+        ApplicationTy, Ty, TypeCtor, TypeWalk,
+    },
+    type_ref::Mutability,
+    Adt,
+};
+
+#[derive(Debug, Clone)]
+pub(super) struct DeferredCastCheck {
+    pub(super) expr: ExprId,
+    pub(super) inner_expr: ExprId,
+    pub(super) from_ty: Ty,
+    pub(super) to_ty: Ty,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CastCheck {
+    Valid,
+    /// The cast doesn't change the type, like `x as i32` for `x: i32`.
+    Trivial,
+    Invalid,
+}
+
+impl<'a, D: HirDatabase> InferenceContext<'a, D> {
+    pub(super) fn check_casts(&mut self) {
+        let cast_checks = mem::replace(&mut self.deferred_cast_checks, Vec::new());
+        let mut tv_stack = Vec::new();
+        for check in cast_checks {
+            let from_ty = self.resolve_ty_completely(&mut tv_stack, check.from_ty);
+            let to_ty = self.resolve_ty_completely(&mut tv_stack, check.to_ty);
+            match check_cast(self.db, &from_ty, &to_ty) {
+                CastCheck::Valid => {}
+                CastCheck::Trivial => {
+                    // The type of a literal may be picked by the cast itself
+                    if let Expr::Literal(_) = &self.body[check.inner_expr] {
+                        continue;
+                    }
+                    self.push_diagnostic(InferenceDiagnostic::TrivialCast {
+                        expr: check.expr,
+                        ty: to_ty,
+                    });
+                }
+                CastCheck::Invalid => self.push_diagnostic(InferenceDiagnostic::InvalidCast {
+                    expr: check.expr,
+                    from_ty,
+                    to_ty,
+                }),
+            }
+        }
+    }
+}
+
+fn check_cast(db: &impl HirDatabase, from_ty: &Ty, to_ty: &Ty) -> CastCheck {
+    if contains_unknown(from_ty) || contains_unknown(to_ty) {
+        return CastCheck::Valid;
+    }
+    if from_ty == to_ty {
+        return CastCheck::Trivial;
+    }
+    let (from, to) = match (from_ty, to_ty) {
+        (Ty::Apply(from), Ty::Apply(to)) => (from, to),
+        // FIXME: check casts involving type parameters and trait objects
+        _ => return CastCheck::Valid,
+    };
+    let is_valid = match (from.ctor, to.ctor) {
+        (TypeCtor::Never, _) => true,
+
+        // Numeric casts
+        (TypeCtor::Int(_), TypeCtor::Int(_))
+        | (TypeCtor::Int(_), TypeCtor::Float(_))
+        | (TypeCtor::Float(_), TypeCtor::Int(_))
+        | (TypeCtor::Float(_), TypeCtor::Float(_))
+        | (TypeCtor::Bool, TypeCtor::Int(_))
+        | (TypeCtor::Char, TypeCtor::Int(_)) => true,
+        (TypeCtor::Int(UncertainIntTy::Known(int_ty)), TypeCtor::Char) => int_ty == IntTy::u8(),
+        (TypeCtor::Adt(Adt::Enum(e)), TypeCtor::Int(_)) => {
+            e.variants(db).iter().all(|variant| variant.fields(db).is_empty())
+        }
+
+        // Pointer casts
+        (TypeCtor::Ref(from_mut), TypeCtor::RawPtr(to_mut)) => {
+            mutability_allows(from_mut, to_mut)
+                && (from.parameters[0] == to.parameters[0]
+                    || array_elem(&from.parameters[0]) == Some(&to.parameters[0]))
+        }
+        (TypeCtor::RawPtr(_), TypeCtor::RawPtr(_)) => {
+            is_thin(&to.parameters[0]) || !is_thin(&from.parameters[0])
+        }
+        (TypeCtor::RawPtr(_), TypeCtor::Int(_)) => is_thin(&from.parameters[0]),
+        (TypeCtor::Int(_), TypeCtor::RawPtr(_)) => is_thin(&to.parameters[0]),
+        (TypeCtor::FnDef(_), TypeCtor::FnPtr { .. })
+        | (TypeCtor::FnDef(_), TypeCtor::RawPtr(_))
+        | (TypeCtor::FnDef(_), TypeCtor::Int(_))
+        | (TypeCtor::FnPtr { .. }, TypeCtor::RawPtr(_))
+        | (TypeCtor::FnPtr { .. }, TypeCtor::Int(_))
+        | (TypeCtor::Closure { .. }, TypeCtor::FnPtr { .. }) => true,
+
+        // Casts which are coercions
+        (TypeCtor::Ref(from_mut), TypeCtor::Ref(to_mut)) => {
+            mutability_allows(from_mut, to_mut)
+                && (from.parameters[0] == to.parameters[0]
+                    || is_array_to_slice(&from.parameters[0], &to.parameters[0])
+                    || is_dyn(&to.parameters[0]))
+        }
+
+        _ => false,
+    };
+    if is_valid {
+        CastCheck::Valid
+    } else {
+        CastCheck::Invalid
+    }
+}
+
+fn contains_unknown(ty: &Ty) -> bool {
+    let mut res = false;
+    ty.walk(&mut |ty| {
+        if let Ty::Unknown = ty {
+            res = true;
+        }
+    });
+    res
+}
+
+fn mutability_allows(from: Mutability, to: Mutability) -> bool {
+    from == Mutability::Mut || to == Mutability::Shared
+}
+
+/// Whether a pointer to `pointee` is a thin pointer, i.e. doesn't carry a
+/// length or a vtable.
+fn is_thin(pointee: &Ty) -> bool {
+    match pointee {
+        Ty::Apply(ApplicationTy { ctor: TypeCtor::Slice, .. })
+        | Ty::Apply(ApplicationTy { ctor: TypeCtor::Str, .. })
+        | Ty::Dyn(_) => false,
+        _ => true,
+    }
+}
+
+fn is_dyn(ty: &Ty) -> bool {
+    match ty {
+        Ty::Dyn(_) => true,
+        _ => false,
+    }
+}
+
+fn array_elem(ty: &Ty) -> Option<&Ty> {
+    match ty {
+        Ty::Apply(ApplicationTy { ctor: TypeCtor::Array { .. }, parameters }) => {
+            Some(&parameters[0])
+        }
+        _ => None,
+    }
+}
+
+fn is_array_to_slice(from: &Ty, to: &Ty) -> bool {
+    match to {
+        Ty::Apply(ApplicationTy { ctor: TypeCtor::Slice, parameters }) => {
+            array_elem(from) == Some(&parameters[0])
+        }
+        _ => false,
+    }
+}
@@ -43,9 +43,10 @@ impl Ty {
                 let inner_ty = Ty::from_hir(db, resolver, inner);
                 Ty::apply_one(TypeCtor::RawPtr(*mutability), inner_ty)
             }
-            TypeRef::Array(inner) => {
+            TypeRef::Array(inner, len) => {
                 let inner_ty = Ty::from_hir(db, resolver, inner);
-                Ty::apply_one(TypeCtor::Array, inner_ty)
+                let len = len.eval(db, resolver).and_then(|it| it.as_u64());
+                Ty::apply_one(TypeCtor::Array { len }, inner_ty)
             }
             TypeRef::Slice(inner) => {
                 let inner_ty = Ty::from_hir(db, resolver, inner);
@@ -531,11 +532,12 @@ pub(crate) fn callable_item_sig(db: &impl HirDatabase, def: CallableDef) -> FnSi
     }
 }
 
-/// Build the type of a specific field of a struct or enum variant.
+/// Build the type of a specific field of a struct, union or enum variant.
 pub(crate) fn type_for_field(db: &impl HirDatabase, field: StructField) -> Ty {
     let parent_def = field.parent_def(db);
     let resolver = match parent_def {
         VariantDef::Struct(it) => it.resolver(db),
+        VariantDef::Union(it) => it.resolver(db),
         VariantDef::EnumVariant(it) => it.parent_enum(db).resolver(db),
     };
     let var_data = parent_def.variant_data(db);
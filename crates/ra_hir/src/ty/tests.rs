@@ -3,7 +3,7 @@ use std::sync::Arc;
 
 use insta::assert_snapshot;
 
-use ra_db::{salsa::Database, FilePosition, SourceDatabase};
+use ra_db::{salsa::Database, FilePosition, ProcMacro, ProcMacroKind, SourceDatabase};
 use ra_syntax::{
     algo,
     ast::{self, AstNode},
@@ -58,6 +58,80 @@ mod future {
     assert_eq!("u64", type_at_pos(&db, pos));
 }
 
+#[test]
+fn cfg_impl_block() {
+    let (mut db, pos) = MockDatabase::with_position(
+        r#"
+//- /main.rs
+use foo::S as T;
+struct S;
+
+#[cfg(test)]
+impl S {
+    fn foo1(&self) -> i32 { 0 }
+}
+
+#[cfg(not(test))]
+impl S {
+    fn foo2(&self) -> i32 { 0 }
+}
+
+fn test() {
+    let t = (S.foo1(), S.foo2(), T.foo3(), T.foo4());
+    t<|>;
+}
+
+//- /foo.rs
+struct S;
+
+#[cfg(not(test))]
+impl S {
+    fn foo3(&self) -> i32 { 0 }
+}
+
+#[cfg(test)]
+impl S {
+    fn foo4(&self) -> i32 { 0 }
+}
+"#,
+    );
+    db.set_crate_graph_from_fixture(crate_graph! {
+        "main": ("/main.rs", ["foo"], cfg = { "test" }),
+        "foo": ("/foo.rs", []),
+    });
+    assert_eq!("(i32, {unknown}, i32, {unknown})", type_at_pos(&db, pos));
+}
+
+#[test]
+fn cfg_record_fields_and_match_arms() {
+    let (mut db, pos) = MockDatabase::with_position(
+        r#"
+//- /main.rs
+struct S {
+    #[cfg(test)]
+    a: u32,
+    #[cfg(not(test))]
+    a: i64,
+}
+
+fn test() {
+    let s = S { #[cfg(test)] a: 1, #[cfg(not(test))] a: 2 };
+    let x = match s.a {
+        #[cfg(test)]
+        _ => 1u8,
+        #[cfg(not(test))]
+        _ => 1i8,
+    };
+    (s.a, x)<|>;
+}
+"#,
+    );
+    db.set_crate_graph_from_fixture(crate_graph! {
+        "main": ("/main.rs", [], cfg = { "test" }),
+    });
+    assert_eq!("(u32, u8)", type_at_pos(&db, pos));
+}
+
 #[test]
 fn infer_box() {
     let (mut db, pos) = MockDatabase::with_position(
@@ -86,7 +160,7 @@ mod boxed {
         "main": ("/main.rs", ["std"]),
         "std": ("/std.rs", []),
     });
-    assert_eq!("(Box<i32>, Box<Box<i32>>, Box<&i32>, Box<[i32;_]>)", type_at_pos(&db, pos));
+    assert_eq!("(Box<i32>, Box<Box<i32>>, Box<&i32>, Box<[i32; 1]>)", type_at_pos(&db, pos));
 }
 
 #[test]
@@ -133,6 +207,185 @@ mod result {
     assert_eq!("i32", type_at_pos(&db, pos));
 }
 
+#[test]
+fn infer_ops_index() {
+    let (mut db, pos) = MockDatabase::with_position(
+        r#"
+//- /main.rs
+
+struct Bar;
+struct Foo;
+
+impl std::ops::Index<u32> for Bar {
+    type Output = Foo;
+}
+
+fn test() {
+    let a = Bar;
+    let b = a[1];
+    b<|>;
+}
+
+//- /std.rs
+
+#[prelude_import] use ops::*;
+mod ops {
+    pub trait Index<Idx> {
+        type Output;
+    }
+}
+"#,
+    );
+    db.set_crate_graph_from_fixture(crate_graph! {
+        "main": ("/main.rs", ["std"]),
+        "std": ("/std.rs", []),
+    });
+    assert_eq!("Foo", type_at_pos(&db, pos));
+}
+
+#[test]
+fn infer_ops_index_autoderef() {
+    let (mut db, pos) = MockDatabase::with_position(
+        r#"
+//- /main.rs
+
+struct Bar;
+struct Foo;
+
+impl std::ops::Index<u32> for Bar {
+    type Output = Foo;
+}
+
+fn test(a: &&Bar) {
+    let b = a[1];
+    b<|>;
+}
+
+//- /std.rs
+
+#[prelude_import] use ops::*;
+mod ops {
+    pub trait Index<Idx> {
+        type Output;
+    }
+}
+"#,
+    );
+    db.set_crate_graph_from_fixture(crate_graph! {
+        "main": ("/main.rs", ["std"]),
+        "std": ("/std.rs", []),
+    });
+    assert_eq!("Foo", type_at_pos(&db, pos));
+}
+
+#[test]
+fn infer_ops_index_mut() {
+    let (mut db, pos) = MockDatabase::with_position(
+        r#"
+//- /main.rs
+
+struct Bar;
+struct Foo;
+
+impl std::ops::Index<u32> for Bar {
+    type Output = Foo;
+}
+
+impl std::ops::IndexMut<u32> for Bar {}
+
+fn test(mut a: Bar) {
+    let b = &mut a[1];
+    b<|>;
+}
+
+//- /std.rs
+
+#[prelude_import] use ops::*;
+mod ops {
+    pub trait Index<Idx> {
+        type Output;
+    }
+    pub trait IndexMut<Idx>: Index<Idx> {}
+}
+"#,
+    );
+    db.set_crate_graph_from_fixture(crate_graph! {
+        "main": ("/main.rs", ["std"]),
+        "std": ("/std.rs", []),
+    });
+    assert_eq!("&mut Foo", type_at_pos(&db, pos));
+}
+
+#[test]
+fn infer_ops_index_in_mutable_place_without_index_mut() {
+    let (mut db, pos) = MockDatabase::with_position(
+        r#"
+//- /main.rs
+
+struct Bar;
+struct Foo;
+
+impl std::ops::Index<u32> for Bar {
+    type Output = Foo;
+}
+
+fn test(mut a: Bar) {
+    let b = &mut a[1];
+    b<|>;
+}
+
+//- /std.rs
+
+#[prelude_import] use ops::*;
+mod ops {
+    pub trait Index<Idx> {
+        type Output;
+    }
+    pub trait IndexMut<Idx>: Index<Idx> {}
+}
+"#,
+    );
+    db.set_crate_graph_from_fixture(crate_graph! {
+        "main": ("/main.rs", ["std"]),
+        "std": ("/std.rs", []),
+    });
+    assert_eq!("&mut Foo", type_at_pos(&db, pos));
+}
+
+#[test]
+fn infer_builtin_index() {
+    assert_snapshot!(
+        infer(r#"
+fn test(a: [u8; 4], s: &[i32]) {
+    let x = a[0];
+    let y = s[1];
+    let i = 2;
+    let z = s[i];
+}
+"#),
+        @r###"
+
+    [9; 10) 'a': [u8; 4]
+    [21; 22) 's': &[i32]
+    [32; 104) '{     ...s[i]; }': ()
+    [42; 43) 'x': u8
+    [46; 47) 'a': [u8; 4]
+    [46; 50) 'a[0]': u8
+    [48; 49) '0': usize
+    [60; 61) 'y': i32
+    [64; 65) 's': &[i32]
+    [64; 68) 's[1]': i32
+    [66; 67) '1': usize
+    [78; 79) 'i': usize
+    [82; 83) '2': usize
+    [93; 94) 'z': i32
+    [97; 98) 's': &[i32]
+    [97; 101) 's[i]': i32
+    [99; 100) 'i': usize
+    "###
+    );
+}
+
 #[test]
 fn infer_for_loop() {
     let (mut db, pos) = MockDatabase::with_position(
@@ -389,6 +642,24 @@ fn test() {
     );
 }
 
+#[test]
+fn infer_union() {
+    let t = type_at(
+        r#"
+//- /main.rs
+union U<T: Copy> { a: T, b: f32 }
+
+fn test(u: U<u32>) {
+    let v = U { b: 1.0 };
+    let U { a } = u;
+    let x = (u.a, u.b, v.a, a);
+    x<|>;
+}
+"#,
+    );
+    assert_eq!(t, "(u32, f32, {unknown}, u32)");
+}
+
 #[test]
 fn infer_enum() {
     assert_snapshot!(
@@ -1183,59 +1454,78 @@ fn test(x: &str, y: isize) {
     [9; 10) 'x': &str
     [18; 19) 'y': isize
     [28; 293) '{     ... []; }': ()
-    [38; 39) 'a': [&str;_]
-    [42; 45) '[x]': [&str;_]
+    [38; 39) 'a': [&str; 1]
+    [42; 45) '[x]': [&str; 1]
     [43; 44) 'x': &str
-    [55; 56) 'b': [[&str;_];_]
-    [59; 65) '[a, a]': [[&str;_];_]
-    [60; 61) 'a': [&str;_]
-    [63; 64) 'a': [&str;_]
-    [75; 76) 'c': [[[&str;_];_];_]
-    [79; 85) '[b, b]': [[[&str;_];_];_]
-    [80; 81) 'b': [[&str;_];_]
-    [83; 84) 'b': [[&str;_];_]
-    [96; 97) 'd': [isize;_]
-    [100; 112) '[y, 1, 2, 3]': [isize;_]
+    [55; 56) 'b': [[&str; 1]; 2]
+    [59; 65) '[a, a]': [[&str; 1]; 2]
+    [60; 61) 'a': [&str; 1]
+    [63; 64) 'a': [&str; 1]
+    [75; 76) 'c': [[[&str; 1]; 2]; 2]
+    [79; 85) '[b, b]': [[[&str; 1]; 2]; 2]
+    [80; 81) 'b': [[&str; 1]; 2]
+    [83; 84) 'b': [[&str; 1]; 2]
+    [96; 97) 'd': [isize; 4]
+    [100; 112) '[y, 1, 2, 3]': [isize; 4]
     [101; 102) 'y': isize
     [104; 105) '1': isize
     [107; 108) '2': isize
     [110; 111) '3': isize
-    [122; 123) 'd': [isize;_]
-    [126; 138) '[1, y, 2, 3]': [isize;_]
+    [122; 123) 'd': [isize; 4]
+    [126; 138) '[1, y, 2, 3]': [isize; 4]
     [127; 128) '1': isize
     [130; 131) 'y': isize
     [133; 134) '2': isize
     [136; 137) '3': isize
-    [148; 149) 'e': [isize;_]
-    [152; 155) '[y]': [isize;_]
+    [148; 149) 'e': [isize; 1]
+    [152; 155) '[y]': [isize; 1]
     [153; 154) 'y': isize
-    [165; 166) 'f': [[isize;_];_]
-    [169; 175) '[d, d]': [[isize;_];_]
-    [170; 171) 'd': [isize;_]
-    [173; 174) 'd': [isize;_]
-    [185; 186) 'g': [[isize;_];_]
-    [189; 195) '[e, e]': [[isize;_];_]
-    [190; 191) 'e': [isize;_]
-    [193; 194) 'e': [isize;_]
-    [206; 207) 'h': [i32;_]
-    [210; 216) '[1, 2]': [i32;_]
+    [165; 166) 'f': [[isize; 4]; 2]
+    [169; 175) '[d, d]': [[isize; 4]; 2]
+    [170; 171) 'd': [isize; 4]
+    [173; 174) 'd': [isize; 4]
+    [185; 186) 'g': [[isize; 1]; 2]
+    [189; 195) '[e, e]': [[isize; 1]; 2]
+    [190; 191) 'e': [isize; 1]
+    [193; 194) 'e': [isize; 1]
+    [206; 207) 'h': [i32; 2]
+    [210; 216) '[1, 2]': [i32; 2]
     [211; 212) '1': i32
     [214; 215) '2': i32
-    [226; 227) 'i': [&str;_]
-    [230; 240) '["a", "b"]': [&str;_]
+    [226; 227) 'i': [&str; 2]
+    [230; 240) '["a", "b"]': [&str; 2]
     [231; 234) '"a"': &str
     [236; 239) '"b"': &str
-    [251; 252) 'b': [[&str;_];_]
-    [255; 265) '[a, ["b"]]': [[&str;_];_]
-    [256; 257) 'a': [&str;_]
-    [259; 264) '["b"]': [&str;_]
+    [251; 252) 'b': [[&str; 1]; 2]
+    [255; 265) '[a, ["b"]]': [[&str; 1]; 2]
+    [256; 257) 'a': [&str; 1]
+    [259; 264) '["b"]': [&str; 1]
     [260; 263) '"b"': &str
-    [275; 276) 'x': [u8;_]
-    [288; 290) '[]': [u8;_]
+    [275; 276) 'x': [u8; 0]
+    [288; 290) '[]': [u8; 0]
     "###
     );
 }
 
+#[test]
+fn infer_array_length() {
+    let t = type_at(
+        r#"
+//- /main.rs
+const N: usize = 2 * (3 + 1);
+const M: usize = N - 1;
+const A: usize = B;
+const B: usize = A;
+
+fn test(a: [u8; M], b: [u8; 0x10], c: [u8; A], d: [u8; unknown]) {
+    let x = (a, b, c, d, [true; N], [1, 2]);
+    x<|>;
+}
+"#,
+    );
+    assert_eq!(t, "([u8; 7], [u8; 16], [u8;_], [u8;_], [bool; 8], [i32; 2])");
+}
+
 #[test]
 fn infer_pattern() {
     assert_snapshot!(
@@ -1287,7 +1577,8 @@ fn test(x: &i32) {
     [140; 141) 'g': {unknown}
     [144; 145) 'e': {unknown}
     [158; 205) 'if let...     }': ()
-    [165; 170) '[val]': {unknown}
+    [165; 170) '[val]': [{unknown}]
+    [166; 169) 'val': {unknown}
     [173; 176) 'opt': {unknown}
     [177; 205) '{     ...     }': ()
     [191; 192) 'h': {unknown}
@@ -1314,6 +1605,25 @@ fn test(x: &i32) {
     );
 }
 
+#[test]
+fn infer_slice_and_tuple_rest_patterns() {
+    let t = type_at(
+        r#"
+//- /main.rs
+fn test(x: [u32; 4], t: (u8, u16, u32, u64)) {
+    let (a, .., d) = t;
+    match x {
+        [b, rest @ .., c] => {
+            let y = (a, b, rest, c, d);
+            y<|>;
+        }
+    }
+}
+"#,
+    );
+    assert_eq!(t, "(u8, u32, [u32; 2], u32, u64)");
+}
+
 #[test]
 fn infer_pattern_match_ergonomics() {
     assert_snapshot!(
@@ -2159,7 +2469,7 @@ fn test() {
     [11; 48) '{     ...&y]; }': ()
     [21; 22) 'y': &{unknown}
     [25; 32) 'unknown': &{unknown}
-    [38; 45) '[y, &y]': [&&{unknown};_]
+    [38; 45) '[y, &y]': [&&{unknown}; 2]
     [39; 40) 'y': &{unknown}
     [42; 44) '&y': &&{unknown}
     [43; 44) 'y': &{unknown}
@@ -2185,7 +2495,7 @@ fn test() {
     [25; 32) 'unknown': &&{unknown}
     [42; 43) 'y': &&{unknown}
     [46; 53) 'unknown': &&{unknown}
-    [59; 77) '[(x, y..., &x)]': [(&&&{unknown}, &&&{unknown});_]
+    [59; 77) '[(x, y..., &x)]': [(&&&{unknown}, &&&{unknown}); 2]
     [60; 66) '(x, y)': (&&&{unknown}, &&&{unknown})
     [61; 62) 'x': &&{unknown}
     [64; 65) 'y': &&{unknown}
@@ -2283,8 +2593,8 @@ fn test_line_buffer() {
         @r###"
 
     [23; 53) '{     ...n']; }': ()
-    [29; 50) '&[0, b...b'\n']': &[u8;_]
-    [30; 50) '[0, b'...b'\n']': [u8;_]
+    [29; 50) '&[0, b...b'\n']': &[u8; 4]
+    [30; 50) '[0, b'...b'\n']': [u8; 4]
     [31; 32) '0': u8
     [34; 39) 'b'\n'': u8
     [41; 42) '1': u8
@@ -3043,11 +3353,11 @@ fn indexing_arrays() {
         @r###"
 
     [10; 26) '{ &mut...[2]; }': ()
-    [12; 23) '&mut [9][2]': &mut {unknown}
-    [17; 20) '[9]': [i32;_]
-    [17; 23) '[9][2]': {unknown}
+    [12; 23) '&mut [9][2]': &mut i32
+    [17; 20) '[9]': [i32; 1]
+    [17; 23) '[9][2]': i32
     [18; 19) '9': i32
-    [21; 22) '2': i32
+    [21; 22) '2': usize
     "###
     )
 }
@@ -3081,6 +3391,65 @@ fn main() {
     );
 }
 
+#[test]
+fn infer_builtin_macros_line_column_file() {
+    let t = type_at(
+        r#"
+//- /main.rs
+#[rustc_builtin_macro]
+macro_rules! line {() => {}}
+#[rustc_builtin_macro]
+macro_rules! column {() => {}}
+#[rustc_builtin_macro]
+macro_rules! file {() => {}}
+
+fn main() {
+    let x = (line!(), column!(), file!());
+    x<|>;
+}
+"#,
+    );
+    assert_eq!(t, "(u32, u32, &str)");
+}
+
+#[test]
+fn infer_builtin_macros_stringify_format_args() {
+    let (mut db, pos) = MockDatabase::with_position(
+        r#"
+//- /main.rs
+#[rustc_builtin_macro]
+macro_rules! stringify {() => {}}
+#[rustc_builtin_macro]
+macro_rules! format_args {() => {}}
+
+fn main() {
+    let x = (stringify!(a + b), format_args!("{} {x}", 1, x = 2));
+    x<|>;
+}
+
+//- /std.rs
+pub mod fmt {
+    pub struct Arguments;
+    impl Arguments {
+        pub fn new_v1(pieces: &[&str], args: &[ArgumentV1]) -> Arguments { loop {} }
+    }
+    pub struct ArgumentV1;
+    impl ArgumentV1 {
+        pub fn new<T>(x: &T, f: fn(&T) -> ()) -> ArgumentV1 { loop {} }
+    }
+    pub trait Display {
+        fn fmt(&self);
+    }
+}
+"#,
+    );
+    db.set_crate_graph_from_fixture(crate_graph! {
+        "main": ("/main.rs", ["std"]),
+        "std": ("/std.rs", []),
+    });
+    assert_eq!("(&str, Arguments)", type_at_pos(&db, pos));
+}
+
 #[test]
 fn infer_legacy_textual_scoped_macros_expanded() {
     assert_snapshot!(
@@ -3224,6 +3593,219 @@ pub fn baz() -> usize { 31usize }
     assert_eq!("(i32, usize)", type_at_pos(&db, pos));
 }
 
+#[test]
+fn infer_macro_with_dollar_crate_is_correct_in_type() {
+    let (mut db, pos) = MockDatabase::with_position(
+        r#"
+//- /main.rs
+fn test() {
+    let x = foo::foo!(loop {});
+    x<|>;
+}
+
+//- /lib.rs
+#[macro_export]
+macro_rules! foo {
+    ($e:expr) => {{ let x: $crate::Foo = $e; x }};
+}
+
+pub struct Foo;
+"#,
+    );
+    db.set_crate_graph_from_fixture(crate_graph! {
+        "main": ("/main.rs", ["foo"]),
+        "foo": ("/lib.rs", []),
+    });
+    assert_eq!("Foo", type_at_pos(&db, pos));
+}
+
+#[test]
+fn infer_macro_locals_are_hygienic() {
+    let t = type_at(
+        r#"
+//- /main.rs
+macro_rules! m {
+    ($e:expr) => {{ let x = 1u32; $e }};
+}
+fn test() {
+    let x = 2i64;
+    let y = m!(x);
+    y<|>;
+}
+"#,
+    );
+    assert_eq!(t, "i64");
+}
+
+/// Expands every proc macro call to `expansion`.
+#[derive(Debug)]
+struct FixedExpander(&'static str);
+
+impl tt::TokenExpander for FixedExpander {
+    fn expand(
+        &self,
+        _subtree: &tt::Subtree,
+        _attrs: Option<&tt::Subtree>,
+    ) -> Result<tt::Subtree, tt::ExpansionError> {
+        let source_file = ast::SourceFile::parse(&format!("m! {{ {} }}", self.0)).tree();
+        let tt = source_file.syntax().descendants().find_map(ast::TokenTree::cast).unwrap();
+        let mut subtree = mbe::ast_to_token_tree(&tt).unwrap().0;
+        subtree.delimiter = tt::Delimiter::None;
+        Ok(subtree)
+    }
+}
+
+fn proc_macro(name: &str, kind: ProcMacroKind, expansion: &'static str) -> ProcMacro {
+    ProcMacro { name: name.into(), kind, expander: Arc::new(FixedExpander(expansion)) }
+}
+
+#[test]
+fn infer_function_like_proc_macro() {
+    let (mut db, pos) = MockDatabase::with_position(
+        r#"
+//- /main.rs
+fn test() {
+    let x = gen::make_u32!();
+    x<|>;
+}
+
+//- /gen.rs
+"#,
+    );
+    db.set_crate_graph_from_fixture(crate_graph! {
+        "main": ("/main.rs", ["gen"]),
+        "gen": ("/gen.rs", []),
+    });
+    db.set_proc_macros("/gen.rs", vec![proc_macro("make_u32", ProcMacroKind::FuncLike, "1u32")]);
+    assert_eq!("u32", type_at_pos(&db, pos));
+}
+
+#[test]
+fn infer_impl_from_custom_derive() {
+    let (mut db, pos) = MockDatabase::with_position(
+        r#"
+//- /main.rs
+use gen::Bytes;
+
+#[derive(Bytes)]
+struct S;
+
+fn test() {
+    S.bytes()<|>;
+}
+
+//- /gen.rs
+"#,
+    );
+    db.set_crate_graph_from_fixture(crate_graph! {
+        "main": ("/main.rs", ["gen"]),
+        "gen": ("/gen.rs", []),
+    });
+    db.set_proc_macros(
+        "/gen.rs",
+        vec![proc_macro(
+            "Bytes",
+            ProcMacroKind::CustomDerive,
+            "trait Bytes { fn bytes(&self) -> u64; } impl Bytes for S {}",
+        )],
+    );
+    assert_eq!("u64", type_at_pos(&db, pos));
+}
+
+#[test]
+fn infer_builtin_derive_clone() {
+    let (mut db, pos) = MockDatabase::with_position(
+        r#"
+//- /main.rs
+#[derive(Clone)]
+struct S;
+
+fn test() {
+    S.clone()<|>;
+}
+
+//- /std.rs
+#[prelude_import] use clone::*;
+mod clone {
+    trait Clone {
+        fn clone(&self) -> Self;
+    }
+}
+"#,
+    );
+    db.set_crate_graph_from_fixture(crate_graph! {
+        "main": ("/main.rs", ["std"]),
+        "std": ("/std.rs", []),
+    });
+    assert_eq!("S", type_at_pos(&db, pos));
+}
+
+#[test]
+fn infer_builtin_derive_in_no_std_crate() {
+    let (mut db, pos) = MockDatabase::with_position(
+        r#"
+//- /main.rs
+#![no_std]
+use core::clone::Clone;
+
+#[derive(Clone)]
+struct S;
+
+fn test() {
+    S.clone()<|>;
+}
+
+//- /core.rs
+pub mod clone {
+    pub trait Clone {
+        fn clone(&self) -> Self;
+    }
+}
+"#,
+    );
+    db.set_crate_graph_from_fixture(crate_graph! {
+        "main": ("/main.rs", ["core"]),
+        "core": ("/core.rs", []),
+    });
+    assert_eq!("S", type_at_pos(&db, pos));
+}
+
+#[test]
+fn infer_builtin_derive_with_generic_params() {
+    let (mut db, pos) = MockDatabase::with_position(
+        r#"
+//- /main.rs
+#[derive(Debug, Clone)]
+struct S;
+
+#[derive(Clone, Debug)]
+struct Wrapper<'a, T: Debug>(&'a T);
+
+fn test() {
+    let s = S;
+    Wrapper(&s).clone()<|>;
+}
+
+//- /std.rs
+#[prelude_import] use clone::*;
+#[prelude_import] use fmt::*;
+mod clone {
+    trait Clone {
+        fn clone(&self) -> Self;
+    }
+}
+mod fmt {
+    trait Debug {}
+}
+"#,
+    );
+    db.set_crate_graph_from_fixture(crate_graph! {
+        "main": ("/main.rs", ["std"]),
+        "std": ("/std.rs", []),
+    });
+    assert_eq!("Wrapper<S>", type_at_pos(&db, pos));
+}
+
 #[ignore]
 #[test]
 fn method_resolution_trait_before_autoref() {
@@ -4618,3 +5200,127 @@ fn no_such_field_diagnostics() {
     "###
     );
 }
+
+#[test]
+fn missing_match_arms_diagnostics() {
+    let diagnostics = MockDatabase::with_files(
+        r"
+        //- /lib.rs
+        enum E { A, B(bool), C { x: u8 } }
+        fn test(e: E, r: &E, b: bool, t: (bool, bool), n: u8, s: &[u8], a: [bool; 2]) {
+            match e { E::A => (), E::B(true) => () }
+            match r { E::A | E::B(_) => (), E::C { x: 0..=127 } => () }
+            match b { true => () }
+            match t { (true, _) => (), (_, true) => () }
+            match n { 0 => (), 1..=254 => () }
+            match n { 0..128 => (), 129..=255 => () }
+            match s { [] => (), [_, ..] if false => () }
+            match a { [true, _] => (), [false, true] => () }
+
+            match e { E::A => (), E::B(_) => (), E::C { .. } => () }
+            match r { &E::A => (), E::B(b) => (), E::C { x } => () }
+            match b { true => (), false => () }
+            match t { (true, _) | (false, true) | (_, false) => () }
+            match n { 0..=127 => (), 128..=255 => () }
+            match n { 0..128 => (), 128..=255 => () }
+            match s { [] => (), [_] => (), [_, _, ..] => () }
+            match a { [true, _] | [false, _] => () }
+            if let E::A = e {}
+        }
+        ",
+    )
+    .diagnostics();
+
+    assert_snapshot!(diagnostics, @r###"
+    "e": missing match arms: `E::B(false)`, `E::C { .. }` not covered
+    "r": missing match arms: `&E::C { x: 128..=255 }` not covered
+    "b": missing match arms: `false` not covered
+    "t": missing match arms: `(false, false)` not covered
+    "n": missing match arms: `255` not covered
+    "n": missing match arms: `128` not covered
+    "s": missing match arms: `&[_, ..]` not covered
+    "a": missing match arms: `[false, false]` not covered
+    "###
+    );
+}
+
+#[test]
+fn missing_match_arms_not_reported_for_arrays_of_unknown_length() {
+    let diagnostics = MockDatabase::with_files(
+        r"
+        //- /lib.rs
+        fn test(a: [u8; LEN], b: bool) {
+            match a { [x, ..] => () }
+            match a { [..] => () }
+            match (b, a) { (true, [.., 0]) => () }
+        }
+        ",
+    )
+    .diagnostics();
+
+    assert_snapshot!(diagnostics, @r###"
+    "(b, a)": missing match arms: `(false, _)` not covered
+    "###
+    );
+}
+
+#[test]
+fn infer_cast_picks_type_of_unsuffixed_literal() {
+    let t = type_at(
+        r#"
+//- /main.rs
+fn test() {
+    9<|>7 as char;
+}
+"#,
+    );
+    assert_eq!(t, "u8");
+}
+
+#[test]
+fn cast_diagnostics() {
+    let diagnostics = MockDatabase::with_files(
+        r"
+        //- /lib.rs
+        struct S;
+        enum Fieldless { A, B }
+        enum WithFields { A(i32), B }
+        fn foo() {}
+        fn test(x: i32, b: bool, r: &mut i32, p: *const u8, s: &[u8]) {
+            x as u8;
+            x as f64;
+            b as i32;
+            b'a' as char;
+            Fieldless::A as u8;
+            r as *mut i32;
+            r as &i32;
+            p as usize;
+            foo as fn();
+            foo as usize;
+            1 as i32;
+            97 as char;
+
+            x as i32;
+            S as i32;
+            x as char;
+            b as f32;
+            WithFields::B as u8;
+            r as *mut u8;
+            s as *const u8 as *const [u8];
+        }
+        ",
+    )
+    .diagnostics();
+
+    assert_snapshot!(diagnostics, @r###"
+    "x as i32": trivial cast: `i32` as `i32`
+    "S as i32": invalid cast: `S` as `i32`
+    "x as char": invalid cast: `i32` as `char`
+    "b as f32": invalid cast: `bool` as `f32`
+    "WithFields::B as u8": invalid cast: `WithFields` as `u8`
+    "r as *mut u8": invalid cast: `&mut i32` as `*mut u8`
+    "s as *const u8": invalid cast: `&[u8]` as `*const u8`
+    "s as *const u8 as *const [u8]": invalid cast: `*const u8` as `*const [u8]`
+    "###
+    );
+}
@@ -6,6 +6,7 @@ use ra_syntax::ast::NameOwner;
 
 use crate::{
     db::{AstDatabase, DefDatabase},
+    hygiene::Hygiene,
     name::{AsName, Name},
     type_ref::TypeRef,
     HasSource, TypeAlias,
@@ -21,8 +22,9 @@ pub(crate) fn type_alias_data_query(
     db: &(impl DefDatabase + AstDatabase),
     typ: TypeAlias,
 ) -> Arc<TypeAliasData> {
-    let node = typ.source(db).ast;
-    let name = node.name().map_or_else(Name::missing, |n| n.as_name());
-    let type_ref = node.type_ref().map(TypeRef::from_ast);
+    let src = typ.source(db);
+    let hygiene = Hygiene::new(db, src.file_id);
+    let name = src.ast.name().map_or_else(Name::missing, |n| n.as_name());
+    let type_ref = src.ast.type_ref().map(|it| TypeRef::from_ast(it, &hygiene));
     Arc::new(TypeAliasData { name, type_ref })
 }
@@ -0,0 +1,240 @@
+//! Entry point for call-hierarchy
+
+use ra_db::SourceDatabase;
+use ra_syntax::{
+    algo::find_node_at_offset,
+    ast::{self, DocCommentsOwner},
+    AstNode, SyntaxKind, TextRange,
+};
+
+use crate::{
+    db::RootDatabase, display::ShortLabel, goto_definition::goto_definition, references,
+    FilePosition, NavigationTarget, RangeInfo,
+};
+
+/// A function which calls, or is called by, the function the hierarchy was
+/// requested for, together with the ranges of the calls.
+#[derive(Debug, Clone)]
+pub struct CallItem {
+    pub target: NavigationTarget,
+    pub ranges: Vec<TextRange>,
+}
+
+/// Resolves the function at `position`, which becomes the root of the hierarchy.
+pub(crate) fn call_hierarchy(
+    db: &RootDatabase,
+    position: FilePosition,
+) -> Option<RangeInfo<Vec<NavigationTarget>>> {
+    let RangeInfo { range, info: navs } = goto_definition(db, position)?;
+    let navs = navs.into_iter().filter(|it| it.kind() == SyntaxKind::FN_DEF).collect::<Vec<_>>();
+    if navs.is_empty() {
+        return None;
+    }
+    Some(RangeInfo::new(range, navs))
+}
+
+pub(crate) fn incoming_calls(db: &RootDatabase, position: FilePosition) -> Option<Vec<CallItem>> {
+    let refs = references::find_all_refs(db, position, None)?.info;
+
+    let mut calls = CallLocations::default();
+    for reference in refs.references() {
+        let parse = db.parse(reference.file_id);
+        let syntax = parse.tree().syntax().clone();
+        let name_ref = match find_node_at_offset::<ast::NameRef>(&syntax, reference.range.start()) {
+            Some(it) => it,
+            None => continue,
+        };
+        if !is_call(&name_ref) {
+            continue;
+        }
+        // Calls outside of functions, like in the initializers of consts, have no caller.
+        if let Some(caller) = name_ref.syntax().ancestors().find_map(ast::FnDef::cast) {
+            let nav = NavigationTarget::from_named(
+                reference.file_id,
+                &caller,
+                caller.doc_comment_text(),
+                caller.short_label(),
+            );
+            calls.add(nav, reference.range);
+        }
+    }
+    Some(calls.into_items())
+}
+
+pub(crate) fn outgoing_calls(db: &RootDatabase, position: FilePosition) -> Option<Vec<CallItem>> {
+    let nav = call_hierarchy(db, position)?.info.into_iter().next()?;
+    let file_id = nav.file_id();
+    let parse = db.parse(file_id);
+    let syntax = parse.tree().syntax().clone();
+    let fn_def = find_node_at_offset::<ast::FnDef>(&syntax, nav.range().start())?;
+    let body = fn_def.body()?;
+    let analyzer = hir::SourceAnalyzer::new(db, file_id, fn_def.syntax(), None);
+
+    let mut calls = CallLocations::default();
+    for node in body.syntax().descendants() {
+        // Calls in nested functions belong to those functions.
+        if node.ancestors().find_map(ast::FnDef::cast).as_ref() != Some(&fn_def) {
+            continue;
+        }
+        let (func, range) = if let Some(call) = ast::CallExpr::cast(node.clone()) {
+            let callee = match call.expr() {
+                Some(it) => it,
+                None => continue,
+            };
+            let func = match analyzer.type_of(db, &callee).and_then(|ty| ty.as_callable()) {
+                Some((hir::CallableDef::Function(it), _)) => it,
+                _ => continue,
+            };
+            let range = match &callee {
+                ast::Expr::PathExpr(path_expr) => path_expr
+                    .path()
+                    .and_then(|it| it.segment())
+                    .and_then(|it| it.name_ref())
+                    .map(|it| it.syntax().text_range()),
+                _ => None,
+            };
+            (func, range.unwrap_or_else(|| callee.syntax().text_range()))
+        } else if let Some(call) = ast::MethodCallExpr::cast(node) {
+            let func = match analyzer.resolve_method_call(&call) {
+                Some(it) => it,
+                None => continue,
+            };
+            let range = match call.name_ref() {
+                Some(it) => it.syntax().text_range(),
+                None => call.syntax().text_range(),
+            };
+            (func, range)
+        } else {
+            continue;
+        };
+        calls.add(NavigationTarget::from_def_source(db, func), range);
+    }
+    Some(calls.into_items())
+}
+
+/// Checks whether `name_ref` names the function of a call or a method call.
+fn is_call(name_ref: &ast::NameRef) -> bool {
+    let parent = match name_ref.syntax().parent() {
+        Some(it) => it,
+        None => return false,
+    };
+    if ast::MethodCallExpr::cast(parent.clone()).is_some() {
+        return true;
+    }
+    let path_expr = match parent
+        .ancestors()
+        .skip_while(|it| ast::PathSegment::cast(it.clone()).is_some())
+        .skip_while(|it| ast::Path::cast(it.clone()).is_some())
+        .next()
+        .and_then(ast::PathExpr::cast)
+    {
+        Some(it) => it,
+        None => return false,
+    };
+    path_expr
+        .syntax()
+        .parent()
+        .and_then(ast::CallExpr::cast)
+        .and_then(|call| call.expr())
+        .map_or(false, |callee| callee.syntax() == path_expr.syntax())
+}
+
+/// Groups call ranges by function, in the order the functions were found.
+#[derive(Default)]
+struct CallLocations {
+    items: Vec<CallItem>,
+}
+
+impl CallLocations {
+    fn add(&mut self, target: NavigationTarget, range: TextRange) {
+        let key = (target.file_id(), target.full_range());
+        match self.items.iter_mut().find(|it| (it.target.file_id(), it.target.full_range()) == key)
+        {
+            Some(item) => item.ranges.push(range),
+            None => self.items.push(CallItem { target, ranges: vec![range] }),
+        }
+    }
+
+    fn into_items(self) -> Vec<CallItem> {
+        self.items
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mock_analysis::{analysis_and_position, single_file_with_position};
+
+    fn render(items: &[crate::CallItem]) -> Vec<String> {
+        items
+            .iter()
+            .map(|it| {
+                let ranges =
+                    it.ranges.iter().map(|r| format!("{:?}", r)).collect::<Vec<_>>().join(", ");
+                format!("{} {}", it.target.name(), ranges)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_call_hierarchy_on_ref() {
+        let (analysis, pos) = single_file_with_position(
+            r#"
+            fn callee() {}
+            fn caller() {
+                call<|>ee();
+            }
+            "#,
+        );
+        let navs = analysis.call_hierarchy(pos).unwrap().unwrap().info;
+        assert_eq!(navs.len(), 1);
+        navs[0].assert_match("callee FN_DEF FileId(1) [13; 27) [16; 22)");
+    }
+
+    #[test]
+    fn test_incoming_calls() {
+        let (analysis, pos) = single_file_with_position(
+            r#"
+            struct S;
+            impl S {
+                fn callee<|>(&self) {}
+            }
+            fn caller1(s: S) {
+                s.callee();
+                s.callee();
+            }
+            fn caller2() {
+                S.callee();
+                let f = S::callee;
+            }
+            "#,
+        );
+        let calls = analysis.incoming_calls(pos).unwrap().unwrap();
+        assert_eq!(render(&calls), vec!["caller1 [143; 149), [171; 177)", "caller2 [240; 246)"]);
+    }
+
+    #[test]
+    fn test_outgoing_calls() {
+        let (analysis, pos) = analysis_and_position(
+            r#"
+            //- /lib.rs
+            mod foo;
+            use foo::bar;
+
+            fn caller<|>() {
+                bar();
+                bar().baz();
+                fn nested() { bar(); }
+            }
+
+            //- /foo.rs
+            pub struct Bar;
+            impl Bar {
+                pub fn baz(&self) {}
+            }
+            pub fn bar() -> Bar { Bar }
+            "#,
+        );
+        let calls = analysis.outgoing_calls(pos).unwrap().unwrap();
+        assert_eq!(render(&calls), vec!["bar [41; 44), [52; 55)", "baz [58; 61)"]);
+    }
+}
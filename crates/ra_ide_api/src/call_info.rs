@@ -2,9 +2,9 @@
 
 use ra_db::SourceDatabase;
 use ra_syntax::{
-    algo::find_node_at_offset,
+    algo::{ancestors_at_offset, find_node_at_offset},
     ast::{self, ArgListOwner},
-    AstNode, SyntaxNode, TextUnit,
+    AstNode, SyntaxNode, TextUnit, T,
 };
 use test_utils::tested_by;
 
@@ -15,29 +15,41 @@ pub(crate) fn call_info(db: &RootDatabase, position: FilePosition) -> Option<Cal
     let parse = db.parse(position.file_id);
     let syntax = parse.tree().syntax().clone();
 
+    if let Some(type_arg_list) = find_node_at_offset::<ast::TypeArgList>(&syntax, position.offset) {
+        if let Some(call_info) = turbofish_call_info(db, position, &type_arg_list) {
+            return Some(call_info);
+        }
+    }
+
     // Find the calling expression and it's NameRef
     let calling_node = FnCallNode::with_node(&syntax, position.offset)?;
     let name_ref = calling_node.name_ref()?;
 
     let analyzer = hir::SourceAnalyzer::new(db, position.file_id, name_ref.syntax(), None);
-    let function = match &calling_node {
+    let (mut call_info, has_self) = match &calling_node {
         FnCallNode::CallExpr(expr) => {
             //FIXME: apply subst
             let (callable_def, _subst) = analyzer.type_of(db, &expr.expr()?)?.as_callable()?;
             match callable_def {
-                hir::CallableDef::Function(it) => it,
-                //FIXME: handle other callables
-                _ => return None,
+                hir::CallableDef::Function(it) => {
+                    (CallInfo::with_fn(db, it), it.data(db).has_self_param())
+                }
+                hir::CallableDef::Struct(it) => (CallInfo::with_struct(db, it)?, false),
+                hir::CallableDef::EnumVariant(it) => (CallInfo::with_enum_variant(db, it)?, false),
             }
         }
-        FnCallNode::MethodCallExpr(expr) => analyzer.resolve_method_call(&expr)?,
+        FnCallNode::MethodCallExpr(expr) => {
+            let function = analyzer.resolve_method_call(&expr)?;
+            (CallInfo::with_fn(db, function), function.data(db).has_self_param())
+        }
+        FnCallNode::MacroCallExpr(expr) => {
+            let macro_def = analyzer.resolve_macro_call(db, &expr)?;
+            (CallInfo::with_macro(db, macro_def)?, false)
+        }
     };
 
-    let mut call_info = CallInfo::new(db, function);
-
     // If we have a calling expression let's find which argument we are on
     let num_params = call_info.parameters().len();
-    let has_self = function.data(db).has_self_param();
 
     if num_params == 1 {
         if !has_self {
@@ -69,26 +81,79 @@ pub(crate) fn call_info(db: &RootDatabase, position: FilePosition) -> Option<Cal
             }
 
             call_info.active_parameter = Some(param);
+        } else if let FnCallNode::MacroCallExpr(expr) = &calling_node {
+            // Macro arguments are not parsed, so we count the commas instead.
+            let token_tree = expr.token_tree()?;
+            if !token_tree.syntax().text_range().contains_inclusive(position.offset) {
+                return None;
+            }
+            let param = count_commas_before(token_tree.syntax(), position.offset);
+            call_info.active_parameter = Some(std::cmp::min(param, num_params - 1));
         }
     }
 
     Some(call_info)
 }
 
+/// Computes parameter information for the generic arguments of a function,
+/// like `foo::<u32, <|>>()` or `x.collect::<<|>>()`.
+fn turbofish_call_info(
+    db: &RootDatabase,
+    position: FilePosition,
+    type_arg_list: &ast::TypeArgList,
+) -> Option<CallInfo> {
+    let parent = type_arg_list.syntax().parent()?;
+    let analyzer = hir::SourceAnalyzer::new(db, position.file_id, type_arg_list.syntax(), None);
+    let function = if let Some(method_call) = ast::MethodCallExpr::cast(parent.clone()) {
+        analyzer.resolve_method_call(&method_call)?
+    } else {
+        let path = ast::PathSegment::cast(parent)?.syntax().parent().and_then(ast::Path::cast)?;
+        // Generic arguments of types, like `Vec::<u32>::new()`, are not a turbofish
+        path.syntax().parent().and_then(ast::PathExpr::cast)?;
+        match analyzer.resolve_path(db, &path)? {
+            hir::PathResolution::Def(hir::ModuleDef::Function(it)) => it,
+            hir::PathResolution::AssocItem(hir::AssocItem::Function(it)) => it,
+            _ => return None,
+        }
+    };
+
+    let signature = FunctionSignature::from_hir(db, function).into_turbofish();
+    let active_parameter = if signature.parameters.is_empty() {
+        None
+    } else {
+        let param = count_commas_before(type_arg_list.syntax(), position.offset);
+        Some(std::cmp::min(param, signature.parameters.len() - 1))
+    };
+    Some(CallInfo { signature, active_parameter })
+}
+
+fn count_commas_before(node: &SyntaxNode, offset: TextUnit) -> usize {
+    node.children_with_tokens()
+        .filter(|it| it.kind() == T![,])
+        .take_while(|it| it.text_range().end() <= offset)
+        .count()
+}
+
 enum FnCallNode {
     CallExpr(ast::CallExpr),
     MethodCallExpr(ast::MethodCallExpr),
+    MacroCallExpr(ast::MacroCall),
 }
 
 impl FnCallNode {
     fn with_node(syntax: &SyntaxNode, offset: TextUnit) -> Option<FnCallNode> {
-        if let Some(expr) = find_node_at_offset::<ast::CallExpr>(syntax, offset) {
-            return Some(FnCallNode::CallExpr(expr));
-        }
-        if let Some(expr) = find_node_at_offset::<ast::MethodCallExpr>(syntax, offset) {
-            return Some(FnCallNode::MethodCallExpr(expr));
-        }
-        None
+        ancestors_at_offset(syntax, offset).find_map(|node| {
+            if let Some(expr) = ast::CallExpr::cast(node.clone()) {
+                return Some(FnCallNode::CallExpr(expr));
+            }
+            if let Some(expr) = ast::MethodCallExpr::cast(node.clone()) {
+                return Some(FnCallNode::MethodCallExpr(expr));
+            }
+            if let Some(expr) = ast::MacroCall::cast(node) {
+                return Some(FnCallNode::MacroCallExpr(expr));
+            }
+            None
+        })
     }
 
     fn name_ref(&self) -> Option<ast::NameRef> {
@@ -101,6 +166,8 @@ impl FnCallNode {
             FnCallNode::MethodCallExpr(call_expr) => {
                 call_expr.syntax().children().filter_map(ast::NameRef::cast).nth(0)
             }
+
+            FnCallNode::MacroCallExpr(call_expr) => call_expr.path()?.segment()?.name_ref(),
         }
     }
 
@@ -108,17 +175,36 @@ impl FnCallNode {
         match self {
             FnCallNode::CallExpr(expr) => expr.arg_list(),
             FnCallNode::MethodCallExpr(expr) => expr.arg_list(),
+            FnCallNode::MacroCallExpr(_) => None,
         }
     }
 }
 
 impl CallInfo {
-    fn new(db: &RootDatabase, function: hir::Function) -> Self {
+    fn with_fn(db: &RootDatabase, function: hir::Function) -> Self {
         let signature = FunctionSignature::from_hir(db, function);
 
         CallInfo { signature, active_parameter: None }
     }
 
+    fn with_struct(db: &RootDatabase, st: hir::Struct) -> Option<Self> {
+        let signature = FunctionSignature::from_struct(db, st)?;
+
+        Some(CallInfo { signature, active_parameter: None })
+    }
+
+    fn with_enum_variant(db: &RootDatabase, variant: hir::EnumVariant) -> Option<Self> {
+        let signature = FunctionSignature::from_enum_variant(db, variant)?;
+
+        Some(CallInfo { signature, active_parameter: None })
+    }
+
+    fn with_macro(db: &RootDatabase, macro_def: hir::MacroDef) -> Option<Self> {
+        let signature = FunctionSignature::from_macro(db, macro_def)?;
+
+        Some(CallInfo { signature, active_parameter: None })
+    }
+
     fn parameters(&self) -> &[String] {
         &self.signature.parameters
     }
@@ -428,6 +514,147 @@ By default this method stops actor's `Context`."#
         );
     }
 
+    #[test]
+    fn test_tuple_struct_constructor() {
+        let info = call_info(
+            r#"
+/// A point
+struct S(u32, i32);
+fn main() {
+    let s = S(0, <|>);
+}"#,
+        );
+
+        assert_eq!(info.parameters(), ["u32", "i32"]);
+        assert_eq!(info.active_parameter, Some(1));
+        assert_eq!(info.label(), "struct S(u32, i32)");
+        assert_eq!(info.doc().map(|it| it.into()), Some("A point".to_string()));
+    }
+
+    #[test]
+    fn test_generic_tuple_struct_constructor() {
+        let info = call_info(
+            r#"
+struct S<T>(T);
+fn main() {
+    let s = S(<|>);
+}"#,
+        );
+
+        assert_eq!(info.parameters(), ["T"]);
+        assert_eq!(info.active_parameter, Some(0));
+        assert_eq!(info.label(), "struct S<T>(T)");
+    }
+
+    #[test]
+    fn test_record_struct_has_no_call_info() {
+        let (analysis, position) = single_file_with_position(
+            r#"
+struct S { x: u32 }
+fn main() {
+    let s = S(<|>);
+}"#,
+        );
+        let call_info = analysis.call_info(position).unwrap();
+        assert!(call_info.is_none());
+    }
+
+    #[test]
+    fn test_enum_variant_constructor() {
+        let info = call_info(
+            r#"
+enum E {
+    /// A variant
+    A(i32, &'static str),
+    B,
+}
+fn main() {
+    let a = E::A(<|>);
+}"#,
+        );
+
+        assert_eq!(info.parameters(), ["i32", "&'static str"]);
+        assert_eq!(info.active_parameter, Some(0));
+        assert_eq!(info.label(), "E::A(i32, &'static str)");
+        assert_eq!(info.doc().map(|it| it.into()), Some("A variant".to_string()));
+    }
+
+    #[test]
+    fn test_macro_call() {
+        let info = call_info(
+            r#"
+/// Adds things
+macro_rules! add {
+    ($a:expr, $($b:expr),*) => { $a $(+ $b)* }
+}
+fn main() {
+    add!(1, <|>);
+}"#,
+        );
+
+        assert_eq!(info.parameters(), ["$a:expr", "$($b:expr),*"]);
+        assert_eq!(info.active_parameter, Some(1));
+        assert_eq!(info.label(), "add!($a:expr, $($b:expr),*)");
+        assert_eq!(info.doc().map(|it| it.into()), Some("Adds things".to_string()));
+    }
+
+    #[test]
+    fn test_macro_call_with_several_rules() {
+        let info = call_info(
+            r#"
+macro_rules! m {
+    ($a:expr) => { $a };
+    ($a:expr, $b:expr) => { $a };
+}
+fn main() {
+    m!(<|>);
+}"#,
+        );
+
+        assert!(info.parameters().is_empty());
+        assert_eq!(info.label(), "m!()");
+    }
+
+    #[test]
+    fn test_turbofish() {
+        let info = call_info(
+            r#"
+fn foo<T, U: Copy>(x: T, y: U) where T: Default {}
+fn main() {
+    foo::<u32, <|>>(1, 2);
+}"#,
+        );
+
+        assert_eq!(info.parameters(), ["T", "U: Copy"]);
+        assert_eq!(info.active_parameter, Some(1));
+        assert_eq!(
+            info.label(),
+            r#"
+fn foo<T, U: Copy>
+where T: Default
+    "#
+            .trim()
+        );
+    }
+
+    #[test]
+    fn test_method_turbofish() {
+        let info = call_info(
+            r#"
+struct S;
+impl S {
+    fn collect<B>(&self) -> B { loop {} }
+}
+fn main() {
+    S.collect::<<|>u32>();
+}"#,
+        );
+
+        assert_eq!(info.parameters(), ["B"]);
+        assert_eq!(info.active_parameter, Some(0));
+        assert_eq!(info.label(), "fn collect<B>");
+    }
+
     #[test]
     fn call_info_bad_offset() {
         covers!(call_info_bad_offset);
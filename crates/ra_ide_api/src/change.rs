@@ -8,6 +8,7 @@ use ra_db::{
 };
 use ra_prof::{memory_usage, profile, Bytes};
 use ra_syntax::SourceFile;
+use ra_text_edit::AtomTextEdit;
 #[cfg(not(feature = "wasm"))]
 use rayon::prelude::*;
 use relative_path::RelativePathBuf;
@@ -22,7 +23,7 @@ use crate::{
 pub struct AnalysisChange {
     new_roots: Vec<(SourceRootId, bool)>,
     roots_changed: FxHashMap<SourceRootId, RootChange>,
-    files_changed: Vec<(FileId, Arc<String>)>,
+    files_changed: Vec<(FileId, Arc<String>, Option<Vec<AtomTextEdit>>)>,
     libraries_added: Vec<LibraryData>,
     crate_graph: Option<CrateGraph>,
     debug_data: DebugData,
@@ -71,7 +72,19 @@ impl AnalysisChange {
     }
 
     pub fn change_file(&mut self, file_id: FileId, new_text: Arc<String>) {
-        self.files_changed.push((file_id, new_text))
+        self.files_changed.push((file_id, new_text, None))
+    }
+
+    /// Changes the text of a file to `new_text`, which is the result of
+    /// applying `edits` to the old text, so that the file can be reparsed
+    /// incrementally.
+    pub fn change_file_with_edits(
+        &mut self,
+        file_id: FileId,
+        new_text: Arc<String>,
+        edits: Vec<AtomTextEdit>,
+    ) {
+        self.files_changed.push((file_id, new_text, Some(edits)))
     }
 
     pub fn remove_file(&mut self, root_id: SourceRootId, file_id: FileId, path: RelativePathBuf) {
@@ -189,11 +202,22 @@ impl RootDatabase {
         for (root_id, root_change) in change.roots_changed {
             self.apply_root_change(root_id, root_change);
         }
-        for (file_id, text) in change.files_changed {
+        for (file_id, text, edits) in change.files_changed {
             let source_root_id = self.file_source_root(file_id);
             let source_root = self.source_root(source_root_id);
             let durability = durability(&source_root);
-            self.set_file_text_with_durability(file_id, text, durability)
+            match edits {
+                Some(edits) => {
+                    let _p = profile("RootDatabase::apply_change/reparse");
+                    let parse =
+                        edits.iter().fold(self.parse(file_id), |parse, edit| parse.reparse(edit));
+                    self.reparses.lock().unwrap().insert(file_id, (text.clone(), parse));
+                }
+                None => {
+                    self.reparses.lock().unwrap().remove(&file_id);
+                }
+            }
+            self.set_file_text_with_durability(file_id, text, durability);
         }
         if !change.libraries_added.is_empty() {
             let mut libraries = Vec::clone(&self.library_roots());
@@ -225,6 +249,7 @@ impl RootDatabase {
         let durability = durability(&source_root);
         for add_file in root_change.added {
             self.set_file_text_with_durability(add_file.file_id, add_file.text, durability);
+            self.reparses.lock().unwrap().remove(&add_file.file_id);
             self.set_file_relative_path_with_durability(
                 add_file.file_id,
                 add_file.path.clone(),
@@ -235,6 +260,7 @@ impl RootDatabase {
         }
         for remove_file in root_change.removed {
             self.set_file_text_with_durability(remove_file.file_id, Default::default(), durability);
+            self.reparses.lock().unwrap().remove(&remove_file.file_id);
             source_root.remove_file(&remove_file.path);
         }
         self.set_source_root_with_durability(root_id, Arc::new(source_root), durability);
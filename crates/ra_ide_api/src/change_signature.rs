@@ -0,0 +1,649 @@
+//! Changes the parameters of a function: they can be reordered, added and
+//! removed, and every call of the function is updated to match.
+
+use hir::{FromSource, HasSource};
+use ra_db::SourceDatabase;
+use ra_syntax::{
+    algo::{find_node_at_offset, non_trivia_sibling},
+    ast::{self, make, ArgListOwner, NameOwner},
+    AstNode, Direction,
+    SyntaxKind::{FN_DEF, TOKEN_TREE, USE_TREE},
+    SyntaxNode, TextRange, T,
+};
+use ra_text_edit::TextEditBuilder;
+
+use crate::{
+    db::RootDatabase, display::split_token_tree, goto_definition::goto_definition, references,
+    FileId, FilePosition, FileRange, SourceChange, SourceFileEdit,
+};
+
+/// A parameter of the new signature of a function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureParam {
+    /// The parameter at this index in the current signature, not counting
+    /// `self`.
+    Existing(usize),
+    /// A new parameter, like `verbose: bool`. Existing calls pass `default`
+    /// for it.
+    Added { param: String, default: String },
+}
+
+enum NewParam {
+    Existing(usize),
+    Added(ast::Param, ast::Expr),
+}
+
+pub(crate) fn change_signature(
+    db: &RootDatabase,
+    position: FilePosition,
+    params: &[SignatureParam],
+) -> Option<SourceChange> {
+    let function = function_at(db, position)?;
+    let params = params
+        .iter()
+        .map(|param| match param {
+            SignatureParam::Existing(idx) => Some(NewParam::Existing(*idx)),
+            SignatureParam::Added { param, default } => {
+                Some(NewParam::Added(make::param(param)?, make::expr_from_text(default)?))
+            }
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    let mut edits: Vec<(FileRange, String)> = Vec::new();
+    let mut declarations: Vec<FileRange> = Vec::new();
+    let mut has_self = false;
+    // The signatures of a trait method and of its impls must stay the same
+    for function in references::related_functions(db, function) {
+        let src = function.source(db);
+        let file_id = src.file_id.original_file(db);
+        // Functions defined by macros can't be edited
+        if src.file_id != hir::HirFileId::from(file_id) {
+            return None;
+        }
+        let param_list = src.ast.param_list()?;
+        let old_params = param_list.params().collect::<Vec<_>>();
+        let new_params = params
+            .iter()
+            .map(|param| match param {
+                NewParam::Existing(idx) => old_params.get(*idx).cloned(),
+                NewParam::Added(param, _) => Some(param.clone()),
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let new_param_list = param_list.with_params(new_params.into_iter());
+        let range = param_list.syntax().text_range();
+        edits.push((FileRange { file_id, range }, new_param_list.syntax().text().to_string()));
+
+        has_self = param_list.self_param().is_some();
+        declarations.push(FileRange { file_id, range: src.ast.name()?.syntax().text_range() });
+    }
+
+    // The references of a method include the ones of the related functions
+    let position =
+        FilePosition { file_id: declarations[0].file_id, offset: declarations[0].range.start() };
+    let refs = references::find_all_refs(db, position, None)?.info;
+    let mut calls: Vec<Call> = Vec::new();
+    for reference in refs.references() {
+        let is_declaration = declarations
+            .iter()
+            .any(|it| it.file_id == reference.file_id && it.range == reference.range);
+        if !is_declaration && !is_import(db, *reference) {
+            calls.push(Call::at(db, *reference, has_self)?);
+        }
+    }
+
+    calls.sort_by_key(|it| it.file_id);
+    let mut calls = calls.into_iter().peekable();
+    while let Some(call) = calls.next() {
+        let file_id = call.file_id;
+        let mut file_calls = vec![call];
+        while let Some(call) = calls.peek() {
+            if call.file_id != file_id {
+                break;
+            }
+            file_calls.extend(calls.next());
+        }
+        let text = db.file_text(file_id);
+        for (range, new_text) in rewrite_calls(&text, file_calls, &params)? {
+            edits.push((FileRange { file_id, range }, new_text));
+        }
+    }
+
+    edits.sort_by_key(|(frange, _)| (frange.file_id, frange.range.start()));
+    let mut builders: Vec<(FileId, TextEditBuilder)> = Vec::new();
+    for (frange, new_text) in edits {
+        let is_same_file = builders.last().map_or(false, |(it, _)| *it == frange.file_id);
+        if !is_same_file {
+            builders.push((frange.file_id, TextEditBuilder::default()));
+        }
+        if let Some((_, builder)) = builders.last_mut() {
+            builder.replace(frange.range, new_text);
+        }
+    }
+    let source_file_edits = builders
+        .into_iter()
+        .map(|(file_id, builder)| SourceFileEdit { file_id, edit: builder.finish() })
+        .collect();
+    Some(SourceChange::source_file_edits("change signature", source_file_edits))
+}
+
+fn function_at(db: &RootDatabase, position: FilePosition) -> Option<hir::Function> {
+    let nav = goto_definition(db, position)?.info.into_iter().find(|it| it.kind() == FN_DEF)?;
+    let parse = db.parse(nav.file_id());
+    let fn_def = find_node_at_offset::<ast::FnDef>(parse.tree().syntax(), nav.range().start())?;
+    hir::Function::from_source(db, hir::Source { file_id: nav.file_id().into(), ast: fn_def })
+}
+
+fn is_import(db: &RootDatabase, reference: FileRange) -> bool {
+    let parse = db.parse(reference.file_id);
+    match find_node_at_offset::<ast::NameRef>(parse.tree().syntax(), reference.range.start()) {
+        Some(name_ref) => name_ref.syntax().ancestors().any(|it| it.kind() == USE_TREE),
+        None => false,
+    }
+}
+
+/// A call of the function, whose arguments are updated.
+struct Call {
+    file_id: FileId,
+    args: CallArgs,
+    /// The number of arguments before the ones matching the parameters: `1`
+    /// for the receiver of a call like `Foo::bar(foo)`.
+    skip: usize,
+}
+
+enum CallArgs {
+    List(ast::ArgList),
+    /// The arguments of a call inside of a macro call, like `(a, b)` in
+    /// `vec![foo(a, b)]`.
+    TokenTree(SyntaxNode),
+}
+
+impl Call {
+    /// Finds the call at a reference to the function. Fails for references
+    /// which aren't calls, like the function passed as a value.
+    fn at(db: &RootDatabase, reference: FileRange, has_self: bool) -> Option<Call> {
+        let parse = db.parse(reference.file_id);
+        let file = parse.tree();
+        let self_args = if has_self { 1 } else { 0 };
+        let offset = reference.range.start();
+        let (args, skip) = match find_node_at_offset::<ast::NameRef>(file.syntax(), offset) {
+            Some(name_ref) => {
+                let parent = name_ref.syntax().parent()?;
+                if let Some(call) = ast::MethodCallExpr::cast(parent.clone()) {
+                    (CallArgs::List(call.arg_list()?), 0)
+                } else {
+                    let path = ast::PathSegment::cast(parent)?.parent_path();
+                    let path_expr = ast::PathExpr::cast(path.syntax().parent()?)?;
+                    let call = ast::CallExpr::cast(path_expr.syntax().parent()?)?;
+                    // The function could be an argument of the call
+                    if call.expr()?.syntax() != path_expr.syntax() {
+                        return None;
+                    }
+                    (CallArgs::List(call.arg_list()?), self_args)
+                }
+            }
+            None => {
+                let token = file.syntax().token_at_offset(offset).right_biased()?;
+                if token.parent().kind() != TOKEN_TREE {
+                    return None;
+                }
+                let args =
+                    non_trivia_sibling(token.clone().into(), Direction::Next)?.into_node()?;
+                if args.kind() != TOKEN_TREE || args.first_token()?.kind() != T!['('] {
+                    return None;
+                }
+                let is_method_call = non_trivia_sibling(token.into(), Direction::Prev)
+                    .map_or(false, |it| it.kind() == T![.]);
+                (CallArgs::TokenTree(args), if is_method_call { 0 } else { self_args })
+            }
+        };
+        Some(Call { file_id: reference.file_id, args, skip })
+    }
+
+    fn range(&self) -> TextRange {
+        match &self.args {
+            CallArgs::List(it) => it.syntax().text_range(),
+            CallArgs::TokenTree(it) => it.text_range(),
+        }
+    }
+
+    /// The new text of the arguments. `arg_text` gives the text of an old
+    /// argument, with the calls nested inside of it already updated.
+    fn rewrite(
+        &self,
+        params: &[NewParam],
+        arg_text: impl Fn(TextRange) -> String,
+    ) -> Option<String> {
+        match &self.args {
+            CallArgs::List(list) => {
+                let old_args = list.args().skip(self.skip).collect::<Vec<_>>();
+                if !keeps_side_effects(&old_args, params) {
+                    return None;
+                }
+                let args = list
+                    .args()
+                    .map(|arg| {
+                        let text = arg_text(arg.syntax().text_range());
+                        if arg.syntax().text() == text.as_str() {
+                            Some(arg)
+                        } else {
+                            make::expr_from_text(&text)
+                        }
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+                let args = reorder_args(args, self.skip, params, |default| default.clone())?;
+                Some(list.with_args(args.into_iter()).syntax().text().to_string())
+            }
+            CallArgs::TokenTree(tree) => {
+                let args = split_token_tree(tree, |_, _| true)
+                    .into_iter()
+                    .map(arg_text)
+                    .collect::<Vec<_>>();
+                let old_args = args
+                    .iter()
+                    .skip(self.skip)
+                    .map(|it| make::expr_from_text(it))
+                    .collect::<Option<Vec<_>>>()?;
+                if !keeps_side_effects(&old_args, params) {
+                    return None;
+                }
+                let args = reorder_args(args, self.skip, params, |default| {
+                    default.syntax().text().to_string()
+                })?;
+                Some(format!("({})", args.join(", ")))
+            }
+        }
+    }
+}
+
+/// Whether the arguments with side effects are all kept, in the same order, as
+/// reordering them would change the order in which they are evaluated, and
+/// dropping them would drop their side effects.
+fn keeps_side_effects(old_args: &[ast::Expr], params: &[NewParam]) -> bool {
+    let is_effectful = |idx: &usize| old_args.get(*idx).map_or(false, |it| !is_pure(it));
+    let kept = params
+        .iter()
+        .filter_map(|param| match param {
+            NewParam::Existing(idx) => Some(*idx),
+            NewParam::Added(..) => None,
+        })
+        .filter(is_effectful);
+    kept.eq((0..old_args.len()).filter(is_effectful))
+}
+
+/// Whether evaluating `expr` has no side effects, like reading a variable.
+/// Calls and blocks are assumed to have some, as are overloaded operators.
+fn is_pure(expr: &ast::Expr) -> bool {
+    let is_pure_opt = |expr: Option<ast::Expr>| expr.map_or(false, |it| is_pure(&it));
+    match expr {
+        ast::Expr::Literal(_) | ast::Expr::PathExpr(_) => true,
+        ast::Expr::ParenExpr(it) => is_pure_opt(it.expr()),
+        ast::Expr::RefExpr(it) => is_pure_opt(it.expr()),
+        ast::Expr::FieldExpr(it) => is_pure_opt(it.expr()),
+        ast::Expr::TupleExpr(it) => it.exprs().all(|it| is_pure(&it)),
+        _ => false,
+    }
+}
+
+/// Puts `args` in the order of the new parameters, after the first `skip`
+/// ones which are kept.
+fn reorder_args<T: Clone>(
+    args: Vec<T>,
+    skip: usize,
+    params: &[NewParam],
+    default: impl Fn(&ast::Expr) -> T,
+) -> Option<Vec<T>> {
+    if args.len() < skip {
+        return None;
+    }
+    let (kept, old) = args.split_at(skip);
+    let new = params.iter().map(|param| match param {
+        NewParam::Existing(idx) => old.get(*idx).cloned(),
+        NewParam::Added(_, it) => Some(default(it)),
+    });
+    kept.iter().cloned().map(Some).chain(new).collect()
+}
+
+/// Computes the new arguments of `calls`, which are all in the file with
+/// `text`. Calls can be nested in the arguments of other calls, so the inner
+/// calls are updated first, and their new text becomes part of the outer
+/// ones.
+fn rewrite_calls(
+    text: &str,
+    mut calls: Vec<Call>,
+    params: &[NewParam],
+) -> Option<Vec<(TextRange, String)>> {
+    calls.sort_by_key(|it| it.range().len());
+    let mut done: Vec<(TextRange, String)> = Vec::new();
+    for call in calls {
+        let range = call.range();
+        if done.iter().any(|(it, _)| *it == range) {
+            continue;
+        }
+        let new_text = call.rewrite(params, |range| splice(text, range, &done))?;
+        done.push((range, new_text));
+    }
+    let is_nested =
+        |range: TextRange| done.iter().any(|(it, _)| *it != range && range.is_subrange(it));
+    Some(done.iter().filter(|(range, _)| !is_nested(*range)).cloned().collect())
+}
+
+/// The text of `range`, with the `edits` inside of it applied.
+fn splice(text: &str, range: TextRange, edits: &[(TextRange, String)]) -> String {
+    let mut inner = edits
+        .iter()
+        .filter(|(it, _)| it.is_subrange(&range))
+        .filter(|(it, _)| {
+            !edits
+                .iter()
+                .any(|(other, _)| other != it && it.is_subrange(other) && other.is_subrange(&range))
+        })
+        .collect::<Vec<_>>();
+    inner.sort_by_key(|(it, _)| it.start());
+
+    let mut res = String::new();
+    let mut offset = range.start();
+    for (it, new_text) in inner {
+        res.push_str(&text[offset.to_usize()..it.start().to_usize()]);
+        res.push_str(new_text);
+        offset = it.end();
+    }
+    res.push_str(&text[offset.to_usize()..range.end().to_usize()]);
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use test_utils::assert_eq_text;
+
+    use crate::mock_analysis::{analysis_and_position, single_file_with_position};
+
+    use super::*;
+
+    fn existing(idx: usize) -> SignatureParam {
+        SignatureParam::Existing(idx)
+    }
+
+    fn added(param: &str, default: &str) -> SignatureParam {
+        SignatureParam::Added { param: param.to_string(), default: default.to_string() }
+    }
+
+    fn check_change_signature(before: &str, params: &[SignatureParam], after: &str) {
+        let (analysis, position) = single_file_with_position(before);
+        let source_change = analysis.change_signature(position, params).unwrap().unwrap();
+        assert_eq!(source_change.source_file_edits.len(), 1);
+        let edit = &source_change.source_file_edits[0];
+        let actual = edit.edit.apply(&analysis.file_text(edit.file_id).unwrap());
+        assert_eq_text!(after, &actual);
+    }
+
+    fn check_not_applicable(before: &str, params: &[SignatureParam]) {
+        let (analysis, position) = single_file_with_position(before);
+        assert!(analysis.change_signature(position, params).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_reorder_params() {
+        check_change_signature(
+            r#"
+fn foo<|>(a: u32, b: &str) {}
+fn main() {
+    foo(1, "x");
+    foo(2, &format!("{}", 4));
+}
+"#,
+            &[existing(1), existing(0)],
+            r#"
+fn foo(b: &str, a: u32) {}
+fn main() {
+    foo("x", 1);
+    foo(&format!("{}", 4), 2);
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn test_add_and_remove_params() {
+        check_change_signature(
+            r#"
+fn foo(a: u32, b: u32) {}
+fn main() {
+    fo<|>o(1, 2);
+}
+"#,
+            &[existing(1), added("verbose: bool", "false")],
+            r#"
+fn foo(b: u32, verbose: bool) {}
+fn main() {
+    foo(2, false);
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn test_add_first_param() {
+        check_change_signature(
+            r#"
+fn foo<|>() {}
+fn main() {
+    foo();
+}
+"#,
+            &[added("x: u32", "92")],
+            r#"
+fn foo(x: u32) {}
+fn main() {
+    foo(92);
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn test_method_and_ufcs_calls() {
+        check_change_signature(
+            r#"
+struct S;
+impl S {
+    fn foo<|>(&self, a: u32, b: u32) {}
+}
+fn main() {
+    S.foo(1, 2);
+    S::foo(&S, 1, 2);
+}
+"#,
+            &[existing(1), existing(0)],
+            r#"
+struct S;
+impl S {
+    fn foo(&self, b: u32, a: u32) {}
+}
+fn main() {
+    S.foo(2, 1);
+    S::foo(&S, 2, 1);
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn test_nested_calls() {
+        check_change_signature(
+            r#"
+fn foo<|>(a: u32, b: u32) -> u32 { a }
+fn main() {
+    foo(foo(1, 2), 3);
+    foo(4, foo(5, foo(6, 7)));
+}
+"#,
+            &[existing(1), existing(0)],
+            r#"
+fn foo(b: u32, a: u32) -> u32 { a }
+fn main() {
+    foo(3, foo(2, 1));
+    foo(foo(foo(7, 6), 5), 4);
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn test_call_in_macro_call() {
+        check_change_signature(
+            r#"
+macro_rules! id { ($($tt:tt)*) => { $($tt)* }; }
+fn foo<|>(a: u32, b: (u32, u32)) {}
+fn main() {
+    id!(foo(1, (2, 3)));
+}
+"#,
+            &[existing(1), existing(0)],
+            r#"
+macro_rules! id { ($($tt:tt)*) => { $($tt)* }; }
+fn foo(b: (u32, u32), a: u32) {}
+fn main() {
+    id!(foo((2, 3), 1));
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn test_multiline_lists() {
+        check_change_signature(
+            r#"
+fn foo<|>(
+    a: u32,
+    b: u32,
+) {}
+fn main() {
+    foo(
+        1,
+        2,
+    );
+}
+"#,
+            &[existing(1), added("c: u32", "3")],
+            r#"
+fn foo(
+    b: u32,
+    c: u32,
+) {}
+fn main() {
+    foo(
+        2,
+        3,
+    );
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn test_trait_method_and_impls() {
+        check_change_signature(
+            r#"
+trait Tr {
+    fn foo(&self, a: u32, b: u32);
+}
+struct S;
+impl Tr for S {
+    fn foo(&self, a: u32, b: u32) {}
+}
+fn main() {
+    S.fo<|>o(1, 2);
+    Tr::foo(&S, 1, 2);
+}
+"#,
+            &[existing(1)],
+            r#"
+trait Tr {
+    fn foo(&self, b: u32);
+}
+struct S;
+impl Tr for S {
+    fn foo(&self, b: u32) {}
+}
+fn main() {
+    S.foo(2);
+    Tr::foo(&S, 2);
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn test_calls_in_other_files() {
+        let (analysis, position) = analysis_and_position(
+            "
+            //- /lib.rs
+            mod bar;
+            pub fn foo<|>(a: u32, b: u32) {}
+
+            //- /bar.rs
+            use crate::foo;
+            fn main() { foo(1, 2); }
+            ",
+        );
+        let source_change =
+            analysis.change_signature(position, &[existing(1), existing(0)]).unwrap().unwrap();
+        let actual = source_change
+            .source_file_edits
+            .iter()
+            .map(|edit| edit.edit.apply(&analysis.file_text(edit.file_id).unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(actual.len(), 2);
+        assert!(actual[0].contains("pub fn foo(b: u32, a: u32) {}"));
+        assert!(actual[1].contains("fn main() { foo(2, 1); }"));
+    }
+
+    #[test]
+    fn test_not_applicable_to_fn_used_as_value() {
+        check_not_applicable(
+            r#"
+fn foo<|>(a: u32, b: u32) {}
+fn main() {
+    let f = foo;
+}
+"#,
+            &[existing(1), existing(0)],
+        );
+    }
+
+    #[test]
+    fn test_not_applicable_if_side_effects_change() {
+        let before = r#"
+fn a() -> u32 { 1 }
+fn foo<|>(x: u32, y: u32) {}
+fn main() {
+    foo(a(), a());
+}
+"#;
+        check_not_applicable(before, &[existing(1), existing(0)]);
+        check_not_applicable(before, &[existing(0)]);
+        check_not_applicable(before, &[existing(0), existing(0), existing(1)]);
+    }
+
+    #[test]
+    fn test_not_applicable_to_invalid_params() {
+        check_not_applicable(
+            r#"
+fn foo<|>(a: u32) {}
+"#,
+            &[existing(1)],
+        );
+        check_not_applicable(
+            r#"
+fn foo<|>(a: u32) {}
+"#,
+            &[added("b: u32, c: u32", "1")],
+        );
+        check_not_applicable(
+            r#"
+fn foo<|>(a: u32) {}
+"#,
+            &[added("b: u32", "1 +")],
+        );
+    }
+}
@@ -44,7 +44,11 @@ fn complete_fields(acc: &mut Completions, ctx: &CompletionContext, receiver: Ty)
                         acc.add_field(ctx, field, &a_ty.parameters);
                     }
                 }
-                // FIXME unions
+                TypeCtor::Adt(Adt::Union(u)) => {
+                    for field in u.fields(ctx.db) {
+                        acc.add_field(ctx, field, &a_ty.parameters);
+                    }
+                }
                 TypeCtor::Tuple { .. } => {
                     for (i, ty) in a_ty.parameters.iter().enumerate() {
                         acc.add_tuple_field(ctx, i, ty);
@@ -102,6 +106,40 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_union_field_completion() {
+        assert_debug_snapshot!(
+        do_ref_completion(
+                r"
+                union U { a: u32, b: f32 }
+                fn foo(u: U) {
+                u.<|>
+                }
+                ",
+        ),
+            @r###"
+       ⋮[
+       ⋮    CompletionItem {
+       ⋮        label: "a",
+       ⋮        source_range: [93; 93),
+       ⋮        delete: [93; 93),
+       ⋮        insert: "a",
+       ⋮        kind: Field,
+       ⋮        detail: "u32",
+       ⋮    },
+       ⋮    CompletionItem {
+       ⋮        label: "b",
+       ⋮        source_range: [93; 93),
+       ⋮        delete: [93; 93),
+       ⋮        insert: "b",
+       ⋮        kind: Field,
+       ⋮        detail: "f32",
+       ⋮    },
+       ⋮]
+        "###
+        );
+    }
+
     #[test]
     fn test_struct_field_completion_self() {
         assert_debug_snapshot!(
@@ -81,6 +81,38 @@ mod tests {
         "###);
     }
 
+    #[test]
+    fn test_record_literal_union() {
+        let completions = complete(
+            r"
+            union U { a: u32, b: f32 }
+            fn foo() {
+                let _ = U { <|> }
+            }
+            ",
+        );
+        assert_debug_snapshot!(completions, @r###"
+       ⋮[
+       ⋮    CompletionItem {
+       ⋮        label: "a",
+       ⋮        source_range: [91; 91),
+       ⋮        delete: [91; 91),
+       ⋮        insert: "a",
+       ⋮        kind: Field,
+       ⋮        detail: "u32",
+       ⋮    },
+       ⋮    CompletionItem {
+       ⋮        label: "b",
+       ⋮        source_range: [91; 91),
+       ⋮        delete: [91; 91),
+       ⋮        insert: "b",
+       ⋮        kind: Field,
+       ⋮        detail: "f32",
+       ⋮    },
+       ⋮]
+        "###);
+    }
+
     #[test]
     fn test_record_literal_two_structs() {
         let completions = complete(
@@ -113,9 +113,8 @@ impl Completions {
         name: Option<String>,
         macro_: hir::MacroDef,
     ) {
-        let ast_node = macro_.source(ctx.db).ast;
         if let Some(name) = name {
-            let detail = macro_label(&ast_node);
+            let detail = macro_.source(ctx.db).map(|src| macro_label(&src.ast));
 
             let macro_braces_to_insert = match name.as_str() {
                 "vec" => "[$0]",
@@ -130,7 +129,7 @@ impl Completions {
             )
             .kind(CompletionItemKind::Macro)
             .set_documentation(macro_.docs(ctx.db))
-            .detail(detail)
+            .set_detail(detail)
             .insert_snippet(macro_declaration + macro_braces_to_insert);
 
             self.add(builder);
@@ -1,11 +1,12 @@
 //! FIXME: write short doc here
 
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use ra_db::{
     salsa::{self, Database, Durability},
-    Canceled, CheckCanceled, CrateId, FileId, SourceDatabase, SourceRootId,
+    Canceled, CheckCanceled, CrateId, FileId, FileReparses, SourceDatabase, SourceRootId,
 };
+use ra_syntax::{Parse, SourceFile};
 use rustc_hash::FxHashMap;
 
 use crate::{
@@ -27,6 +28,9 @@ pub(crate) struct RootDatabase {
     runtime: salsa::Runtime<RootDatabase>,
     pub(crate) feature_flags: Arc<FeatureFlags>,
     pub(crate) debug_data: Arc<DebugData>,
+    /// Files reparsed incrementally by the last change, with the text they
+    /// were reparsed into.
+    pub(crate) reparses: Arc<Mutex<FxHashMap<FileId, (Arc<String>, Parse<SourceFile>)>>>,
     pub(crate) last_gc: crate::wasm_shims::Instant,
     pub(crate) last_gc_check: crate::wasm_shims::Instant,
 }
@@ -43,6 +47,18 @@ impl hir::debug::HirDebugHelper for RootDatabase {
     }
 }
 
+impl FileReparses for RootDatabase {
+    fn take_reparse(&self, file_id: FileId, text: &Arc<String>) -> Option<Parse<SourceFile>> {
+        let (reparsed_text, parse) = self.reparses.lock().unwrap().remove(&file_id)?;
+        // Compare the `Arc`s, not the texts, which would be as slow as parsing
+        if Arc::ptr_eq(&reparsed_text, text) {
+            Some(parse)
+        } else {
+            None
+        }
+    }
+}
+
 impl salsa::Database for RootDatabase {
     fn salsa_runtime(&self) -> &salsa::Runtime<RootDatabase> {
         &self.runtime
@@ -75,6 +91,7 @@ impl RootDatabase {
             last_gc_check: crate::wasm_shims::Instant::now(),
             feature_flags: Arc::new(feature_flags),
             debug_data: Default::default(),
+            reparses: Default::default(),
         };
         db.set_crate_graph_with_durability(Default::default(), Durability::HIGH);
         db.set_local_roots_with_durability(Default::default(), Durability::HIGH);
@@ -95,6 +112,7 @@ impl salsa::ParallelDatabase for RootDatabase {
             last_gc_check: self.last_gc_check,
             feature_flags: Arc::clone(&self.feature_flags),
             debug_data: Arc::clone(&self.debug_data),
+            reparses: Arc::clone(&self.reparses),
         })
     }
 }
@@ -88,6 +88,20 @@ pub(crate) fn diagnostics(db: &RootDatabase, file_id: FileId) -> Vec<Diagnostic>
             severity: Severity::Error,
             fix: Some(fix),
         })
+    })
+    .on::<hir::diagnostics::TrivialCast, _>(|d| {
+        let node = d.ast(db);
+        let fix = node.expr().map(|expr| {
+            let mut builder = TextEditBuilder::default();
+            builder.replace(node.syntax().text_range(), expr.syntax().text().to_string());
+            SourceChange::source_file_edit_from("remove redundant cast", file_id, builder.finish())
+        });
+        res.borrow_mut().push(Diagnostic {
+            range: d.highlight_range(),
+            message: d.message(),
+            severity: Severity::WeakWarning,
+            fix,
+        })
     });
     let source_file = db.parse(file_id).tree();
     let src =
@@ -518,6 +532,121 @@ mod tests {
         check_no_diagnostic(content);
     }
 
+    #[test]
+    fn test_union_literal_with_one_field() {
+        let content = r"
+            union TestUnion {
+                one: i32,
+                two: f32,
+            }
+
+            fn test_fn() {
+                let u = TestUnion { two: 1.0 };
+            }
+        ";
+
+        check_no_diagnostic(content);
+    }
+
+    #[test]
+    fn test_union_literal_field_count() {
+        let (analysis, file_id) = single_file(
+            r"
+            union TestUnion {
+                one: i32,
+                two: f32,
+            }
+
+            fn test_fn() {
+                let u = TestUnion { one: 1, two: 1.0 };
+                let v = TestUnion {};
+            }
+            ",
+        );
+        let diagnostics = analysis.diagnostics(file_id).unwrap();
+        assert_eq!(diagnostics.len(), 2);
+        for diagnostic in diagnostics {
+            assert_eq!(diagnostic.message, "union expressions should have exactly one field");
+        }
+    }
+
+    #[test]
+    fn test_missing_match_arms() {
+        let (analysis, file_id) = single_file(
+            r"
+            enum Direction { North, East, South, West }
+
+            fn test_fn(dir: &Direction) {
+                match dir {
+                    Direction::North => {}
+                    Direction::South => {}
+                }
+            }
+            ",
+        );
+        let diagnostics = analysis.diagnostics(file_id).unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "missing match arms: `&Direction::East`, `&Direction::West` not covered"
+        );
+    }
+
+    #[test]
+    fn test_no_diagnostic_for_exhaustive_match() {
+        check_no_diagnostic(
+            r"
+            enum Direction { North, East, South, West }
+
+            fn test_fn(dir: Direction, opt: Option<u8>) {
+                match dir {
+                    Direction::North | Direction::South => {}
+                    _ => {}
+                }
+                match (dir, true) {
+                    (Direction::North, _) => {}
+                    (_, false) => {}
+                    (Direction::East, true) | (Direction::South, true) | (Direction::West, true) => {}
+                }
+                match opt {
+                    Some(x) if x > 0 => {}
+                    _ => {}
+                }
+            }
+            ",
+        );
+    }
+
+    #[test]
+    fn test_remove_trivial_cast() {
+        let before = r"
+            fn test_fn(x: i32) -> i64 {
+                (x as i32 + 1) as i64
+            }
+        ";
+        let after = r"
+            fn test_fn(x: i32) -> i64 {
+                (x + 1) as i64
+            }
+        ";
+        check_apply_diagnostic_fix(before, after);
+    }
+
+    #[test]
+    fn test_no_diagnostic_for_valid_casts() {
+        let content = r"
+            enum E { A, B }
+            fn test_fn(x: i32, r: &i32) {
+                let a = x as u8;
+                let b = E::B as i32;
+                let c = r as *const i32 as usize;
+                let d = 1 as u64;
+            }
+        ";
+
+        check_no_diagnostic(content);
+    }
+
     #[test]
     fn test_unresolved_module_diagnostic() {
         let (analysis, file_id) = single_file("mod foo;");
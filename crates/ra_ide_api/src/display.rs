@@ -8,10 +8,12 @@ mod short_label;
 
 use ra_syntax::{
     ast::{self, AstNode, AttrsOwner, NameOwner, TypeParamsOwner},
-    SyntaxKind::{ATTR, COMMENT},
+    SyntaxElement,
+    SyntaxKind::{ATTR, COMMA, COMMENT},
+    SyntaxNode, TextRange,
 };
 
-pub use function_signature::FunctionSignature;
+pub use function_signature::{CallableKind, FunctionSignature};
 pub use navigation_target::NavigationTarget;
 pub use structure::{file_structure, StructureNode};
 
@@ -61,6 +63,38 @@ pub(crate) fn where_predicates<N: TypeParamsOwner>(node: &N) -> Vec<String> {
     res
 }
 
+/// Splits a token tree like `(a, (b, c))` on its top-level commas, returning
+/// the range of each part. `is_separator` is given the elements between the
+/// delimiters and the index of a comma, and decides if it separates two parts.
+pub(crate) fn split_token_tree(
+    tree: &SyntaxNode,
+    is_separator: impl Fn(&[SyntaxElement], usize) -> bool,
+) -> Vec<TextRange> {
+    let elements: Vec<_> =
+        tree.children_with_tokens().filter(|it| !it.kind().is_trivia()).collect();
+    // Skip the delimiters
+    let elements = match elements.len() {
+        0..=2 => return vec![],
+        len => &elements[1..len - 1],
+    };
+
+    let mut res = vec![];
+    let mut current: Option<TextRange> = None;
+    for (idx, element) in elements.iter().enumerate() {
+        if element.kind() == COMMA && is_separator(elements, idx) {
+            res.extend(current.take());
+            continue;
+        }
+        let range = element.text_range();
+        current = Some(match current {
+            Some(current) => TextRange::from_to(current.start(), range.end()),
+            None => range,
+        });
+    }
+    res.extend(current);
+    res
+}
+
 pub(crate) fn macro_label(node: &ast::MacroCall) -> String {
     let name = node.name().map(|name| name.syntax().text().to_string()).unwrap_or_default();
     let vis = if node.has_atom_attr("macro_export") { "#[macro_export]\n" } else { "" };
@@ -4,17 +4,36 @@ use std::fmt::{self, Display};
 
 use hir::{Docs, Documentation, HasSource};
 use join_to_string::join;
-use ra_syntax::ast::{self, AstNode, NameOwner, VisibilityOwner};
+use ra_syntax::{
+    ast::{self, AstNode, NameOwner, VisibilityOwner},
+    SyntaxElement,
+    SyntaxKind::TOKEN_TREE,
+    SyntaxNode, TextRange, T,
+};
 use std::convert::From;
 
 use crate::{
     db,
-    display::{generic_parameters, where_predicates},
+    display::{generic_parameters, split_token_tree, where_predicates},
 };
 
+/// What kind of callable a signature belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallableKind {
+    Function,
+    /// A tuple struct, called like `Point(1, 2)`.
+    StructConstructor,
+    /// A tuple variant, called like `Some(1)`.
+    VariantConstructor,
+    Macro,
+    /// The generic parameters of a function, given with `foo::<u32>`.
+    Turbofish,
+}
+
 /// Contains information about a function signature
 #[derive(Debug)]
 pub struct FunctionSignature {
+    pub kind: CallableKind,
     /// Optional visibility
     pub visibility: Option<String>,
     /// Name of the function
@@ -42,6 +61,124 @@ impl FunctionSignature {
         let ast_node = function.source(db).ast;
         FunctionSignature::from(&ast_node).with_doc_opt(doc)
     }
+
+    /// The signature of the constructor of a tuple struct.
+    pub(crate) fn from_struct(db: &db::RootDatabase, st: hir::Struct) -> Option<Self> {
+        let node = st.source(db).ast;
+        let fields = match node.kind() {
+            ast::StructKind::Tuple(fields) => fields,
+            _ => return None,
+        };
+        let sig = FunctionSignature {
+            kind: CallableKind::StructConstructor,
+            visibility: node.visibility().map(|n| n.syntax().text().to_string()),
+            name: node.name().map(|n| n.text().to_string()),
+            ret_type: None,
+            parameters: tuple_field_types(&fields),
+            generic_parameters: generic_parameters(&node),
+            where_predicates: where_predicates(&node),
+            doc: None,
+        };
+        Some(sig.with_doc_opt(st.docs(db)))
+    }
+
+    /// The signature of the constructor of a tuple variant.
+    pub(crate) fn from_enum_variant(
+        db: &db::RootDatabase,
+        variant: hir::EnumVariant,
+    ) -> Option<Self> {
+        let node = variant.source(db).ast;
+        let fields = match node.kind() {
+            ast::StructKind::Tuple(fields) => fields,
+            _ => return None,
+        };
+        let parent_name = variant.parent_enum(db).name(db).map(|it| it.to_string());
+        let name = match (parent_name, node.name()) {
+            (Some(parent), Some(name)) => Some(format!("{}::{}", parent, name.text())),
+            (None, name) => name.map(|it| it.text().to_string()),
+            (Some(_), None) => None,
+        };
+        let sig = FunctionSignature {
+            kind: CallableKind::VariantConstructor,
+            visibility: None,
+            name,
+            ret_type: None,
+            parameters: tuple_field_types(&fields),
+            generic_parameters: vec![],
+            where_predicates: vec![],
+            doc: None,
+        };
+        Some(sig.with_doc_opt(variant.docs(db)))
+    }
+
+    /// The signature of a `macro_rules!` macro. The parameters are the
+    /// fragments of the matcher if the macro has a single rule.
+    pub(crate) fn from_macro(db: &db::RootDatabase, macro_: hir::MacroDef) -> Option<Self> {
+        let node = macro_.source(db)?.ast;
+        let sig = FunctionSignature {
+            kind: CallableKind::Macro,
+            visibility: None,
+            name: node.name().map(|n| n.text().to_string()),
+            ret_type: None,
+            parameters: macro_params(&node),
+            generic_parameters: vec![],
+            where_predicates: vec![],
+            doc: None,
+        };
+        Some(sig.with_doc_opt(macro_.docs(db)))
+    }
+
+    /// Turns the signature of a function into the signature of its turbofish,
+    /// whose parameters are the generic parameters of the function.
+    pub(crate) fn into_turbofish(self) -> Self {
+        FunctionSignature {
+            kind: CallableKind::Turbofish,
+            parameters: self.generic_parameters,
+            generic_parameters: vec![],
+            ret_type: None,
+            ..self
+        }
+    }
+}
+
+fn tuple_field_types(fields: &ast::TupleFieldDefList) -> Vec<String> {
+    fields
+        .fields()
+        .filter_map(|field| field.type_ref())
+        .map(|ty| ty.syntax().text().to_string())
+        .collect()
+}
+
+fn macro_params(node: &ast::MacroCall) -> Vec<String> {
+    let rules = match node.token_tree() {
+        Some(it) => it,
+        None => return vec![],
+    };
+    // Each rule is `(matcher) => {transcriber}`, so the matchers are every
+    // other token tree.
+    let mut matchers = rules.syntax().children().filter(|it| it.kind() == TOKEN_TREE).step_by(2);
+    let matcher = match (matchers.next(), matchers.next()) {
+        (Some(matcher), None) => matcher,
+        _ => return vec![],
+    };
+    split_matcher(&matcher)
+}
+
+/// Splits a matcher like `($a:expr, $($b:ident),*)` on the top-level commas,
+/// ignoring the separators of repetitions.
+fn split_matcher(matcher: &SyntaxNode) -> Vec<String> {
+    let text = matcher.text().to_string();
+    let base = matcher.text_range().start();
+    let slice = |range: TextRange| {
+        text[(range.start() - base).to_usize()..(range.end() - base).to_usize()].to_string()
+    };
+
+    let is_separator =
+        |elements: &[SyntaxElement], idx: usize| match elements.get(idx + 1).map(|it| it.kind()) {
+            Some(T![*]) | Some(T![+]) | Some(T![?]) => false,
+            _ => true,
+        };
+    split_token_tree(matcher, is_separator).into_iter().map(slice).collect()
 }
 
 impl From<&'_ ast::FnDef> for FunctionSignature {
@@ -59,6 +196,7 @@ impl From<&'_ ast::FnDef> for FunctionSignature {
         }
 
         FunctionSignature {
+            kind: CallableKind::Function,
             visibility: node.visibility().map(|n| n.syntax().text().to_string()),
             name: node.name().map(|n| n.text().to_string()),
             ret_type: node
@@ -81,7 +219,12 @@ impl Display for FunctionSignature {
         }
 
         if let Some(name) = &self.name {
-            write!(f, "fn {}", name)?;
+            match self.kind {
+                CallableKind::Function | CallableKind::Turbofish => write!(f, "fn {}", name)?,
+                CallableKind::StructConstructor => write!(f, "struct {}", name)?,
+                CallableKind::VariantConstructor => write!(f, "{}", name)?,
+                CallableKind::Macro => write!(f, "{}!", name)?,
+            }
         }
 
         if !self.generic_parameters.is_empty() {
@@ -91,7 +234,11 @@ impl Display for FunctionSignature {
                 .to_fmt(f)?;
         }
 
-        join(self.parameters.iter()).separator(", ").surround_with("(", ")").to_fmt(f)?;
+        let (open, close) = match self.kind {
+            CallableKind::Turbofish => ("<", ">"),
+            _ => ("(", ")"),
+        };
+        join(self.parameters.iter()).separator(", ").surround_with(open, close).to_fmt(f)?;
 
         if let Some(t) = &self.ret_type {
             write!(f, " -> {}", t)?;
@@ -234,15 +234,18 @@ impl NavigationTarget {
         }
     }
 
-    pub(crate) fn from_macro_def(db: &RootDatabase, macro_call: hir::MacroDef) -> NavigationTarget {
-        let src = macro_call.source(db);
+    pub(crate) fn from_macro_def(
+        db: &RootDatabase,
+        macro_call: hir::MacroDef,
+    ) -> Option<NavigationTarget> {
+        let src = macro_call.source(db)?;
         log::debug!("nav target {:#?}", src.ast.syntax());
-        NavigationTarget::from_named(
+        Some(NavigationTarget::from_named(
             src.file_id.original_file(db),
             &src.ast,
             src.ast.doc_comment_text(),
             None,
-        )
+        ))
     }
 
     #[cfg(test)]
@@ -0,0 +1,53 @@
+//! Looking through macro calls: tokens of a macro call argument are mapped to
+//! the tokens of the expansion, so that the expanded code can be analyzed.
+
+use std::iter::successors;
+
+use hir::Source;
+use ra_db::FileId;
+use ra_syntax::{ast, AstNode, SourceFile, SyntaxKind::IDENT, SyntaxToken, TextRange, TextUnit};
+
+use crate::db::RootDatabase;
+
+/// Maps `token` into the expansion of the macro call whose argument it is
+/// part of, and further into the expansions of nested macro calls. Returns
+/// `token` itself if it isn't part of a macro call argument.
+pub(crate) fn descend_into_macros(
+    db: &RootDatabase,
+    file_id: FileId,
+    token: SyntaxToken,
+) -> Source<SyntaxToken> {
+    let src = Source { file_id: file_id.into(), ast: token };
+    successors(Some(src), |token| {
+        let macro_call = token.ast.parent().ancestors().find_map(ast::MacroCall::cast)?;
+        let tt = macro_call.token_tree()?;
+        if !token.ast.text_range().is_subrange(&tt.syntax().text_range()) {
+            return None;
+        }
+        let analyzer =
+            hir::SourceAnalyzer::new_for_source(db, token.with_ast(macro_call.syntax()), None);
+        let expansion = analyzer.expand(db, &macro_call)?;
+        expansion.map_token_down(db, token.as_ref())
+    })
+    .last()
+    .unwrap()
+}
+
+/// Finds the `NameRef` an identifier at `offset` inside of a macro call
+/// argument is expanded to. Returns it together with the range of the
+/// identifier.
+pub(crate) fn find_name_ref_in_macro_call(
+    db: &RootDatabase,
+    file_id: FileId,
+    file: &SourceFile,
+    offset: TextUnit,
+) -> Option<(Source<ast::NameRef>, TextRange)> {
+    let token = file.syntax().token_at_offset(offset).find(|it| it.kind() == IDENT)?;
+    let range = token.text_range();
+    let token = descend_into_macros(db, file_id, token);
+    if token.file_id == file_id.into() {
+        return None;
+    }
+    let name_ref = ast::NameRef::cast(token.ast.parent())?;
+    Some((token.with_ast(name_ref), range))
+}
@@ -0,0 +1,363 @@
+//! Recursive expansion of the macro call under the cursor, pretty-printed as
+//! readable Rust code.
+
+use hir::{Source, SourceAnalyzer};
+use ra_db::FilePosition;
+use ra_syntax::{
+    algo::{find_node_at_offset, replace_descendants},
+    ast, AstNode, SyntaxElement, SyntaxKind,
+    SyntaxKind::*,
+    SyntaxNode, TextRange, T,
+};
+use rustc_hash::FxHashMap;
+
+use crate::db::RootDatabase;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ExpandedMacro {
+    pub name: String,
+    pub expansion: String,
+}
+
+pub(crate) fn expand_macro(db: &RootDatabase, position: FilePosition) -> Option<ExpandedMacro> {
+    let parse = db.parse(position.file_id);
+    let file = parse.tree();
+    let name_ref = find_node_at_offset::<ast::NameRef>(file.syntax(), position.offset)?;
+    let mac = name_ref.syntax().ancestors().find_map(ast::MacroCall::cast)?;
+    let name = mac.path()?.syntax().text().to_string();
+
+    let source = Source { file_id: position.file_id.into(), ast: mac.syntax() };
+    let mut budget = EXPANSION_LIMIT;
+    let expanded = expand_macro_recur(db, source, &mac, 0, &mut budget)?;
+
+    Some(ExpandedMacro { name, expansion: insert_whitespaces(expanded) })
+}
+
+/// The depth of nested expansions after which the remaining macro calls are
+/// left unexpanded, like rustc's default `recursion_limit`.
+const EXPANSION_DEPTH_LIMIT: usize = 64;
+
+/// The total number of expansions after which the remaining macro calls are
+/// left unexpanded: a macro expanding to several recursive calls would reach
+/// the depth limit only after an exponential number of expansions.
+const EXPANSION_LIMIT: usize = 1024;
+
+/// Expands `macro_call` and then, recursively, all macro calls in its
+/// expansion. `budget` is the number of expansions left.
+fn expand_macro_recur(
+    db: &RootDatabase,
+    source: Source<&SyntaxNode>,
+    macro_call: &ast::MacroCall,
+    depth: usize,
+    budget: &mut usize,
+) -> Option<SyntaxNode> {
+    if depth >= EXPANSION_DEPTH_LIMIT || *budget == 0 {
+        return None;
+    }
+    *budget -= 1;
+    let analyzer = SourceAnalyzer::new_for_source(db, source, None);
+    let file_id = analyzer.expand(db, macro_call)?.file_id();
+    let expanded = db.parse_or_expand(file_id)?;
+
+    let mut replaces: FxHashMap<SyntaxElement, SyntaxElement> = FxHashMap::default();
+    for child in expanded.descendants().filter_map(ast::MacroCall::cast) {
+        let source = Source { file_id, ast: child.syntax() };
+        let new_node = match expand_macro_recur(db, source, &child, depth + 1, budget) {
+            Some(it) => it,
+            None => continue,
+        };
+        // The expansion may consist of a single macro call.
+        if expanded == *child.syntax() {
+            return Some(new_node);
+        }
+        replaces.insert(child.syntax().clone().into(), new_node.into());
+    }
+
+    Some(replace_descendants(&expanded, &replaces))
+}
+
+/// Pretty-prints the tokens of `syn`: macro expansions carry no whitespace of
+/// their own, so line breaks and indentation are derived from the braces and
+/// semicolons. The `None`-delimited groups of the expansion, like the `$e` in
+/// `$e * 2`, are printed in parentheses.
+fn insert_whitespaces(syn: SyntaxNode) -> String {
+    let groups: Vec<TextRange> = syn
+        .descendants()
+        .filter_map(ast::Expr::cast)
+        .filter(is_group)
+        .map(|it| it.syntax().text_range())
+        .collect();
+    let mut res = String::new();
+    let mut indent = 0;
+    let mut last: Option<SyntaxKind> = None;
+    let mut tokens = syn
+        .descendants_with_tokens()
+        .filter_map(|it| it.into_token())
+        .filter(|it| !it.kind().is_trivia())
+        .peekable();
+
+    while let Some(token) = tokens.next() {
+        let kind = token.kind();
+        let next = tokens.peek().map(|it| it.kind());
+        let range = token.text_range();
+        for _ in groups.iter().filter(|it| it.start() == range.start()) {
+            res.push('(');
+        }
+        match kind {
+            T!['{'] if next == Some(T!['}']) => {
+                space(&mut res);
+                res.push('{');
+            }
+            T!['{'] => {
+                space(&mut res);
+                res.push('{');
+                indent += 1;
+                newline(&mut res, indent);
+            }
+            T!['}'] if last == Some(T!['{']) => res.push('}'),
+            T!['}'] => {
+                indent = indent.saturating_sub(1);
+                newline(&mut res, indent);
+                res.push('}');
+            }
+            T![;] => {
+                res.push(';');
+                if next.is_some() {
+                    newline(&mut res, indent);
+                }
+            }
+            T![,] | T![:] => {
+                res.push_str(token.text());
+                res.push(' ');
+            }
+            T![')'] | T![']'] => {
+                trim_end(&mut res);
+                res.push_str(token.text());
+            }
+            _ if token.parent().kind() == BIN_EXPR || [T![=], T![=>], T![->]].contains(&kind) => {
+                space(&mut res);
+                res.push_str(token.text());
+                res.push(' ');
+            }
+            _ => res.push_str(token.text()),
+        }
+        for _ in groups.iter().filter(|it| it.end() == range.end()) {
+            res.push(')');
+        }
+
+        match next {
+            Some(T![else]) if kind == T!['}'] => res.push(' '),
+            Some(next) if kind == T!['}'] && !next.is_punct() => newline(&mut res, indent),
+            Some(next) if is_text(kind) && is_text(next) => res.push(' '),
+            _ => (),
+        }
+        last = Some(kind);
+    }
+
+    return res;
+
+    fn is_group(expr: &ast::Expr) -> bool {
+        let parent = match expr.syntax().parent().and_then(ast::Expr::cast) {
+            Some(it) => it,
+            None => return false,
+        };
+        let is_first = parent.syntax().first_child().as_ref() == Some(expr.syntax());
+        let (prec, parent_prec) = (precedence(expr), precedence(&parent));
+        match parent {
+            // Assignments are right-associative and comparisons can't be chained
+            ast::Expr::BinExpr(_) if is_first => {
+                prec < parent_prec || (prec == parent_prec && (prec == 1 || prec == 5))
+            }
+            ast::Expr::BinExpr(_) => prec < parent_prec || (prec == parent_prec && prec != 1),
+            ast::Expr::RangeExpr(_) => prec <= parent_prec,
+            ast::Expr::CastExpr(_)
+            | ast::Expr::PrefixExpr(_)
+            | ast::Expr::RefExpr(_)
+            | ast::Expr::BoxExpr(_)
+            | ast::Expr::CallExpr(_)
+            | ast::Expr::MethodCallExpr(_)
+            | ast::Expr::FieldExpr(_)
+            | ast::Expr::IndexExpr(_)
+            | ast::Expr::TryExpr(_)
+            | ast::Expr::AwaitExpr(_) => is_first && prec < parent_prec,
+            _ => false,
+        }
+    }
+
+    /// How tightly `expr` binds its operands, from `return` and closures
+    /// extending as far as possible to atoms.
+    fn precedence(expr: &ast::Expr) -> u8 {
+        use ast::BinOp::*;
+        match expr {
+            ast::Expr::ReturnExpr(_) | ast::Expr::BreakExpr(_) | ast::Expr::LambdaExpr(_) => 0,
+            ast::Expr::BinExpr(it) => match it.op_kind() {
+                Some(BooleanOr) => 3,
+                Some(BooleanAnd) => 4,
+                Some(EqualityTest)
+                | Some(NegatedEqualityTest)
+                | Some(LesserEqualTest)
+                | Some(GreaterEqualTest)
+                | Some(LesserTest)
+                | Some(GreaterTest) => 5,
+                Some(BitwiseOr) => 6,
+                Some(BitwiseXor) => 7,
+                Some(BitwiseAnd) => 8,
+                Some(LeftShift) | Some(RightShift) => 9,
+                Some(Addition) | Some(Subtraction) => 10,
+                Some(Multiplication) | Some(Division) | Some(Remainder) => 11,
+                // Assignments
+                _ => 1,
+            },
+            ast::Expr::RangeExpr(_) => 2,
+            ast::Expr::CastExpr(_) => 12,
+            ast::Expr::PrefixExpr(_) | ast::Expr::RefExpr(_) | ast::Expr::BoxExpr(_) => 13,
+            ast::Expr::CallExpr(_)
+            | ast::Expr::MethodCallExpr(_)
+            | ast::Expr::FieldExpr(_)
+            | ast::Expr::IndexExpr(_)
+            | ast::Expr::TryExpr(_)
+            | ast::Expr::AwaitExpr(_) => 14,
+            _ => 15,
+        }
+    }
+
+    fn is_text(kind: SyntaxKind) -> bool {
+        kind.is_keyword() || kind.is_literal() || kind == IDENT || kind == LIFETIME
+    }
+
+    fn space(res: &mut String) {
+        if !res.is_empty() && !res.ends_with(char::is_whitespace) {
+            res.push(' ');
+        }
+    }
+
+    fn newline(res: &mut String, indent: usize) {
+        trim_end(res);
+        res.push('\n');
+        res.push_str(&"    ".repeat(indent));
+    }
+
+    fn trim_end(res: &mut String) {
+        let len = res.trim_end().len();
+        res.truncate(len);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mock_analysis::single_file_with_position;
+
+    fn check_expand_macro(fixture: &str, expected: (&str, &str)) {
+        let (analysis, pos) = single_file_with_position(fixture);
+        let result = analysis.expand_macro(pos).unwrap().unwrap();
+        assert_eq!(result.name, expected.0);
+        assert_eq!(result.expansion, expected.1);
+    }
+
+    #[test]
+    fn macro_expand_recursive_expansion() {
+        check_expand_macro(
+            r#"
+            macro_rules! bar {
+                () => { fn  b() {} }
+            }
+            macro_rules! foo {
+                () => { bar!(); }
+            }
+            f<|>oo!();
+            "#,
+            ("foo", "fn b() {}"),
+        );
+    }
+
+    #[test]
+    fn macro_expand_multiple_lines() {
+        check_expand_macro(
+            r#"
+            macro_rules! foo {
+                () => {
+                    fn some_thing() -> u32 {
+                        let a = 0;
+                        a + 10
+                    }
+                }
+            }
+            f<|>oo!();
+            "#,
+            ("foo", "fn some_thing() -> u32 {\n    let a = 0;\n    a + 10\n}"),
+        );
+    }
+
+    #[test]
+    fn macro_expand_nested_call_in_expression() {
+        check_expand_macro(
+            r#"
+            macro_rules! id {
+                ($($tt:tt)*) => { $($tt)* }
+            }
+            macro_rules! double {
+                ($e:expr) => { id!($e) * 2 }
+            }
+            fn main() {
+                let x = dou<|>ble!(1 + 1);
+            }
+            "#,
+            ("double", "(1 + 1) * 2"),
+        );
+    }
+
+    #[test]
+    fn macro_expand_groups_in_parentheses() {
+        check_expand_macro(
+            r#"
+            macro_rules! sub {
+                ($a:expr, $b:expr) => { -$a - $b }
+            }
+            fn main() {
+                let x = s<|>ub!(1 - 2, 3 - 4);
+            }
+            "#,
+            ("sub", "-(1 - 2) - (3 - 4)"),
+        );
+    }
+
+    #[test]
+    fn macro_expand_stops_at_recursion_limit() {
+        check_expand_macro(
+            r#"
+            macro_rules! foo {
+                () => { foo!(); }
+            }
+            f<|>oo!();
+            "#,
+            ("foo", "foo!();"),
+        );
+    }
+
+    #[test]
+    fn macro_expand_stops_after_expansion_limit() {
+        let (analysis, pos) = single_file_with_position(
+            r#"
+            macro_rules! foo {
+                () => { foo!(); foo!(); }
+            }
+            f<|>oo!();
+            "#,
+        );
+        let result = analysis.expand_macro(pos).unwrap().unwrap();
+        assert_eq!(result.expansion.matches("foo!();").count(), super::EXPANSION_LIMIT + 1);
+    }
+
+    #[test]
+    fn macro_expand_not_a_macro_call() {
+        let (analysis, pos) = single_file_with_position(
+            r#"
+            fn foo() {}
+            fn main() {
+                f<|>oo();
+            }
+            "#,
+        );
+        assert!(analysis.expand_macro(pos).unwrap().is_none());
+    }
+}
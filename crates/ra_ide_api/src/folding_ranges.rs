@@ -3,10 +3,10 @@
 use rustc_hash::FxHashSet;
 
 use ra_syntax::{
-    ast::{self, AstNode, AstToken, VisibilityOwner},
+    ast::{self, AstNode, AstToken, CommentKind, CommentShape, VisibilityOwner},
     Direction, NodeOrToken, SourceFile,
     SyntaxKind::{self, *},
-    SyntaxNode, TextRange,
+    SyntaxNode, TextRange, TextUnit,
 };
 
 #[derive(Debug, PartialEq, Eq)]
@@ -15,6 +15,14 @@ pub enum FoldKind {
     Imports,
     Mods,
     Block,
+    /// Argument and parameter lists.
+    ArgList,
+    MatchArm,
+    WhereClause,
+    /// A run of attributes on the same item.
+    Attrs,
+    /// Code between `// region: name` and `// endregion` comments.
+    Region,
 }
 
 #[derive(Debug)]
@@ -28,6 +36,9 @@ pub(crate) fn folding_ranges(file: &SourceFile) -> Vec<Fold> {
     let mut visited_comments = FxHashSet::default();
     let mut visited_imports = FxHashSet::default();
     let mut visited_mods = FxHashSet::default();
+    let mut visited_attrs = FxHashSet::default();
+    // Starts of the `// region:` markers which are not closed yet
+    let mut region_starts: Vec<TextUnit> = vec![];
 
     for element in file.syntax().descendants_with_tokens() {
         // Fold items that span multiple lines
@@ -46,6 +57,23 @@ pub(crate) fn folding_ranges(file: &SourceFile) -> Vec<Fold> {
             NodeOrToken::Token(token) => {
                 // Fold groups of comments
                 if let Some(comment) = ast::Comment::cast(token) {
+                    match region_marker(&comment) {
+                        Some(RegionMarker::Start) => {
+                            region_starts.push(comment.syntax().text_range().start());
+                            continue;
+                        }
+                        Some(RegionMarker::End) => {
+                            if let Some(start) = region_starts.pop() {
+                                let end = comment.syntax().text_range().end();
+                                res.push(Fold {
+                                    range: TextRange::from_to(start, end),
+                                    kind: FoldKind::Region,
+                                })
+                            }
+                            continue;
+                        }
+                        None => (),
+                    }
                     if !visited_comments.contains(&comment) {
                         if let Some(range) =
                             contiguous_range_for_comment(comment, &mut visited_comments)
@@ -72,10 +100,19 @@ pub(crate) fn folding_ranges(file: &SourceFile) -> Vec<Fold> {
                         res.push(Fold { range, kind: FoldKind::Mods })
                     }
                 }
+
+                // Fold groups of attributes
+                if node.kind() == ATTR && !visited_attrs.contains(&node) {
+                    if let Some(range) = contiguous_range_for_group(&node, &mut visited_attrs) {
+                        res.push(Fold { range, kind: FoldKind::Attrs })
+                    }
+                }
             }
         }
     }
 
+    // Regions are only known at their end marker, so restore the source order
+    res.sort_by_key(|fold| (fold.range.start(), fold.range.end()));
     res
 }
 
@@ -91,11 +128,41 @@ fn fold_kind(kind: SyntaxKind) -> Option<FoldKind> {
         | BLOCK
         | MATCH_ARM_LIST
         | ENUM_VARIANT_LIST
-        | TOKEN_TREE => Some(FoldKind::Block),
+        | RECORD_FIELD_LIST
+        | ARRAY_EXPR
+        | TOKEN_TREE
+        | STRING
+        | RAW_STRING
+        | BYTE_STRING
+        | RAW_BYTE_STRING => Some(FoldKind::Block),
+        ARG_LIST | PARAM_LIST => Some(FoldKind::ArgList),
+        MATCH_ARM => Some(FoldKind::MatchArm),
+        WHERE_CLAUSE => Some(FoldKind::WhereClause),
         _ => None,
     }
 }
 
+enum RegionMarker {
+    Start,
+    End,
+}
+
+/// Recognizes `// region: name` and `// endregion` comments, as used by
+/// VS Code for other languages.
+fn region_marker(comment: &ast::Comment) -> Option<RegionMarker> {
+    if comment.kind() != (CommentKind { shape: CommentShape::Line, doc: None }) {
+        return None;
+    }
+    let text = comment.text()[comment.prefix().len()..].trim();
+    if text.starts_with("endregion") {
+        Some(RegionMarker::End)
+    } else if text == "region" || text.starts_with("region:") {
+        Some(RegionMarker::Start)
+    } else {
+        None
+    }
+}
+
 fn has_visibility(node: &SyntaxNode) -> bool {
     ast::Module::cast(node.clone()).and_then(|m| m.visibility()).is_some()
 }
@@ -171,7 +238,7 @@ fn contiguous_range_for_comment(
                     }
                 }
                 if let Some(c) = ast::Comment::cast(token) {
-                    if c.kind() == group_kind {
+                    if c.kind() == group_kind && region_marker(&c).is_none() {
                         visited.insert(c.clone());
                         last = c;
                         continue;
@@ -375,4 +442,86 @@ fn main() <fold>{
         let folds = &[FoldKind::Block, FoldKind::Block];
         do_check(text, folds);
     }
+
+    #[test]
+    fn test_fold_multiline_match_arms() {
+        let text = r#"
+fn main() <fold>{
+    match foo <fold>{
+        <fold>Some(x) => <fold>{
+            x
+        }</fold></fold>,
+        <fold>None => Bar <fold>{
+            baz: 1,
+        }</fold></fold>,
+    }</fold>
+}</fold>"#;
+
+        let folds = &[
+            FoldKind::Block,
+            FoldKind::Block,
+            FoldKind::MatchArm,
+            FoldKind::Block,
+            FoldKind::MatchArm,
+            FoldKind::Block,
+        ];
+        do_check(text, folds);
+    }
+
+    #[test]
+    fn test_fold_arg_lists_and_where_clauses() {
+        let text = r#"
+fn foo<T><fold>(
+    a: u32,
+    b: T,
+)</fold> <fold>where
+    T: Clone,</fold>
+<fold>{
+    foo<fold>(
+        1,
+        <fold>[
+            2,
+        ]</fold>,
+    )</fold>;
+}</fold>"#;
+
+        let folds = &[
+            FoldKind::ArgList,
+            FoldKind::WhereClause,
+            FoldKind::Block,
+            FoldKind::ArgList,
+            FoldKind::Block,
+        ];
+        do_check(text, folds);
+    }
+
+    #[test]
+    fn test_fold_attrs_and_strings() {
+        let text = r#"
+<fold>#[derive(Debug)]
+#[cfg(test)]</fold>
+struct Foo;
+
+const S: &str = <fold>"multi
+line"</fold>;"#;
+
+        let folds = &[FoldKind::Attrs, FoldKind::Block];
+        do_check(text, folds);
+    }
+
+    #[test]
+    fn test_fold_regions() {
+        let text = r#"
+<fold>// region: state machine
+// A plain comment is not folded with the markers
+fn foo() <fold>{
+    <fold>// region
+    let x = 1;
+    // endregion</fold>
+}</fold>
+// endregion</fold>"#;
+
+        let folds = &[FoldKind::Region, FoldKind::Block, FoldKind::Region];
+        do_check(text, folds);
+    }
 }
@@ -0,0 +1,153 @@
+//! Range formatting shouldn't touch code outside of the selection, but
+//! `rustfmt` can't format an arbitrary piece of text. So we cut the selection
+//! down to the whole items and statements it contains.
+
+use ra_syntax::{
+    ast::{self, AstNode},
+    SourceFile, SyntaxKind, SyntaxNode, TextRange,
+};
+
+pub(crate) fn formatting_range(file: &SourceFile, range: TextRange) -> Option<TextRange> {
+    let mut acc = None;
+    collect_formatting_units(file.syntax(), range, &mut acc);
+    acc
+}
+
+fn collect_formatting_units(node: &SyntaxNode, range: TextRange, acc: &mut Option<TextRange>) {
+    for child in node.children() {
+        let child_range = child.text_range();
+        if child_range.intersection(&range).is_none() {
+            continue;
+        }
+        if is_formatting_unit(&child) && child_range.is_subrange(&range) {
+            *acc = Some(match *acc {
+                Some(acc) => TextRange::from_to(acc.start(), child_range.end()),
+                None => child_range,
+            });
+        } else {
+            collect_formatting_units(&child, range, acc);
+        }
+    }
+}
+
+fn is_formatting_unit(node: &SyntaxNode) -> bool {
+    let kind = node.kind();
+    if ast::ModuleItem::can_cast(kind) || ast::ImplItem::can_cast(kind) || ast::Stmt::can_cast(kind)
+    {
+        return true;
+    }
+    // The tail expression of a block
+    ast::Expr::can_cast(kind) && node.parent().map(|it| it.kind()) == Some(SyntaxKind::BLOCK)
+}
+
+#[cfg(test)]
+mod tests {
+    use test_utils::{assert_eq_text, extract_range};
+
+    use super::*;
+
+    fn check(before: &str, after: &str) {
+        let (range, before) = extract_range(before);
+        let file = SourceFile::parse(&before).tree();
+        let actual = match formatting_range(&file, range) {
+            Some(range) => format!(
+                "{}<|>{}<|>{}",
+                &before[..range.start().to_usize()],
+                &before[range.start().to_usize()..range.end().to_usize()],
+                &before[range.end().to_usize()..]
+            ),
+            None => before.clone(),
+        };
+        assert_eq_text!(after, &actual);
+    }
+
+    #[test]
+    fn formatting_range_whole_items() {
+        check(
+            r#"
+fn foo() {}
+<|>fn bar() {}
+struct S;<|>
+fn baz() {}
+"#,
+            r#"
+fn foo() {}
+<|>fn bar() {}
+struct S;<|>
+fn baz() {}
+"#,
+        );
+    }
+
+    #[test]
+    fn formatting_range_cuts_partial_items() {
+        check(
+            r#"
+fn foo() { 1<|> }
+fn bar() {}
+fn baz<|>() {}
+"#,
+            r#"
+fn foo() { 1 }
+<|>fn bar() {}<|>
+fn baz() {}
+"#,
+        );
+    }
+
+    #[test]
+    fn formatting_range_statements() {
+        check(
+            r#"
+fn foo() {
+    let x = <|>1;
+    let y = 2;
+    foo(x,    y);
+    x + y<|>
+}
+"#,
+            r#"
+fn foo() {
+    let x = 1;
+    <|>let y = 2;
+    foo(x,    y);
+    x + y<|>
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn formatting_range_impl_items() {
+        check(
+            r#"
+impl S {
+    fn foo() {}
+  <|>  fn bar() {}
+}<|>
+"#,
+            r#"
+impl S {
+    fn foo() {}
+    <|>fn bar() {}<|>
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn formatting_range_nothing_to_format() {
+        check(
+            r#"
+fn foo() {
+    let x = <|>1<|>;
+}
+"#,
+            r#"
+fn foo() {
+    let x = 1;
+}
+"#,
+        );
+    }
+}
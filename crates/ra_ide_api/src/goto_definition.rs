@@ -13,6 +13,7 @@ use ra_syntax::{
 use crate::{
     db::RootDatabase,
     display::ShortLabel,
+    expand::find_name_ref_in_macro_call,
     name_ref_kind::{classify_name_ref, NameRefKind::*},
     FilePosition, NavigationTarget, RangeInfo,
 };
@@ -24,13 +25,20 @@ pub(crate) fn goto_definition(
     let parse = db.parse(position.file_id);
     let syntax = parse.tree().syntax().clone();
     if let Some(name_ref) = find_node_at_offset::<ast::NameRef>(&syntax, position.offset) {
-        let navs = reference_definition(db, position.file_id, &name_ref).to_vec();
+        let src = hir::Source { file_id: position.file_id.into(), ast: &name_ref };
+        let navs = reference_definition(db, src).to_vec();
         return Some(RangeInfo::new(name_ref.syntax().text_range(), navs.to_vec()));
     }
     if let Some(name) = find_node_at_offset::<ast::Name>(&syntax, position.offset) {
         let navs = name_definition(db, position.file_id, &name)?;
         return Some(RangeInfo::new(name.syntax().text_range(), navs));
     }
+    if let Some((name_ref, range)) =
+        find_name_ref_in_macro_call(db, position.file_id, &parse.tree(), position.offset)
+    {
+        let navs = reference_definition(db, name_ref.as_ref()).to_vec();
+        return Some(RangeInfo::new(range, navs));
+    }
     None
 }
 
@@ -52,15 +60,22 @@ impl ReferenceResult {
 
 pub(crate) fn reference_definition(
     db: &RootDatabase,
-    file_id: FileId,
-    name_ref: &ast::NameRef,
+    name_ref: hir::Source<&ast::NameRef>,
 ) -> ReferenceResult {
     use self::ReferenceResult::*;
 
-    let analyzer = hir::SourceAnalyzer::new(db, file_id, name_ref.syntax(), None);
+    // Local bindings are never resolved into macro files, so their pointers
+    // always point into the original file.
+    let file_id = name_ref.file_id.original_file(db);
+    let analyzer =
+        hir::SourceAnalyzer::new_for_source(db, name_ref.with_ast(name_ref.ast.syntax()), None);
+    let name_ref = name_ref.ast;
 
     match classify_name_ref(db, &analyzer, name_ref) {
-        Some(Macro(mac)) => return Exact(NavigationTarget::from_macro_def(db, mac)),
+        Some(Macro(mac)) => match NavigationTarget::from_macro_def(db, mac) {
+            Some(nav) => return Exact(nav),
+            None => return Approximate(vec![]),
+        },
         Some(FieldAccess(field)) => return Exact(NavigationTarget::from_field(db, field)),
         Some(AssocItem(assoc)) => return Exact(NavigationTarget::from_assoc_item(db, assoc)),
         Some(Method(func)) => return Exact(NavigationTarget::from_def_source(db, func)),
@@ -312,6 +327,53 @@ mod tests {
         );
     }
 
+    #[test]
+    fn goto_definition_works_in_macro_call_arguments() {
+        check_goto(
+            "
+            //- /lib.rs
+            macro_rules! id {
+                ($($tt:tt)*) => { $($tt)* }
+            }
+            fn foo() {}
+            fn bar() {
+                id!(fo<|>o());
+            }
+            ",
+            "foo FN_DEF FileId(1) [52; 63) [55; 58)",
+        );
+    }
+
+    #[test]
+    fn goto_definition_works_for_fields_and_locals_in_macro_call_arguments() {
+        check_goto(
+            "
+            //- /lib.rs
+            macro_rules! id {
+                ($($tt:tt)*) => { $($tt)* }
+            }
+            struct Foo { bar: u32 }
+            fn baz(foo: Foo) {
+                id!(foo.b<|>ar);
+            }
+            ",
+            "bar RECORD_FIELD_DEF FileId(1) [65; 73) [65; 68)",
+        );
+        check_goto(
+            "
+            //- /lib.rs
+            macro_rules! id {
+                ($($tt:tt)*) => { $($tt)* }
+            }
+            struct Foo { bar: u32 }
+            fn baz(foo: Foo) {
+                id!(f<|>oo.bar);
+            }
+            ",
+            "foo BIND_PAT FileId(1) [83; 86) [83; 86)",
+        );
+    }
+
     #[test]
     fn goto_definition_works_for_methods() {
         covers!(goto_definition_works_for_methods);
@@ -1,6 +1,6 @@
 //! FIXME: write short doc here
 
-use hir::{Adt, HasSource, HirDisplay};
+use hir::{Adt, FromSource, HasSource, HirDisplay};
 use ra_db::SourceDatabase;
 use ra_syntax::{
     algo::{
@@ -17,6 +17,7 @@ use crate::{
         description_from_symbol, docs_from_symbol, macro_label, rust_code_markup,
         rust_code_markup_with_doc, ShortLabel,
     },
+    expand::find_name_ref_in_macro_call,
     name_ref_kind::{classify_name_ref, NameRefKind::*},
     FilePosition, FileRange, RangeInfo,
 };
@@ -101,16 +102,26 @@ pub(crate) fn hover(db: &RootDatabase, position: FilePosition) -> Option<RangeIn
     let mut res = HoverResult::new();
 
     let mut range = None;
-    if let Some(name_ref) = find_node_at_offset::<ast::NameRef>(file.syntax(), position.offset) {
-        let analyzer = hir::SourceAnalyzer::new(db, position.file_id, name_ref.syntax(), None);
+    let name_ref = match find_node_at_offset::<ast::NameRef>(file.syntax(), position.offset) {
+        Some(name_ref) => {
+            let range = name_ref.syntax().text_range();
+            Some((hir::Source { file_id: position.file_id.into(), ast: name_ref }, range))
+        }
+        None => find_name_ref_in_macro_call(db, position.file_id, &file, position.offset),
+    };
+    if let Some((name_ref_src, name_ref_range)) = name_ref {
+        let name_ref = &name_ref_src.ast;
+        let analyzer =
+            hir::SourceAnalyzer::new_for_source(db, name_ref_src.with_ast(name_ref.syntax()), None);
 
         let mut no_fallback = false;
 
-        match classify_name_ref(db, &analyzer, &name_ref) {
+        match classify_name_ref(db, &analyzer, name_ref) {
             Some(Method(it)) => res.extend(from_def_source(db, it)),
             Some(Macro(it)) => {
-                let src = it.source(db);
-                res.extend(hover_text(src.ast.doc_comment_text(), Some(macro_label(&src.ast))));
+                if let Some(src) = it.source(db) {
+                    res.extend(hover_text(src.ast.doc_comment_text(), Some(macro_label(&src.ast))));
+                }
             }
             Some(FieldAccess(it)) => {
                 let src = it.source(db);
@@ -120,7 +131,7 @@ pub(crate) fn hover(db: &RootDatabase, position: FilePosition) -> Option<RangeIn
             }
             Some(AssocItem(it)) => res.extend(match it {
                 hir::AssocItem::Function(it) => from_def_source(db, it),
-                hir::AssocItem::Const(it) => from_def_source(db, it),
+                hir::AssocItem::Const(it) => from_const(db, it),
                 hir::AssocItem::TypeAlias(it) => from_def_source(db, it),
             }),
             Some(Def(it)) => {
@@ -135,7 +146,7 @@ pub(crate) fn hover(db: &RootDatabase, position: FilePosition) -> Option<RangeIn
                     hir::ModuleDef::Adt(Adt::Union(it)) => res.extend(from_def_source(db, it)),
                     hir::ModuleDef::Adt(Adt::Enum(it)) => res.extend(from_def_source(db, it)),
                     hir::ModuleDef::EnumVariant(it) => res.extend(from_def_source(db, it)),
-                    hir::ModuleDef::Const(it) => res.extend(from_def_source(db, it)),
+                    hir::ModuleDef::Const(it) => res.extend(from_const(db, it)),
                     hir::ModuleDef::Static(it) => res.extend(from_def_source(db, it)),
                     hir::ModuleDef::Trait(it) => res.extend(from_def_source(db, it)),
                     hir::ModuleDef::TypeAlias(it) => res.extend(from_def_source(db, it)),
@@ -163,9 +174,18 @@ pub(crate) fn hover(db: &RootDatabase, position: FilePosition) -> Option<RangeIn
             None => {}
         }
 
+        if no_fallback && name_ref_src.file_id != position.file_id.into() {
+            // The expression is inside of a macro expansion, so the type can't
+            // be looked up by range below
+            let expr = name_ref.syntax().ancestors().find_map(ast::Expr::cast);
+            if let Some(ty) = expr.and_then(|expr| analyzer.type_of(db, &expr)) {
+                res.extend(Some(rust_code_markup(ty.display(db).to_string())));
+            }
+        }
+
         if res.is_empty() && !no_fallback {
             // Fallback index based approach:
-            let symbols = crate::symbol_index::index_resolve(db, &name_ref);
+            let symbols = crate::symbol_index::index_resolve(db, name_ref);
             for sym in symbols {
                 let docs = docs_from_symbol(db, &sym);
                 let desc = description_from_symbol(db, &sym);
@@ -174,7 +194,7 @@ pub(crate) fn hover(db: &RootDatabase, position: FilePosition) -> Option<RangeIn
         }
 
         if !res.is_empty() {
-            range = Some(name_ref.syntax().text_range())
+            range = Some(name_ref_range)
         }
     } else if let Some(name) = find_node_at_offset::<ast::Name>(file.syntax(), position.offset) {
         if let Some(parent) = name.syntax().parent() {
@@ -191,7 +211,11 @@ pub(crate) fn hover(db: &RootDatabase, position: FilePosition) -> Option<RangeIn
                     hover_text(node.doc_comment_text(), node.short_label())
                 })
                 .visit(|node: ast::ConstDef| {
-                    hover_text(node.doc_comment_text(), node.short_label())
+                    let src = hir::Source { file_id: position.file_id.into(), ast: node.clone() };
+                    match hir::Const::from_source(db, src) {
+                        Some(it) => from_const(db, it),
+                        None => hover_text(node.doc_comment_text(), node.short_label()),
+                    }
                 })
                 .visit(|node: ast::StaticDef| {
                     hover_text(node.doc_comment_text(), node.short_label())
@@ -240,6 +264,15 @@ pub(crate) fn hover(db: &RootDatabase, position: FilePosition) -> Option<RangeIn
         let src = def.source(db);
         hover_text(src.ast.doc_comment_text(), src.ast.short_label())
     }
+
+    fn from_const(db: &RootDatabase, konst: hir::Const) -> Option<String> {
+        let src = konst.source(db);
+        let label = src.ast.short_label().map(|label| match konst.value(db) {
+            Some(value) => format!("{} = {}", label, value),
+            None => label,
+        });
+        hover_text(src.ast.doc_comment_text(), label)
+    }
 }
 
 pub(crate) fn type_of(db: &RootDatabase, frange: FileRange) -> Option<String> {
@@ -363,6 +396,38 @@ mod tests {
         );
     }
 
+    #[test]
+    fn hover_looks_through_macro_calls() {
+        check_hover_result(
+            r#"
+            //- /main.rs
+            macro_rules! id {
+                ($($tt:tt)*) => { $($tt)* }
+            }
+            pub fn foo() -> u32 { 1 }
+
+            fn main() {
+                let foo_test = id!(fo<|>o());
+            }
+        "#,
+            &["pub fn foo() -> u32"],
+        );
+        check_hover_result(
+            r#"
+            //- /main.rs
+            macro_rules! id {
+                ($($tt:tt)*) => { $($tt)* }
+            }
+
+            fn main() {
+                let foo_test: u32 = 1;
+                id!(foo_<|>test);
+            }
+        "#,
+            &["u32"],
+        );
+    }
+
     #[test]
     fn hover_shows_fn_signature_on_fn_name() {
         check_hover_result(
@@ -423,7 +488,7 @@ mod tests {
                 const foo<|>: u32 = 0;
             }
         "#,
-            &["const foo: u32"],
+            &["const foo: u32 = 0"],
         );
 
         check_hover_result(
@@ -437,6 +502,37 @@ mod tests {
         );
     }
 
+    #[test]
+    fn hover_const_value() {
+        check_hover_result(
+            r#"
+            //- /main.rs
+            const KB: usize = 1 << 10;
+            const SIZE: usize = 4 * KB + (2 - 1);
+
+            fn main() {
+                let buf = [0u8; SIZ<|>E];
+            }
+        "#,
+            &["const SIZE: usize = 4097"],
+        );
+    }
+
+    #[test]
+    fn hover_array_with_const_length() {
+        check_hover_result(
+            r#"
+            //- /main.rs
+            const N: usize = 2 * 3;
+
+            fn main() {
+                let buf<|> = [0u8; N];
+            }
+        "#,
+            &["[u8; 6]"],
+        );
+    }
+
     #[test]
     fn hover_some() {
         let (analysis, position) = single_file_with_position(
@@ -20,11 +20,15 @@ mod status;
 mod completion;
 mod runnables;
 mod name_ref_kind;
+mod expand;
+mod expand_macro;
 mod goto_definition;
 mod goto_type_definition;
 mod extend_selection;
 mod hover;
 mod call_info;
+mod call_hierarchy;
+mod change_signature;
 mod syntax_highlighting;
 mod parent_module;
 mod references;
@@ -38,6 +42,7 @@ mod line_index_utils;
 mod join_lines;
 mod typing;
 mod matching_brace;
+mod formatting_range;
 mod display;
 mod inlay_hints;
 mod wasm_shims;
@@ -49,6 +54,7 @@ mod test_utils;
 
 use std::sync::Arc;
 
+use ra_cfg::CfgOptions;
 use ra_db::{
     salsa::{self, ParallelDatabase},
     CheckCanceled, SourceDatabase,
@@ -61,24 +67,29 @@ use crate::{db::LineIndexDatabase, symbol_index::FileSymbol};
 
 pub use crate::{
     assists::{Assist, AssistId},
+    call_hierarchy::CallItem,
     change::{AnalysisChange, LibraryData},
+    change_signature::SignatureParam,
     completion::{CompletionItem, CompletionItemKind, InsertTextFormat},
     diagnostics::Severity,
-    display::{file_structure, FunctionSignature, NavigationTarget, StructureNode},
+    display::{file_structure, CallableKind, FunctionSignature, NavigationTarget, StructureNode},
+    expand_macro::ExpandedMacro,
     feature_flags::FeatureFlags,
     folding_ranges::{Fold, FoldKind},
     hover::HoverResult,
     inlay_hints::{InlayHint, InlayKind},
     line_index::{LineCol, LineIndex},
     line_index_utils::translate_offset_with_edit,
-    references::ReferenceSearchResult,
+    references::{ReferenceSearchResult, SearchScope},
     runnables::{Runnable, RunnableKind},
-    syntax_highlighting::HighlightedRange,
+    syntax_highlighting::{
+        Highlight, HighlightModifier, HighlightModifiers, HighlightTag, HighlightedRange,
+    },
 };
 
 pub use hir::Documentation;
 pub use ra_db::{
-    Canceled, CrateGraph, CrateId, Edition, FileId, FilePosition, FileRange, SourceRootId,
+    Canceled, CrateGraph, CrateId, Edition, Env, FileId, FilePosition, FileRange, SourceRootId,
 };
 
 pub type Cancelable<T> = Result<T, Canceled>;
@@ -322,7 +333,12 @@ impl Analysis {
         change.add_root(source_root, true);
         let mut crate_graph = CrateGraph::default();
         let file_id = FileId(0);
-        crate_graph.add_crate_root(file_id, Edition::Edition2018);
+        crate_graph.add_crate_root(
+            file_id,
+            Edition::Edition2018,
+            CfgOptions::default(),
+            Env::default(),
+        );
         change.add_file(source_root, file_id, "main.rs".into(), Arc::new(text));
         change.set_crate_graph(crate_graph);
         host.apply_change(change);
@@ -380,6 +396,15 @@ impl Analysis {
         self.with_db(|db| syntax_tree::syntax_tree(&db, file_id, text_range))
     }
 
+    /// Returns the range covering the whole items and statements inside of the
+    /// selection, which is what range formatting works with.
+    pub fn formatting_range(&self, frange: FileRange) -> Cancelable<Option<TextRange>> {
+        self.with_db(|db| {
+            let parse = db.parse(frange.file_id);
+            formatting_range::formatting_range(&parse.tree(), frange.range)
+        })
+    }
+
     /// Returns an edit to remove all newlines in the range, cleaning up minor
     /// stuff like trailing commas.
     pub fn join_lines(&self, frange: FileRange) -> Cancelable<SourceChange> {
@@ -469,12 +494,38 @@ impl Analysis {
         self.with_db(|db| goto_type_definition::goto_type_definition(db, position))
     }
 
-    /// Finds all usages of the reference at point.
+    /// Finds all usages of the reference at point, only in `search_scope` if
+    /// given.
     pub fn find_all_refs(
         &self,
         position: FilePosition,
+        search_scope: Option<SearchScope>,
     ) -> Cancelable<Option<ReferenceSearchResult>> {
-        self.with_db(|db| references::find_all_refs(db, position).map(|it| it.info))
+        self.with_db(|db| references::find_all_refs(db, position, search_scope).map(|it| it.info))
+    }
+
+    /// Resolves the function at `position`, the root of a call hierarchy.
+    pub fn call_hierarchy(
+        &self,
+        position: FilePosition,
+    ) -> Cancelable<Option<RangeInfo<Vec<NavigationTarget>>>> {
+        self.with_db(|db| call_hierarchy::call_hierarchy(db, position))
+    }
+
+    /// Finds the functions calling the function at `position`.
+    pub fn incoming_calls(&self, position: FilePosition) -> Cancelable<Option<Vec<CallItem>>> {
+        self.with_db(|db| call_hierarchy::incoming_calls(db, position))
+    }
+
+    /// Finds the functions called by the function at `position`.
+    pub fn outgoing_calls(&self, position: FilePosition) -> Cancelable<Option<Vec<CallItem>>> {
+        self.with_db(|db| call_hierarchy::outgoing_calls(db, position))
+    }
+
+    /// Expands the macro call at `position`, including the macro calls
+    /// produced by the expansion.
+    pub fn expand_macro(&self, position: FilePosition) -> Cancelable<Option<ExpandedMacro>> {
+        self.with_db(|db| expand_macro::expand_macro(db, position))
     }
 
     /// Returns a short text describing element at position.
@@ -548,6 +599,16 @@ impl Analysis {
         self.with_db(|db| references::rename(db, position, new_name))
     }
 
+    /// Returns the edit which changes the parameters of the function at
+    /// `position` to `params`, updating all of its calls.
+    pub fn change_signature(
+        &self,
+        position: FilePosition,
+        params: &[SignatureParam],
+    ) -> Cancelable<Option<SourceChange>> {
+        self.with_db(|db| change_signature::change_signature(db, position, params))
+    }
+
     /// Performs an operation on that may be Canceled.
     fn with_db<F: FnOnce(&db::RootDatabase) -> T + std::panic::UnwindSafe, T>(
         &self,
@@ -62,13 +62,15 @@ pub(crate) fn classify_name_ref(
         let record_lit = field_expr.syntax().ancestors().find_map(ast::RecordLit::cast);
 
         if let Some(ty) = record_lit.and_then(|lit| analyzer.type_of(db, &lit.into())) {
-            if let Some((hir::Adt::Struct(s), _)) = ty.as_adt() {
-                let hir_path = hir::Path::from_name_ref(name_ref);
-                let hir_name = hir_path.as_ident().unwrap();
-
-                if let Some(field) = s.field(db, hir_name) {
-                    return Some(FieldAccess(field));
-                }
+            let hir_path = hir::Path::from_name_ref(name_ref);
+            let hir_name = hir_path.as_ident().unwrap();
+            let field = match ty.as_adt() {
+                Some((hir::Adt::Struct(s), _)) => s.field(db, hir_name),
+                Some((hir::Adt::Union(u), _)) => u.field(db, hir_name),
+                _ => None,
+            };
+            if let Some(field) = field {
+                return Some(FieldAccess(field));
             }
         }
     }
@@ -1,13 +1,37 @@
-//! FIXME: write short doc here
+//! Find-all-references and rename.
+//!
+//! Local bindings are handled by `SourceAnalyzer::find_all_refs`. For items,
+//! fields and modules, every crate which can see the definition is searched.
+//! The references of a trait method include the ones of its impls, as calls
+//! resolve to either.
 
-use hir::{Either, ModuleSource};
+mod classify;
+mod search_scope;
+
+use hir::{db::HirDatabase, Either, ModuleSource};
 use ra_db::SourceDatabase;
-use ra_syntax::{algo::find_node_at_offset, ast, AstNode, SourceFile, SyntaxNode};
+use ra_syntax::{
+    algo::find_node_at_offset,
+    ast, AstNode, AstPtr, SourceFile,
+    SyntaxKind::{RECORD_FIELD_DEF, TOKEN_TREE},
+    SyntaxNode, TextUnit,
+};
 use relative_path::{RelativePath, RelativePathBuf};
 
 use crate::{
-    db::RootDatabase, FileId, FilePosition, FileRange, FileSystemEdit, NavigationTarget, RangeInfo,
-    SourceChange, SourceFileEdit, TextRange,
+    db::RootDatabase,
+    expand::{descend_into_macros, find_name_ref_in_macro_call},
+    FileId, FilePosition, FileRange, FileSystemEdit, NavigationTarget, RangeInfo, SourceChange,
+    SourceFileEdit, TextRange,
+};
+
+pub use self::search_scope::SearchScope;
+
+use self::{
+    classify::{
+        classify_name, classify_name_ref_to_def, classify_record_pat_field, NameDefinition,
+    },
+    search_scope::search_scope,
 };
 
 #[derive(Debug, Clone)]
@@ -50,41 +74,239 @@ impl IntoIterator for ReferenceSearchResult {
 pub(crate) fn find_all_refs(
     db: &RootDatabase,
     position: FilePosition,
+    search_scope: Option<SearchScope>,
 ) -> Option<RangeInfo<ReferenceSearchResult>> {
     let parse = db.parse(position.file_id);
-    let RangeInfo { range, info: (binding, analyzer) } = find_binding(db, &parse.tree(), position)?;
-    let declaration = NavigationTarget::from_bind_pat(position.file_id, &binding);
+    let syntax = parse.tree().syntax().clone();
 
-    let references = analyzer
-        .find_all_refs(&binding)
-        .into_iter()
-        .map(move |ref_desc| FileRange { file_id: position.file_id, range: ref_desc.range })
-        .collect::<Vec<_>>();
+    if let Some(RangeInfo { range, info: (binding, analyzer) }) =
+        find_binding(db, &parse.tree(), position)
+    {
+        let declaration = NavigationTarget::from_bind_pat(position.file_id, &binding);
+        let mut references = analyzer
+            .find_all_refs(&binding)
+            .into_iter()
+            .map(|ref_desc| ref_desc.range)
+            .chain(find_refs_in_macro_calls(db, position.file_id, &binding))
+            .map(move |range| FileRange { file_id: position.file_id, range })
+            .collect::<Vec<_>>();
+        references.sort_by_key(|it| it.range.start());
+        return Some(RangeInfo::new(range, ReferenceSearchResult { declaration, references }));
+    }
 
-    return Some(RangeInfo::new(range, ReferenceSearchResult { declaration, references }));
+    let RangeInfo { range, info: def } = find_definition(db, &syntax, position)?;
+    let declaration = def.declaration(db)?;
+    let mut references = process_definition(db, &def, &declaration, search_scope.as_ref());
+    if let NameDefinition::Def(hir::ModuleDef::Function(function)) = def {
+        for related in related_functions(db, function) {
+            if related == function {
+                continue;
+            }
+            let def = NameDefinition::Def(related.into());
+            let related_declaration = match def.declaration(db) {
+                Some(it) => it,
+                None => continue,
+            };
+            references.extend(process_definition(
+                db,
+                &def,
+                &related_declaration,
+                search_scope.as_ref(),
+            ));
+            let file_id = related_declaration.file_id();
+            if search_scope.as_ref().map_or(true, |scope| scope.contains(file_id)) {
+                references.push(FileRange { file_id, range: related_declaration.range() });
+            }
+        }
+        references.sort_by_key(|it| (it.file_id, it.range.start()));
+        references.dedup_by_key(|it| (it.file_id, it.range));
+    }
+    Some(RangeInfo::new(range, ReferenceSearchResult { declaration, references }))
+}
 
-    fn find_binding<'a>(
-        db: &RootDatabase,
-        source_file: &SourceFile,
-        position: FilePosition,
-    ) -> Option<RangeInfo<(ast::BindPat, hir::SourceAnalyzer)>> {
-        let syntax = source_file.syntax();
-        if let Some(binding) = find_node_at_offset::<ast::BindPat>(syntax, position.offset) {
-            let range = binding.syntax().text_range();
+/// The functions which calls to `function` may resolve to: for methods of
+/// traits, the method in the trait and in all of its impls.
+pub(crate) fn related_functions(db: &RootDatabase, function: hir::Function) -> Vec<hir::Function> {
+    let trait_ = match function.container(db) {
+        Some(hir::Container::Trait(it)) => it,
+        Some(hir::Container::ImplBlock(it)) => match it.target_trait_ref(db) {
+            Some(trait_ref) => trait_ref.trait_,
+            None => return vec![function],
+        },
+        None => return vec![function],
+    };
+    let name = function.name(db);
+    let same_name = |item: hir::AssocItem| match item {
+        hir::AssocItem::Function(it) if it.name(db) == name => Some(it),
+        _ => None,
+    };
+
+    let mut res = trait_.items(db).into_iter().filter_map(&same_name).collect::<Vec<_>>();
+    let crate_graph = db.crate_graph();
+    for crate_id in crate_graph.iter() {
+        let source_root = db.file_source_root(crate_graph.crate_root(crate_id));
+        for krate in hir::Crate::source_root_crates(db, source_root) {
+            if krate.crate_id() != crate_id {
+                continue;
+            }
+            for impl_block in db.impls_in_crate(krate).lookup_impl_blocks_for_trait(trait_) {
+                for it in impl_block.items(db).into_iter().filter_map(&same_name) {
+                    if !res.contains(&it) {
+                        res.push(it);
+                    }
+                }
+            }
+        }
+    }
+    res
+}
+
+fn find_binding<'a>(
+    db: &RootDatabase,
+    source_file: &SourceFile,
+    position: FilePosition,
+) -> Option<RangeInfo<(ast::BindPat, hir::SourceAnalyzer)>> {
+    let syntax = source_file.syntax();
+    if let Some(binding) = find_node_at_offset::<ast::BindPat>(syntax, position.offset) {
+        let range = binding.syntax().text_range();
+        let analyzer = hir::SourceAnalyzer::new(db, position.file_id, binding.syntax(), None);
+        return Some(RangeInfo::new(range, (binding, analyzer)));
+    };
+    let (name_ref, range) = match find_node_at_offset::<ast::NameRef>(syntax, position.offset) {
+        Some(name_ref) => {
+            let range = name_ref.syntax().text_range();
+            (hir::Source { file_id: position.file_id.into(), ast: name_ref }, range)
+        }
+        None => find_name_ref_in_macro_call(db, position.file_id, source_file, position.offset)?,
+    };
+    let analyzer =
+        hir::SourceAnalyzer::new_for_source(db, name_ref.with_ast(name_ref.ast.syntax()), None);
+    let resolved = analyzer.resolve_local_name(&name_ref.ast)?;
+    if let Either::A(ptr) = resolved.ptr() {
+        if let ast::Pat::BindPat(binding) = ptr.to_node(source_file.syntax()) {
             let analyzer = hir::SourceAnalyzer::new(db, position.file_id, binding.syntax(), None);
             return Some(RangeInfo::new(range, (binding, analyzer)));
+        }
+    }
+    None
+}
+
+/// Finds the references to `binding` from inside of macro call arguments,
+/// which `SourceAnalyzer::find_all_refs` doesn't look at.
+fn find_refs_in_macro_calls(
+    db: &RootDatabase,
+    file_id: FileId,
+    binding: &ast::BindPat,
+) -> Vec<TextRange> {
+    let (fn_def, name) =
+        match (binding.syntax().ancestors().find_map(ast::FnDef::cast), binding.name()) {
+            (Some(fn_def), Some(name)) => (fn_def, name),
+            _ => return Vec::new(),
         };
-        let name_ref = find_node_at_offset::<ast::NameRef>(syntax, position.offset)?;
-        let range = name_ref.syntax().text_range();
-        let analyzer = hir::SourceAnalyzer::new(db, position.file_id, name_ref.syntax(), None);
-        let resolved = analyzer.resolve_local_name(&name_ref)?;
-        if let Either::A(ptr) = resolved.ptr() {
-            if let ast::Pat::BindPat(binding) = ptr.to_node(source_file.syntax()) {
-                return Some(RangeInfo::new(range, (binding, analyzer)));
+    let ptr = Either::A(AstPtr::new(&ast::Pat::from(binding.clone())));
+    fn_def
+        .syntax()
+        .descendants_with_tokens()
+        .filter_map(|it| it.into_token())
+        .filter(|token| token.parent().kind() == TOKEN_TREE && token.text() == name.text())
+        .filter(|token| {
+            let token = descend_into_macros(db, file_id, token.clone());
+            if token.file_id == file_id.into() {
+                return false;
+            }
+            let name_ref = match ast::NameRef::cast(token.ast.parent()) {
+                Some(it) => it,
+                None => return false,
+            };
+            let analyzer =
+                hir::SourceAnalyzer::new_for_source(db, token.with_ast(name_ref.syntax()), None);
+            match analyzer.resolve_local_name(&name_ref) {
+                Some(entry) => entry.ptr() == ptr,
+                None => false,
+            }
+        })
+        .map(|token| token.text_range())
+        .collect()
+}
+
+fn find_definition(
+    db: &RootDatabase,
+    syntax: &SyntaxNode,
+    position: FilePosition,
+) -> Option<RangeInfo<NameDefinition>> {
+    if let Some(name) = find_node_at_offset::<ast::Name>(syntax, position.offset) {
+        let def = classify_name(db, position.file_id, &name)?;
+        return Some(RangeInfo::new(name.syntax().text_range(), def));
+    }
+    if let Some(name_ref) = find_node_at_offset::<ast::NameRef>(syntax, position.offset) {
+        let src = hir::Source { file_id: position.file_id.into(), ast: &name_ref };
+        let def = classify_name_ref_to_def(db, src)?;
+        return Some(RangeInfo::new(name_ref.syntax().text_range(), def));
+    }
+    let file = SourceFile::cast(syntax.clone())?;
+    let (name_ref, range) =
+        find_name_ref_in_macro_call(db, position.file_id, &file, position.offset)?;
+    let def = classify_name_ref_to_def(db, name_ref.as_ref())?;
+    Some(RangeInfo::new(range, def))
+}
+
+/// Finds all references to `def` in the crates which can see it, restricted
+/// to `scope` if given. Candidate files are first searched for the name
+/// textually, and each hit is then checked by resolving it.
+fn process_definition(
+    db: &RootDatabase,
+    def: &NameDefinition,
+    declaration: &NavigationTarget,
+    scope: Option<&SearchScope>,
+) -> Vec<FileRange> {
+    let name = declaration.name().as_str();
+    if name.is_empty() {
+        return Vec::new();
+    }
+
+    let mut refs = Vec::new();
+    for file_id in search_scope(db, declaration.file_id()) {
+        if !scope.map_or(true, |scope| scope.contains(file_id)) {
+            continue;
+        }
+        let text = db.file_text(file_id);
+        let mut parse = None;
+        for (idx, _) in text.match_indices(name) {
+            let offset = TextUnit::from_usize(idx);
+            let tree = parse.get_or_insert_with(|| db.parse(file_id).tree());
+            // Fields are named by `Name`s rather than `NameRef`s in record patterns
+            if let NameDefinition::Field(field) = def {
+                if let Some(it) = find_node_at_offset::<ast::Name>(tree.syntax(), offset) {
+                    let range = it.syntax().text_range();
+                    if range.start() == offset && it.text() == name {
+                        if classify_record_pat_field(db, file_id, &it) == Some(*field) {
+                            refs.push(FileRange { file_id, range });
+                        }
+                        continue;
+                    }
+                }
+            }
+            let (name_ref, range) = match find_node_at_offset::<ast::NameRef>(tree.syntax(), offset)
+            {
+                Some(it) => {
+                    let range = it.syntax().text_range();
+                    (hir::Source { file_id: file_id.into(), ast: it }, range)
+                }
+                None => match find_name_ref_in_macro_call(db, file_id, tree, offset) {
+                    Some(it) => it,
+                    None => continue,
+                },
+            };
+            if range.start() != offset || name_ref.ast.text() != name {
+                continue;
+            }
+            if classify_name_ref_to_def(db, name_ref.as_ref()).as_ref() == Some(def) {
+                refs.push(FileRange { file_id, range });
             }
         }
-        None
     }
+    refs.sort_by_key(|it| (it.file_id, it.range.start()));
+    refs
 }
 
 pub(crate) fn rename(
@@ -93,15 +315,28 @@ pub(crate) fn rename(
     new_name: &str,
 ) -> Option<RangeInfo<SourceChange>> {
     let parse = db.parse(position.file_id);
-    if let Some((ast_name, ast_module)) =
-        find_name_and_module_at_offset(parse.tree().syntax(), position)
-    {
+    let syntax = parse.tree().syntax().clone();
+    if let Some((ast_name, ast_module)) = find_name_and_module_at_offset(&syntax, position) {
         let range = ast_name.syntax().text_range();
-        rename_mod(db, &ast_name, &ast_module, position, new_name)
-            .map(|info| RangeInfo::new(range, info))
-    } else {
-        rename_reference(db, position, new_name)
+        let module_src = hir::Source { file_id: position.file_id.into(), ast: ast_module };
+        let module = hir::Module::from_declaration(db, module_src);
+        return rename_mod(db, module, position.file_id, range, new_name)
+            .map(|info| RangeInfo::new(range, info));
     }
+    if let Some(name_ref) = find_node_at_offset::<ast::NameRef>(&syntax, position.offset) {
+        let src = hir::Source { file_id: position.file_id.into(), ast: &name_ref };
+        if let Some(NameDefinition::Def(hir::ModuleDef::Module(module))) =
+            classify_name_ref_to_def(db, src)
+        {
+            let range = name_ref.syntax().text_range();
+            let decl = module.declaration_source(db)?;
+            let decl_file_id = decl.file_id.original_file(db);
+            let decl_range = decl.ast.name()?.syntax().text_range();
+            return rename_mod(db, Some(module), decl_file_id, decl_range, new_name)
+                .map(|info| RangeInfo::new(range, info));
+        }
+    }
+    rename_reference(db, position, new_name)
 }
 
 fn find_name_and_module_at_offset(
@@ -130,15 +365,14 @@ fn source_edit_from_file_id_range(
 
 fn rename_mod(
     db: &RootDatabase,
-    ast_name: &ast::Name,
-    ast_module: &ast::Module,
-    position: FilePosition,
+    module: Option<hir::Module>,
+    decl_file_id: FileId,
+    decl_range: TextRange,
     new_name: &str,
 ) -> Option<SourceChange> {
     let mut source_file_edits = Vec::new();
     let mut file_system_edits = Vec::new();
-    let module_src = hir::Source { file_id: position.file_id.into(), ast: ast_module.clone() };
-    if let Some(module) = hir::Module::from_declaration(db, module_src) {
+    if let Some(module) = module {
         let src = module.definition_source(db);
         let file_id = src.file_id.as_original_file();
         match src.ast {
@@ -157,7 +391,7 @@ fn rename_mod(
                 if let Some(path) = dst_path {
                     let move_file = FileSystemEdit::MoveFile {
                         src: file_id,
-                        dst_source_root: db.file_source_root(position.file_id),
+                        dst_source_root: db.file_source_root(decl_file_id),
                         dst_path: path,
                     };
                     file_system_edits.push(move_file);
@@ -167,15 +401,17 @@ fn rename_mod(
         }
     }
 
-    let edit = SourceFileEdit {
-        file_id: position.file_id,
-        edit: {
-            let mut builder = ra_text_edit::TextEditBuilder::default();
-            builder.replace(ast_name.syntax().text_range(), new_name.into());
-            builder.finish()
-        },
-    };
-    source_file_edits.push(edit);
+    source_file_edits.push(source_edit_from_file_id_range(decl_file_id, decl_range, new_name));
+
+    if let Some(module) = module {
+        let def = NameDefinition::Def(module.into());
+        let declaration = NavigationTarget::from_module_to_decl(db, module);
+        source_file_edits.extend(
+            process_definition(db, &def, &declaration, None)
+                .into_iter()
+                .map(|it| source_edit_from_file_id_range(it.file_id, it.range, new_name)),
+        );
+    }
 
     Some(SourceChange::from_edits("rename", source_file_edits, file_system_edits))
 }
@@ -185,11 +421,14 @@ fn rename_reference(
     position: FilePosition,
     new_name: &str,
 ) -> Option<RangeInfo<SourceChange>> {
-    let RangeInfo { range, info: refs } = find_all_refs(db, position)?;
+    let RangeInfo { range, info: refs } = find_all_refs(db, position, None)?;
+    // Things without a name, like tuple fields, can't be renamed.
+    refs.declaration().focus_range()?;
+    let is_field = refs.declaration().kind() == RECORD_FIELD_DEF;
 
     let edit = refs
         .into_iter()
-        .map(|range| source_edit_from_file_id_range(range.file_id, range.range, new_name))
+        .map(|range| rename_edit(db, range, new_name, is_field))
         .collect::<Vec<_>>();
 
     if edit.is_empty() {
@@ -199,6 +438,49 @@ fn rename_reference(
     Some(RangeInfo::new(range, SourceChange::source_file_edits("rename", edit)))
 }
 
+/// Renames the name at `frange`. A field shorthand, like `x` in `S { x }`,
+/// names both a field and a local, so it's expanded to keep the other name.
+fn rename_edit(
+    db: &RootDatabase,
+    frange: FileRange,
+    new_name: &str,
+    is_field: bool,
+) -> SourceFileEdit {
+    let parse = db.parse(frange.file_id);
+    let shorthand = match field_shorthand_at(parse.tree().syntax(), frange.range) {
+        Some(it) => it,
+        None => return source_edit_from_file_id_range(frange.file_id, frange.range, new_name),
+    };
+    let range = shorthand.text_range();
+    let mut text = shorthand.text().to_string();
+    let name_start = (frange.range.start() - range.start()).to_usize();
+    let name_end = (frange.range.end() - range.start()).to_usize();
+    let old_name = text[name_start..name_end].to_string();
+    let new_text = if is_field {
+        format!("{}: {}", new_name, text)
+    } else {
+        text.replace_range(name_start..name_end, new_name);
+        format!("{}: {}", old_name, text)
+    };
+    source_edit_from_file_id_range(frange.file_id, range, &new_text)
+}
+
+/// Finds the field shorthand in a record literal or pattern whose name is at
+/// `range`.
+fn field_shorthand_at(syntax: &SyntaxNode, range: TextRange) -> Option<SyntaxNode> {
+    if let Some(name_ref) = find_node_at_offset::<ast::NameRef>(syntax, range.start()) {
+        let field = ast::RecordField::cast(name_ref.syntax().parent()?)?;
+        return match field.expr() {
+            Some(_) => None,
+            None => Some(name_ref.syntax().clone()),
+        };
+    }
+    let name = find_node_at_offset::<ast::Name>(syntax, range.start())?;
+    let bind_pat = ast::BindPat::cast(name.syntax().parent()?)?;
+    ast::RecordFieldPatList::cast(bind_pat.syntax().parent()?)?;
+    Some(bind_pat.syntax().clone())
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
@@ -249,9 +531,165 @@ mod tests {
         assert_eq!(refs.len(), 2);
     }
 
+    #[test]
+    fn test_find_all_refs_for_struct() {
+        let code = r#"
+    struct Foo<|> {
+        a: i32,
+    }
+
+    impl Foo {
+        fn new() -> Foo {
+            Foo { a: 0 }
+        }
+    }
+
+    fn main() {
+        let f: Foo = Foo::new();
+    }"#;
+
+        let refs = get_all_refs(code);
+        assert_eq!(refs.len(), 6);
+    }
+
+    #[test]
+    fn test_find_all_refs_for_method() {
+        let code = r#"
+    struct Foo { bar: i32 }
+
+    impl Foo {
+        fn get(&self) -> i32 { self.bar }
+    }
+
+    fn main() {
+        let f = Foo { bar: 92 };
+        f.get<|>();
+        let _ = f.bar + f.get();
+    }"#;
+
+        let refs = get_all_refs(code);
+        assert_eq!(refs.len(), 3);
+    }
+
+    #[test]
+    fn test_find_all_refs_for_field() {
+        let code = r#"
+    struct Foo { bar<|>: i32 }
+
+    impl Foo {
+        fn get(&self) -> i32 { self.bar }
+    }
+
+    fn main() {
+        let f = Foo { bar: 92 };
+        f.get();
+        let _ = f.bar + f.get();
+    }"#;
+
+        let refs = get_all_refs(code);
+        assert_eq!(refs.len(), 4);
+    }
+
+    #[test]
+    fn test_find_all_refs_for_trait_method() {
+        let code = r#"
+    trait Foo {
+        fn foo<|>(&self);
+    }
+
+    struct S;
+
+    impl Foo for S {
+        fn foo(&self) {}
+    }
+
+    fn main() {
+        S.foo();
+    }"#;
+
+        let refs = get_all_refs(code);
+        assert_eq!(refs.len(), 3);
+    }
+
+    #[test]
+    fn test_find_all_refs_for_impl_method_of_trait() {
+        let code = r#"
+    trait Foo {
+        fn foo(&self);
+    }
+
+    struct S;
+
+    impl Foo for S {
+        fn foo<|>(&self) {}
+    }
+
+    fn f<T: Foo>(t: T) {
+        t.foo();
+        S.foo();
+    }"#;
+
+        let refs = get_all_refs(code);
+        assert_eq!(refs.len(), 4);
+    }
+
+    #[test]
+    fn test_find_all_refs_across_files() {
+        let (analysis, pos) = analysis_and_position(
+            "
+            //- /lib.rs
+            mod foo;
+            pub fn spam<|>() {}
+
+            //- /foo.rs
+            use crate::spam;
+            fn f() { spam(); crate::spam(); }
+            ",
+        );
+        let refs = analysis.find_all_refs(pos, None).unwrap().unwrap();
+        assert_eq!(refs.len(), 4);
+        assert_eq!(refs.references().iter().filter(|it| it.file_id == FileId(2)).count(), 3);
+    }
+
+    #[test]
+    fn test_find_all_refs_in_macro_calls() {
+        let code = r#"
+    macro_rules! id {
+        ($($tt:tt)*) => { $($tt)* }
+    }
+
+    fn foo<|>() {}
+
+    fn main() {
+        id!(foo());
+        foo();
+    }"#;
+
+        let refs = get_all_refs(code);
+        assert_eq!(refs.len(), 3);
+    }
+
+    #[test]
+    fn test_find_all_refs_for_local_in_macro_calls() {
+        let code = r#"
+    macro_rules! id {
+        ($($tt:tt)*) => { $($tt)* }
+    }
+
+    fn main() {
+        let i = 1;
+        id!(i<|> + 1);
+        id!(id!(i));
+        i;
+    }"#;
+
+        let refs = get_all_refs(code);
+        assert_eq!(refs.len(), 4);
+    }
+
     fn get_all_refs(text: &str) -> ReferenceSearchResult {
         let (analysis, position) = single_file_with_position(text);
-        analysis.find_all_refs(position).unwrap().unwrap()
+        analysis.find_all_refs(position, None).unwrap().unwrap()
     }
 
     #[test]
@@ -330,6 +768,113 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_rename_for_function() {
+        test_rename(
+            r#"
+    fn foo<|>(x: u32) -> u32 { x }
+
+    fn main() {
+        let y = foo(1) + foo(2);
+    }"#,
+            "bar",
+            r#"
+    fn bar(x: u32) -> u32 { x }
+
+    fn main() {
+        let y = bar(1) + bar(2);
+    }"#,
+        );
+    }
+
+    #[test]
+    fn test_rename_for_field() {
+        test_rename(
+            r#"
+    struct S { x: i32 }
+
+    fn f(s: S) -> S {
+        let y = s.x<|>;
+        S { x: y }
+    }"#,
+            "z",
+            r#"
+    struct S { z: i32 }
+
+    fn f(s: S) -> S {
+        let y = s.z;
+        S { z: y }
+    }"#,
+        );
+    }
+
+    #[test]
+    fn test_rename_for_field_shorthand() {
+        test_rename(
+            r#"
+    struct S { x<|>: i32 }
+
+    fn f(x: i32) -> i32 {
+        let s = S { x };
+        let S { x } = s;
+        x
+    }"#,
+            "z",
+            r#"
+    struct S { z: i32 }
+
+    fn f(x: i32) -> i32 {
+        let s = S { z: x };
+        let S { z: x } = s;
+        x
+    }"#,
+        );
+    }
+
+    #[test]
+    fn test_rename_for_local_in_field_shorthand() {
+        test_rename(
+            r#"
+    struct S { x: i32 }
+
+    fn f(x<|>: i32) -> S {
+        S { x }
+    }"#,
+            "y",
+            r#"
+    struct S { x: i32 }
+
+    fn f(y: i32) -> S {
+        S { x: y }
+    }"#,
+        );
+    }
+
+    #[test]
+    fn test_rename_for_enum_variant() {
+        test_rename(
+            r#"
+    enum E { A<|>, B }
+
+    fn f(e: E) -> E {
+        match e {
+            E::A => E::B,
+            E::B => E::A,
+        }
+    }"#,
+            "C",
+            r#"
+    enum E { C, B }
+
+    fn f(e: E) -> E {
+        match e {
+            E::C => E::B,
+            E::B => E::C,
+        }
+    }"#,
+        );
+    }
+
     #[test]
     fn test_rename_mod() {
         let (analysis, position) = analysis_and_position(
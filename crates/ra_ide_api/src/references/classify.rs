@@ -0,0 +1,134 @@
+//! Classifies names and name references into the definitions they denote, so
+//! that all usages of a definition can be matched against each other.
+
+use hir::{FieldSource, FromSource, Source};
+use ra_db::FileId;
+use ra_syntax::{ast, AstNode};
+
+use crate::{
+    db::RootDatabase,
+    name_ref_kind::{classify_name_ref, NameRefKind},
+    NavigationTarget,
+};
+
+/// An item-like definition which can be referenced from other files.
+///
+/// Local bindings are not included: their references are always in the same
+/// body and are found through `SourceAnalyzer::find_all_refs`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum NameDefinition {
+    Macro(hir::MacroDef),
+    Field(hir::StructField),
+    Def(hir::ModuleDef),
+}
+
+impl NameDefinition {
+    fn from_name_ref_kind(kind: NameRefKind) -> Option<NameDefinition> {
+        let def = match kind {
+            NameRefKind::Method(func) => NameDefinition::Def(func.into()),
+            NameRefKind::Macro(mac) => NameDefinition::Macro(mac),
+            NameRefKind::FieldAccess(field) => NameDefinition::Field(field),
+            NameRefKind::AssocItem(assoc) => NameDefinition::Def(match assoc {
+                hir::AssocItem::Function(it) => it.into(),
+                hir::AssocItem::Const(it) => it.into(),
+                hir::AssocItem::TypeAlias(it) => it.into(),
+            }),
+            NameRefKind::Def(def) => NameDefinition::Def(def),
+            // `Self` is an alias, not a reference to the type by name.
+            NameRefKind::SelfType(_)
+            | NameRefKind::Pat(_)
+            | NameRefKind::SelfParam(_)
+            | NameRefKind::GenericParam(_) => return None,
+        };
+        Some(def)
+    }
+
+    pub(crate) fn declaration(&self, db: &RootDatabase) -> Option<NavigationTarget> {
+        match *self {
+            NameDefinition::Macro(mac) => NavigationTarget::from_macro_def(db, mac),
+            NameDefinition::Field(field) => Some(NavigationTarget::from_field(db, field)),
+            NameDefinition::Def(hir::ModuleDef::Module(module)) => {
+                Some(NavigationTarget::from_module_to_decl(db, module))
+            }
+            NameDefinition::Def(def) => NavigationTarget::from_def(db, def),
+        }
+    }
+}
+
+pub(crate) fn classify_name_ref_to_def(
+    db: &RootDatabase,
+    name_ref: hir::Source<&ast::NameRef>,
+) -> Option<NameDefinition> {
+    let analyzer =
+        hir::SourceAnalyzer::new_for_source(db, name_ref.with_ast(name_ref.ast.syntax()), None);
+    classify_name_ref(db, &analyzer, name_ref.ast).and_then(NameDefinition::from_name_ref_kind)
+}
+
+/// Finds the definition introduced by `name`, if it is an item, a field or a
+/// module declaration.
+pub(crate) fn classify_name(
+    db: &RootDatabase,
+    file_id: FileId,
+    name: &ast::Name,
+) -> Option<NameDefinition> {
+    let parent = name.syntax().parent()?;
+    if ast::RecordFieldPat::cast(parent.clone()).is_some() {
+        return classify_record_pat_field(db, file_id, name).map(NameDefinition::Field);
+    }
+    let file_id = file_id.into();
+
+    if let Some(it) = ast::RecordFieldDef::cast(parent.clone()) {
+        let src = Source { file_id, ast: FieldSource::Named(it) };
+        return hir::StructField::from_source(db, src).map(NameDefinition::Field);
+    }
+    if let Some(it) = ast::Module::cast(parent.clone()) {
+        let src = Source { file_id, ast: it };
+        return hir::Module::from_declaration(db, src).map(|it| NameDefinition::Def(it.into()));
+    }
+    if let Some(it) = ast::StructDef::cast(parent.clone()) {
+        let def: hir::ModuleDef = if it.is_union() {
+            hir::Union::from_source(db, Source { file_id, ast: it })?.into()
+        } else {
+            hir::Struct::from_source(db, Source { file_id, ast: it })?.into()
+        };
+        return Some(NameDefinition::Def(def));
+    }
+
+    let def: hir::ModuleDef = if let Some(it) = ast::FnDef::cast(parent.clone()) {
+        hir::Function::from_source(db, Source { file_id, ast: it })?.into()
+    } else if let Some(it) = ast::EnumDef::cast(parent.clone()) {
+        hir::Enum::from_source(db, Source { file_id, ast: it })?.into()
+    } else if let Some(it) = ast::EnumVariant::cast(parent.clone()) {
+        hir::EnumVariant::from_source(db, Source { file_id, ast: it })?.into()
+    } else if let Some(it) = ast::TraitDef::cast(parent.clone()) {
+        hir::Trait::from_source(db, Source { file_id, ast: it })?.into()
+    } else if let Some(it) = ast::TypeAliasDef::cast(parent.clone()) {
+        hir::TypeAlias::from_source(db, Source { file_id, ast: it })?.into()
+    } else if let Some(it) = ast::ConstDef::cast(parent.clone()) {
+        hir::Const::from_source(db, Source { file_id, ast: it })?.into()
+    } else if let Some(it) = ast::StaticDef::cast(parent) {
+        hir::Static::from_source(db, Source { file_id, ast: it })?.into()
+    } else {
+        return None;
+    };
+    Some(NameDefinition::Def(def))
+}
+
+/// Finds the field named by `name` in a record pattern, like `x` in `S { x: a }`
+/// or in the shorthand `S { x }`.
+pub(crate) fn classify_record_pat_field(
+    db: &RootDatabase,
+    file_id: FileId,
+    name: &ast::Name,
+) -> Option<hir::StructField> {
+    let parent = name.syntax().parent()?;
+    let field_pat_list = match ast::RecordFieldPat::cast(parent.clone()) {
+        Some(field_pat) => field_pat.syntax().parent()?,
+        None => ast::BindPat::cast(parent)?.syntax().parent()?,
+    };
+    let record_pat = ast::RecordFieldPatList::cast(field_pat_list)?.syntax().parent()?;
+    let record_pat = ast::RecordPat::cast(record_pat)?;
+    let analyzer = hir::SourceAnalyzer::new(db, file_id, record_pat.syntax(), None);
+    let variant = analyzer.resolve_record_pattern(&record_pat)?;
+    variant.fields(db).into_iter().find(|field| field.name(db).to_string() == name.text().as_str())
+}
@@ -0,0 +1,58 @@
+//! Computes the set of files which may contain references to a definition.
+
+use ra_db::{CrateId, FileId, SourceDatabase};
+use rustc_hash::FxHashSet;
+
+use crate::db::RootDatabase;
+
+/// A set of files in which references are searched for.
+#[derive(Debug, Clone)]
+pub struct SearchScope {
+    files: FxHashSet<FileId>,
+}
+
+impl SearchScope {
+    /// Restricts the search to a single file, like for highlighting the
+    /// references in the current document.
+    pub fn single_file(file_id: FileId) -> SearchScope {
+        let mut files = FxHashSet::default();
+        files.insert(file_id);
+        SearchScope { files }
+    }
+
+    pub(crate) fn contains(&self, file_id: FileId) -> bool {
+        self.files.contains(&file_id)
+    }
+}
+
+/// Returns all files of the crates containing `file_id` and of every crate
+/// which (transitively) depends on them.
+pub(crate) fn search_scope(db: &RootDatabase, file_id: FileId) -> FxHashSet<FileId> {
+    let crate_graph = db.crate_graph();
+    let mut crates: FxHashSet<CrateId> =
+        db.source_root_crates(db.file_source_root(file_id)).iter().copied().collect();
+
+    loop {
+        let dependents = crate_graph
+            .iter()
+            .filter(|krate| !crates.contains(krate))
+            .filter(|&krate| {
+                crate_graph.dependencies(krate).any(|dep| crates.contains(&dep.crate_id()))
+            })
+            .collect::<Vec<_>>();
+        if dependents.is_empty() {
+            break;
+        }
+        crates.extend(dependents);
+    }
+
+    let mut source_roots = FxHashSet::default();
+    source_roots.insert(db.file_source_root(file_id));
+    source_roots
+        .extend(crates.iter().map(|&krate| db.file_source_root(crate_graph.crate_root(krate))));
+
+    source_roots
+        .into_iter()
+        .flat_map(|root| db.source_root(root).walk().collect::<Vec<_>>())
+        .collect()
+}
@@ -1,4 +1,6 @@
-//! FIXME: write short doc here
+//! Computes semantic highlighting for a file.
+
+mod tags;
 
 use rustc_hash::{FxHashMap, FxHashSet};
 
@@ -9,19 +11,22 @@ use ra_syntax::{
     ast::{self, NameOwner},
     AstNode, Direction, SmolStr, SyntaxElement, SyntaxKind,
     SyntaxKind::*,
-    TextRange, T,
+    SyntaxNode, TextRange, T,
 };
 
 use crate::{
     db::RootDatabase,
+    expand::descend_into_macros,
     name_ref_kind::{classify_name_ref, NameRefKind::*},
     FileId,
 };
 
+pub use tags::{Highlight, HighlightModifier, HighlightModifiers, HighlightTag};
+
 #[derive(Debug)]
 pub struct HighlightedRange {
     pub range: TextRange,
-    pub tag: &'static str,
+    pub highlight: Highlight,
     pub binding_hash: Option<u64>,
 }
 
@@ -65,18 +70,6 @@ pub(crate) fn highlight(db: &RootDatabase, file_id: FileId) -> Vec<HighlightedRa
     let parse = db.parse(file_id);
     let root = parse.tree().syntax().clone();
 
-    fn calc_binding_hash(file_id: FileId, text: &SmolStr, shadow_count: u32) -> u64 {
-        fn hash<T: std::hash::Hash + std::fmt::Debug>(x: T) -> u64 {
-            use std::{collections::hash_map::DefaultHasher, hash::Hasher};
-
-            let mut hasher = DefaultHasher::new();
-            x.hash(&mut hasher);
-            hasher.finish()
-        }
-
-        hash((file_id, text, shadow_count))
-    }
-
     // Visited nodes to handle highlighting priorities
     // FIXME: retain only ranges here
     let mut highlighted: FxHashSet<SyntaxElement> = FxHashSet::default();
@@ -88,59 +81,57 @@ pub(crate) fn highlight(db: &RootDatabase, file_id: FileId) -> Vec<HighlightedRa
             continue;
         }
         let mut binding_hash = None;
-        let tag = match node.kind() {
+        let mut highlight: Highlight = match node.kind() {
             FN_DEF => {
                 bindings_shadow_count.clear();
                 continue;
             }
-            COMMENT => "comment",
-            STRING | RAW_STRING | RAW_BYTE_STRING | BYTE_STRING => "string",
-            ATTR => "attribute",
+            COMMENT => HighlightTag::Comment.into(),
+            STRING | RAW_STRING | RAW_BYTE_STRING | BYTE_STRING => HighlightTag::String.into(),
+            ATTR => HighlightTag::Attribute.into(),
             NAME_REF => {
                 if let Some(name_ref) = node.as_node().cloned().and_then(ast::NameRef::cast) {
                     // FIXME: try to reuse the SourceAnalyzers
                     let analyzer = hir::SourceAnalyzer::new(db, file_id, name_ref.syntax(), None);
-                    match classify_name_ref(db, &analyzer, &name_ref) {
-                        Some(Method(_)) => "function",
-                        Some(Macro(_)) => "macro",
-                        Some(FieldAccess(_)) => "field",
-                        Some(AssocItem(hir::AssocItem::Function(_))) => "function",
-                        Some(AssocItem(hir::AssocItem::Const(_))) => "constant",
-                        Some(AssocItem(hir::AssocItem::TypeAlias(_))) => "type",
-                        Some(Def(hir::ModuleDef::Module(_))) => "module",
-                        Some(Def(hir::ModuleDef::Function(_))) => "function",
-                        Some(Def(hir::ModuleDef::Adt(_))) => "type",
-                        Some(Def(hir::ModuleDef::EnumVariant(_))) => "constant",
-                        Some(Def(hir::ModuleDef::Const(_))) => "constant",
-                        Some(Def(hir::ModuleDef::Static(_))) => "constant",
-                        Some(Def(hir::ModuleDef::Trait(_))) => "type",
-                        Some(Def(hir::ModuleDef::TypeAlias(_))) => "type",
-                        Some(Def(hir::ModuleDef::BuiltinType(_))) => "type",
-                        Some(SelfType(_)) => "type",
-                        Some(Pat(ptr)) => {
-                            let pat = ptr.to_node(&root);
-                            if let Some(name) = pat.name() {
-                                let text = name.text();
-                                let shadow_count =
-                                    bindings_shadow_count.entry(text.clone()).or_default();
-                                binding_hash =
-                                    Some(calc_binding_hash(file_id, &text, *shadow_count))
-                            }
-
-                            if is_variable_mutable(db, &analyzer, ptr.to_node(&root)) {
-                                "variable.mut"
-                            } else {
-                                "variable"
-                            }
-                        }
-                        Some(SelfParam(_)) => "type",
-                        Some(GenericParam(_)) => "type",
-                        None => "text",
-                    }
+                    highlight_name_ref(
+                        db,
+                        &analyzer,
+                        &name_ref,
+                        &root,
+                        file_id,
+                        &mut bindings_shadow_count,
+                        &mut binding_hash,
+                    )
                 } else {
-                    "text"
+                    HighlightTag::Text.into()
                 }
             }
+            IDENT if node.as_token().map(|it| it.parent().kind()) == Some(TOKEN_TREE) => {
+                // An identifier in a macro call argument, highlight it the
+                // way it is used in the expansion
+                let token = descend_into_macros(db, file_id, node.as_token().unwrap().clone());
+                if token.file_id == file_id.into() {
+                    continue;
+                }
+                let name_ref = match ast::NameRef::cast(token.ast.parent()) {
+                    Some(it) => it,
+                    None => continue,
+                };
+                let analyzer = hir::SourceAnalyzer::new_for_source(
+                    db,
+                    token.with_ast(name_ref.syntax()),
+                    None,
+                );
+                highlight_name_ref(
+                    db,
+                    &analyzer,
+                    &name_ref,
+                    &root,
+                    file_id,
+                    &mut bindings_shadow_count,
+                    &mut binding_hash,
+                )
+            }
             NAME => {
                 if let Some(name) = node.as_node().cloned().and_then(ast::Name::cast) {
                     let analyzer = hir::SourceAnalyzer::new(db, file_id, name.syntax(), None);
@@ -154,31 +145,32 @@ pub(crate) fn highlight(db: &RootDatabase, file_id: FileId) -> Vec<HighlightedRa
                         }
 
                         if is_variable_mutable(db, &analyzer, pat) {
-                            "variable.mut"
+                            HighlightTag::Variable | HighlightModifier::Mutable
                         } else {
-                            "variable"
+                            HighlightTag::Variable.into()
                         }
                     } else {
-                        name.syntax()
-                            .parent()
-                            .map(|x| match x.kind() {
-                                TYPE_PARAM | STRUCT_DEF | ENUM_DEF | TRAIT_DEF | TYPE_ALIAS_DEF => {
-                                    "type"
-                                }
-                                RECORD_FIELD_DEF => "field",
-                                _ => "function",
-                            })
-                            .unwrap_or("function")
+                        let tag =
+                            name.syntax()
+                                .parent()
+                                .map(|x| match x.kind() {
+                                    TYPE_PARAM | STRUCT_DEF | ENUM_DEF | TRAIT_DEF
+                                    | TYPE_ALIAS_DEF => HighlightTag::Type,
+                                    RECORD_FIELD_DEF => HighlightTag::Field,
+                                    _ => HighlightTag::Function,
+                                })
+                                .unwrap_or(HighlightTag::Function);
+                        tag.into()
                     }
                 } else {
-                    "text"
+                    HighlightTag::Text.into()
                 }
             }
-            INT_NUMBER | FLOAT_NUMBER | CHAR | BYTE => "literal",
-            LIFETIME => "parameter",
-            T![unsafe] => "keyword.unsafe",
-            k if is_control_keyword(k) => "keyword.control",
-            k if k.is_keyword() => "keyword",
+            INT_NUMBER | FLOAT_NUMBER | CHAR | BYTE => HighlightTag::Literal.into(),
+            LIFETIME => HighlightTag::Parameter.into(),
+            T![unsafe] => HighlightTag::Keyword | HighlightModifier::Unsafe,
+            k if is_control_keyword(k) => HighlightTag::Keyword | HighlightModifier::Control,
+            k if k.is_keyword() => HighlightTag::Keyword.into(),
             _ => {
                 if let Some(macro_call) = node.as_node().cloned().and_then(ast::MacroCall::cast) {
                     if let Some(path) = macro_call.path() {
@@ -195,7 +187,7 @@ pub(crate) fn highlight(db: &RootDatabase, file_id: FileId) -> Vec<HighlightedRa
                                 }
                                 res.push(HighlightedRange {
                                     range: TextRange::from_to(range_start, range_end),
-                                    tag: "macro",
+                                    highlight: HighlightTag::Macro.into(),
                                     binding_hash: None,
                                 })
                             }
@@ -205,11 +197,87 @@ pub(crate) fn highlight(db: &RootDatabase, file_id: FileId) -> Vec<HighlightedRa
                 continue;
             }
         };
-        res.push(HighlightedRange { range: node.text_range(), tag, binding_hash })
+        if let Some(modifier) = enclosing_modifier(&node) {
+            highlight |= modifier;
+        }
+        res.push(HighlightedRange { range: node.text_range(), highlight, binding_hash })
     }
     res
 }
 
+fn highlight_name_ref(
+    db: &RootDatabase,
+    analyzer: &hir::SourceAnalyzer,
+    name_ref: &ast::NameRef,
+    root: &SyntaxNode,
+    file_id: FileId,
+    bindings_shadow_count: &mut FxHashMap<SmolStr, u32>,
+    binding_hash: &mut Option<u64>,
+) -> Highlight {
+    match classify_name_ref(db, analyzer, name_ref) {
+        Some(Method(_)) => HighlightTag::Function.into(),
+        Some(Macro(_)) => HighlightTag::Macro.into(),
+        Some(FieldAccess(_)) => HighlightTag::Field.into(),
+        Some(AssocItem(hir::AssocItem::Function(_))) => HighlightTag::Function.into(),
+        Some(AssocItem(hir::AssocItem::Const(_))) => HighlightTag::Constant.into(),
+        Some(AssocItem(hir::AssocItem::TypeAlias(_))) => HighlightTag::Type.into(),
+        Some(Def(hir::ModuleDef::Module(_))) => HighlightTag::Module.into(),
+        Some(Def(hir::ModuleDef::Function(_))) => HighlightTag::Function.into(),
+        Some(Def(hir::ModuleDef::Adt(_))) => HighlightTag::Type.into(),
+        Some(Def(hir::ModuleDef::EnumVariant(_))) => HighlightTag::Constant.into(),
+        Some(Def(hir::ModuleDef::Const(_))) => HighlightTag::Constant.into(),
+        Some(Def(hir::ModuleDef::Static(_))) => HighlightTag::Constant | HighlightModifier::Static,
+        Some(Def(hir::ModuleDef::Trait(_))) => HighlightTag::Type.into(),
+        Some(Def(hir::ModuleDef::TypeAlias(_))) => HighlightTag::Type.into(),
+        Some(Def(hir::ModuleDef::BuiltinType(_))) => HighlightTag::Type.into(),
+        Some(SelfType(_)) => HighlightTag::Type.into(),
+        Some(Pat(ptr)) => {
+            let pat = ptr.to_node(root);
+            if let Some(name) = pat.name() {
+                let text = name.text();
+                let shadow_count = bindings_shadow_count.entry(text.clone()).or_default();
+                *binding_hash = Some(calc_binding_hash(file_id, &text, *shadow_count))
+            }
+
+            if is_variable_mutable(db, analyzer, ptr.to_node(root)) {
+                HighlightTag::Variable | HighlightModifier::Mutable
+            } else {
+                HighlightTag::Variable.into()
+            }
+        }
+        Some(SelfParam(_)) => HighlightTag::Type.into(),
+        Some(GenericParam(_)) => HighlightTag::Type.into(),
+        None => HighlightTag::Text.into(),
+    }
+}
+
+fn calc_binding_hash(file_id: FileId, text: &SmolStr, shadow_count: u32) -> u64 {
+    fn hash<T: std::hash::Hash + std::fmt::Debug>(x: T) -> u64 {
+        use std::{collections::hash_map::DefaultHasher, hash::Hasher};
+
+        let mut hasher = DefaultHasher::new();
+        x.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    hash((file_id, text, shadow_count))
+}
+
+/// Marks tokens which are arguments of an attribute or a macro call.
+fn enclosing_modifier(element: &SyntaxElement) -> Option<HighlightModifier> {
+    let parent = match element {
+        SyntaxElement::Node(it) => it.parent()?,
+        SyntaxElement::Token(it) => it.parent(),
+    };
+    parent.ancestors().find_map(|it| match it.kind() {
+        ATTR => Some(HighlightModifier::Attribute),
+        TOKEN_TREE if it.parent().map(|it| it.kind()) == Some(MACRO_CALL) => {
+            Some(HighlightModifier::MacroGenerated)
+        }
+        _ => None,
+    })
+}
+
 pub(crate) fn highlight_as_html(db: &RootDatabase, file_id: FileId, rainbow: bool) -> String {
     let parse = db.parse(file_id);
 
@@ -252,7 +320,8 @@ pub(crate) fn highlight_as_html(db: &RootDatabase, file_id: FileId, rainbow: boo
         if ranges.is_empty() {
             buf.push_str(&text);
         } else {
-            let classes = ranges.iter().map(|x| x.tag).collect::<Vec<_>>().join(" ");
+            let classes =
+                ranges.iter().map(|x| x.highlight.to_string()).collect::<Vec<_>>().join(" ");
             let binding_hash = ranges.first().and_then(|x| x.binding_hash);
             let color = match (rainbow, binding_hash) {
                 (true, Some(hash)) => format!(
@@ -369,4 +438,31 @@ fn bar() {
         std::fs::write(dst_file, &actual_html).unwrap();
         assert_eq_text!(expected_html, actual_html);
     }
+
+    #[test]
+    fn highlights_identifiers_in_macro_call_arguments() {
+        let (analysis, file_id) = single_file(
+            r#"
+macro_rules! id {
+    ($($tt:tt)*) => { $($tt)* }
+}
+struct Foo { bar: u32 }
+fn baz(foo: Foo) -> u32 {
+    id!(foo.bar)
+}
+"#
+            .trim(),
+        );
+        let text = analysis.file_text(file_id).unwrap();
+        let highlights = analysis.highlight(file_id).unwrap();
+        let highlight_at = |offset: usize| {
+            highlights
+                .iter()
+                .find(|it| it.range.start().to_usize() == offset)
+                .map(|it| it.highlight.to_string())
+        };
+        let call = text.find("id!(").unwrap();
+        assert_eq!(highlight_at(call + 4).as_ref().map(String::as_str), Some("variable.macro"));
+        assert_eq!(highlight_at(call + 8).as_ref().map(String::as_str), Some("field.macro"));
+    }
 }
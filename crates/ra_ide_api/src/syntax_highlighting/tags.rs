@@ -0,0 +1,184 @@
+//! Defines token tags we use for syntax highlighting.
+//! A tag is not unlike a CSS class.
+
+use std::{fmt, ops};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Highlight {
+    pub tag: HighlightTag,
+    pub modifiers: HighlightModifiers,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct HighlightModifiers(u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HighlightTag {
+    Attribute,
+    Comment,
+    Constant,
+    Field,
+    Function,
+    Keyword,
+    Literal,
+    Macro,
+    Module,
+    Parameter,
+    String,
+    Text,
+    Type,
+    Variable,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum HighlightModifier {
+    /// Used with keywords like `if` and `break`.
+    Control = 0,
+    /// A mutable binding, or a binding of a `&mut` reference.
+    Mutable,
+    Unsafe,
+    Static,
+    /// Tokens inside of an attribute.
+    Attribute,
+    /// Tokens inside of a macro call, which end up in the expansion.
+    MacroGenerated,
+}
+
+impl HighlightTag {
+    pub const ALL: &'static [HighlightTag] = &[
+        HighlightTag::Attribute,
+        HighlightTag::Comment,
+        HighlightTag::Constant,
+        HighlightTag::Field,
+        HighlightTag::Function,
+        HighlightTag::Keyword,
+        HighlightTag::Literal,
+        HighlightTag::Macro,
+        HighlightTag::Module,
+        HighlightTag::Parameter,
+        HighlightTag::String,
+        HighlightTag::Text,
+        HighlightTag::Type,
+        HighlightTag::Variable,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HighlightTag::Attribute => "attribute",
+            HighlightTag::Comment => "comment",
+            HighlightTag::Constant => "constant",
+            HighlightTag::Field => "field",
+            HighlightTag::Function => "function",
+            HighlightTag::Keyword => "keyword",
+            HighlightTag::Literal => "literal",
+            HighlightTag::Macro => "macro",
+            HighlightTag::Module => "module",
+            HighlightTag::Parameter => "parameter",
+            HighlightTag::String => "string",
+            HighlightTag::Text => "text",
+            HighlightTag::Type => "type",
+            HighlightTag::Variable => "variable",
+        }
+    }
+}
+
+impl fmt::Display for HighlightTag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl HighlightModifier {
+    pub const ALL: &'static [HighlightModifier] = &[
+        HighlightModifier::Control,
+        HighlightModifier::Mutable,
+        HighlightModifier::Unsafe,
+        HighlightModifier::Static,
+        HighlightModifier::Attribute,
+        HighlightModifier::MacroGenerated,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HighlightModifier::Control => "control",
+            HighlightModifier::Mutable => "mut",
+            HighlightModifier::Unsafe => "unsafe",
+            HighlightModifier::Static => "static",
+            HighlightModifier::Attribute => "attribute",
+            HighlightModifier::MacroGenerated => "macro",
+        }
+    }
+
+    pub fn mask(self) -> u32 {
+        1 << (self as u32)
+    }
+}
+
+impl fmt::Display for HighlightModifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+/// Renders the highlight as a dotted list, like `variable.mut`.
+impl fmt::Display for Highlight {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.tag)?;
+        for modifier in self.modifiers.iter() {
+            write!(f, ".{}", modifier)?
+        }
+        Ok(())
+    }
+}
+
+impl From<HighlightTag> for Highlight {
+    fn from(tag: HighlightTag) -> Highlight {
+        Highlight::new(tag)
+    }
+}
+
+impl Highlight {
+    pub(crate) fn new(tag: HighlightTag) -> Highlight {
+        Highlight { tag, modifiers: HighlightModifiers::default() }
+    }
+}
+
+impl ops::BitOr<HighlightModifier> for HighlightTag {
+    type Output = Highlight;
+
+    fn bitor(self, rhs: HighlightModifier) -> Highlight {
+        Highlight::new(self) | rhs
+    }
+}
+
+impl ops::BitOrAssign<HighlightModifier> for HighlightModifiers {
+    fn bitor_assign(&mut self, rhs: HighlightModifier) {
+        self.0 |= rhs.mask();
+    }
+}
+
+impl ops::BitOrAssign<HighlightModifier> for Highlight {
+    fn bitor_assign(&mut self, rhs: HighlightModifier) {
+        self.modifiers |= rhs;
+    }
+}
+
+impl ops::BitOr<HighlightModifier> for Highlight {
+    type Output = Highlight;
+
+    fn bitor(mut self, rhs: HighlightModifier) -> Highlight {
+        self |= rhs;
+        self
+    }
+}
+
+impl HighlightModifiers {
+    pub fn contains(self, modifier: HighlightModifier) -> bool {
+        self.0 & modifier.mask() != 0
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = HighlightModifier> {
+        HighlightModifier::ALL.iter().copied().filter(move |it| self.contains(*it))
+    }
+}
@@ -1,19 +1,30 @@
 //! FIXME: write short doc here
 
 use lsp_types::{
-    CodeActionProviderCapability, CodeLensOptions, CompletionOptions,
+    CodeActionOptions, CodeActionProviderCapability, CodeLensOptions, CompletionOptions,
     DocumentOnTypeFormattingOptions, FoldingRangeProviderCapability, GenericCapability,
     ImplementationProviderCapability, RenameOptions, RenameProviderCapability, ServerCapabilities,
     SignatureHelpOptions, TextDocumentSyncCapability, TextDocumentSyncKind,
     TextDocumentSyncOptions, TypeDefinitionProviderCapability,
 };
 
+/// Serializes `server_capabilities`, adding the capabilities `lsp-types`
+/// doesn't know about yet.
+pub fn server_capabilities_json() -> serde_json::Value {
+    let mut caps = serde_json::to_value(server_capabilities()).unwrap();
+    caps["semanticTokensProvider"] =
+        serde_json::to_value(crate::semantic_tokens::semantic_tokens_options()).unwrap();
+    caps["callHierarchyProvider"] = serde_json::Value::Bool(true);
+    caps
+}
+
 pub fn server_capabilities() -> ServerCapabilities {
     ServerCapabilities {
         text_document_sync: Some(TextDocumentSyncCapability::Options(TextDocumentSyncOptions {
             open_close: Some(true),
-            change: Some(TextDocumentSyncKind::Full),
+            change: Some(TextDocumentSyncKind::Incremental),
             will_save: None,
+            // Registered dynamically when format on save is enabled
             will_save_wait_until: None,
             save: None,
         })),
@@ -32,10 +43,18 @@ pub fn server_capabilities() -> ServerCapabilities {
         document_highlight_provider: Some(true),
         document_symbol_provider: Some(true),
         workspace_symbol_provider: Some(true),
-        code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+        code_action_provider: Some(CodeActionProviderCapability::Options(CodeActionOptions {
+            code_action_kinds: Some(vec![
+                "quickfix".to_string(),
+                "refactor.extract".to_string(),
+                "refactor.inline".to_string(),
+                "refactor.rewrite".to_string(),
+                "source.organizeImports".to_string(),
+            ]),
+        })),
         code_lens_provider: Some(CodeLensOptions { resolve_provider: Some(true) }),
         document_formatting_provider: Some(true),
-        document_range_formatting_provider: None,
+        document_range_formatting_provider: Some(true),
         document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
             first_trigger_character: "=".to_string(),
             more_trigger_character: Some(vec![".".to_string()]),
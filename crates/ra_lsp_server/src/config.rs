@@ -1,5 +1,6 @@
 //! FIXME: write short doc here
 
+use ra_project_model::CargoFeatures;
 use rustc_hash::FxHashMap;
 
 use serde::{Deserialize, Deserializer};
@@ -22,6 +23,29 @@ pub struct ServerConfig {
 
     pub lru_capacity: Option<usize>,
 
+    /// Whether to format documents with `rustfmt` on `willSaveWaitUntil`.
+    ///
+    /// Defaults to `false`
+    #[serde(deserialize_with = "nullable_bool_false")]
+    pub format_on_save: bool,
+
+    /// Whether to build `proc-macro` crates with `cargo check` and expand
+    /// their macros in a separate process.
+    ///
+    /// Defaults to `false`
+    #[serde(deserialize_with = "nullable_bool_false")]
+    pub proc_macro_enabled: bool,
+
+    /// Whether to run the build scripts with `cargo check`, so that the files
+    /// they generate in `OUT_DIR` can be included.
+    ///
+    /// Defaults to `false`
+    #[serde(deserialize_with = "nullable_bool_false")]
+    pub load_out_dirs_from_check: bool,
+
+    /// The features to load the cargo workspaces and run `cargo check` with.
+    pub cargo_features: CargoFeatures,
+
     /// For internal usage to make integrated tests faster.
     #[serde(deserialize_with = "nullable_bool_true")]
     pub with_sysroot: bool,
@@ -37,6 +61,10 @@ impl Default for ServerConfig {
             exclude_globs: Vec::new(),
             use_client_watching: false,
             lru_capacity: None,
+            format_on_save: false,
+            proc_macro_enabled: false,
+            load_out_dirs_from_check: false,
+            cargo_features: CargoFeatures::default(),
             with_sysroot: true,
             feature_flags: FxHashMap::default(),
         }
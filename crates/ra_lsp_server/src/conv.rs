@@ -240,8 +240,13 @@ impl ConvWith<&LineIndex> for Fold {
             kind: match self.kind {
                 FoldKind::Comment => Some(lsp_types::FoldingRangeKind::Comment),
                 FoldKind::Imports => Some(lsp_types::FoldingRangeKind::Imports),
-                FoldKind::Mods => None,
-                FoldKind::Block => None,
+                FoldKind::Region => Some(lsp_types::FoldingRangeKind::Region),
+                FoldKind::Mods
+                | FoldKind::Block
+                | FoldKind::ArgList
+                | FoldKind::MatchArm
+                | FoldKind::WhereClause
+                | FoldKind::Attrs => None,
             },
         }
     }
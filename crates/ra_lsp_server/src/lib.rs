@@ -7,12 +7,13 @@ mod conv;
 mod main_loop;
 mod markdown;
 pub mod req;
+mod semantic_tokens;
 pub mod config;
 mod world;
 
 pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
 pub use crate::{
-    caps::server_capabilities,
+    caps::{server_capabilities, server_capabilities_json},
     config::ServerConfig,
     main_loop::LspError,
     main_loop::{main_loop, show_message},
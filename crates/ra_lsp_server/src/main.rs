@@ -8,7 +8,11 @@ use ra_prof;
 
 fn main() -> Result<()> {
     setup_logging()?;
-    run_server()?;
+    match std::env::args().nth(1).as_ref().map(String::as_str) {
+        // The proc macro expander runs as a child process of the server.
+        Some("proc-macro") => ra_proc_macro_srv::run()?,
+        _ => run_server()?,
+    }
     Ok(())
 }
 
@@ -32,7 +36,7 @@ fn run_server() -> Result<()> {
     log::info!("lifecycle: server started");
 
     let (connection, io_threads) = Connection::stdio();
-    let server_capabilities = serde_json::to_value(ra_lsp_server::server_capabilities()).unwrap();
+    let server_capabilities = ra_lsp_server::server_capabilities_json();
 
     let initialize_params = connection.initialize(server_capabilities)?;
     let initialize_params: lsp_types::InitializeParams = serde_json::from_value(initialize_params)?;
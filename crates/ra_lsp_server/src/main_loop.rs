@@ -8,9 +8,13 @@ use std::{error::Error, fmt, path::PathBuf, sync::Arc, time::Instant};
 
 use crossbeam_channel::{select, unbounded, RecvError, Sender};
 use lsp_server::{Connection, ErrorCode, Message, Notification, Request, RequestId, Response};
-use lsp_types::{ClientCapabilities, NumberOrString};
-use ra_ide_api::{Canceled, FeatureFlags, FileId, LibraryData, SourceRootId};
+use lsp_types::{ClientCapabilities, NumberOrString, TextDocumentContentChangeEvent};
+use ra_ide_api::{Canceled, FeatureFlags, FileId, LibraryData, LineIndex, SourceRootId};
+use ra_proc_macro::ProcMacroClient;
 use ra_prof::profile;
+use ra_project_model::ProjectWorkspace;
+use ra_syntax::TextRange;
+use ra_text_edit::AtomTextEdit;
 use ra_vfs::{VfsTask, Watch};
 use relative_path::RelativePathBuf;
 use rustc_hash::FxHashSet;
@@ -18,6 +22,7 @@ use serde::{de::DeserializeOwned, Serialize};
 use threadpool::ThreadPool;
 
 use crate::{
+    conv::ConvWith,
     main_loop::{
         pending_requests::{PendingRequest, PendingRequests},
         subscriptions::Subscriptions,
@@ -67,6 +72,7 @@ pub fn main_loop(
                 let workspace = ra_project_model::ProjectWorkspace::discover_with_sysroot(
                     ws_root.as_path(),
                     config.with_sysroot,
+                    &config.cargo_features,
                 );
                 match workspace {
                     Ok(workspace) => loaded_workspaces.push(workspace),
@@ -111,6 +117,25 @@ pub fn main_loop(
             connection.sender.send(request.into()).unwrap();
         }
 
+        if config.format_on_save {
+            let registration_options = req::TextDocumentRegistrationOptions {
+                document_selector: Some(vec![req::DocumentFilter {
+                    language: Some("rust".to_string()),
+                    scheme: None,
+                    pattern: None,
+                }]),
+            };
+            let registration = req::Registration {
+                id: "format-on-save".to_string(),
+                method: "textDocument/willSaveWaitUntil".to_string(),
+                register_options: Some(serde_json::to_value(registration_options).unwrap()),
+            };
+            let params = req::RegistrationParams { registrations: vec![registration] };
+            let request =
+                request_new::<req::RegisterCapability>(loop_state.next_request_id(), params);
+            connection.sender.send(request.into()).unwrap();
+        }
+
         let feature_flags = {
             let mut ff = FeatureFlags::default();
             for (flag, value) in config.feature_flags {
@@ -127,14 +152,27 @@ pub fn main_loop(
         };
         log::info!("feature_flags: {:#?}", feature_flags);
 
+        let proc_macro_client = if config.proc_macro_enabled {
+            let client = std::env::current_exe()
+                .and_then(|path| ProcMacroClient::extern_process(&path, vec!["proc-macro"]));
+            client.unwrap_or_else(|e| {
+                log::error!("failed to run the proc macro server: {}", e);
+                ProcMacroClient::dummy()
+            })
+        } else {
+            ProcMacroClient::dummy()
+        };
+
         WorldState::new(
             ws_roots,
             workspaces,
             config.lru_capacity,
             &globs,
             Watch(!config.use_client_watching),
+            proc_macro_client,
             Options {
                 publish_decorations: config.publish_decorations,
+                format_on_save: config.format_on_save,
                 supports_location_link: client_caps
                     .text_document
                     .and_then(|it| it.definition)
@@ -149,6 +187,15 @@ pub fn main_loop(
     let (task_sender, task_receiver) = unbounded::<Task>();
     let (libdata_sender, libdata_receiver) = unbounded::<LibraryData>();
 
+    if config.proc_macro_enabled || config.load_out_dirs_from_check {
+        load_build_outputs_on_threadpool(
+            &pool,
+            (*world_state.workspaces).clone(),
+            task_sender.clone(),
+            connection.sender.clone(),
+        );
+    }
+
     log::info!("server initialized, serving requests");
     {
         let task_sender = task_sender;
@@ -204,6 +251,8 @@ pub fn main_loop(
 enum Task {
     Respond(Response),
     Notify(Notification),
+    /// The workspaces, with the outputs of building them.
+    BuildOutputs(Vec<ProjectWorkspace>),
 }
 
 enum Event {
@@ -241,6 +290,12 @@ impl fmt::Debug for Event {
                     .field("error", &resp.error)
                     .finish();
             }
+            Event::Task(Task::BuildOutputs(workspaces)) => {
+                return f
+                    .debug_struct("BuildOutputs")
+                    .field("n_workspaces", &workspaces.len())
+                    .finish();
+            }
             _ => (),
         }
         match self {
@@ -297,6 +352,9 @@ fn loop_turn(
     let mut state_changed = false;
     match event {
         Event::Task(task) => {
+            if let Task::BuildOutputs(_) = &task {
+                state_changed = true;
+            }
             on_task(task, &connection.sender, &mut loop_state.pending_requests, world_state);
             world_state.maybe_collect_garbage();
         }
@@ -395,6 +453,9 @@ fn on_task(
         Task::Notify(n) => {
             msg_sender.send(n.into()).unwrap();
         }
+        Task::BuildOutputs(workspaces) => {
+            state.set_workspaces(workspaces);
+        }
     }
 }
 
@@ -428,6 +489,8 @@ fn on_request(
         })?
         .on::<req::AnalyzerStatus>(handlers::handle_analyzer_status)?
         .on::<req::SyntaxTree>(handlers::handle_syntax_tree)?
+        .on::<req::ExpandMacro>(handlers::handle_expand_macro)?
+        .on::<req::ChangeSignature>(handlers::handle_change_signature)?
         .on::<req::OnTypeFormatting>(handlers::handle_on_type_formatting)?
         .on::<req::DocumentSymbolRequest>(handlers::handle_document_symbol)?
         .on::<req::WorkspaceSymbol>(handlers::handle_workspace_symbol)?
@@ -437,6 +500,8 @@ fn on_request(
         .on::<req::ParentModule>(handlers::handle_parent_module)?
         .on::<req::Runnables>(handlers::handle_runnables)?
         .on::<req::DecorationsRequest>(handlers::handle_decorations)?
+        .on::<req::SemanticTokensFullRequest>(handlers::handle_semantic_tokens)?
+        .on::<req::SemanticTokensFullDeltaRequest>(handlers::handle_semantic_tokens_delta)?
         .on::<req::Completion>(handlers::handle_completion)?
         .on::<req::CodeActionRequest>(handlers::handle_code_action)?
         .on::<req::CodeLensRequest>(handlers::handle_code_lens)?
@@ -447,7 +512,12 @@ fn on_request(
         .on::<req::PrepareRenameRequest>(handlers::handle_prepare_rename)?
         .on::<req::Rename>(handlers::handle_rename)?
         .on::<req::References>(handlers::handle_references)?
+        .on::<req::CallHierarchyPrepare>(handlers::handle_call_hierarchy_prepare)?
+        .on::<req::CallHierarchyIncomingCalls>(handlers::handle_call_hierarchy_incoming)?
+        .on::<req::CallHierarchyOutgoingCalls>(handlers::handle_call_hierarchy_outgoing)?
         .on::<req::Formatting>(handlers::handle_formatting)?
+        .on::<req::RangeFormatting>(handlers::handle_range_formatting)?
+        .on::<req::WillSaveWaitUntil>(handlers::handle_will_save_wait_until)?
         .on::<req::DocumentHighlightRequest>(handlers::handle_document_highlight)?
         .on::<req::InlayHints>(handlers::handle_inlay_hints)?
         .finish();
@@ -493,11 +563,24 @@ fn on_notification(
         Err(not) => not,
     };
     let not = match notification_cast::<req::DidChangeTextDocument>(not) {
-        Ok(mut params) => {
+        Ok(params) => {
             let uri = params.text_document.uri;
             let path = uri.to_file_path().map_err(|()| format!("invalid uri: {}", uri))?;
-            let text =
-                params.content_changes.pop().ok_or_else(|| "empty changes".to_string())?.text;
+            let file_id = state
+                .vfs
+                .read()
+                .path2file(&path)
+                .map(|it| FileId(it.0))
+                .ok_or_else(|| format!("unknown file: {}", path.display()))?;
+            // The VFS changes of the previous notification were already
+            // committed, so the analysis holds the current text of the document.
+            let mut text = state.analysis_host.analysis().file_text(file_id)?.to_string();
+            match apply_document_changes(&mut text, params.content_changes) {
+                Some(edits) => state.file_edits.entry(file_id).or_default().extend(edits),
+                None => {
+                    state.file_edits.remove(&file_id);
+                }
+            }
             state.vfs.write().change_file_overlay(path.as_path(), text);
             return Ok(());
         }
@@ -510,6 +593,7 @@ fn on_notification(
             if let Some(file_id) = state.vfs.write().remove_file_overlay(path.as_path()) {
                 subs.remove_sub(FileId(file_id.0));
             }
+            state.semantic_tokens_cache.lock().remove(&uri);
             let params = req::PublishDiagnosticsParams { uri, diagnostics: Vec::new() };
             let not = notification_new::<req::PublishDiagnostics>(params);
             msg_sender.send(not.into()).unwrap();
@@ -708,6 +792,29 @@ fn update_file_notifications_on_threadpool(
     });
 }
 
+/// Builds the workspaces in the background, as this runs `cargo check` and can
+/// take minutes, and reports the build failures.
+fn load_build_outputs_on_threadpool(
+    pool: &ThreadPool,
+    mut workspaces: Vec<ProjectWorkspace>,
+    sender: Sender<Task>,
+    msg_sender: Sender<Message>,
+) {
+    pool.execute(move || {
+        for workspace in workspaces.iter_mut() {
+            if let Err(e) = workspace.load_build_outputs() {
+                log::error!("building the workspace failed: {}", e);
+                show_message(
+                    req::MessageType::Error,
+                    format!("rust-analyzer failed to build the workspace: {}", e),
+                    &msg_sender,
+                );
+            }
+        }
+        sender.send(Task::BuildOutputs(workspaces)).unwrap();
+    });
+}
+
 pub fn show_message(typ: req::MessageType, message: impl Into<String>, sender: &Sender<Message>) {
     let message = message.into();
     let params = req::ShowMessageParams { typ, message };
@@ -719,6 +826,41 @@ fn is_canceled(e: &Box<dyn std::error::Error + Send + Sync>) -> bool {
     e.downcast_ref::<Canceled>().is_some()
 }
 
+/// Applies the `didChange` content changes to the text of a document.
+///
+/// The changes must be applied sequentially: the range of each change refers
+/// to the text produced by the previous ones. Returns the changes as edits, to
+/// reparse the document incrementally, unless its whole text was replaced.
+fn apply_document_changes(
+    old_text: &mut String,
+    content_changes: Vec<TextDocumentContentChangeEvent>,
+) -> Option<Vec<AtomTextEdit>> {
+    let mut line_index = LineIndex::new(old_text);
+    let mut line_index_is_stale = false;
+    let mut edits = Some(Vec::new());
+    for change in content_changes {
+        match change.range {
+            Some(range) => {
+                if line_index_is_stale {
+                    line_index = LineIndex::new(old_text);
+                }
+                let range: TextRange = range.conv_with(&line_index);
+                old_text
+                    .replace_range(range.start().to_usize()..range.end().to_usize(), &change.text);
+                if let Some(edits) = &mut edits {
+                    edits.push(AtomTextEdit::replace(range, change.text));
+                }
+            }
+            None => {
+                *old_text = change.text;
+                edits = None;
+            }
+        }
+        line_index_is_stale = true;
+    }
+    edits
+}
+
 fn notification_is<N: lsp_types::notification::Notification>(notification: &Notification) -> bool {
     notification.method == N::METHOD
 }
@@ -746,3 +888,72 @@ where
 {
     Request::new(id, R::METHOD.to_string(), params)
 }
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{Position, Range, TextDocumentContentChangeEvent};
+
+    use super::apply_document_changes;
+
+    fn change(
+        range: Option<((u64, u64), (u64, u64))>,
+        text: &str,
+    ) -> TextDocumentContentChangeEvent {
+        TextDocumentContentChangeEvent {
+            range: range.map(|((l1, c1), (l2, c2))| {
+                Range::new(Position::new(l1, c1), Position::new(l2, c2))
+            }),
+            range_length: None,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_apply_document_changes() {
+        let mut text = String::new();
+        apply_document_changes(&mut text, vec![]);
+        assert_eq!(text, "");
+
+        apply_document_changes(&mut text, vec![change(None, "the")]);
+        assert_eq!(text, "the");
+
+        let edits = apply_document_changes(
+            &mut text,
+            vec![change(Some(((0, 3), (0, 3))), " quick"), change(Some(((0, 9), (0, 9))), " fox")],
+        )
+        .unwrap();
+        assert_eq!(text, "the quick fox");
+        assert_eq!(edits.iter().fold("the".to_string(), |text, edit| edit.apply(text)), text);
+
+        apply_document_changes(
+            &mut text,
+            vec![
+                change(Some(((0, 9), (0, 13))), "\ndog"),
+                change(Some(((1, 0), (1, 3))), "lazy\ndog"),
+            ],
+        );
+        assert_eq!(text, "the quick\nlazy\ndog");
+
+        apply_document_changes(
+            &mut text,
+            vec![change(Some(((0, 4), (2, 0))), ""), change(Some(((0, 0), (0, 4))), "a ")],
+        );
+        assert_eq!(text, "a dog");
+
+        apply_document_changes(
+            &mut text,
+            vec![
+                change(Some(((0, 2), (0, 2))), "\u{1F600} "),
+                change(Some(((0, 5), (0, 5))), "big "),
+            ],
+        );
+        assert_eq!(text, "a \u{1F600} big dog");
+
+        let edits = apply_document_changes(
+            &mut text,
+            vec![change(Some(((0, 0), (0, 12))), ""), change(None, "fresh")],
+        );
+        assert_eq!(text, "fresh");
+        assert!(edits.is_none());
+    }
+}
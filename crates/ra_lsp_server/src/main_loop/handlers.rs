@@ -5,11 +5,15 @@ use std::{fmt::Write as _, io::Write as _};
 use lsp_server::ErrorCode;
 use lsp_types::{
     CodeAction, CodeActionResponse, CodeLens, Command, CompletionItem, Diagnostic,
-    DocumentFormattingParams, DocumentHighlight, DocumentSymbol, FoldingRange, FoldingRangeParams,
-    Hover, HoverContents, Location, MarkupContent, MarkupKind, Position, PrepareRenameResponse,
-    Range, RenameParams, SymbolInformation, TextDocumentIdentifier, TextEdit, WorkspaceEdit,
+    DocumentFormattingParams, DocumentHighlight, DocumentRangeFormattingParams, DocumentSymbol,
+    FoldingRange, FoldingRangeParams, Hover, HoverContents, Location, MarkupContent, MarkupKind,
+    Position, PrepareRenameResponse, Range, RenameParams, SymbolInformation,
+    TextDocumentIdentifier, TextEdit, WillSaveTextDocumentParams, WorkspaceEdit,
+};
+use ra_ide_api::{
+    AssistId, FileId, FilePosition, FileRange, Highlight, HighlightModifier, NavigationTarget,
+    Query, Runnable, RunnableKind, SearchScope, SignatureParam,
 };
-use ra_ide_api::{AssistId, FileId, FilePosition, FileRange, Query, Runnable, RunnableKind};
 use ra_prof::profile;
 use ra_syntax::{AstNode, SyntaxKind, TextRange, TextUnit};
 use rustc_hash::FxHashMap;
@@ -20,6 +24,7 @@ use crate::{
     cargo_target_spec::{runnable_args, CargoTargetSpec},
     conv::{to_location, Conv, ConvWith, MapConvWith, TryConvWith, TryConvWithToVec},
     req::{self, Decoration, InlayHint, InlayHintsParams, InlayKind},
+    semantic_tokens::{diff_tokens, new_result_id, to_semantic_tokens},
     world::WorldSnapshot,
     LspError, Result,
 };
@@ -43,6 +48,18 @@ pub fn handle_syntax_tree(world: WorldSnapshot, params: req::SyntaxTreeParams) -
     Ok(res)
 }
 
+pub fn handle_expand_macro(
+    world: WorldSnapshot,
+    params: req::ExpandMacroParams,
+) -> Result<Option<req::ExpandedMacro>> {
+    let _p = profile("handle_expand_macro");
+    let file_id = params.text_document.try_conv_with(&world)?;
+    let line_index = world.analysis().file_line_index(file_id)?;
+    let offset = params.position.conv_with(&line_index);
+    let res = world.analysis().expand_macro(FilePosition { file_id, offset })?;
+    Ok(res.map(|it| req::ExpandedMacro { name: it.name, expansion: it.expansion }))
+}
+
 pub fn handle_selection_range(
     world: WorldSnapshot,
     params: req::SelectionRangeParams,
@@ -130,6 +147,30 @@ pub fn handle_on_enter(
     }
 }
 
+pub fn handle_change_signature(
+    world: WorldSnapshot,
+    params: req::ChangeSignatureParams,
+) -> Result<Option<req::SourceChange>> {
+    let _p = profile("handle_change_signature");
+    let file_id = params.text_document.try_conv_with(&world)?;
+    let line_index = world.analysis().file_line_index(file_id)?;
+    let offset = params.position.conv_with(&line_index);
+    let signature = params
+        .params
+        .into_iter()
+        .map(|it| match it {
+            req::SignatureParam::Existing { index } => SignatureParam::Existing(index),
+            req::SignatureParam::Added { param, default } => {
+                SignatureParam::Added { param, default }
+            }
+        })
+        .collect::<Vec<_>>();
+    match world.analysis().change_signature(FilePosition { file_id, offset }, &signature)? {
+        None => Ok(None),
+        Some(edit) => Ok(Some(edit.try_conv_with(&world)?)),
+    }
+}
+
 pub fn handle_on_type_formatting(
     world: WorldSnapshot,
     params: req::DocumentOnTypeFormattingParams,
@@ -337,6 +378,47 @@ pub fn handle_decorations(
     highlight(&world, file_id)
 }
 
+pub fn handle_semantic_tokens(
+    world: WorldSnapshot,
+    params: req::SemanticTokensParams,
+) -> Result<Option<req::SemanticTokens>> {
+    let _p = profile("handle_semantic_tokens");
+    let file_id = params.text_document.try_conv_with(&world)?;
+    let tokens = semantic_tokens(&world, file_id)?;
+    world.semantic_tokens_cache.lock().insert(params.text_document.uri, tokens.clone());
+    Ok(Some(tokens))
+}
+
+pub fn handle_semantic_tokens_delta(
+    world: WorldSnapshot,
+    params: req::SemanticTokensDeltaParams,
+) -> Result<Option<req::SemanticTokensFullDeltaResult>> {
+    let _p = profile("handle_semantic_tokens_delta");
+    let file_id = params.text_document.try_conv_with(&world)?;
+    let tokens = semantic_tokens(&world, file_id)?;
+
+    let mut cache = world.semantic_tokens_cache.lock();
+    let previous = cache.insert(params.text_document.uri, tokens.clone());
+    let res = match previous {
+        Some(ref previous) if previous.result_id.as_ref() == Some(&params.previous_result_id) => {
+            req::SemanticTokensFullDeltaResult::TokensDelta(req::SemanticTokensDelta {
+                edits: diff_tokens(previous, &tokens),
+                result_id: tokens.result_id,
+            })
+        }
+        _ => req::SemanticTokensFullDeltaResult::Tokens(tokens),
+    };
+    Ok(Some(res))
+}
+
+fn semantic_tokens(world: &WorldSnapshot, file_id: FileId) -> Result<req::SemanticTokens> {
+    let text = world.analysis().file_text(file_id)?;
+    let line_index = world.analysis().file_line_index(file_id)?;
+    let ranges = world.analysis().highlight(file_id)?;
+    let data = to_semantic_tokens(&text, &line_index, ranges);
+    Ok(req::SemanticTokens { result_id: Some(new_result_id()), data })
+}
+
 pub fn handle_completion(
     world: WorldSnapshot,
     params: req::CompletionParams,
@@ -475,50 +557,215 @@ pub fn handle_references(
     params: req::ReferenceParams,
 ) -> Result<Option<Vec<Location>>> {
     let position = params.text_document_position.try_conv_with(&world)?;
-    let line_index = world.analysis().file_line_index(position.file_id)?;
 
-    let refs = match world.analysis().find_all_refs(position)? {
+    let refs = match world.analysis().find_all_refs(position, None)? {
         None => return Ok(None),
         Some(refs) => refs,
     };
 
+    // References can be in other files, so each needs the line index of its own file.
+    let location = |r: &FileRange| -> Option<Location> {
+        let line_index = world.analysis().file_line_index(r.file_id).ok()?;
+        to_location(r.file_id, r.range, &world, &line_index).ok()
+    };
     let locations = if params.context.include_declaration {
-        refs.into_iter()
-            .filter_map(|r| to_location(r.file_id, r.range, &world, &line_index).ok())
-            .collect()
+        refs.into_iter().filter_map(|r| location(&r)).collect()
     } else {
         // Only iterate over the references if include_declaration was false
-        refs.references()
-            .iter()
-            .filter_map(|r| to_location(r.file_id, r.range, &world, &line_index).ok())
-            .collect()
+        refs.references().iter().filter_map(location).collect()
     };
 
     Ok(Some(locations))
 }
 
+pub fn handle_call_hierarchy_prepare(
+    world: WorldSnapshot,
+    params: req::TextDocumentPositionParams,
+) -> Result<Option<Vec<req::CallHierarchyItem>>> {
+    let _p = profile("handle_call_hierarchy_prepare");
+    let position = params.try_conv_with(&world)?;
+    let nav_info = match world.analysis().call_hierarchy(position)? {
+        None => return Ok(None),
+        Some(it) => it,
+    };
+    let res = nav_info
+        .info
+        .iter()
+        .map(|nav| to_call_hierarchy_item(&world, nav))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Some(res))
+}
+
+pub fn handle_call_hierarchy_incoming(
+    world: WorldSnapshot,
+    params: req::CallHierarchyCallsParams,
+) -> Result<Option<Vec<req::CallHierarchyIncomingCall>>> {
+    let _p = profile("handle_call_hierarchy_incoming");
+    let position = call_hierarchy_item_position(&world, &params.item)?;
+    let calls = match world.analysis().incoming_calls(position)? {
+        None => return Ok(None),
+        Some(it) => it,
+    };
+    let mut res = Vec::new();
+    for call in calls {
+        let line_index = world.analysis().file_line_index(call.target.file_id())?;
+        res.push(req::CallHierarchyIncomingCall {
+            from: to_call_hierarchy_item(&world, &call.target)?,
+            from_ranges: call.ranges.into_iter().map(|it| it.conv_with(&line_index)).collect(),
+        });
+    }
+    Ok(Some(res))
+}
+
+pub fn handle_call_hierarchy_outgoing(
+    world: WorldSnapshot,
+    params: req::CallHierarchyCallsParams,
+) -> Result<Option<Vec<req::CallHierarchyOutgoingCall>>> {
+    let _p = profile("handle_call_hierarchy_outgoing");
+    let position = call_hierarchy_item_position(&world, &params.item)?;
+    let calls = match world.analysis().outgoing_calls(position)? {
+        None => return Ok(None),
+        Some(it) => it,
+    };
+    // Outgoing calls are all located in the item's own file.
+    let line_index = world.analysis().file_line_index(position.file_id)?;
+    let res = calls
+        .into_iter()
+        .map(|call| {
+            Ok(req::CallHierarchyOutgoingCall {
+                to: to_call_hierarchy_item(&world, &call.target)?,
+                from_ranges: call.ranges.into_iter().map(|it| it.conv_with(&line_index)).collect(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Some(res))
+}
+
+fn call_hierarchy_item_position(
+    world: &WorldSnapshot,
+    item: &req::CallHierarchyItem,
+) -> Result<FilePosition> {
+    let file_id = item.uri.try_conv_with(world)?;
+    let line_index = world.analysis().file_line_index(file_id)?;
+    let offset = item.selection_range.start.conv_with(&line_index);
+    Ok(FilePosition { file_id, offset })
+}
+
+fn to_call_hierarchy_item(
+    world: &WorldSnapshot,
+    nav: &NavigationTarget,
+) -> Result<req::CallHierarchyItem> {
+    let line_index = world.analysis().file_line_index(nav.file_id())?;
+    Ok(req::CallHierarchyItem {
+        name: nav.name().to_string(),
+        kind: nav.kind().conv(),
+        detail: nav.description().map(|it| it.to_string()),
+        uri: nav.file_id().try_conv_with(world)?,
+        range: nav.full_range().conv_with(&line_index),
+        selection_range: nav.range().conv_with(&line_index),
+    })
+}
+
 pub fn handle_formatting(
     world: WorldSnapshot,
     params: DocumentFormattingParams,
+) -> Result<Option<Vec<TextEdit>>> {
+    format_document(&world, &params.text_document)
+}
+
+pub fn handle_range_formatting(
+    world: WorldSnapshot,
+    params: DocumentRangeFormattingParams,
 ) -> Result<Option<Vec<TextEdit>>> {
     let file_id = params.text_document.try_conv_with(&world)?;
-    let file = world.analysis().file_text(file_id)?;
+    let line_index = world.analysis().file_line_index(file_id)?;
+    let requested = params.range.conv_with(&line_index);
+    let range = match world.analysis().formatting_range(FileRange { file_id, range: requested })? {
+        None => return Ok(Some(Vec::new())),
+        Some(it) => it,
+    };
+    let start_line = range.start().conv_with(&line_index).line;
+    let end_line = range.end().conv_with(&line_index).line;
+    // `--file-lines` is unstable and uses one-based lines
+    let file_lines =
+        format!(r#"[{{"file":"stdin","range":[{},{}]}}]"#, start_line + 1, end_line + 1);
+    let text = world.analysis().file_text(file_id)?;
+    let formatted = match run_rustfmt(
+        &params.text_document,
+        &text,
+        &["--unstable-features", "--file-lines", &file_lines],
+    )? {
+        None => return Ok(None),
+        Some(it) => it,
+    };
+    let edit = range_formatting_edit(&text, &formatted, requested)
+        .map(|(range, new_text)| TextEdit { range: range.conv_with(&line_index), new_text });
+    Ok(Some(edit.into_iter().collect()))
+}
 
-    let file_line_index = world.analysis().file_line_index(file_id)?;
-    let end_position = TextUnit::of_str(&file).conv_with(&file_line_index);
+/// `rustfmt` formats whole lines, so the edit may reach out of the requested
+/// range, like when the selection starts in the middle of a line. We don't
+/// apply such edits at all, as cutting them down could leave broken code.
+fn range_formatting_edit(
+    text: &str,
+    formatted: &str,
+    requested: TextRange,
+) -> Option<(TextRange, String)> {
+    let (range, new_text) = minimal_edit(text, formatted)?;
+    if !range.is_subrange(&requested) {
+        log::info!("not applying range formatting outside of the requested range");
+        return None;
+    }
+    Some((range, new_text))
+}
 
+pub fn handle_will_save_wait_until(
+    world: WorldSnapshot,
+    params: WillSaveTextDocumentParams,
+) -> Result<Option<Vec<TextEdit>>> {
+    if !world.options.format_on_save {
+        return Ok(None);
+    }
+    format_document(&world, &params.text_document)
+}
+
+fn format_document(
+    world: &WorldSnapshot,
+    text_document: &TextDocumentIdentifier,
+) -> Result<Option<Vec<TextEdit>>> {
+    let file_id = text_document.try_conv_with(world)?;
+    let text = world.analysis().file_text(file_id)?;
+    let line_index = world.analysis().file_line_index(file_id)?;
+    let end_position = TextUnit::of_str(&text).conv_with(&line_index);
+    let formatted = match run_rustfmt(text_document, &text, &[])? {
+        None => return Ok(None),
+        Some(it) => it,
+    };
+    Ok(Some(vec![TextEdit {
+        range: Range::new(Position::new(0, 0), end_position),
+        new_text: formatted,
+    }]))
+}
+
+/// Pipes `text` through `rustfmt`, returning `None` if the file has syntax
+/// errors.
+fn run_rustfmt(
+    text_document: &TextDocumentIdentifier,
+    text: &str,
+    args: &[&str],
+) -> Result<Option<String>> {
     use std::process;
     let mut rustfmt = process::Command::new("rustfmt");
-    rustfmt.stdin(process::Stdio::piped()).stdout(process::Stdio::piped());
+    rustfmt.args(args).stdin(process::Stdio::piped()).stdout(process::Stdio::piped());
 
-    if let Ok(path) = params.text_document.uri.to_file_path() {
+    if let Ok(path) = text_document.uri.to_file_path() {
         if let Some(parent) = path.parent() {
             rustfmt.current_dir(parent);
         }
     }
     let mut rustfmt = rustfmt.spawn()?;
 
-    rustfmt.stdin.as_mut().unwrap().write_all(file.as_bytes())?;
+    rustfmt.stdin.as_mut().unwrap().write_all(text.as_bytes())?;
 
     let output = rustfmt.wait_with_output()?;
     let captured_stdout = String::from_utf8(output.stdout)?;
@@ -550,10 +797,25 @@ pub fn handle_formatting(
         }
     }
 
-    Ok(Some(vec![TextEdit {
-        range: Range::new(Position::new(0, 0), end_position),
-        new_text: captured_stdout,
-    }]))
+    Ok(Some(captured_stdout))
+}
+
+/// Returns a single edit which turns `old` into `new`, leaving their common
+/// prefix and suffix alone, so that we don't touch text which didn't change.
+fn minimal_edit(old: &str, new: &str) -> Option<(TextRange, String)> {
+    if old == new {
+        return None;
+    }
+    let prefix = common_prefix_len(old.chars(), new.chars());
+    let suffix = common_prefix_len(old[prefix..].chars().rev(), new[prefix..].chars().rev());
+    let range =
+        TextRange::from_to(TextUnit::from_usize(prefix), TextUnit::from_usize(old.len() - suffix));
+    Some((range, new[prefix..new.len() - suffix].to_string()))
+}
+
+/// Length in bytes of the common prefix of two sequences of chars.
+fn common_prefix_len(old: impl Iterator<Item = char>, new: impl Iterator<Item = char>) -> usize {
+    old.zip(new).take_while(|(old, new)| old == new).map(|(c, _)| c.len_utf8()).sum()
 }
 
 pub fn handle_code_action(
@@ -568,6 +830,17 @@ pub fn handle_code_action(
     let assists = world.analysis().assists(FileRange { file_id, range })?.into_iter();
     let diagnostics = world.analysis().diagnostics(file_id)?;
     let mut res = CodeActionResponse::default();
+    // Clients may only ask for some kinds of actions, like the ones to run on
+    // save. A kind includes its subkinds: `refactor` includes
+    // `refactor.extract`.
+    let only = params.context.only;
+    let is_requested = |action: &CodeAction| match (&only, &action.kind) {
+        (None, _) => true,
+        (Some(only), Some(kind)) => only.iter().any(|it| {
+            kind == it || (kind.starts_with(it.as_str()) && kind[it.len()..].starts_with('.'))
+        }),
+        (Some(_), None) => false,
+    };
 
     let fixes_from_diagnostics = diagnostics
         .into_iter()
@@ -586,18 +859,36 @@ pub fn handle_code_action(
         };
         let action = CodeAction {
             title: command.title.clone(),
-            kind: None,
+            kind: Some("quickfix".to_string()),
             diagnostics: None,
             edit: None,
             command: Some(command),
         };
-        res.push(action.into());
+        if is_requested(&action) {
+            res.push(action.into());
+        }
     }
 
     for assist in assists {
         let title = assist.change.label.clone();
         let edit = assist.change.try_conv_with(&world)?;
 
+        // Organizing imports runs on save, which only waits for edits, not
+        // for commands.
+        if assist.id == AssistId("organize_imports") {
+            let action = CodeAction {
+                title,
+                kind: Some(assist_kind(assist.id).to_string()),
+                diagnostics: None,
+                edit: Some(edit.workspace_edit),
+                command: None,
+            };
+            if is_requested(&action) {
+                res.push(action.into());
+            }
+            continue;
+        }
+
         let command = Command {
             title,
             command: "rust-analyzer.applySourceChange".to_string(),
@@ -605,20 +896,33 @@ pub fn handle_code_action(
         };
         let action = CodeAction {
             title: command.title.clone(),
-            kind: match assist.id {
-                AssistId("introduce_variable") => Some("refactor.extract.variable".to_string()),
-                _ => None,
-            },
+            kind: Some(assist_kind(assist.id).to_string()),
             diagnostics: None,
             edit: None,
             command: Some(command),
         };
-        res.push(action.into());
+        if is_requested(&action) {
+            res.push(action.into());
+        }
     }
 
     Ok(Some(res))
 }
 
+/// The LSP code action kind of an assist. Keep the kinds advertised in
+/// `server_capabilities` in sync.
+fn assist_kind(id: AssistId) -> &'static str {
+    match id {
+        AssistId("organize_imports") => "source.organizeImports",
+        AssistId("extract_function") => "refactor.extract.function",
+        AssistId("introduce_variable") => "refactor.extract.variable",
+        AssistId("inline_call")
+        | AssistId("inline_all_calls")
+        | AssistId("inline_local_variable") => "refactor.inline",
+        _ => "refactor.rewrite",
+    }
+}
+
 pub fn handle_code_lens(
     world: WorldSnapshot,
     params: req::CodeLensParams,
@@ -733,13 +1037,17 @@ pub fn handle_document_highlight(
     let file_id = params.text_document.try_conv_with(&world)?;
     let line_index = world.analysis().file_line_index(file_id)?;
 
-    let refs = match world.analysis().find_all_refs(params.try_conv_with(&world)?)? {
+    let refs = match world
+        .analysis()
+        .find_all_refs(params.try_conv_with(&world)?, Some(SearchScope::single_file(file_id)))?
+    {
         None => return Ok(None),
         Some(refs) => refs,
     };
 
     Ok(Some(
         refs.into_iter()
+            .filter(|r| r.file_id == file_id)
             .map(|r| DocumentHighlight { range: r.range.conv_with(&line_index), kind: None })
             .collect(),
     ))
@@ -801,6 +1109,25 @@ fn to_lsp_runnable(
         cwd: world.workspace_root_for(file_id).map(|root| root.to_string_lossy().to_string()),
     })
 }
+
+/// Decorations only know about the modifiers which existed before semantic
+/// tokens, so the rest are dropped.
+fn decoration_tag(highlight: Highlight) -> String {
+    let mut res = highlight.tag.to_string();
+    for modifier in highlight.modifiers.iter() {
+        match modifier {
+            HighlightModifier::Control | HighlightModifier::Mutable | HighlightModifier::Unsafe => {
+                res.push('.');
+                res.push_str(modifier.as_str());
+            }
+            HighlightModifier::Static
+            | HighlightModifier::Attribute
+            | HighlightModifier::MacroGenerated => (),
+        }
+    }
+    res
+}
+
 fn highlight(world: &WorldSnapshot, file_id: FileId) -> Result<Vec<Decoration>> {
     let line_index = world.analysis().file_line_index(file_id)?;
     let res = world
@@ -809,7 +1136,7 @@ fn highlight(world: &WorldSnapshot, file_id: FileId) -> Result<Vec<Decoration>>
         .into_iter()
         .map(|h| Decoration {
             range: h.range.conv_with(&line_index),
-            tag: h.tag,
+            tag: decoration_tag(h.highlight),
             binding_hash: h.binding_hash.map(|x| x.to_string()),
         })
         .collect();
@@ -835,3 +1162,32 @@ pub fn handle_inlay_hints(
         })
         .collect())
 }
+
+#[cfg(test)]
+mod tests {
+    use ra_syntax::{TextRange, TextUnit};
+
+    use super::range_formatting_edit;
+
+    fn range(start: usize, end: usize) -> TextRange {
+        TextRange::from_to(TextUnit::from_usize(start), TextUnit::from_usize(end))
+    }
+
+    #[test]
+    fn test_range_formatting_edit() {
+        let text = "fn a() {}\nfn b( ) {}\nfn c() {}\n";
+        let formatted = "fn a() {}\nfn b() {}\nfn c() {}\n";
+        assert_eq!(
+            range_formatting_edit(text, formatted, range(10, 21)),
+            Some((range(15, 16), String::new()))
+        );
+        assert_eq!(range_formatting_edit(text, text, range(10, 21)), None);
+    }
+
+    #[test]
+    fn test_range_formatting_edit_outside_of_requested_range() {
+        let text = "fn a( ) {}\nfn b( ) {}\n";
+        let formatted = "fn a() {}\nfn b() {}\n";
+        assert_eq!(range_formatting_edit(text, formatted, range(11, 21)), None);
+    }
+}
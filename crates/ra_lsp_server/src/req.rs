@@ -1,17 +1,18 @@
 //! FIXME: write short doc here
 
-use lsp_types::{Location, Position, Range, TextDocumentIdentifier, Url};
+use lsp_types::{Location, Position, Range, SymbolKind, TextDocumentIdentifier, Url};
 use rustc_hash::FxHashMap;
 use serde::{Deserialize, Serialize};
 
 pub use lsp_types::{
     notification::*, request::*, ApplyWorkspaceEditParams, CodeActionParams, CodeLens,
     CodeLensParams, CompletionParams, CompletionResponse, DidChangeConfigurationParams,
-    DidChangeWatchedFilesParams, DidChangeWatchedFilesRegistrationOptions,
+    DidChangeWatchedFilesParams, DidChangeWatchedFilesRegistrationOptions, DocumentFilter,
     DocumentOnTypeFormattingParams, DocumentSymbolParams, DocumentSymbolResponse,
     FileSystemWatcher, Hover, InitializeResult, MessageType, PublishDiagnosticsParams,
     ReferenceParams, Registration, RegistrationParams, ShowMessageParams, SignatureHelp,
-    TextDocumentEdit, TextDocumentPositionParams, TextEdit, WorkspaceEdit, WorkspaceSymbolParams,
+    TextDocumentEdit, TextDocumentPositionParams, TextDocumentRegistrationOptions, TextEdit,
+    WorkspaceEdit, WorkspaceSymbolParams,
 };
 
 pub enum AnalyzerStatus {}
@@ -45,6 +46,28 @@ pub struct SyntaxTreeParams {
     pub range: Option<Range>,
 }
 
+pub enum ExpandMacro {}
+
+impl Request for ExpandMacro {
+    type Params = ExpandMacroParams;
+    type Result = Option<ExpandedMacro>;
+    const METHOD: &'static str = "rust-analyzer/expandMacro";
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpandMacroParams {
+    pub text_document: TextDocumentIdentifier,
+    pub position: Position,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpandedMacro {
+    pub name: String,
+    pub expansion: String,
+}
+
 pub enum SelectionRangeRequest {}
 
 impl Request for SelectionRangeRequest {
@@ -108,10 +131,190 @@ pub struct PublishDecorationsParams {
 #[serde(rename_all = "camelCase")]
 pub struct Decoration {
     pub range: Range,
-    pub tag: &'static str,
+    pub tag: String,
     pub binding_hash: Option<String>,
 }
 
+pub enum SemanticTokensFullRequest {}
+
+impl Request for SemanticTokensFullRequest {
+    type Params = SemanticTokensParams;
+    type Result = Option<SemanticTokens>;
+    const METHOD: &'static str = "textDocument/semanticTokens/full";
+}
+
+pub enum SemanticTokensFullDeltaRequest {}
+
+impl Request for SemanticTokensFullDeltaRequest {
+    type Params = SemanticTokensDeltaParams;
+    type Result = Option<SemanticTokensFullDeltaResult>;
+    const METHOD: &'static str = "textDocument/semanticTokens/full/delta";
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensParams {
+    pub text_document: TextDocumentIdentifier,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensDeltaParams {
+    pub text_document: TextDocumentIdentifier,
+    pub previous_result_id: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokens {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result_id: Option<String>,
+    #[serde(serialize_with = "serialize_tokens")]
+    pub data: Vec<SemanticToken>,
+}
+
+/// A single token, relative to the start of the previous one. On the wire, the
+/// tokens are flattened into an array of integers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SemanticToken {
+    pub delta_line: u32,
+    pub delta_start: u32,
+    pub length: u32,
+    pub token_type: u32,
+    pub token_modifiers_bitset: u32,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+pub enum SemanticTokensFullDeltaResult {
+    Tokens(SemanticTokens),
+    TokensDelta(SemanticTokensDelta),
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensDelta {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result_id: Option<String>,
+    pub edits: Vec<SemanticTokensEdit>,
+}
+
+/// Replaces `delete_count` integers starting at `start` in the flattened
+/// token array.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensEdit {
+    pub start: u32,
+    pub delete_count: u32,
+    #[serde(skip_serializing_if = "Option::is_none", serialize_with = "serialize_opt_tokens")]
+    pub data: Option<Vec<SemanticToken>>,
+}
+
+fn serialize_tokens<S: serde::Serializer>(
+    tokens: &[SemanticToken],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    use serde::ser::SerializeSeq;
+
+    let mut seq = serializer.serialize_seq(Some(tokens.len() * 5))?;
+    for token in tokens {
+        seq.serialize_element(&token.delta_line)?;
+        seq.serialize_element(&token.delta_start)?;
+        seq.serialize_element(&token.length)?;
+        seq.serialize_element(&token.token_type)?;
+        seq.serialize_element(&token.token_modifiers_bitset)?;
+    }
+    seq.end()
+}
+
+fn serialize_opt_tokens<S: serde::Serializer>(
+    tokens: &Option<Vec<SemanticToken>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match tokens {
+        Some(tokens) => serialize_tokens(tokens, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// The `semanticTokensProvider` server capability.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensOptions {
+    pub legend: SemanticTokensLegend,
+    pub range: bool,
+    pub full: SemanticTokensFullOptions,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensLegend {
+    pub token_types: Vec<&'static str>,
+    pub token_modifiers: Vec<&'static str>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticTokensFullOptions {
+    pub delta: bool,
+}
+
+pub enum CallHierarchyPrepare {}
+
+impl Request for CallHierarchyPrepare {
+    type Params = TextDocumentPositionParams;
+    type Result = Option<Vec<CallHierarchyItem>>;
+    const METHOD: &'static str = "textDocument/prepareCallHierarchy";
+}
+
+pub enum CallHierarchyIncomingCalls {}
+
+impl Request for CallHierarchyIncomingCalls {
+    type Params = CallHierarchyCallsParams;
+    type Result = Option<Vec<CallHierarchyIncomingCall>>;
+    const METHOD: &'static str = "callHierarchy/incomingCalls";
+}
+
+pub enum CallHierarchyOutgoingCalls {}
+
+impl Request for CallHierarchyOutgoingCalls {
+    type Params = CallHierarchyCallsParams;
+    type Result = Option<Vec<CallHierarchyOutgoingCall>>;
+    const METHOD: &'static str = "callHierarchy/outgoingCalls";
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CallHierarchyItem {
+    pub name: String,
+    pub kind: SymbolKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    pub uri: Url,
+    pub range: Range,
+    pub selection_range: Range,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CallHierarchyCallsParams {
+    pub item: CallHierarchyItem,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CallHierarchyIncomingCall {
+    pub from: CallHierarchyItem,
+    pub from_ranges: Vec<Range>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CallHierarchyOutgoingCall {
+    pub to: CallHierarchyItem,
+    pub from_ranges: Vec<Range>,
+}
+
 pub enum ParentModule {}
 
 impl Request for ParentModule {
@@ -143,6 +346,31 @@ impl Request for OnEnter {
     const METHOD: &'static str = "rust-analyzer/onEnter";
 }
 
+pub enum ChangeSignature {}
+
+impl Request for ChangeSignature {
+    type Params = ChangeSignatureParams;
+    type Result = Option<SourceChange>;
+    const METHOD: &'static str = "rust-analyzer/changeSignature";
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeSignatureParams {
+    pub text_document: TextDocumentIdentifier,
+    pub position: Position,
+    pub params: Vec<SignatureParam>,
+}
+
+/// A parameter of the new signature: either `{ "index": 1 }` for the second
+/// existing parameter, or `{ "param": "verbose: bool", "default": "false" }`.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum SignatureParam {
+    Existing { index: usize },
+    Added { param: String, default: String },
+}
+
 pub enum Runnables {}
 
 impl Request for Runnables {
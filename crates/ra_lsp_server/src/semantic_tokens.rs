@@ -0,0 +1,217 @@
+//! Conversion of highlighted ranges to LSP semantic tokens, and the diffing
+//! used to answer delta requests.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use ra_ide_api::{
+    HighlightModifier, HighlightModifiers, HighlightTag, HighlightedRange, LineIndex,
+};
+use ra_syntax::TextRange;
+
+use crate::{
+    conv::ConvWith,
+    req::{
+        SemanticToken, SemanticTokens, SemanticTokensEdit, SemanticTokensFullOptions,
+        SemanticTokensLegend, SemanticTokensOptions,
+    },
+};
+
+fn token_type(tag: HighlightTag) -> &'static str {
+    match tag {
+        HighlightTag::Attribute => "attribute",
+        HighlightTag::Comment => "comment",
+        HighlightTag::Constant => "constant",
+        HighlightTag::Field => "property",
+        HighlightTag::Function => "function",
+        HighlightTag::Keyword => "keyword",
+        HighlightTag::Literal => "number",
+        HighlightTag::Macro => "macro",
+        HighlightTag::Module => "namespace",
+        HighlightTag::Parameter => "parameter",
+        HighlightTag::String => "string",
+        HighlightTag::Text => "text",
+        HighlightTag::Type => "type",
+        HighlightTag::Variable => "variable",
+    }
+}
+
+fn token_modifier(modifier: HighlightModifier) -> &'static str {
+    match modifier {
+        HighlightModifier::Control => "controlFlow",
+        HighlightModifier::Mutable => "mutable",
+        HighlightModifier::Unsafe => "unsafe",
+        HighlightModifier::Static => "static",
+        HighlightModifier::Attribute => "attribute",
+        HighlightModifier::MacroGenerated => "macroGenerated",
+    }
+}
+
+/// The legend maps token types and modifiers to their indices in
+/// `HighlightTag::ALL` and `HighlightModifier::ALL`.
+pub(crate) fn semantic_tokens_options() -> SemanticTokensOptions {
+    SemanticTokensOptions {
+        legend: SemanticTokensLegend {
+            token_types: HighlightTag::ALL.iter().map(|&it| token_type(it)).collect(),
+            token_modifiers: HighlightModifier::ALL.iter().map(|&it| token_modifier(it)).collect(),
+        },
+        range: false,
+        full: SemanticTokensFullOptions { delta: true },
+    }
+}
+
+fn type_index(tag: HighlightTag) -> u32 {
+    HighlightTag::ALL.iter().position(|&it| it == tag).unwrap() as u32
+}
+
+fn modifiers_bitset(modifiers: HighlightModifiers) -> u32 {
+    modifiers
+        .iter()
+        .map(|it| HighlightModifier::ALL.iter().position(|&m| m == it).unwrap())
+        .fold(0, |acc, idx| acc | (1 << idx))
+}
+
+/// Converts highlighted ranges of a file into semantic tokens.
+///
+/// LSP tokens can't overlap or span several lines, so ranges nested in an
+/// earlier one (like paths inside of an attribute) are dropped and multiline
+/// ranges (like block comments) are split per line.
+pub(crate) fn to_semantic_tokens(
+    text: &str,
+    line_index: &LineIndex,
+    mut ranges: Vec<HighlightedRange>,
+) -> Vec<SemanticToken> {
+    ranges.sort_by_key(|it| it.range.start());
+    let mut builder = SemanticTokensBuilder::default();
+    let mut last_end = None;
+    for range in ranges {
+        if last_end.map_or(false, |end| range.range.start() < end) {
+            continue;
+        }
+        last_end = Some(range.range.end());
+
+        let token_type = type_index(range.highlight.tag);
+        let modifiers = modifiers_bitset(range.highlight.modifiers);
+        let start = range.range.start().conv_with(line_index);
+        let (mut line, mut col) = (start.line as u32, start.character as u32);
+        for piece in text[range_to_std(range.range)].split('\n') {
+            let len = piece.trim_end_matches('\r').encode_utf16().count() as u32;
+            if len != 0 {
+                builder.push(line, col, len, token_type, modifiers);
+            }
+            line += 1;
+            col = 0;
+        }
+    }
+    builder.build()
+}
+
+fn range_to_std(range: TextRange) -> std::ops::Range<usize> {
+    range.start().to_usize()..range.end().to_usize()
+}
+
+#[derive(Default)]
+struct SemanticTokensBuilder {
+    prev_line: u32,
+    prev_col: u32,
+    data: Vec<SemanticToken>,
+}
+
+impl SemanticTokensBuilder {
+    fn push(&mut self, line: u32, col: u32, length: u32, token_type: u32, modifiers: u32) {
+        let delta_line = line - self.prev_line;
+        let delta_start = if delta_line == 0 { col - self.prev_col } else { col };
+        self.data.push(SemanticToken {
+            delta_line,
+            delta_start,
+            length,
+            token_type,
+            token_modifiers_bitset: modifiers,
+        });
+        self.prev_line = line;
+        self.prev_col = col;
+    }
+
+    fn build(self) -> Vec<SemanticToken> {
+        self.data
+    }
+}
+
+static NEXT_RESULT_ID: AtomicUsize = AtomicUsize::new(1);
+
+pub(crate) fn new_result_id() -> String {
+    NEXT_RESULT_ID.fetch_add(1, Ordering::SeqCst).to_string()
+}
+
+/// Computes the edits which turn `old` into `new`. Tokens are compared as a
+/// whole, so a single edit replacing everything between the common prefix and
+/// the common suffix is produced.
+pub(crate) fn diff_tokens(old: &SemanticTokens, new: &SemanticTokens) -> Vec<SemanticTokensEdit> {
+    let (old, new) = (&old.data, &new.data);
+    let prefix = old.iter().zip(new.iter()).take_while(|(a, b)| a == b).count();
+    let (old_rest, new_rest) = (&old[prefix..], &new[prefix..]);
+    let suffix =
+        old_rest.iter().rev().zip(new_rest.iter().rev()).take_while(|(a, b)| a == b).count();
+
+    let deleted = &old_rest[..old_rest.len() - suffix];
+    let inserted = &new_rest[..new_rest.len() - suffix];
+    if deleted.is_empty() && inserted.is_empty() {
+        return Vec::new();
+    }
+    vec![SemanticTokensEdit {
+        start: 5 * prefix as u32,
+        delete_count: 5 * deleted.len() as u32,
+        data: Some(inserted.to_vec()),
+    }]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(data: &[u32]) -> SemanticTokens {
+        SemanticTokens {
+            result_id: None,
+            data: data
+                .chunks(5)
+                .map(|it| SemanticToken {
+                    delta_line: it[0],
+                    delta_start: it[1],
+                    length: it[2],
+                    token_type: it[3],
+                    token_modifiers_bitset: it[4],
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_diff_insert_in_the_middle() {
+        let old = tokens(&[0, 0, 2, 1, 0, 0, 3, 4, 2, 0, 1, 0, 1, 5, 0]);
+        let new = tokens(&[0, 0, 2, 1, 0, 0, 3, 4, 2, 0, 0, 5, 2, 3, 1, 1, 0, 1, 5, 0]);
+        let edits = diff_tokens(&old, &new);
+        assert_eq!(
+            edits,
+            vec![SemanticTokensEdit {
+                start: 10,
+                delete_count: 0,
+                data: Some(tokens(&[0, 5, 2, 3, 1]).data),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_diff_replace_and_unchanged() {
+        let old = tokens(&[0, 0, 2, 1, 0, 1, 0, 1, 5, 0]);
+        let new = tokens(&[0, 0, 2, 1, 0, 1, 0, 3, 5, 0]);
+        let edits = diff_tokens(&old, &new);
+        assert_eq!(
+            edits,
+            vec![SemanticTokensEdit {
+                start: 5,
+                delete_count: 5,
+                data: Some(tokens(&[1, 0, 3, 5, 0]).data),
+            }]
+        );
+        assert!(diff_tokens(&new, &new).is_empty());
+    }
+}
@@ -8,24 +8,31 @@ use std::{
 use crossbeam_channel::{unbounded, Receiver};
 use lsp_server::ErrorCode;
 use lsp_types::Url;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
+use ra_cfg::CfgOptions;
+use ra_db::{IncludeDir, ProcMacro, ProcMacroKind};
 use ra_ide_api::{
     Analysis, AnalysisChange, AnalysisHost, CrateGraph, FeatureFlags, FileId, LibraryData,
     SourceRootId,
 };
-use ra_project_model::ProjectWorkspace;
+use ra_proc_macro::ProcMacroClient;
+use ra_project_model::{get_rustc_cfg_options, ProjectWorkspace};
+use ra_text_edit::AtomTextEdit;
 use ra_vfs::{LineEndings, RootEntry, Vfs, VfsChange, VfsFile, VfsRoot, VfsTask, Watch};
 use ra_vfs_glob::{Glob, RustPackageFilterBuilder};
 use relative_path::RelativePathBuf;
+use rustc_hash::FxHashMap;
 
 use crate::{
     main_loop::pending_requests::{CompletedRequest, LatestRequests},
+    req::SemanticTokens,
     LspError, Result,
 };
 
 #[derive(Debug, Clone)]
 pub struct Options {
     pub publish_decorations: bool,
+    pub format_on_save: bool,
     pub supports_location_link: bool,
 }
 
@@ -45,6 +52,16 @@ pub struct WorldState {
     pub vfs: Arc<RwLock<Vfs>>,
     pub task_receiver: Receiver<VfsTask>,
     pub latest_requests: Arc<RwLock<LatestRequests>>,
+    /// The last semantic tokens sent for each document, used to compute deltas.
+    pub semantic_tokens_cache: Arc<Mutex<FxHashMap<Url, SemanticTokens>>>,
+    /// The edits of the documents changed since the last `process_changes`,
+    /// used to reparse them incrementally.
+    pub file_edits: FxHashMap<FileId, Vec<AtomTextEdit>>,
+    /// The path of each source root, used to find the roots of `include!`d
+    /// directories.
+    root_paths: Vec<(PathBuf, SourceRootId)>,
+    default_cfg_options: CfgOptions,
+    proc_macro_client: ProcMacroClient,
 }
 
 /// An immutable snapshot of the world's state at a point in time.
@@ -54,6 +71,8 @@ pub struct WorldSnapshot {
     pub analysis: Analysis,
     pub vfs: Arc<RwLock<Vfs>>,
     pub latest_requests: Arc<RwLock<LatestRequests>>,
+    /// The last semantic tokens sent for each document, used to compute deltas.
+    pub semantic_tokens_cache: Arc<Mutex<FxHashMap<Url, SemanticTokens>>>,
 }
 
 impl WorldState {
@@ -63,6 +82,7 @@ impl WorldState {
         lru_capacity: Option<usize>,
         exclude_globs: &[Glob],
         watch: Watch,
+        proc_macro_client: ProcMacroClient,
         options: Options,
         feature_flags: FeatureFlags,
     ) -> WorldState {
@@ -88,33 +108,28 @@ impl WorldState {
         }
         let (task_sender, task_receiver) = unbounded();
         let task_sender = Box::new(move |t| task_sender.send(t).unwrap());
-        let (mut vfs, vfs_roots) = Vfs::new(roots, task_sender, watch);
+        let (vfs, vfs_roots) = Vfs::new(roots, task_sender, watch);
         let roots_to_scan = vfs_roots.len();
+        let mut root_paths = Vec::new();
         for r in vfs_roots {
             let vfs_root_path = vfs.root2path(r);
+            root_paths.push((vfs_root_path.clone(), SourceRootId(r.0)));
             let is_local = folder_roots.iter().any(|it| vfs_root_path.starts_with(it));
             change.add_root(SourceRootId(r.0), is_local);
             change.set_debug_root_path(SourceRootId(r.0), vfs_root_path.display().to_string());
         }
 
-        // Create crate graph from all the workspaces
-        let mut crate_graph = CrateGraph::default();
-        let mut load = |path: &std::path::Path| {
-            let vfs_file = vfs.load(path);
-            vfs_file.map(|f| FileId(f.0))
+        // FIXME: Read default cfgs from config
+        let default_cfg_options = {
+            let mut opts = get_rustc_cfg_options();
+            opts.insert_atom("test".into());
+            opts.insert_atom("debug_assertions".into());
+            opts
         };
-        for ws in workspaces.iter() {
-            let (graph, crate_names) = ws.to_crate_graph(&mut load);
-            let shift = crate_graph.extend(graph);
-            for (crate_id, name) in crate_names {
-                change.set_debug_crate_name(crate_id.shift(shift), name)
-            }
-        }
-        change.set_crate_graph(crate_graph);
 
         let mut analysis_host = AnalysisHost::new(lru_capacity, feature_flags);
         analysis_host.apply_change(change);
-        WorldState {
+        let mut world = WorldState {
             options,
             roots_to_scan,
             roots: folder_roots,
@@ -123,7 +138,74 @@ impl WorldState {
             vfs: Arc::new(RwLock::new(vfs)),
             task_receiver,
             latest_requests: Default::default(),
+            semantic_tokens_cache: Default::default(),
+            file_edits: Default::default(),
+            root_paths,
+            default_cfg_options,
+            proc_macro_client,
+        };
+        world.load_crate_graph();
+        world
+    }
+
+    /// Replaces the workspaces, like once their build outputs are loaded, and
+    /// recreates the crate graph from them.
+    pub fn set_workspaces(&mut self, workspaces: Vec<ProjectWorkspace>) {
+        self.workspaces = Arc::new(workspaces);
+        self.load_crate_graph();
+    }
+
+    /// Creates the crate graph from all the workspaces.
+    fn load_crate_graph(&mut self) {
+        let mut change = AnalysisChange::new();
+        let mut crate_graph = CrateGraph::default();
+        {
+            let mut vfs = self.vfs.write();
+            let root_paths = &self.root_paths;
+            let proc_macro_client = &self.proc_macro_client;
+            let mut load = |path: &Path| {
+                let vfs_file = vfs.load(path);
+                vfs_file.map(|f| FileId(f.0))
+            };
+            let mut load_include_dir = |path: &Path| {
+                // Roots may be nested, so pick the innermost one containing `path`
+                let (root_path, source_root) = root_paths
+                    .iter()
+                    .filter(|(root_path, _)| path.starts_with(root_path))
+                    .max_by_key(|(root_path, _)| root_path.components().count())?;
+                IncludeDir::new(path, *source_root, root_path)
+            };
+            let mut load_proc_macros = |path: &Path| {
+                proc_macro_client
+                    .by_dylib_path(path)
+                    .into_iter()
+                    .map(|(name, kind, expander)| {
+                        let kind = match kind {
+                            ra_proc_macro::ProcMacroKind::CustomDerive => {
+                                ProcMacroKind::CustomDerive
+                            }
+                            ra_proc_macro::ProcMacroKind::FuncLike => ProcMacroKind::FuncLike,
+                            ra_proc_macro::ProcMacroKind::Attr => ProcMacroKind::Attr,
+                        };
+                        ProcMacro { name, kind, expander }
+                    })
+                    .collect::<Vec<_>>()
+            };
+            for ws in self.workspaces.iter() {
+                let (graph, crate_names) = ws.to_crate_graph(
+                    &self.default_cfg_options,
+                    &mut load,
+                    &mut load_include_dir,
+                    &mut load_proc_macros,
+                );
+                let shift = crate_graph.extend(graph);
+                for (crate_id, name) in crate_names {
+                    change.set_debug_crate_name(crate_id.shift(shift), name)
+                }
+            }
         }
+        change.set_crate_graph(crate_graph);
+        self.analysis_host.apply_change(change);
     }
 
     /// Returns a vec of libraries
@@ -162,7 +244,11 @@ impl WorldState {
                     change.remove_file(SourceRootId(root.0), FileId(file.0), path)
                 }
                 VfsChange::ChangeFile { file, text } => {
-                    change.change_file(FileId(file.0), text);
+                    let file_id = FileId(file.0);
+                    match self.file_edits.remove(&file_id) {
+                        Some(edits) => change.change_file_with_edits(file_id, text, edits),
+                        None => change.change_file(file_id, text),
+                    }
                 }
             }
         }
@@ -184,6 +270,7 @@ impl WorldState {
             analysis: self.analysis_host.analysis(),
             vfs: Arc::clone(&self.vfs),
             latest_requests: Arc::clone(&self.latest_requests),
+            semantic_tokens_cache: Arc::clone(&self.semantic_tokens_cache),
         }
     }
 
@@ -220,6 +220,24 @@ pub use std::collections::HashMap;
     );
 }
 
+#[test]
+fn test_will_save_wait_until_registered_with_format_on_save() {
+    let server = Project::with_fixture(
+        r#"
+//- Cargo.toml
+[package]
+name = "foo"
+version = "0.0.0"
+
+//- src/lib.rs
+fn main() {}
+"#,
+    )
+    .format_on_save(true)
+    .server();
+    server.wait_until_registered("textDocument/willSaveWaitUntil");
+}
+
 #[test]
 fn test_missing_module_code_action() {
     let server = project(
@@ -263,11 +281,24 @@ fn main() {}
               "command": "rust-analyzer.applySourceChange",
               "title": "create module"
             },
+            "kind": "quickfix",
             "title": "create module"
           }
         ]),
     );
 
+    server.request::<CodeActionRequest>(
+        CodeActionParams {
+            text_document: server.doc_id("src/lib.rs"),
+            range: Range::new(Position::new(0, 4), Position::new(0, 7)),
+            context: CodeActionContext {
+                diagnostics: Vec::new(),
+                only: Some(vec!["source.organizeImports".to_string()]),
+            },
+        },
+        json!([]),
+    );
+
     server.request::<CodeActionRequest>(
         CodeActionParams {
             text_document: server.doc_id("src/lib.rs"),
@@ -332,6 +363,7 @@ fn main() {{}}
               "command": "rust-analyzer.applySourceChange",
               "title": "create module"
             },
+            "kind": "quickfix",
             "title": "create module"
           }
         ]),
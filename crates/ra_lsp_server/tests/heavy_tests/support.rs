@@ -25,13 +25,20 @@ use ra_lsp_server::{main_loop, req, ServerConfig};
 pub struct Project<'a> {
     fixture: &'a str,
     with_sysroot: bool,
+    format_on_save: bool,
     tmp_dir: Option<TempDir>,
     roots: Vec<PathBuf>,
 }
 
 impl<'a> Project<'a> {
     pub fn with_fixture(fixture: &str) -> Project {
-        Project { fixture, tmp_dir: None, roots: vec![], with_sysroot: false }
+        Project {
+            fixture,
+            tmp_dir: None,
+            roots: vec![],
+            with_sysroot: false,
+            format_on_save: false,
+        }
     }
 
     pub fn tmp_dir(mut self, tmp_dir: TempDir) -> Project<'a> {
@@ -49,6 +56,11 @@ impl<'a> Project<'a> {
         self
     }
 
+    pub fn format_on_save(mut self, format_on_save: bool) -> Project<'a> {
+        self.format_on_save = format_on_save;
+        self
+    }
+
     pub fn server(self) -> Server {
         let tmp_dir = self.tmp_dir.unwrap_or_else(|| TempDir::new().unwrap());
         static INIT: Once = Once::new();
@@ -72,7 +84,12 @@ impl<'a> Project<'a> {
 
         let roots = self.roots.into_iter().map(|root| tmp_dir.path().join(root)).collect();
 
-        Server::new(tmp_dir, self.with_sysroot, roots, paths)
+        let config = ServerConfig {
+            with_sysroot: self.with_sysroot,
+            format_on_save: self.format_on_save,
+            ..ServerConfig::default()
+        };
+        Server::new(tmp_dir, config, roots, paths)
     }
 }
 
@@ -91,7 +108,7 @@ pub struct Server {
 impl Server {
     fn new(
         dir: TempDir,
-        with_sysroot: bool,
+        config: ServerConfig,
         roots: Vec<PathBuf>,
         files: Vec<(PathBuf, String)>,
     ) -> Server {
@@ -117,7 +134,7 @@ impl Server {
                         window: None,
                         experimental: None,
                     },
-                    ServerConfig { with_sysroot, ..ServerConfig::default() },
+                    config,
                     connection,
                 )
                 .unwrap()
@@ -210,6 +227,18 @@ impl Server {
             _ => false,
         })
     }
+    pub fn wait_until_registered(&self, method: &str) {
+        self.wait_for_message_cond(1, &|msg: &Message| match msg {
+            Message::Request(request) if request.method == "client/registerCapability" => {
+                let (_id, params) = request
+                    .clone()
+                    .extract::<req::RegistrationParams>("client/registerCapability")
+                    .unwrap();
+                params.registrations.iter().any(|it| it.method == method)
+            }
+            _ => false,
+        })
+    }
     fn wait_for_message_cond(&self, n: usize, cond: &dyn Fn(&Message) -> bool) {
         let mut total = 0;
         for msg in self.messages.borrow().iter() {
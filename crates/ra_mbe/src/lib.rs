@@ -9,6 +9,7 @@ mod syntax_bridge;
 mod tt_iter;
 mod subtree_source;
 
+pub use ra_parser::FragmentKind;
 pub use tt::{Delimiter, Punct};
 
 use crate::{
@@ -31,8 +32,9 @@ pub enum ExpandError {
 }
 
 pub use crate::syntax_bridge::{
-    ast_to_token_tree, syntax_node_to_token_tree, token_tree_to_expr, token_tree_to_items,
-    token_tree_to_macro_stmts, token_tree_to_pat, token_tree_to_ty,
+    ast_to_token_tree, parse_to_token_tree, syntax_node_to_token_tree, token_tree_to_expr,
+    token_tree_to_items, token_tree_to_macro_stmts, token_tree_to_pat, token_tree_to_syntax_node,
+    token_tree_to_ty, RevTokenMap, TokenMap,
 };
 
 /// This struct contains AST for a single `macro_rules` definition. What might
@@ -42,6 +44,8 @@ pub use crate::syntax_bridge::{
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct MacroRules {
     pub(crate) rules: Vec<Rule>,
+    /// Offset applied to the token ids of the call argument, see `Shift`.
+    shift: Shift,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -72,10 +76,85 @@ impl MacroRules {
             validate(&rule.lhs)?;
         }
 
-        Ok(MacroRules { rules })
+        Ok(MacroRules { rules, shift: Shift::new(tt) })
     }
+
     pub fn expand(&self, tt: &tt::Subtree) -> Result<tt::Subtree, ExpandError> {
-        mbe_expander::expand(self, tt)
+        // apply shift
+        let mut tt = tt.clone();
+        self.shift.shift_all(&mut tt);
+        mbe_expander::expand(self, &tt)
+    }
+
+    /// Maps a token id of the macro call argument to the id it has in the
+    /// expansion.
+    pub fn map_id_down(&self, id: tt::TokenId) -> tt::TokenId {
+        self.shift.shift(id)
+    }
+
+    /// Maps a token id of the expansion back to the id in either the macro
+    /// definition or the macro call argument.
+    pub fn map_id_up(&self, id: tt::TokenId) -> (tt::TokenId, Origin) {
+        match self.shift.unshift(id) {
+            Some(id) => (id, Origin::Call),
+            None => (id, Origin::Def),
+        }
+    }
+}
+
+/// Where a token of the expansion came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Origin {
+    Def,
+    Call,
+}
+
+/// Token ids of the macro definition and of the call argument are allocated
+/// independently, so ids of the argument are shifted past the highest id of
+/// the definition before expansion.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Shift(u32);
+
+impl Shift {
+    fn new(tt: &tt::Subtree) -> Shift {
+        fn max_id(subtree: &tt::Subtree) -> Option<u32> {
+            subtree
+                .token_trees
+                .iter()
+                .filter_map(|tt| match tt {
+                    tt::TokenTree::Subtree(subtree) => max_id(subtree),
+                    tt::TokenTree::Leaf(tt::Leaf::Ident(ident))
+                        if ident.id != tt::TokenId::unspecified() =>
+                    {
+                        Some(ident.id.0)
+                    }
+                    _ => None,
+                })
+                .max()
+        }
+
+        Shift(max_id(tt).map_or(0, |it| it + 1))
+    }
+
+    fn shift_all(self, tt: &mut tt::Subtree) {
+        for t in tt.token_trees.iter_mut() {
+            match t {
+                tt::TokenTree::Leaf(tt::Leaf::Ident(ident)) => ident.id = self.shift(ident.id),
+                tt::TokenTree::Subtree(subtree) => self.shift_all(subtree),
+                _ => (),
+            }
+        }
+    }
+
+    fn shift(self, id: tt::TokenId) -> tt::TokenId {
+        if id == tt::TokenId::unspecified() {
+            return id;
+        }
+        tt::TokenId(id.0 + self.0)
+    }
+
+    fn unshift(self, id: tt::TokenId) -> Option<tt::TokenId> {
+        id.0.checked_sub(self.0).map(tt::TokenId)
     }
 }
 
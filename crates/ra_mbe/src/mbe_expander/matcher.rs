@@ -115,7 +115,7 @@ fn match_subtree(
                     bail!("leftover tokens");
                 }
             }
-            Op::Var { name, kind } => {
+            Op::Var { name, kind, .. } => {
                 let kind = kind.as_ref().ok_or(ExpandError::UnexpectedToken)?;
                 match match_meta_var(kind.as_str(), src)? {
                     Some(fragment) => {
@@ -128,6 +128,7 @@ fn match_subtree(
             Op::Repeat { subtree, kind, separator } => {
                 match_repeat(bindings, subtree, kind, separator, src)?
             }
+            Op::MetaVarExpr(_) => bail!("meta-variable expression in pattern"),
         }
     }
     Ok(())
@@ -334,6 +335,7 @@ fn collect_vars(buf: &mut Vec<SmolStr>, pattern: &tt::Subtree) -> Result<(), Exp
             Op::TokenTree(tt::TokenTree::Leaf(_)) => (),
             Op::TokenTree(tt::TokenTree::Subtree(subtree)) => collect_vars(buf, subtree)?,
             Op::Repeat { subtree, .. } => collect_vars(buf, subtree)?,
+            Op::MetaVarExpr(_) => (),
         }
     }
     Ok(())
@@ -5,7 +5,7 @@ use ra_syntax::SmolStr;
 
 use crate::{
     mbe_expander::{Binding, Bindings, Fragment},
-    parser::{parse_template, Op, RepeatKind, Separator},
+    parser::{parse_template, MetaVarExpr, Op, RepeatKind, Separator},
     ExpandError,
 };
 
@@ -15,6 +15,22 @@ impl Bindings {
     }
 
     fn get(&self, name: &str, nesting: &[usize]) -> Result<&Fragment, ExpandError> {
+        match self.get_nested(name, nesting)? {
+            Binding::Fragment(it) => Ok(it),
+            Binding::Nested(_) => Err(ExpandError::BindingError(format!(
+                "expected simple binding, found nested binding `{}`",
+                name
+            ))),
+            Binding::Empty => Err(ExpandError::BindingError(format!(
+                "expected simple binding, found empty binding `{}`",
+                name
+            ))),
+        }
+    }
+
+    /// Returns the binding of `name` in the current iterations of the
+    /// repetitions, which might still be nested.
+    fn get_nested(&self, name: &str, nesting: &[usize]) -> Result<&Binding, ExpandError> {
         let mut b = self.inner.get(name).ok_or_else(|| {
             ExpandError::BindingError(format!("could not find binding `{}`", name))
         })?;
@@ -32,17 +48,7 @@ impl Bindings {
                 }
             };
         }
-        match b {
-            Binding::Fragment(it) => Ok(it),
-            Binding::Nested(_) => Err(ExpandError::BindingError(format!(
-                "expected simple binding, found nested binding `{}`",
-                name
-            ))),
-            Binding::Empty => Err(ExpandError::BindingError(format!(
-                "expected simple binding, found empty binding `{}`",
-                name
-            ))),
-        }
+        Ok(b)
     }
 }
 
@@ -51,7 +57,12 @@ pub(super) fn transcribe(
     bindings: &Bindings,
 ) -> Result<tt::Subtree, ExpandError> {
     assert!(template.delimiter == tt::Delimiter::None);
-    let mut ctx = ExpandCtx { bindings: &bindings, nesting: Vec::new(), var_expanded: false };
+    let mut ctx = ExpandCtx {
+        bindings: &bindings,
+        nesting: Vec::new(),
+        lengths: Vec::new(),
+        var_expanded: false,
+    };
     expand_subtree(&mut ctx, template)
 }
 
@@ -59,6 +70,8 @@ pub(super) fn transcribe(
 struct ExpandCtx<'a> {
     bindings: &'a Bindings,
     nesting: Vec<usize>,
+    /// The number of iterations of each repetition in `nesting`, if known.
+    lengths: Vec<Option<usize>>,
     var_expanded: bool,
 }
 
@@ -71,25 +84,29 @@ fn expand_subtree(ctx: &mut ExpandCtx, template: &tt::Subtree) -> Result<tt::Sub
                 let tt = expand_subtree(ctx, tt)?;
                 buf.push(tt.into());
             }
-            Op::Var { name, kind: _ } => {
-                let fragment = expand_var(ctx, name)?;
+            Op::Var { name, id, .. } => {
+                let fragment = expand_var(ctx, name, id)?;
                 push_fragment(&mut buf, fragment);
             }
             Op::Repeat { subtree, kind, separator } => {
                 let fragment = expand_repeat(ctx, subtree, kind, separator)?;
                 push_fragment(&mut buf, fragment)
             }
+            Op::MetaVarExpr(expr) => {
+                let fragment = expand_meta_var_expr(ctx, expr)?;
+                push_fragment(&mut buf, fragment)
+            }
         }
     }
     Ok(tt::Subtree { delimiter: template.delimiter, token_trees: buf })
 }
 
-fn expand_var(ctx: &mut ExpandCtx, v: &SmolStr) -> Result<Fragment, ExpandError> {
+fn expand_var(ctx: &mut ExpandCtx, v: &SmolStr, id: tt::TokenId) -> Result<Fragment, ExpandError> {
+    // Tokens made up from the template keep the id of the template token, so
+    // that they can be traced back to the macro definition.
     let res = if v == "crate" {
         // We simply produce identifier `$crate` here. And it will be resolved when lowering ast to Path.
-        let tt =
-            tt::Leaf::from(tt::Ident { text: "$crate".into(), id: tt::TokenId::unspecified() })
-                .into();
+        let tt = tt::Leaf::from(tt::Ident { text: "$crate".into(), id }).into();
         Fragment::Tokens(tt)
     } else if !ctx.bindings.contains(v) {
         // Note that it is possible to have a `$var` inside a macro which is not bound.
@@ -109,8 +126,7 @@ fn expand_var(ctx: &mut ExpandCtx, v: &SmolStr) -> Result<Fragment, ExpandError>
             delimiter: tt::Delimiter::None,
             token_trees: vec![
                 tt::Leaf::from(tt::Punct { char: '$', spacing: tt::Spacing::Alone }).into(),
-                tt::Leaf::from(tt::Ident { text: v.clone(), id: tt::TokenId::unspecified() })
-                    .into(),
+                tt::Leaf::from(tt::Ident { text: v.clone(), id }).into(),
             ],
         }
         .into();
@@ -123,6 +139,87 @@ fn expand_var(ctx: &mut ExpandCtx, v: &SmolStr) -> Result<Fragment, ExpandError>
     Ok(res)
 }
 
+fn expand_meta_var_expr(ctx: &mut ExpandCtx, expr: MetaVarExpr) -> Result<Fragment, ExpandError> {
+    let value = match expr {
+        MetaVarExpr::Count { name, depth } => {
+            let binding = ctx.bindings.get_nested(name, &ctx.nesting)?;
+            let levels = repeat_levels(binding);
+            if depth >= levels {
+                return Err(ExpandError::BindingError(format!(
+                    "`{}` doesn't repeat {} times",
+                    name,
+                    depth + 1
+                )));
+            }
+            count(binding, levels - 1 - depth)
+        }
+        MetaVarExpr::Ignore { name } => {
+            ctx.bindings.get(name, &ctx.nesting)?;
+            ctx.var_expanded = true;
+            let tt = tt::Subtree { delimiter: tt::Delimiter::None, token_trees: vec![] }.into();
+            return Ok(Fragment::Tokens(tt));
+        }
+        MetaVarExpr::Index { depth } => {
+            let idx = enclosing_repeat(ctx, depth)?;
+            ctx.nesting[idx]
+        }
+        MetaVarExpr::Length { depth } => {
+            let idx = enclosing_repeat(ctx, depth)?;
+            ctx.lengths[idx].ok_or_else(|| {
+                ExpandError::BindingError("unknown length of repetition".to_string())
+            })?
+        }
+    };
+    let tt = tt::Leaf::from(tt::Literal { text: value.to_string().into() }).into();
+    Ok(Fragment::Tokens(tt))
+}
+
+/// The index in `ctx.nesting` of the repetition `depth` levels out.
+fn enclosing_repeat(ctx: &ExpandCtx, depth: usize) -> Result<usize, ExpandError> {
+    ctx.nesting
+        .len()
+        .checked_sub(depth + 1)
+        .ok_or_else(|| ExpandError::BindingError(format!("no repetition {} levels out", depth)))
+}
+
+/// The number of nested repetitions `binding` consists of.
+fn repeat_levels(binding: &Binding) -> usize {
+    match binding {
+        Binding::Nested(bindings) => 1 + bindings.first().map_or(0, repeat_levels),
+        Binding::Fragment(_) | Binding::Empty => 0,
+    }
+}
+
+/// Counts the iterations at `depth` of all the repetitions in `binding`.
+fn count(binding: &Binding, depth: usize) -> usize {
+    match binding {
+        Binding::Nested(bindings) if depth == 0 => bindings.len(),
+        Binding::Nested(bindings) => bindings.iter().map(|it| count(it, depth - 1)).sum(),
+        Binding::Fragment(_) => 1,
+        Binding::Empty => 0,
+    }
+}
+
+/// The number of iterations of the repetition `template`, taken from the
+/// first meta-variable in it which repeats at this level.
+fn repeat_len(ctx: &ExpandCtx, template: &tt::Subtree) -> Option<usize> {
+    parse_template(template).filter_map(Result::ok).find_map(|op| {
+        let name = match op {
+            Op::Var { name, .. }
+            | Op::MetaVarExpr(MetaVarExpr::Count { name, .. })
+            | Op::MetaVarExpr(MetaVarExpr::Ignore { name }) => name,
+            Op::Repeat { subtree, .. } | Op::TokenTree(tt::TokenTree::Subtree(subtree)) => {
+                return repeat_len(ctx, subtree)
+            }
+            Op::MetaVarExpr(_) | Op::TokenTree(tt::TokenTree::Leaf(_)) => return None,
+        };
+        match ctx.bindings.get_nested(name, &ctx.nesting) {
+            Ok(Binding::Nested(bindings)) => Some(bindings.len()),
+            _ => None,
+        }
+    })
+}
+
 fn expand_repeat(
     ctx: &mut ExpandCtx,
     template: &tt::Subtree,
@@ -130,7 +227,9 @@ fn expand_repeat(
     separator: Option<Separator>,
 ) -> Result<Fragment, ExpandError> {
     let mut buf: Vec<tt::TokenTree> = Vec::new();
+    let len = repeat_len(ctx, template);
     ctx.nesting.push(0);
+    ctx.lengths.push(len);
     // Dirty hack to make macro-expansion terminate.
     // This should be replaced by a propper macro-by-example implementation
     let mut limit = 65536;
@@ -202,6 +301,7 @@ fn expand_repeat(
     ctx.var_expanded = some_var_expanded || old_var_expanded;
 
     ctx.nesting.pop().unwrap();
+    ctx.lengths.pop().unwrap();
     for _ in 0..has_seps {
         buf.pop();
     }
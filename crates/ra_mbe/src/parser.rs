@@ -1,5 +1,5 @@
-//! Parser recognizes special macro syntax, `$var` and `$(repeat)*`, in token
-//! trees.
+//! Parser recognizes special macro syntax, `$var`, `$(repeat)*` and
+//! meta-variable expressions like `${count(var)}`, in token trees.
 
 use ra_syntax::SmolStr;
 use smallvec::SmallVec;
@@ -8,11 +8,28 @@ use crate::{tt_iter::TtIter, ExpandError};
 
 #[derive(Debug)]
 pub(crate) enum Op<'a> {
-    Var { name: &'a SmolStr, kind: Option<&'a SmolStr> },
+    Var { name: &'a SmolStr, kind: Option<&'a SmolStr>, id: tt::TokenId },
     Repeat { subtree: &'a tt::Subtree, kind: RepeatKind, separator: Option<Separator> },
+    MetaVarExpr(MetaVarExpr<'a>),
     TokenTree(&'a tt::TokenTree),
 }
 
+/// A meta-variable expression, which can only appear in templates.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum MetaVarExpr<'a> {
+    /// `${count(var, depth)}`: the number of times `$var` repeats, counting the
+    /// repetitions `depth` levels above the innermost one.
+    Count { name: &'a SmolStr, depth: usize },
+    /// `${ignore(var)}`: expands to nothing, but repeats as `$var` does.
+    Ignore { name: &'a SmolStr },
+    /// `${index(depth)}`: the index of the current iteration of the enclosing
+    /// repetition `depth` levels out.
+    Index { depth: usize },
+    /// `${length(depth)}`: the number of iterations of the enclosing repetition
+    /// `depth` levels out.
+    Length { depth: usize },
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub(crate) enum RepeatKind {
     ZeroOrMore,
@@ -57,7 +74,7 @@ pub(crate) fn parse_pattern<'a>(
     parse_inner(pattern, Mode::Pattern)
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 enum Mode {
     Pattern,
     Template,
@@ -95,22 +112,33 @@ fn next_op<'a>(
         tt::TokenTree::Leaf(tt::Leaf::Punct(tt::Punct { char: '$', .. })) => {
             let second = src.next().ok_or_else(|| err!("bad var 1"))?;
             match second {
+                tt::TokenTree::Subtree(subtree)
+                    if mode == Mode::Template && subtree.delimiter == tt::Delimiter::Brace =>
+                {
+                    Op::MetaVarExpr(parse_meta_var_expr(subtree)?)
+                }
                 tt::TokenTree::Subtree(subtree) => {
                     let (separator, kind) = parse_repeat(src)?;
                     Op::Repeat { subtree, separator, kind }
                 }
                 tt::TokenTree::Leaf(leaf) => match leaf {
+                    // `$$` produces a single `$`
+                    tt::Leaf::Punct(tt::Punct { char: '$', .. }) if mode == Mode::Template => {
+                        Op::TokenTree(second)
+                    }
                     tt::Leaf::Punct(..) => Err(ExpandError::UnexpectedToken)?,
                     tt::Leaf::Ident(ident) => {
                         let name = &ident.text;
                         let kind = eat_fragment_kind(src, mode)?;
-                        Op::Var { name, kind }
+                        let id = ident.id;
+                        Op::Var { name, kind, id }
                     }
                     tt::Leaf::Literal(lit) => {
                         if is_boolean_literal(lit) {
                             let name = &lit.text;
                             let kind = eat_fragment_kind(src, mode)?;
-                            Op::Var { name, kind }
+                            let id = tt::TokenId::unspecified();
+                            Op::Var { name, kind, id }
                         } else {
                             bail!("bad var 2");
                         }
@@ -135,6 +163,58 @@ fn eat_fragment_kind<'a>(
     Ok(None)
 }
 
+fn parse_meta_var_expr(subtree: &tt::Subtree) -> Result<MetaVarExpr, ExpandError> {
+    let mut src = TtIter::new(subtree);
+    let func = src.expect_ident().map_err(|()| err!("bad meta-variable expression"))?;
+    let args = match src.expect_subtree() {
+        Ok(it) if it.delimiter == tt::Delimiter::Parenthesis => it,
+        _ => bail!("bad meta-variable expression"),
+    };
+    if src.len() > 0 {
+        bail!("leftover tokens");
+    }
+
+    let mut args = TtIter::new(args);
+    let res = match func.text.as_str() {
+        "count" => {
+            let name = parse_meta_var_name(&mut args)?;
+            let depth = match args.expect_char(',') {
+                Ok(()) => parse_depth(&mut args)?,
+                Err(()) => 0,
+            };
+            MetaVarExpr::Count { name, depth }
+        }
+        "ignore" => MetaVarExpr::Ignore { name: parse_meta_var_name(&mut args)? },
+        "index" => MetaVarExpr::Index { depth: parse_depth(&mut args)? },
+        "length" => MetaVarExpr::Length { depth: parse_depth(&mut args)? },
+        _ => bail!("unknown meta-variable expression"),
+    };
+    if args.len() > 0 {
+        bail!("leftover tokens");
+    }
+    Ok(res)
+}
+
+/// Parses the name of a meta-variable, written either as `var` or `$var`.
+fn parse_meta_var_name<'a>(src: &mut TtIter<'a>) -> Result<&'a SmolStr, ExpandError> {
+    let mut fork = src.clone();
+    if fork.expect_char('$').is_ok() {
+        *src = fork;
+    }
+    let ident = src.expect_ident().map_err(|()| err!("expected meta-variable"))?;
+    Ok(&ident.text)
+}
+
+/// Parses the optional depth argument of a meta-variable expression, which
+/// defaults to the innermost repetition.
+fn parse_depth(src: &mut TtIter) -> Result<usize, ExpandError> {
+    if src.len() == 0 {
+        return Ok(0);
+    }
+    let lit = src.expect_literal().map_err(|()| err!("expected depth"))?;
+    lit.text.parse().map_err(|_| err!("bad depth"))
+}
+
 fn is_boolean_literal(lit: &tt::Literal) -> bool {
     match lit.text.as_str() {
         "true" | "false" => true,
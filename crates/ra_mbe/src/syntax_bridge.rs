@@ -14,12 +14,19 @@ use crate::subtree_source::SubtreeTokenSource;
 use crate::ExpandError;
 
 /// Maps `tt::TokenId` to the relative range of the original token.
-#[derive(Default)]
+#[derive(Debug, PartialEq, Eq, Default)]
 pub struct TokenMap {
     /// Maps `tt::TokenId` to the *relative* source range.
     tokens: Vec<TextRange>,
 }
 
+/// Maps the ranges of the tokens in a syntax tree built from a token tree
+/// (the result of a macro expansion) back to their `tt::TokenId`.
+#[derive(Debug, PartialEq, Eq, Default)]
+pub struct RevTokenMap {
+    ranges: Vec<(TextRange, tt::TokenId)>,
+}
+
 /// Convert the syntax tree (what user has written) to a `TokenTree` (what macro
 /// will consume).
 pub fn ast_to_token_tree(ast: &ast::TokenTree) -> Option<(tt::Subtree, TokenMap)> {
@@ -37,6 +44,66 @@ pub fn syntax_node_to_token_tree(node: &SyntaxNode) -> Option<(tt::Subtree, Toke
     Some((tt, token_map))
 }
 
+/// Lexes `text`, like the source of a `proc_macro::TokenStream`, into an
+/// undelimited token tree. Returns `None` if the delimiters are unbalanced.
+pub fn parse_to_token_tree(text: &str) -> Option<(tt::Subtree, TokenMap)> {
+    let mut token_map = TokenMap::default();
+    let mut stack = vec![tt::Subtree { delimiter: tt::Delimiter::None, token_trees: Vec::new() }];
+    let tokens = ra_syntax::tokenize(text);
+    let mut offset = TextUnit::from(0);
+    for (i, token) in tokens.iter().enumerate() {
+        let range = TextRange::offset_len(offset, token.len);
+        offset += token.len;
+        let token_text = &text[range.start().to_usize()..range.end().to_usize()];
+        let leaf: tt::Leaf = match token.kind {
+            T!['('] | T!['{'] | T!['['] => {
+                let delimiter = match token.kind {
+                    T!['('] => tt::Delimiter::Parenthesis,
+                    T!['{'] => tt::Delimiter::Brace,
+                    _ => tt::Delimiter::Bracket,
+                };
+                stack.push(tt::Subtree { delimiter, token_trees: Vec::new() });
+                continue;
+            }
+            T![')'] | T!['}'] | T![']'] => {
+                let delimiter = match token.kind {
+                    T![')'] => tt::Delimiter::Parenthesis,
+                    T!['}'] => tt::Delimiter::Brace,
+                    _ => tt::Delimiter::Bracket,
+                };
+                let subtree = stack.pop()?;
+                if subtree.delimiter != delimiter {
+                    return None;
+                }
+                stack.last_mut()?.token_trees.push(subtree.into());
+                continue;
+            }
+            kind if kind.is_trivia() => continue,
+            kind if kind.is_punct() => {
+                let char = token_text.chars().next()?;
+                let spacing = match tokens.get(i + 1) {
+                    Some(next) if next.kind.is_punct() => tt::Spacing::Joint,
+                    _ => tt::Spacing::Alone,
+                };
+                tt::Punct { char, spacing }.into()
+            }
+            T![true] | T![false] => tt::Literal { text: token_text.into() }.into(),
+            kind if kind.is_keyword() || kind == IDENT || kind == LIFETIME => {
+                let id = token_map.alloc(range);
+                tt::Ident { text: token_text.into(), id }.into()
+            }
+            kind if kind.is_literal() => tt::Literal { text: token_text.into() }.into(),
+            _ => return None,
+        };
+        stack.last_mut()?.token_trees.push(leaf.into());
+    }
+    if stack.len() != 1 {
+        return None;
+    }
+    let subtree = stack.pop()?;
+    Some((subtree, token_map))
+}
+
 // The following items are what `rustc` macro can be parsed into :
 // link: https://github.com/rust-lang/rust/blob/9ebf47851a357faa4cd97f4b1dc7835f6376e639/src/libsyntax/ext/expand.rs#L141
 // * Expr(P<ast::Expr>)                     -> token_tree_to_expr
@@ -49,10 +116,12 @@ pub fn syntax_node_to_token_tree(node: &SyntaxNode) -> Option<(tt::Subtree, Toke
 // * ImplItems(SmallVec<[ast::ImplItem; 1]>)
 // * ForeignItems(SmallVec<[ast::ForeignItem; 1]>
 
-fn fragment_to_syntax_node(
+/// Parses the token tree (result of macro expansion) as the given fragment,
+/// returning the tree along with the map from its token ranges to token ids.
+pub fn token_tree_to_syntax_node(
     tt: &tt::Subtree,
     fragment_kind: FragmentKind,
-) -> Result<Parse<SyntaxNode>, ExpandError> {
+) -> Result<(Parse<SyntaxNode>, RevTokenMap), ExpandError> {
     let tmp;
     let tokens = match tt {
         tt::Subtree { delimiter: tt::Delimiter::None, token_trees } => token_trees.as_slice(),
@@ -69,8 +138,15 @@ fn fragment_to_syntax_node(
         return Err(ExpandError::ConversionError);
     }
     //FIXME: would be cool to report errors
-    let parse = tree_sink.inner.finish();
-    Ok(parse)
+    let (parse, range_map) = tree_sink.finish();
+    Ok((parse, range_map))
+}
+
+fn fragment_to_syntax_node(
+    tt: &tt::Subtree,
+    fragment_kind: FragmentKind,
+) -> Result<Parse<SyntaxNode>, ExpandError> {
+    token_tree_to_syntax_node(tt, fragment_kind).map(|(parse, _)| parse)
 }
 
 /// Parses the token tree (result of macro expansion) to an expression
@@ -109,6 +185,11 @@ impl TokenMap {
         self.tokens.get(idx).copied()
     }
 
+    pub fn token_by_relative_range(&self, relative_range: TextRange) -> Option<tt::TokenId> {
+        let idx = self.tokens.iter().position(|&it| it == relative_range)?;
+        Some(tt::TokenId(idx as u32))
+    }
+
     fn alloc(&mut self, relative_range: TextRange) -> tt::TokenId {
         let id = self.tokens.len();
         self.tokens.push(relative_range);
@@ -116,6 +197,27 @@ impl TokenMap {
     }
 }
 
+impl RevTokenMap {
+    pub fn range_of(&self, token_id: tt::TokenId) -> Option<TextRange> {
+        self.ranges.iter().find(|&(_, id)| *id == token_id).map(|&(range, _)| range)
+    }
+
+    pub fn token_by_range(&self, range: TextRange) -> Option<tt::TokenId> {
+        // Ranges are added in the order of the tokens in the text
+        let idx = self.ranges.binary_search_by_key(&range.start(), |(it, _)| it.start()).ok()?;
+        let (it, id) = self.ranges[idx];
+        if it == range {
+            Some(id)
+        } else {
+            None
+        }
+    }
+
+    fn add(&mut self, range: TextRange, token_id: tt::TokenId) {
+        self.ranges.push((range, token_id))
+    }
+}
+
 /// Returns the textual content of a doc comment block as a quoted string
 /// That is, strips leading `///` (or `/**`, etc)
 /// and strips the ending `*/`
@@ -262,6 +364,7 @@ struct TtTreeSink<'a> {
     cursor: Cursor<'a>,
     text_pos: TextUnit,
     inner: SyntaxTreeBuilder,
+    range_map: RevTokenMap,
 
     // Number of roots
     // Use for detect ill-form tree which is not single root
@@ -275,9 +378,14 @@ impl<'a> TtTreeSink<'a> {
             cursor,
             text_pos: 0.into(),
             inner: SyntaxTreeBuilder::default(),
+            range_map: RevTokenMap::default(),
             roots: smallvec::SmallVec::new(),
         }
     }
+
+    fn finish(self) -> (Parse<SyntaxNode>, RevTokenMap) {
+        (self.inner.finish(), self.range_map)
+    }
 }
 
 fn delim_to_str(d: tt::Delimiter, closing: bool) -> SmolStr {
@@ -308,6 +416,13 @@ impl<'a> TreeSink for TtTreeSink<'a> {
             match self.cursor.token_tree() {
                 Some(tt::TokenTree::Leaf(leaf)) => {
                     self.cursor = self.cursor.bump();
+                    if let tt::Leaf::Ident(ident) = leaf {
+                        if ident.id != tt::TokenId::unspecified() {
+                            let start = self.text_pos + TextUnit::of_str(&self.buf);
+                            let range = TextRange::offset_len(start, TextUnit::of_str(&ident.text));
+                            self.range_map.add(range, ident.id);
+                        }
+                    }
                     self.buf += &format!("{}", leaf);
                 }
                 Some(tt::TokenTree::Subtree(subtree)) => {
@@ -337,6 +452,7 @@ impl<'a> TreeSink for TtTreeSink<'a> {
         {
             if curr.spacing == tt::Spacing::Alone {
                 self.inner.token(WHITESPACE, " ".into());
+                self.text_pos += TextUnit::of_char(' ');
             }
         }
     }
@@ -425,4 +541,52 @@ mod tests {
         let expansion = expand(&rules, "stmts!();");
         assert!(token_tree_to_expr(&expansion).is_err());
     }
+
+    #[test]
+    fn expansion_tokens_map_back_to_definition_and_call() {
+        let rules = create_rules(
+            r#"
+            macro_rules! foo {
+                ($i:ident) => { fn $i() {} }
+            }
+            "#,
+        );
+        let source_file = ast::SourceFile::parse("foo!(bar);").ok().unwrap();
+        let macro_call = source_file.syntax().descendants().find_map(ast::MacroCall::cast).unwrap();
+        let arg = macro_call.token_tree().unwrap();
+        let (tt, token_map) = ast_to_token_tree(&arg).unwrap();
+        let expansion = rules.expand(&tt).unwrap();
+        let (parse, range_map) = token_tree_to_syntax_node(&expansion, Items).unwrap();
+        let tokens: Vec<_> = parse
+            .syntax_node()
+            .descendants_with_tokens()
+            .filter_map(|it| it.into_token())
+            .filter(|it| it.kind() == IDENT || it.kind() == T![fn])
+            .collect();
+        assert_eq!(tokens.len(), 2);
+
+        let id = range_map.token_by_range(tokens[0].text_range()).unwrap();
+        assert_eq!(rules.map_id_up(id).1, crate::Origin::Def);
+
+        let id = range_map.token_by_range(tokens[1].text_range()).unwrap();
+        let (id, origin) = rules.map_id_up(id);
+        assert_eq!(origin, crate::Origin::Call);
+        let range = token_map.relative_range_of(id).unwrap() + arg.syntax().text_range().start();
+        assert_eq!(range, TextRange::from_to(5.into(), 8.into()));
+        assert_eq!(range_map.range_of(rules.map_id_down(id)), Some(tokens[1].text_range()));
+    }
+
+    #[test]
+    fn parse_text_to_token_tree() {
+        let (tt, token_map) = parse_to_token_tree("foo(1, {x})").unwrap();
+        assert_eq!(tt.to_string(), "foo (1 , {x})");
+        assert_eq!(tt.delimiter, tt::Delimiter::None);
+        assert_eq!(
+            token_map.relative_range_of(tt::TokenId(1)),
+            Some(TextRange::from_to(8.into(), 9.into()))
+        );
+
+        assert!(parse_to_token_tree("foo(1, {x)}").is_none());
+        assert!(parse_to_token_tree("foo(").is_none());
+    }
 }
@@ -1363,6 +1363,52 @@ macro_rules! delegate_impl {
     assert_expansion(MacroKind::Items, &rules, r#"delegate_impl ! {[G , & 'a mut G , deref] pub trait Data : GraphBase {@ section type type NodeWeight ;}}"#, "impl <> Data for & \'a mut G where G : Data {}");
 }
 
+#[test]
+fn test_dollar_crate_keeps_definition_token_id() {
+    let rules = create_rules(
+        r#"
+macro_rules! foo {
+    () => { $crate::bar() }
+}
+"#,
+    );
+    let expanded = expand(&rules, "foo!();");
+    let dollar_crate = match &expanded.token_trees[0] {
+        tt::TokenTree::Leaf(tt::Leaf::Ident(it)) => it.clone(),
+        _ => panic!("expected `$crate`"),
+    };
+    assert_eq!(dollar_crate.text, "$crate");
+    assert_eq!(rules.map_id_up(dollar_crate.id).1, Origin::Def);
+}
+
+#[test]
+fn test_meta_var_exprs() {
+    let rules = create_rules(
+        r#"
+macro_rules! foo {
+    ($($a:ident $($b:ident)*),*) => {
+        ${count(a)} ${count(b)} ${count($b, 1)}
+        $([${index()} ${length()} ${count(b)} $(${index(1)} ${index()} ${ignore(b)})*])*
+    }
+}
+"#,
+    );
+    let expanded = expand(&rules, "foo!(x p q, y r);");
+    assert_eq!(expanded.to_string(), "2 3 2 [0 2 2 0 0 0 1] [1 2 1 1 0]");
+}
+
+#[test]
+fn test_dollar_dollar() {
+    let rules = create_rules(
+        r#"
+macro_rules! foo {
+    () => { $$x }
+}
+"#,
+    );
+    assert_eq!(expand(&rules, "foo!();").to_string(), "$ x");
+}
+
 pub(crate) fn create_rules(macro_definition: &str) -> MacroRules {
     let source_file = ast::SourceFile::parse(macro_definition).ok().unwrap();
     let macro_definition =
@@ -0,0 +1,99 @@
+//! Client side of procedural macro expansion.
+//!
+//! Proc macros are dylibs compiled by rustc, and running them means running
+//! arbitrary code which might panic, abort or loop forever. So we load them
+//! into a separate server process (see `ra_proc_macro_srv`) and exchange token
+//! trees with it over stdin/stdout.
+
+mod process;
+pub mod msg;
+pub mod rpc;
+
+use std::{
+    ffi::OsString,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use smol_str::SmolStr;
+use tt::{ExpansionError, TokenExpander};
+
+use crate::process::ProcMacroProcessSrv;
+pub use crate::rpc::ProcMacroKind;
+
+/// Expands the macro called `macro_name` from the dylib at `dylib_path`.
+#[derive(Debug)]
+pub struct ProcMacroProcessExpander {
+    process: Arc<ProcMacroProcessSrv>,
+    dylib_path: PathBuf,
+    macro_name: SmolStr,
+}
+
+impl TokenExpander for ProcMacroProcessExpander {
+    fn expand(
+        &self,
+        subtree: &tt::Subtree,
+        attrs: Option<&tt::Subtree>,
+    ) -> Result<tt::Subtree, ExpansionError> {
+        let task = rpc::ExpansionTask {
+            macro_body: subtree.clone(),
+            macro_name: self.macro_name.to_string(),
+            attributes: attrs.cloned(),
+            lib: self.dylib_path.clone(),
+        };
+        self.process.expand(task)
+    }
+}
+
+#[derive(Debug)]
+pub struct ProcMacroClient {
+    process: Option<Arc<ProcMacroProcessSrv>>,
+}
+
+impl ProcMacroClient {
+    /// Spawns `process_path` with `args` as the proc macro server.
+    pub fn extern_process(
+        process_path: &Path,
+        args: impl IntoIterator<Item = impl Into<OsString>>,
+    ) -> io::Result<ProcMacroClient> {
+        let process = ProcMacroProcessSrv::run(process_path, args)?;
+        Ok(ProcMacroClient { process: Some(Arc::new(process)) })
+    }
+
+    /// A client which doesn't know about any proc macros.
+    pub fn dummy() -> ProcMacroClient {
+        ProcMacroClient { process: None }
+    }
+
+    /// Lists the proc macros of the dylib at `dylib_path`, together with the
+    /// expanders for them.
+    pub fn by_dylib_path(
+        &self,
+        dylib_path: &Path,
+    ) -> Vec<(SmolStr, ProcMacroKind, Arc<dyn TokenExpander>)> {
+        let process = match &self.process {
+            Some(it) => it,
+            None => return Vec::new(),
+        };
+        let macros = match process.find_proc_macros(dylib_path) {
+            Ok(it) => it,
+            Err(err) => {
+                log::error!("failed to list proc macros of {}: {}", dylib_path.display(), err);
+                return Vec::new();
+            }
+        };
+        macros
+            .into_iter()
+            .map(|(name, kind)| {
+                let name = SmolStr::new(&name);
+                let expander: Arc<dyn TokenExpander> = Arc::new(ProcMacroProcessExpander {
+                    process: process.clone(),
+                    dylib_path: dylib_path.to_path_buf(),
+                    macro_name: name.clone(),
+                });
+                (name, kind, expander)
+            })
+            .collect()
+    }
+}
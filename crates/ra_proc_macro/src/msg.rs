@@ -0,0 +1,74 @@
+//! Messages of the proc macro protocol: each message is a single line of JSON.
+
+use std::io::{self, BufRead, Write};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::rpc::{ExpansionResult, ExpansionTask, ListMacrosResult, ListMacrosTask};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum Request {
+    ListMacro(ListMacrosTask),
+    ExpansionMacro(ExpansionTask),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum Response {
+    Error(ResponseError),
+    ListMacro(ListMacrosResult),
+    ExpansionMacro(ExpansionResult),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ResponseError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    /// The server couldn't understand the request.
+    InvalidRequest,
+    /// The dylib couldn't be loaded or doesn't contain the requested macro.
+    LoadError,
+    /// The macro itself failed, for example because it panicked.
+    ExpansionError,
+}
+
+pub trait Message: Serialize + DeserializeOwned {
+    /// Reads the next message, or `None` if the other side closed the stream.
+    fn read(r: &mut impl BufRead) -> io::Result<Option<Self>> {
+        let text = match read_line(r)? {
+            None => return Ok(None),
+            Some(text) => text,
+        };
+        let msg = serde_json::from_str(&text)?;
+        Ok(Some(msg))
+    }
+
+    fn write(self, w: &mut impl Write) -> io::Result<()> {
+        let text = serde_json::to_string(&self)?;
+        write_line(w, &text)
+    }
+}
+
+impl Message for Request {}
+impl Message for Response {}
+
+fn read_line(r: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut buf = String::new();
+    if r.read_line(&mut buf)? == 0 {
+        return Ok(None);
+    }
+    let len = buf.trim_end_matches('\n').len();
+    buf.truncate(len);
+    Ok(Some(buf))
+}
+
+fn write_line(w: &mut impl Write, msg: &str) -> io::Result<()> {
+    log::debug!("> {}", msg);
+    w.write_all(msg.as_bytes())?;
+    w.write_all(b"\n")?;
+    w.flush()?;
+    Ok(())
+}
@@ -0,0 +1,180 @@
+//! Handles the external proc macro server process.
+
+use std::{
+    ffi::OsString,
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+    sync::Mutex,
+    time::Duration,
+};
+
+use crossbeam_channel::{Receiver, RecvTimeoutError};
+use tt::ExpansionError;
+
+use crate::{
+    msg::{Message, Request, Response},
+    rpc::{ExpansionResult, ExpansionTask, ListMacrosResult, ListMacrosTask, ProcMacroKind},
+};
+
+/// How long the server may take to answer a request. A macro which takes
+/// longer is assumed to loop forever.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Connection to the server process. Requests are sent one at a time; if the
+/// server dies, for example because a macro aborted the process, or doesn't
+/// answer in time, the pending request fails and the server is restarted for
+/// the next one.
+#[derive(Debug)]
+pub(crate) struct ProcMacroProcessSrv {
+    process: Mutex<Process>,
+}
+
+impl ProcMacroProcessSrv {
+    pub(crate) fn run(
+        process_path: &Path,
+        args: impl IntoIterator<Item = impl Into<OsString>>,
+    ) -> io::Result<ProcMacroProcessSrv> {
+        let process = Process::run(process_path, args)?;
+        Ok(ProcMacroProcessSrv { process: Mutex::new(process) })
+    }
+
+    pub(crate) fn find_proc_macros(
+        &self,
+        dylib_path: &Path,
+    ) -> Result<Vec<(String, ProcMacroKind)>, ExpansionError> {
+        let task = ListMacrosTask { lib: dylib_path.to_path_buf() };
+        match self.send_request(Request::ListMacro(task))? {
+            Response::ListMacro(ListMacrosResult { macros }) => Ok(macros),
+            res => Err(unexpected_response(res)),
+        }
+    }
+
+    pub(crate) fn expand(&self, task: ExpansionTask) -> Result<tt::Subtree, ExpansionError> {
+        match self.send_request(Request::ExpansionMacro(task))? {
+            Response::ExpansionMacro(ExpansionResult { expansion }) => Ok(expansion),
+            res => Err(unexpected_response(res)),
+        }
+    }
+
+    fn send_request(&self, req: Request) -> Result<Response, ExpansionError> {
+        // A poisoned lock only means that an earlier request panicked; if that
+        // broke the connection, the server is restarted below.
+        let mut process = self.process.lock().unwrap_or_else(|err| err.into_inner());
+        let res = match process.send_request(req) {
+            Ok(res) => res,
+            Err(err) => {
+                if let Err(restart_err) = process.restart() {
+                    log::error!("failed to restart the proc macro server: {}", restart_err);
+                }
+                return Err(ExpansionError::Io(err.to_string()));
+            }
+        };
+        match res {
+            Response::Error(err) => Err(ExpansionError::Expansion(err.message)),
+            res => Ok(res),
+        }
+    }
+}
+
+fn unexpected_response(res: Response) -> ExpansionError {
+    ExpansionError::Protocol(format!("unexpected response: {:?}", res))
+}
+
+#[derive(Debug)]
+struct Process {
+    path: PathBuf,
+    args: Vec<OsString>,
+    child: Child,
+    stdin: ChildStdin,
+    /// Responses read from stdout by `_reader`, so that waiting for them can
+    /// time out.
+    responses: Receiver<Response>,
+    _reader: jod_thread::JoinHandle<()>,
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        // Closes stdout, which stops the reader thread before it is joined.
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl Process {
+    fn run(
+        process_path: &Path,
+        args: impl IntoIterator<Item = impl Into<OsString>>,
+    ) -> io::Result<Process> {
+        let path = process_path.to_path_buf();
+        let args = args.into_iter().map(Into::into).collect::<Vec<_>>();
+        let (child, stdin, stdout) = spawn(&path, &args)?;
+        let (responses, _reader) = read_responses(stdout)?;
+        Ok(Process { path, args, child, stdin, responses, _reader })
+    }
+
+    fn restart(&mut self) -> io::Result<()> {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let (child, stdin, stdout) = spawn(&self.path, &self.args)?;
+        let (responses, reader) = read_responses(stdout)?;
+        self.child = child;
+        self.stdin = stdin;
+        self.responses = responses;
+        self._reader = reader;
+        Ok(())
+    }
+
+    fn send_request(&mut self, req: Request) -> io::Result<Response> {
+        req.write(&mut self.stdin)?;
+        match self.responses.recv_timeout(REQUEST_TIMEOUT) {
+            Ok(res) => Ok(res),
+            Err(RecvTimeoutError::Timeout) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("proc macro server didn't answer in {:?}", REQUEST_TIMEOUT),
+            )),
+            Err(RecvTimeoutError::Disconnected) => {
+                Err(io::Error::new(io::ErrorKind::UnexpectedEof, "proc macro server crashed"))
+            }
+        }
+    }
+}
+
+/// Reads the responses of the server on a separate thread until it closes
+/// stdout or sends garbage.
+fn read_responses(
+    mut stdout: BufReader<ChildStdout>,
+) -> io::Result<(Receiver<Response>, jod_thread::JoinHandle<()>)> {
+    let (sender, receiver) = crossbeam_channel::unbounded();
+    let thread =
+        jod_thread::Builder::new().name("ProcMacroReader".to_string()).spawn(move || loop {
+            match Response::read(&mut stdout) {
+                Ok(Some(res)) => {
+                    if sender.send(res).is_err() {
+                        break;
+                    }
+                }
+                Ok(None) => break,
+                Err(err) => {
+                    log::error!("invalid response from the proc macro server: {}", err);
+                    break;
+                }
+            }
+        })?;
+    Ok((receiver, thread))
+}
+
+fn spawn(
+    path: &Path,
+    args: &[OsString],
+) -> io::Result<(Child, ChildStdin, BufReader<ChildStdout>)> {
+    let mut child = Command::new(path)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()?;
+    let stdin = child.stdin.take().expect("stdin is piped");
+    let stdout = child.stdout.take().expect("stdout is piped");
+    Ok((child, stdin, BufReader::new(stdout)))
+}
@@ -0,0 +1,213 @@
+//! Payloads of the proc macro protocol.
+//!
+//! `tt` types don't implement serde traits themselves, so we use serde's
+//! remote derives to (de)serialize them.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use smol_str::SmolStr;
+use tt::{Delimiter, Ident, Leaf, Literal, Punct, Spacing, Subtree, TokenId, TokenTree};
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ProcMacroKind {
+    CustomDerive,
+    FuncLike,
+    Attr,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ListMacrosTask {
+    pub lib: PathBuf,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct ListMacrosResult {
+    pub macros: Vec<(String, ProcMacroKind)>,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ExpansionTask {
+    /// Argument of the macro call, or the annotated item for derives and
+    /// attribute macros.
+    #[serde(with = "SubtreeDef")]
+    pub macro_body: Subtree,
+
+    pub macro_name: String,
+
+    /// Arguments of the attribute for attribute macros.
+    #[serde(with = "opt_subtree_def")]
+    pub attributes: Option<Subtree>,
+
+    pub lib: PathBuf,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct ExpansionResult {
+    #[serde(with = "SubtreeDef")]
+    pub expansion: Subtree,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "TokenId")]
+struct TokenIdDef(u32);
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Delimiter")]
+enum DelimiterDef {
+    Parenthesis,
+    Brace,
+    Bracket,
+    None,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Subtree")]
+struct SubtreeDef {
+    #[serde(with = "DelimiterDef")]
+    delimiter: Delimiter,
+    #[serde(with = "vec_token_tree")]
+    token_trees: Vec<TokenTree>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "TokenTree")]
+enum TokenTreeDef {
+    #[serde(with = "LeafDef")]
+    Leaf(Leaf),
+    #[serde(with = "SubtreeDef")]
+    Subtree(Subtree),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Leaf")]
+enum LeafDef {
+    #[serde(with = "LiteralDef")]
+    Literal(Literal),
+    #[serde(with = "PunctDef")]
+    Punct(Punct),
+    #[serde(with = "IdentDef")]
+    Ident(Ident),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Literal")]
+struct LiteralDef {
+    text: SmolStr,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Punct")]
+struct PunctDef {
+    char: char,
+    #[serde(with = "SpacingDef")]
+    spacing: Spacing,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Spacing")]
+enum SpacingDef {
+    Alone,
+    Joint,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Ident")]
+struct IdentDef {
+    text: SmolStr,
+    #[serde(with = "TokenIdDef")]
+    id: TokenId,
+}
+
+mod opt_subtree_def {
+    use super::{Subtree, SubtreeDef};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct Helper(#[serde(with = "SubtreeDef")] Subtree);
+
+    pub fn serialize<S>(value: &Option<Subtree>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        value.clone().map(Helper).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Subtree>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let helper = Option::deserialize(deserializer)?;
+        Ok(helper.map(|Helper(subtree)| subtree))
+    }
+}
+
+mod vec_token_tree {
+    use super::{TokenTree, TokenTreeDef};
+    use serde::{ser::SerializeSeq, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S>(value: &[TokenTree], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        #[derive(Serialize)]
+        struct Helper<'a>(#[serde(with = "TokenTreeDef")] &'a TokenTree);
+
+        let mut seq = serializer.serialize_seq(Some(value.len()))?;
+        for tt in value {
+            seq.serialize_element(&Helper(tt))?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<TokenTree>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Helper(#[serde(with = "TokenTreeDef")] TokenTree);
+
+        let helpers: Vec<Helper> = Deserialize::deserialize(deserializer)?;
+        Ok(helpers.into_iter().map(|Helper(tt)| tt).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture_token_tree() -> Subtree {
+        let ident = Ident { text: "Foo".into(), id: TokenId(42) };
+        let punct = Punct { char: ';', spacing: Spacing::Alone };
+        let body = Subtree {
+            delimiter: Delimiter::Brace,
+            token_trees: vec![TokenTree::Leaf(Literal { text: "1u32".into() }.into())],
+        };
+        Subtree {
+            delimiter: Delimiter::None,
+            token_trees: vec![
+                TokenTree::Leaf(Ident { text: "struct".into(), id: TokenId(0) }.into()),
+                TokenTree::Leaf(ident.into()),
+                TokenTree::Leaf(punct.into()),
+                TokenTree::Subtree(body),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_proc_macro_rpc_works() {
+        let tt = fixture_token_tree();
+        let task = ExpansionTask {
+            macro_body: tt.clone(),
+            macro_name: "MyDerive".into(),
+            attributes: None,
+            lib: PathBuf::from("libproc.so"),
+        };
+
+        let json = serde_json::to_string(&task).unwrap();
+        let back: ExpansionTask = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(task, back);
+        assert_eq!(back.macro_body, tt);
+    }
+}
@@ -0,0 +1,139 @@
+//! Loads the proc macros of a dylib and runs them through the bridge.
+
+use std::{fs::File, io, path::Path};
+
+use goblin::{mach::Mach, Object};
+use libloading::Library;
+use memmap::Mmap;
+use ra_proc_macro::ProcMacroKind;
+
+use crate::{
+    proc_macro::bridge::{self, client::ProcMacro},
+    rustc_server::{Rustc, TokenStream},
+};
+
+/// rustc exports the macros of a proc macro crate in a symbol whose name
+/// starts with this, followed by a hash.
+const REGISTRAR_SYMBOL: &str = "__rustc_proc_macro_decls_";
+
+fn invalid_data_err(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+fn find_registrar_symbol(file: &Path) -> io::Result<Option<String>> {
+    let file = File::open(file)?;
+    let buffer = unsafe { Mmap::map(&file)? };
+    let object = Object::parse(&buffer).map_err(invalid_data_err)?;
+
+    let name = match object {
+        Object::Elf(elf) => {
+            let symbols = elf.dynstrtab.to_vec().map_err(invalid_data_err)?;
+            symbols.into_iter().find(|s| s.starts_with(REGISTRAR_SYMBOL)).map(|s| s.to_string())
+        }
+        Object::PE(pe) => pe
+            .exports
+            .iter()
+            .flat_map(|s| s.name)
+            .find(|s| s.starts_with(REGISTRAR_SYMBOL))
+            .map(|s| s.to_string()),
+        Object::Mach(Mach::Binary(binary)) => {
+            let exports = binary.exports().map_err(invalid_data_err)?;
+            exports
+                .iter()
+                // `dlsym` wants the names without the leading underscore of Mach-O
+                .filter(|s| s.name.starts_with('_'))
+                .map(|s| &s.name[1..])
+                .find(|s| s.starts_with(REGISTRAR_SYMBOL))
+                .map(|s| s.to_string())
+        }
+        _ => None,
+    };
+    Ok(name)
+}
+
+/// Loads the dylib with its own copy of the symbols it defines: otherwise
+/// they can be bound to the ones of an already loaded dylib, like when two
+/// versions of a proc macro crate are used.
+#[cfg(target_os = "linux")]
+fn load_library(file: &Path) -> io::Result<Library> {
+    use libloading::os::unix::Library as UnixLibrary;
+    use std::os::raw::c_int;
+
+    const RTLD_NOW: c_int = 0x00002;
+    const RTLD_DEEPBIND: c_int = 0x00008;
+
+    UnixLibrary::open(Some(file), RTLD_NOW | RTLD_DEEPBIND).map(|lib| lib.into())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn load_library(file: &Path) -> io::Result<Library> {
+    Library::new(file)
+}
+
+/// The proc macros of a dylib, which stay valid as long as it is loaded.
+pub struct Expander {
+    _lib: Library,
+    macros: Vec<ProcMacro>,
+}
+
+impl Expander {
+    pub fn new(file: &Path) -> io::Result<Expander> {
+        let symbol_name = find_registrar_symbol(file)?.ok_or_else(|| {
+            invalid_data_err(format!("{} is not a proc macro dylib", file.display()))
+        })?;
+
+        let lib = load_library(file)?;
+        let macros = unsafe {
+            let macros: libloading::Symbol<&&[ProcMacro]> = lib.get(symbol_name.as_bytes())?;
+            macros.to_vec()
+        };
+        Ok(Expander { _lib: lib, macros })
+    }
+
+    pub fn list_macros(&self) -> Vec<(String, ProcMacroKind)> {
+        self.macros
+            .iter()
+            .map(|proc_macro| match proc_macro {
+                ProcMacro::CustomDerive { trait_name, .. } => {
+                    (trait_name.to_string(), ProcMacroKind::CustomDerive)
+                }
+                ProcMacro::Bang { name, .. } => (name.to_string(), ProcMacroKind::FuncLike),
+                ProcMacro::Attr { name, .. } => (name.to_string(), ProcMacroKind::Attr),
+            })
+            .collect()
+    }
+
+    /// Runs the macro called `macro_name`. Returns `None` if there is no such
+    /// macro, and the panic message if it panics.
+    pub fn expand(
+        &self,
+        macro_name: &str,
+        macro_body: &tt::Subtree,
+        attributes: Option<&tt::Subtree>,
+    ) -> Option<Result<tt::Subtree, String>> {
+        let body = TokenStream::with_subtree_contents(macro_body);
+        let res = self.macros.iter().find_map(|proc_macro| match proc_macro {
+            ProcMacro::CustomDerive { trait_name, client, .. } if *trait_name == macro_name => {
+                Some(client.run(&bridge::server::SameThread, Rustc::default(), body.clone()))
+            }
+            ProcMacro::Bang { name, client } if *name == macro_name => {
+                Some(client.run(&bridge::server::SameThread, Rustc::default(), body.clone()))
+            }
+            ProcMacro::Attr { name, client } if *name == macro_name => {
+                let attributes =
+                    attributes.map(TokenStream::with_subtree_contents).unwrap_or_default();
+                Some(client.run(
+                    &bridge::server::SameThread,
+                    Rustc::default(),
+                    attributes,
+                    body.clone(),
+                ))
+            }
+            _ => None,
+        })?;
+        let res = res.map(TokenStream::into_subtree).map_err(|panic| {
+            panic.as_str().unwrap_or("proc macro panicked with a non-string payload").to_string()
+        });
+        Some(res)
+    }
+}
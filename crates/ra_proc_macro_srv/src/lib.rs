@@ -0,0 +1,203 @@
+//! Server side of procedural macro expansion.
+//!
+//! This runs in a separate process (`ra_lsp_server proc-macro`) and answers
+//! the requests of `ra_proc_macro::ProcMacroClient`, one per line on stdin.
+//! A macro which panics, aborts or loops only takes down this process, which
+//! the client restarts.
+
+mod dylib;
+mod proc_macro;
+mod rustc_server;
+
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use ra_proc_macro::{
+    msg::{ErrorCode, Message, Request, Response, ResponseError},
+    rpc::{ExpansionResult, ExpansionTask, ListMacrosResult, ListMacrosTask},
+};
+
+use crate::dylib::Expander;
+
+/// Serves requests from stdin until the client closes it.
+pub fn run() -> io::Result<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut stdin = stdin.lock();
+    let mut stdout = stdout.lock();
+
+    let mut srv = ProcMacroSrv::default();
+    while let Some(req) = Request::read(&mut stdin)? {
+        let res = srv.handle(req);
+        res.write(&mut stdout)?;
+    }
+    Ok(())
+}
+
+/// The loaded dylibs, by path and modification time, so that a dylib is
+/// reloaded once it is rebuilt.
+#[derive(Default)]
+struct ProcMacroSrv {
+    expanders: HashMap<(PathBuf, SystemTime), Expander>,
+}
+
+impl ProcMacroSrv {
+    fn handle(&mut self, req: Request) -> Response {
+        let res = match req {
+            Request::ListMacro(task) => self.list_macros(&task).map(Response::ListMacro),
+            Request::ExpansionMacro(task) => self.expand(&task).map(Response::ExpansionMacro),
+        };
+        res.unwrap_or_else(Response::Error)
+    }
+
+    fn list_macros(&mut self, task: &ListMacrosTask) -> Result<ListMacrosResult, ResponseError> {
+        let expander = self.expander(&task.lib)?;
+        Ok(ListMacrosResult { macros: expander.list_macros() })
+    }
+
+    fn expand(&mut self, task: &ExpansionTask) -> Result<ExpansionResult, ResponseError> {
+        let expander = self.expander(&task.lib)?;
+        match expander.expand(&task.macro_name, &task.macro_body, task.attributes.as_ref()) {
+            Some(Ok(expansion)) => Ok(ExpansionResult { expansion }),
+            Some(Err(message)) => Err(ResponseError { code: ErrorCode::ExpansionError, message }),
+            None => Err(ResponseError {
+                code: ErrorCode::LoadError,
+                message: format!(
+                    "no proc macro named `{}` in {}",
+                    task.macro_name,
+                    task.lib.display()
+                ),
+            }),
+        }
+    }
+
+    /// Loads the proc macros of the dylib at `path`.
+    ///
+    /// FIXME: the dylib has to be built by a rustc whose `proc_macro` bridge
+    /// ABI is the one of our copy, and we don't check that it is.
+    fn expander(&mut self, path: &Path) -> Result<&Expander, ResponseError> {
+        let err = |e: io::Error| ResponseError {
+            code: ErrorCode::LoadError,
+            message: format!("can't load {}: {}", path.display(), e),
+        };
+        let modified = fs::metadata(path).and_then(|it| it.modified()).map_err(err)?;
+        let key = (path.to_path_buf(), modified);
+        if !self.expanders.contains_key(&key) {
+            let expander = Expander::new(path).map_err(err)?;
+            // Unload the previous builds of the dylib
+            self.expanders.retain(|(lib, _), _| lib != path);
+            self.expanders.insert(key.clone(), expander);
+        }
+        Ok(&self.expanders[&key])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use cargo_metadata::Message;
+    use ra_proc_macro::ProcMacroKind;
+
+    use super::*;
+
+    /// Builds the `ra_proc_macro_test` crate and returns the path of its dylib.
+    fn fixture_dylib() -> PathBuf {
+        let manifest_path =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("../ra_proc_macro_test/Cargo.toml");
+        let output = Command::new("cargo")
+            .args(&["build", "--message-format=json", "--manifest-path"])
+            .arg(&manifest_path)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+        for message in cargo_metadata::parse_messages(output.stdout.as_slice()) {
+            let artifact = match message {
+                Ok(Message::CompilerArtifact(it)) => it,
+                _ => continue,
+            };
+            if artifact.target.name == "ra_proc_macro_test" {
+                return artifact.filenames.into_iter().next().unwrap();
+            }
+        }
+        panic!("ra_proc_macro_test was not built")
+    }
+
+    fn expand(macro_name: &str, body: &str, attributes: Option<&str>) -> String {
+        let parse = |text| ra_mbe::parse_to_token_tree(text).unwrap().0;
+        let task = ExpansionTask {
+            macro_body: parse(body),
+            macro_name: macro_name.to_string(),
+            attributes: attributes.map(parse),
+            lib: fixture_dylib(),
+        };
+        match ProcMacroSrv::default().handle(Request::ExpansionMacro(task)) {
+            Response::ExpansionMacro(res) => res.expansion.to_string(),
+            res => panic!("unexpected response: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn lists_macros_of_dylib() {
+        let task = ListMacrosTask { lib: fixture_dylib() };
+        let mut macros = match ProcMacroSrv::default().handle(Request::ListMacro(task)) {
+            Response::ListMacro(res) => res.macros,
+            res => panic!("unexpected response: {:?}", res),
+        };
+        macros.sort_by(|(a, _), (b, _)| a.cmp(b));
+        assert_eq!(
+            macros,
+            vec![
+                ("DummyTrait".to_string(), ProcMacroKind::CustomDerive),
+                ("rename".to_string(), ProcMacroKind::Attr),
+                ("twice".to_string(), ProcMacroKind::FuncLike),
+            ]
+        );
+    }
+
+    #[test]
+    fn expands_function_like_macro() {
+        assert_eq!(expand("twice", "1", None), "1 + 1");
+    }
+
+    #[test]
+    fn expands_custom_derive() {
+        assert_eq!(expand("DummyTrait", "struct Foo;", None), "impl DummyTrait for Foo {}");
+    }
+
+    #[test]
+    fn expands_attribute_macro() {
+        assert_eq!(expand("rename", "fn foo() {}", Some("bar")), "fn bar () {}");
+    }
+
+    #[test]
+    fn unknown_macro_is_an_error_response() {
+        let task = ExpansionTask {
+            macro_body: ra_mbe::parse_to_token_tree("1").unwrap().0,
+            macro_name: "thrice".to_string(),
+            attributes: None,
+            lib: fixture_dylib(),
+        };
+        match ProcMacroSrv::default().handle(Request::ExpansionMacro(task)) {
+            Response::Error(err) => assert_eq!(err.code, ErrorCode::LoadError),
+            res => panic!("unexpected response: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn missing_dylib_is_an_error_response() {
+        let task = ListMacrosTask { lib: PathBuf::from("/no/such/libproc.so") };
+        match ProcMacroSrv::default().handle(Request::ListMacro(task)) {
+            Response::Error(err) => {
+                assert_eq!(err.code, ErrorCode::LoadError);
+                assert!(err.message.contains("libproc.so"), "{}", err.message);
+            }
+            res => panic!("unexpected response: {:?}", res),
+        }
+    }
+}
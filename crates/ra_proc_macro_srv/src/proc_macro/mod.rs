@@ -0,0 +1,46 @@
+//! The server side of rustc's `proc_macro` bridge, through which the macros
+//! of a dylib are run: the dylib's copy of `proc_macro` is the client, and
+//! `rustc_server` implements the server over our token trees.
+//!
+//! Copied from `src/libproc_macro` of rust-lang/rust, keeping only what the
+//! server needs. The bridge ABI is unstable, so the dylibs have to be built by
+//! a compiler which agrees with this copy.
+
+pub mod bridge;
+
+/// The stream taken and returned by the macros, which only exists on the
+/// client side.
+pub enum TokenStream {}
+
+/// Describes how a sequence of token trees is delimited.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Delimiter {
+    Parenthesis,
+    Brace,
+    Bracket,
+    None,
+}
+
+/// Whether a `Punct` is followed immediately by another `Punct` or by another
+/// token or whitespace.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Spacing {
+    Alone,
+    Joint,
+}
+
+/// A line-column pair representing the start or end of a `Span`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LineColumn {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// The level of a diagnostic emitted by a macro.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Level {
+    Error,
+    Warning,
+    Note,
+    Help,
+}
@@ -0,0 +1,408 @@
+//! Implements the `proc_macro` server traits over `tt`, so that the macros of
+//! a dylib can be run on our token trees.
+//!
+//! Spans are the `TokenId`s of identifiers. Other tokens have no id, and there
+//! is neither hygiene nor source information, so most span queries return the
+//! unspecified `TokenId`.
+
+use std::{collections::HashMap, ops::Bound, vec::IntoIter};
+
+use crate::proc_macro::{
+    bridge::{self, server},
+    Delimiter, Level, LineColumn, Spacing,
+};
+
+/// A sequence of token trees, without delimiters.
+#[derive(Debug, Clone, Default)]
+pub struct TokenStream {
+    pub token_trees: Vec<tt::TokenTree>,
+}
+
+impl TokenStream {
+    /// The tokens of `subtree`, without its delimiters: the arguments of
+    /// `foo!(...)` are passed to `foo` without the parentheses.
+    pub fn with_subtree_contents(subtree: &tt::Subtree) -> TokenStream {
+        TokenStream { token_trees: subtree.token_trees.clone() }
+    }
+
+    pub fn into_subtree(self) -> tt::Subtree {
+        tt::Subtree { delimiter: tt::Delimiter::None, token_trees: self.token_trees }
+    }
+}
+
+#[derive(Clone)]
+pub struct TokenStreamIter {
+    trees: IntoIter<tt::TokenTree>,
+}
+
+/// FIXME: token trees don't record the file they come from.
+#[derive(Clone)]
+pub struct SourceFile;
+
+pub struct Diagnostic {
+    level: Level,
+    message: String,
+}
+
+/// Identifiers are interned, as the bridge requires them to be `Copy`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IdentId(u32);
+
+#[derive(Default)]
+struct IdentInterner {
+    ids: HashMap<tt::Ident, IdentId>,
+    idents: Vec<tt::Ident>,
+}
+
+impl IdentInterner {
+    fn intern(&mut self, ident: tt::Ident) -> IdentId {
+        if let Some(&id) = self.ids.get(&ident) {
+            return id;
+        }
+        let id = IdentId(self.idents.len() as u32);
+        self.idents.push(ident.clone());
+        self.ids.insert(ident, id);
+        id
+    }
+
+    fn get(&self, id: IdentId) -> &tt::Ident {
+        &self.idents[id.0 as usize]
+    }
+}
+
+#[derive(Default)]
+pub struct Rustc {
+    idents: IdentInterner,
+}
+
+impl server::Types for Rustc {
+    type TokenStream = TokenStream;
+    type TokenStreamBuilder = TokenStream;
+    type TokenStreamIter = TokenStreamIter;
+    type Group = tt::Subtree;
+    type Punct = tt::Punct;
+    type Ident = IdentId;
+    type Literal = tt::Literal;
+    type SourceFile = SourceFile;
+    type MultiSpan = Vec<tt::TokenId>;
+    type Diagnostic = Diagnostic;
+    type Span = tt::TokenId;
+}
+
+impl server::TokenStream for Rustc {
+    fn new(&mut self) -> Self::TokenStream {
+        TokenStream::default()
+    }
+
+    fn is_empty(&mut self, stream: &Self::TokenStream) -> bool {
+        stream.token_trees.is_empty()
+    }
+
+    fn from_str(&mut self, src: &str) -> Self::TokenStream {
+        let (mut subtree, _) = match ra_mbe::parse_to_token_tree(src) {
+            Some(it) => it,
+            None => panic!("unbalanced delimiters in `{}`", src),
+        };
+        // The ids of the parsed identifiers don't refer to any source tokens
+        forget_token_ids(&mut subtree);
+        TokenStream { token_trees: subtree.token_trees }
+    }
+
+    fn to_string(&mut self, stream: &Self::TokenStream) -> String {
+        stream.clone().into_subtree().to_string()
+    }
+
+    fn from_token_tree(
+        &mut self,
+        tree: bridge::TokenTree<Self::Group, Self::Punct, Self::Ident, Self::Literal>,
+    ) -> Self::TokenStream {
+        let tree = match tree {
+            bridge::TokenTree::Group(group) => group.into(),
+            bridge::TokenTree::Punct(punct) => tt::Leaf::from(punct).into(),
+            bridge::TokenTree::Ident(ident) => {
+                tt::Leaf::from(self.idents.get(ident).clone()).into()
+            }
+            bridge::TokenTree::Literal(literal) => tt::Leaf::from(literal).into(),
+        };
+        TokenStream { token_trees: vec![tree] }
+    }
+
+    fn into_iter(&mut self, stream: Self::TokenStream) -> Self::TokenStreamIter {
+        TokenStreamIter { trees: stream.token_trees.into_iter() }
+    }
+}
+
+impl server::TokenStreamBuilder for Rustc {
+    fn new(&mut self) -> Self::TokenStreamBuilder {
+        TokenStream::default()
+    }
+
+    fn push(&mut self, builder: &mut Self::TokenStreamBuilder, stream: Self::TokenStream) {
+        builder.token_trees.extend(stream.token_trees)
+    }
+
+    fn build(&mut self, builder: Self::TokenStreamBuilder) -> Self::TokenStream {
+        builder
+    }
+}
+
+impl server::TokenStreamIter for Rustc {
+    fn next(
+        &mut self,
+        iter: &mut Self::TokenStreamIter,
+    ) -> Option<bridge::TokenTree<Self::Group, Self::Punct, Self::Ident, Self::Literal>> {
+        let tree = match iter.trees.next()? {
+            tt::TokenTree::Subtree(group) => bridge::TokenTree::Group(group),
+            tt::TokenTree::Leaf(tt::Leaf::Punct(punct)) => bridge::TokenTree::Punct(punct),
+            tt::TokenTree::Leaf(tt::Leaf::Ident(ident)) => {
+                bridge::TokenTree::Ident(self.idents.intern(ident))
+            }
+            tt::TokenTree::Leaf(tt::Leaf::Literal(literal)) => bridge::TokenTree::Literal(literal),
+        };
+        Some(tree)
+    }
+}
+
+impl server::Group for Rustc {
+    fn new(&mut self, delimiter: Delimiter, stream: Self::TokenStream) -> Self::Group {
+        let delimiter = match delimiter {
+            Delimiter::Parenthesis => tt::Delimiter::Parenthesis,
+            Delimiter::Brace => tt::Delimiter::Brace,
+            Delimiter::Bracket => tt::Delimiter::Bracket,
+            Delimiter::None => tt::Delimiter::None,
+        };
+        tt::Subtree { delimiter, token_trees: stream.token_trees }
+    }
+
+    fn delimiter(&mut self, group: &Self::Group) -> Delimiter {
+        match group.delimiter {
+            tt::Delimiter::Parenthesis => Delimiter::Parenthesis,
+            tt::Delimiter::Brace => Delimiter::Brace,
+            tt::Delimiter::Bracket => Delimiter::Bracket,
+            tt::Delimiter::None => Delimiter::None,
+        }
+    }
+
+    fn stream(&mut self, group: &Self::Group) -> Self::TokenStream {
+        TokenStream { token_trees: group.token_trees.clone() }
+    }
+
+    fn span(&mut self, _group: &Self::Group) -> Self::Span {
+        tt::TokenId::unspecified()
+    }
+
+    fn span_open(&mut self, _group: &Self::Group) -> Self::Span {
+        tt::TokenId::unspecified()
+    }
+
+    fn span_close(&mut self, _group: &Self::Group) -> Self::Span {
+        tt::TokenId::unspecified()
+    }
+
+    fn set_span(&mut self, _group: &mut Self::Group, _span: Self::Span) {}
+}
+
+impl server::Punct for Rustc {
+    fn new(&mut self, ch: char, spacing: Spacing) -> Self::Punct {
+        let spacing = match spacing {
+            Spacing::Alone => tt::Spacing::Alone,
+            Spacing::Joint => tt::Spacing::Joint,
+        };
+        tt::Punct { char: ch, spacing }
+    }
+
+    fn as_char(&mut self, punct: Self::Punct) -> char {
+        punct.char
+    }
+
+    fn spacing(&mut self, punct: Self::Punct) -> Spacing {
+        match punct.spacing {
+            tt::Spacing::Alone => Spacing::Alone,
+            tt::Spacing::Joint => Spacing::Joint,
+        }
+    }
+
+    fn span(&mut self, _punct: Self::Punct) -> Self::Span {
+        tt::TokenId::unspecified()
+    }
+
+    fn with_span(&mut self, punct: Self::Punct, _span: Self::Span) -> Self::Punct {
+        punct
+    }
+}
+
+impl server::Ident for Rustc {
+    fn new(&mut self, string: &str, span: Self::Span, is_raw: bool) -> Self::Ident {
+        let text = if is_raw { format!("r#{}", string).into() } else { string.into() };
+        self.idents.intern(tt::Ident { text, id: span })
+    }
+
+    fn span(&mut self, ident: Self::Ident) -> Self::Span {
+        self.idents.get(ident).id
+    }
+
+    fn with_span(&mut self, ident: Self::Ident, span: Self::Span) -> Self::Ident {
+        let ident = tt::Ident { id: span, ..self.idents.get(ident).clone() };
+        self.idents.intern(ident)
+    }
+}
+
+impl server::Literal for Rustc {
+    fn debug(&mut self, literal: &Self::Literal) -> String {
+        format!("{:?}", literal)
+    }
+
+    fn integer(&mut self, n: &str) -> Self::Literal {
+        tt::Literal { text: n.into() }
+    }
+
+    fn typed_integer(&mut self, n: &str, kind: &str) -> Self::Literal {
+        tt::Literal { text: format!("{}{}", n, kind).into() }
+    }
+
+    fn float(&mut self, n: &str) -> Self::Literal {
+        // `1f64.to_string()` is "1", which would be an integer
+        let text = if n.contains('.') { n.into() } else { format!("{}.0", n).into() };
+        tt::Literal { text }
+    }
+
+    fn f32(&mut self, n: &str) -> Self::Literal {
+        tt::Literal { text: format!("{}f32", n).into() }
+    }
+
+    fn f64(&mut self, n: &str) -> Self::Literal {
+        tt::Literal { text: format!("{}f64", n).into() }
+    }
+
+    fn string(&mut self, string: &str) -> Self::Literal {
+        tt::Literal { text: format!("\"{}\"", string.escape_debug()).into() }
+    }
+
+    fn character(&mut self, ch: char) -> Self::Literal {
+        tt::Literal { text: format!("'{}'", ch.escape_debug()).into() }
+    }
+
+    fn byte_string(&mut self, bytes: &[u8]) -> Self::Literal {
+        let escaped = bytes
+            .iter()
+            .flat_map(|&b| std::ascii::escape_default(b))
+            .map(char::from)
+            .collect::<String>();
+        tt::Literal { text: format!("b\"{}\"", escaped).into() }
+    }
+
+    fn span(&mut self, _literal: &Self::Literal) -> Self::Span {
+        tt::TokenId::unspecified()
+    }
+
+    fn set_span(&mut self, _literal: &mut Self::Literal, _span: Self::Span) {}
+
+    fn subspan(
+        &mut self,
+        _literal: &Self::Literal,
+        _start: Bound<usize>,
+        _end: Bound<usize>,
+    ) -> Option<Self::Span> {
+        None
+    }
+}
+
+impl server::SourceFile for Rustc {
+    fn eq(&mut self, _file1: &Self::SourceFile, _file2: &Self::SourceFile) -> bool {
+        true
+    }
+
+    fn path(&mut self, _file: &Self::SourceFile) -> String {
+        String::new()
+    }
+
+    fn is_real(&mut self, _file: &Self::SourceFile) -> bool {
+        false
+    }
+}
+
+impl server::MultiSpan for Rustc {
+    fn new(&mut self) -> Self::MultiSpan {
+        Vec::new()
+    }
+
+    fn push(&mut self, spans: &mut Self::MultiSpan, span: Self::Span) {
+        spans.push(span)
+    }
+}
+
+impl server::Diagnostic for Rustc {
+    fn new(&mut self, level: Level, msg: &str, _spans: Self::MultiSpan) -> Self::Diagnostic {
+        Diagnostic { level, message: msg.to_string() }
+    }
+
+    fn sub(
+        &mut self,
+        _diag: &mut Self::Diagnostic,
+        _level: Level,
+        _msg: &str,
+        _spans: Self::MultiSpan,
+    ) {
+    }
+
+    fn emit(&mut self, diag: Self::Diagnostic) {
+        // FIXME: send the diagnostics back to the client
+        log::warn!("proc macro diagnostic: {:?}: {}", diag.level, diag.message)
+    }
+}
+
+impl server::Span for Rustc {
+    fn debug(&mut self, span: Self::Span) -> String {
+        format!("{:?}", span.0)
+    }
+
+    fn def_site(&mut self) -> Self::Span {
+        tt::TokenId::unspecified()
+    }
+
+    fn call_site(&mut self) -> Self::Span {
+        tt::TokenId::unspecified()
+    }
+
+    fn source_file(&mut self, _span: Self::Span) -> Self::SourceFile {
+        SourceFile
+    }
+
+    fn parent(&mut self, _span: Self::Span) -> Option<Self::Span> {
+        None
+    }
+
+    fn source(&mut self, span: Self::Span) -> Self::Span {
+        span
+    }
+
+    fn start(&mut self, _span: Self::Span) -> LineColumn {
+        LineColumn { line: 0, column: 0 }
+    }
+
+    fn end(&mut self, _span: Self::Span) -> LineColumn {
+        LineColumn { line: 0, column: 0 }
+    }
+
+    fn join(&mut self, first: Self::Span, _second: Self::Span) -> Option<Self::Span> {
+        Some(first)
+    }
+
+    fn resolved_at(&mut self, _span: Self::Span, at: Self::Span) -> Self::Span {
+        at
+    }
+
+    fn source_text(&mut self, _span: Self::Span) -> Option<String> {
+        None
+    }
+}
+
+fn forget_token_ids(subtree: &mut tt::Subtree) {
+    for tree in subtree.token_trees.iter_mut() {
+        match tree {
+            tt::TokenTree::Subtree(subtree) => forget_token_ids(subtree),
+            tt::TokenTree::Leaf(tt::Leaf::Ident(ident)) => ident.id = tt::TokenId::unspecified(),
+            tt::TokenTree::Leaf(_) => (),
+        }
+    }
+}
@@ -0,0 +1,52 @@
+//! Proc macros which `ra_proc_macro_srv` builds and expands in its tests.
+
+extern crate proc_macro;
+
+use std::iter;
+
+use proc_macro::{Ident, Punct, Spacing, TokenStream, TokenTree};
+
+/// `twice!(x)` expands to `x + x`.
+#[proc_macro]
+pub fn twice(input: TokenStream) -> TokenStream {
+    let plus = TokenTree::from(Punct::new('+', Spacing::Alone));
+    input.clone().into_iter().chain(iter::once(plus)).chain(input).collect()
+}
+
+/// `#[rename(bar)] fn foo() {}` expands to `fn bar() {}`.
+#[proc_macro_attribute]
+pub fn rename(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let new_name = attr.to_string();
+    let mut after_fn = false;
+    item.into_iter()
+        .map(|tt| match tt {
+            TokenTree::Ident(ref ident) if after_fn => {
+                after_fn = false;
+                Ident::new(&new_name, ident.span()).into()
+            }
+            TokenTree::Ident(ref ident) => {
+                after_fn = ident.to_string() == "fn";
+                tt
+            }
+            tt => tt,
+        })
+        .collect()
+}
+
+/// Implements the empty `DummyTrait` for the annotated type.
+#[proc_macro_derive(DummyTrait)]
+pub fn derive_dummy_trait(item: TokenStream) -> TokenStream {
+    let mut tokens = item.into_iter();
+    let name = tokens
+        .by_ref()
+        .find(|tt| match tt {
+            TokenTree::Ident(ident) => {
+                let ident = ident.to_string();
+                ident == "struct" || ident == "enum" || ident == "union"
+            }
+            _ => false,
+        })
+        .and_then(|_| tokens.next())
+        .expect("expected a type definition");
+    format!("impl DummyTrait for {} {{}}", name).parse().unwrap()
+}
@@ -1,11 +1,17 @@
 //! FIXME: write short doc here
 
-use std::path::{Path, PathBuf};
+use std::{
+    io::{BufReader, Read},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+};
 
-use cargo_metadata::{CargoOpt, MetadataCommand};
+use cargo_metadata::{Message, MetadataCommand};
 use ra_arena::{impl_arena_id, Arena, RawId};
 use ra_db::Edition;
 use rustc_hash::FxHashMap;
+use serde::Deserialize;
 
 use crate::Result;
 
@@ -21,6 +27,38 @@ pub struct CargoWorkspace {
     packages: Arena<Package, PackageData>,
     targets: Arena<Target, TargetData>,
     pub(crate) workspace_root: PathBuf,
+    features: CargoFeatures,
+}
+
+/// The features to enable in the packages of the workspace, used both to
+/// query `cargo metadata` and to build them with `cargo check`. Without any
+/// option set, the default features are enabled.
+#[derive(Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CargoFeatures {
+    /// Do not activate the `default` feature.
+    pub no_default_features: bool,
+    /// Activate all available features.
+    pub all_features: bool,
+    /// List of features to activate.
+    pub features: Vec<String>,
+}
+
+impl CargoFeatures {
+    fn args(&self) -> Vec<String> {
+        let mut res = Vec::new();
+        if self.all_features {
+            res.push("--all-features".to_string());
+        }
+        if self.no_default_features {
+            res.push("--no-default-features".to_string());
+        }
+        if !self.features.is_empty() {
+            res.push("--features".to_string());
+            res.push(self.features.join(" "));
+        }
+        res
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -33,12 +71,16 @@ impl_arena_id!(Target);
 
 #[derive(Debug, Clone)]
 struct PackageData {
+    id: String,
     name: String,
     manifest: PathBuf,
     targets: Vec<Target>,
     is_member: bool,
     dependencies: Vec<PackageDependency>,
     edition: Edition,
+    features: Vec<String>,
+    proc_macro_dylib_path: Option<PathBuf>,
+    out_dir: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone)]
@@ -73,6 +115,7 @@ impl TargetKind {
                 "test" => TargetKind::Test,
                 "bench" => TargetKind::Bench,
                 "example" => TargetKind::Example,
+                "proc-macro" => TargetKind::Lib,
                 _ if kind.contains("lib") => TargetKind::Lib,
                 _ => continue,
             };
@@ -91,6 +134,9 @@ impl Package {
     pub fn edition(self, ws: &CargoWorkspace) -> Edition {
         ws.packages[self].edition
     }
+    pub fn features(self, ws: &CargoWorkspace) -> &[String] {
+        &ws.packages[self].features
+    }
     pub fn targets<'a>(self, ws: &'a CargoWorkspace) -> impl Iterator<Item = Target> + 'a {
         ws.packages[self].targets.iter().cloned()
     }
@@ -104,6 +150,16 @@ impl Package {
     ) -> impl Iterator<Item = &'a PackageDependency> + 'a {
         ws.packages[self].dependencies.iter()
     }
+    /// The compiled dylib of a `proc-macro` package, if it was built by
+    /// `CargoWorkspace::load_build_outputs`.
+    pub fn proc_macro_dylib_path(self, ws: &CargoWorkspace) -> Option<&Path> {
+        ws.packages[self].proc_macro_dylib_path.as_ref().map(PathBuf::as_path)
+    }
+    /// The `OUT_DIR` of the build script of the package, if it was run by
+    /// `CargoWorkspace::load_build_outputs`.
+    pub fn out_dir(self, ws: &CargoWorkspace) -> Option<&Path> {
+        ws.packages[self].out_dir.as_ref().map(PathBuf::as_path)
+    }
 }
 
 impl Target {
@@ -122,9 +178,12 @@ impl Target {
 }
 
 impl CargoWorkspace {
-    pub fn from_cargo_metadata(cargo_toml: &Path) -> Result<CargoWorkspace> {
+    pub fn from_cargo_metadata(
+        cargo_toml: &Path,
+        features: &CargoFeatures,
+    ) -> Result<CargoWorkspace> {
         let mut meta = MetadataCommand::new();
-        meta.manifest_path(cargo_toml).features(CargoOpt::AllFeatures);
+        meta.manifest_path(cargo_toml).other_options(features.args());
         if let Some(parent) = cargo_toml.parent() {
             meta.current_dir(parent);
         }
@@ -138,12 +197,16 @@ impl CargoWorkspace {
         for meta_pkg in meta.packages {
             let is_member = ws_members.contains(&meta_pkg.id);
             let pkg = packages.alloc(PackageData {
+                id: meta_pkg.id.repr.clone(),
                 name: meta_pkg.name,
                 manifest: meta_pkg.manifest_path.clone(),
                 targets: Vec::new(),
                 is_member,
                 edition: Edition::from_string(&meta_pkg.edition),
                 dependencies: Vec::new(),
+                features: Vec::new(),
+                proc_macro_dylib_path: None,
+                out_dir: None,
             });
             let pkg_data = &mut packages[pkg];
             pkg_by_id.insert(meta_pkg.id.clone(), pkg);
@@ -164,9 +227,15 @@ impl CargoWorkspace {
                 let dep = PackageDependency { name: dep_node.name, pkg: pkg_by_id[&dep_node.pkg] };
                 packages[source].dependencies.push(dep);
             }
+            packages[source].features.extend(node.features);
         }
 
-        Ok(CargoWorkspace { packages, targets, workspace_root: meta.workspace_root })
+        Ok(CargoWorkspace {
+            packages,
+            targets,
+            workspace_root: meta.workspace_root,
+            features: features.clone(),
+        })
     }
 
     pub fn packages<'a>(&'a self) -> impl Iterator<Item = Package> + ExactSizeIterator + 'a {
@@ -176,4 +245,73 @@ impl CargoWorkspace {
     pub fn target_by_root(&self, root: &Path) -> Option<Target> {
         self.packages().filter_map(|pkg| pkg.targets(self).find(|it| it.root(self) == root)).next()
     }
+
+    /// Runs `cargo check` with the features the workspace was loaded with,
+    /// which compiles the `proc-macro` packages of the workspace and runs the
+    /// build scripts, and records the paths of the resulting dylibs and
+    /// `OUT_DIR`s.
+    pub fn load_build_outputs(&mut self) -> Result<()> {
+        let mut child = Command::new("cargo")
+            .args(&["check", "--message-format=json"])
+            .args(self.features.args())
+            .arg("--manifest-path")
+            .arg(self.workspace_root.join("Cargo.toml"))
+            .current_dir(&self.workspace_root)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("cargo check failed: {}", e))?;
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        // Drain stderr concurrently, as cargo blocks once the pipe is full
+        let mut stderr = child.stderr.take().unwrap();
+        let stderr = thread::spawn(move || {
+            let mut buf = String::new();
+            let _ = stderr.read_to_string(&mut buf);
+            buf
+        });
+
+        let mut pkg_by_id = FxHashMap::default();
+        for (pkg, data) in self.packages.iter() {
+            pkg_by_id.insert(data.id.clone(), pkg);
+        }
+        for message in cargo_metadata::parse_messages(stdout) {
+            let artifact = match message {
+                Ok(Message::CompilerArtifact(it)) => it,
+                Ok(Message::BuildScriptExecuted(script)) => {
+                    if let Some(&pkg) = pkg_by_id.get(&script.package_id.repr) {
+                        // `out_dir` is empty when cargo is older than 1.41
+                        if script.out_dir != PathBuf::new() {
+                            self.packages[pkg].out_dir = Some(script.out_dir);
+                        }
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+            if !artifact.target.kind.iter().any(|kind| kind == "proc-macro") {
+                continue;
+            }
+            let pkg = match pkg_by_id.get(&artifact.package_id.repr) {
+                Some(&it) => it,
+                None => continue,
+            };
+            let dylib = artifact.filenames.into_iter().find(|path| is_dylib(path));
+            if dylib.is_some() {
+                self.packages[pkg].proc_macro_dylib_path = dylib;
+            }
+        }
+        let status = child.wait()?;
+        let stderr = stderr.join().unwrap_or_default();
+        if !status.success() {
+            Err(format!("cargo check failed ({}):\n{}", status, stderr.trim_end()))?;
+        }
+        Ok(())
+    }
+}
+
+fn is_dylib(path: &Path) -> bool {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("so") | Some("dylib") | Some("dll") => true,
+        _ => false,
+    }
 }
@@ -2,6 +2,7 @@
 
 use std::path::PathBuf;
 
+use rustc_hash::{FxHashMap, FxHashSet};
 use serde::Deserialize;
 
 /// A root points to the directory which contains Rust crates. rust-analyzer watches all files in
@@ -14,11 +15,24 @@ pub struct Root {
 
 /// A crate points to the root module of a crate and lists the dependencies of the crate. This is
 /// useful in creating the crate graph.
+///
+/// Build systems which don't go through cargo can also describe how each crate is compiled: the
+/// `--cfg` flags (in `rustc --print cfg` syntax, like `unix` or `target_os="linux"`), the enabled
+/// features, the environment variables visible to `env!` (including `OUT_DIR`) and extra
+/// directories with generated sources that the crate `include!`s.
 #[derive(Clone, Debug, Deserialize)]
 pub struct Crate {
     pub(crate) root_module: PathBuf,
     pub(crate) edition: Edition,
     pub(crate) deps: Vec<Dep>,
+    #[serde(default)]
+    pub(crate) cfg: FxHashSet<String>,
+    #[serde(default)]
+    pub(crate) features: FxHashSet<String>,
+    #[serde(default)]
+    pub(crate) env: FxHashMap<String, String>,
+    #[serde(default)]
+    pub(crate) include_dirs: Vec<PathBuf>,
 }
 
 #[derive(Clone, Copy, Debug, Deserialize)]
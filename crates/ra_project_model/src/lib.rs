@@ -8,15 +8,18 @@ use std::{
     error::Error,
     fs::File,
     io::BufReader,
+    iter,
     path::{Path, PathBuf},
+    process::Command,
 };
 
-use ra_db::{CrateGraph, CrateId, Edition, FileId};
+use ra_cfg::CfgOptions;
+use ra_db::{CrateGraph, CrateId, Edition, Env, FileId, IncludeDir, ProcMacro};
 use rustc_hash::FxHashMap;
 use serde_json::from_reader;
 
 pub use crate::{
-    cargo_workspace::{CargoWorkspace, Package, Target, TargetKind},
+    cargo_workspace::{CargoFeatures, CargoWorkspace, Package, Target, TargetKind},
     json_project::JsonProject,
     sysroot::Sysroot,
 };
@@ -58,11 +61,15 @@ impl PackageRoot {
 }
 
 impl ProjectWorkspace {
-    pub fn discover(path: &Path) -> Result<ProjectWorkspace> {
-        ProjectWorkspace::discover_with_sysroot(path, true)
+    pub fn discover(path: &Path, cargo_features: &CargoFeatures) -> Result<ProjectWorkspace> {
+        ProjectWorkspace::discover_with_sysroot(path, true, cargo_features)
     }
 
-    pub fn discover_with_sysroot(path: &Path, with_sysroot: bool) -> Result<ProjectWorkspace> {
+    pub fn discover_with_sysroot(
+        path: &Path,
+        with_sysroot: bool,
+        cargo_features: &CargoFeatures,
+    ) -> Result<ProjectWorkspace> {
         match find_rust_project_json(path) {
             Some(json_path) => {
                 let file = File::open(json_path)?;
@@ -71,7 +78,7 @@ impl ProjectWorkspace {
             }
             None => {
                 let cargo_toml = find_cargo_toml(path)?;
-                let cargo = CargoWorkspace::from_cargo_metadata(&cargo_toml)?;
+                let cargo = CargoWorkspace::from_cargo_metadata(&cargo_toml, cargo_features)?;
                 let sysroot =
                     if with_sysroot { Sysroot::discover(&cargo_toml)? } else { Sysroot::default() };
                 Ok(ProjectWorkspace::Cargo { cargo, sysroot })
@@ -79,6 +86,20 @@ impl ProjectWorkspace {
         }
     }
 
+    /// Compiles the `proc-macro` crates and runs the build scripts of the
+    /// workspace, so that their macros can be expanded and the files they
+    /// generate can be included. Does nothing for `rust-project.json`
+    /// workspaces, which list their `OUT_DIR`s themselves.
+    ///
+    /// If the build fails, the outputs which were built are kept and the error
+    /// contains cargo's output.
+    pub fn load_build_outputs(&mut self) -> Result<()> {
+        match self {
+            ProjectWorkspace::Json { .. } => Ok(()),
+            ProjectWorkspace::Cargo { cargo, .. } => cargo.load_build_outputs(),
+        }
+    }
+
     /// Returns the roots for the current `ProjectWorkspace`
     /// The return type contains the path and whether or not
     /// the root is a member of the current workspace
@@ -89,6 +110,15 @@ impl ProjectWorkspace {
                 for root in &project.roots {
                     roots.push(PackageRoot::new(root.path.clone(), true));
                 }
+                // Directories with generated code usually live outside of the project roots, so
+                // they are watched separately.
+                for krate in &project.crates {
+                    for dir in &krate.include_dirs {
+                        if !roots.iter().any(|root| dir.starts_with(root.path())) {
+                            roots.push(PackageRoot::new(dir.clone(), false));
+                        }
+                    }
+                }
                 roots
             }
             ProjectWorkspace::Cargo { cargo, sysroot } => {
@@ -97,6 +127,9 @@ impl ProjectWorkspace {
                     let root = pkg.root(&cargo).to_path_buf();
                     let member = pkg.is_member(&cargo);
                     roots.push(PackageRoot::new(root, member));
+                    if let Some(out_dir) = pkg.out_dir(&cargo) {
+                        roots.push(PackageRoot::new(out_dir.to_path_buf(), false));
+                    }
                 }
                 for krate in sysroot.crates() {
                     roots.push(PackageRoot::new(krate.root_dir(&sysroot).to_path_buf(), false))
@@ -117,7 +150,10 @@ impl ProjectWorkspace {
 
     pub fn to_crate_graph(
         &self,
+        default_cfg_options: &CfgOptions,
         load: &mut dyn FnMut(&Path) -> Option<FileId>,
+        load_include_dir: &mut dyn FnMut(&Path) -> Option<IncludeDir>,
+        load_proc_macros: &mut dyn FnMut(&Path) -> Vec<ProcMacro>,
     ) -> (CrateGraph, FxHashMap<CrateId, String>) {
         let mut crate_graph = CrateGraph::default();
         let mut names = FxHashMap::default();
@@ -131,7 +167,30 @@ impl ProjectWorkspace {
                             json_project::Edition::Edition2015 => Edition::Edition2015,
                             json_project::Edition::Edition2018 => Edition::Edition2018,
                         };
-                        crates.insert(crate_id, crate_graph.add_crate_root(file_id, edition));
+                        let cfg_options = {
+                            let mut opts = default_cfg_options.clone();
+                            for cfg in &krate.cfg {
+                                insert_cfg(&mut opts, cfg);
+                            }
+                            opts.insert_features(krate.features.iter().map(Into::into));
+                            opts
+                        };
+                        let env = {
+                            let mut env = Env::default();
+                            for (key, value) in &krate.env {
+                                env.set(key, value.clone());
+                            }
+                            env
+                        };
+                        let graph_crate_id =
+                            crate_graph.add_crate_root(file_id, edition, cfg_options, env);
+                        let include_dirs = krate
+                            .include_dirs
+                            .iter()
+                            .filter_map(|dir| load_include_dir(dir))
+                            .collect();
+                        crate_graph.set_include_dirs(graph_crate_id, include_dirs);
+                        crates.insert(crate_id, graph_crate_id);
                     }
                 }
 
@@ -157,7 +216,12 @@ impl ProjectWorkspace {
                 let mut sysroot_crates = FxHashMap::default();
                 for krate in sysroot.crates() {
                     if let Some(file_id) = load(krate.root(&sysroot)) {
-                        let crate_id = crate_graph.add_crate_root(file_id, Edition::Edition2018);
+                        let crate_id = crate_graph.add_crate_root(
+                            file_id,
+                            Edition::Edition2018,
+                            default_cfg_options.clone(),
+                            Env::default(),
+                        );
                         sysroot_crates.insert(krate, crate_id);
                         names.insert(crate_id, krate.name(&sysroot).to_string());
                     }
@@ -186,11 +250,39 @@ impl ProjectWorkspace {
                         let root = tgt.root(&cargo);
                         if let Some(file_id) = load(root) {
                             let edition = pkg.edition(&cargo);
-                            let crate_id = crate_graph.add_crate_root(file_id, edition);
+                            let cfg_options = {
+                                let mut opts = default_cfg_options.clone();
+                                opts.insert_features(pkg.features(&cargo).iter().map(Into::into));
+                                opts
+                            };
+                            // `OUT_DIR` is only known once the build scripts ran
+                            let out_dir = pkg.out_dir(&cargo);
+                            let env = {
+                                let mut env = Env::default();
+                                env.set("CARGO_PKG_NAME", pkg.name(&cargo).to_string());
+                                env.set(
+                                    "CARGO_MANIFEST_DIR",
+                                    pkg.root(&cargo).display().to_string(),
+                                );
+                                if let Some(out_dir) = out_dir {
+                                    env.set("OUT_DIR", out_dir.display().to_string());
+                                }
+                                env
+                            };
+                            let crate_id =
+                                crate_graph.add_crate_root(file_id, edition, cfg_options, env);
+                            let include_dirs = iter::once(pkg.root(&cargo))
+                                .chain(out_dir)
+                                .filter_map(|dir| load_include_dir(dir))
+                                .collect();
+                            crate_graph.set_include_dirs(crate_id, include_dirs);
                             names.insert(crate_id, pkg.name(&cargo).to_string());
                             if tgt.kind(&cargo) == TargetKind::Lib {
                                 lib_tgt = Some(crate_id);
                                 pkg_to_lib_crate.insert(pkg, crate_id);
+                                if let Some(dylib) = pkg.proc_macro_dylib_path(&cargo) {
+                                    crate_graph.set_proc_macros(crate_id, load_proc_macros(dylib));
+                                }
                             }
                             pkg_crates.entry(pkg).or_insert_with(Vec::new).push(crate_id);
                         }
@@ -255,6 +347,42 @@ impl ProjectWorkspace {
     }
 }
 
+/// Queries `rustc --print cfg` for the cfg options of the host target, like
+/// `unix`, `target_os = "linux"` or `target_pointer_width = "64"`.
+pub fn get_rustc_cfg_options() -> CfgOptions {
+    let mut cfg_options = CfgOptions::default();
+
+    match (|| -> Result<_> {
+        // `cfg(test)` and `cfg(debug_assertions)` are handled outside, so we suppress them here.
+        let output = Command::new("rustc").args(&["--print", "cfg", "-O"]).output()?;
+        if !output.status.success() {
+            Err("failed to get rustc cfgs")?;
+        }
+        Ok(String::from_utf8(output.stdout)?)
+    })() {
+        Ok(rustc_cfgs) => {
+            for line in rustc_cfgs.lines() {
+                insert_cfg(&mut cfg_options, line);
+            }
+        }
+        Err(e) => log::error!("failed to get rustc cfgs: {}", e),
+    }
+
+    cfg_options
+}
+
+/// Adds a single cfg in `rustc --print cfg` syntax: either `atom` or `key="value"`.
+fn insert_cfg(cfg_options: &mut CfgOptions, cfg: &str) {
+    match cfg.find('=') {
+        None => cfg_options.insert_atom(cfg.into()),
+        Some(pos) => {
+            let key = &cfg[..pos];
+            let value = cfg[pos + 1..].trim_matches('"');
+            cfg_options.insert_key_value(key.into(), value.into());
+        }
+    }
+}
+
 fn find_rust_project_json(path: &Path) -> Option<PathBuf> {
     if path.ends_with("rust-project.json") {
         return Some(path.to_path_buf());
@@ -286,3 +414,69 @@ fn find_cargo_toml(path: &Path) -> Result<PathBuf> {
     }
     Err(format!("can't find Cargo.toml at {}", path.display()))?
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use ra_cfg::{CfgExpr, CfgOptions};
+    use ra_db::{FileId, IncludeDir, SourceRootId};
+
+    use crate::{JsonProject, ProjectWorkspace};
+
+    #[test]
+    fn json_project_describes_how_crates_are_compiled() {
+        let project: JsonProject = serde_json::from_str(
+            r#"{
+                "roots": ["/project"],
+                "crates": [
+                    {
+                        "root_module": "/project/src/lib.rs",
+                        "edition": "2018",
+                        "deps": [],
+                        "cfg": ["unix", "target_os=\"linux\""],
+                        "features": ["std"],
+                        "env": { "OUT_DIR": "/build/out" },
+                        "include_dirs": ["/build/out", "/project/gen"]
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let ws = ProjectWorkspace::Json { project };
+
+        let roots = ws.to_roots().into_iter().map(|it| it.path().clone()).collect::<Vec<_>>();
+        assert_eq!(roots, vec![PathBuf::from("/project"), PathBuf::from("/build/out")]);
+
+        let (crate_graph, _) = ws.to_crate_graph(
+            &CfgOptions::default(),
+            &mut |_| Some(FileId(0)),
+            &mut |path| {
+                let (root, source_root) = if path.starts_with("/project") {
+                    ("/project", SourceRootId(0))
+                } else {
+                    ("/build/out", SourceRootId(1))
+                };
+                IncludeDir::new(path, source_root, Path::new(root))
+            },
+            &mut |_| Vec::new(),
+        );
+        let krate = crate_graph.iter().next().unwrap();
+
+        let cfg_options = crate_graph.cfg_options(krate);
+        let kv = |k: &str, v: &str| CfgExpr::KeyValue { key: k.into(), value: v.into() };
+        assert_eq!(cfg_options.check(&CfgExpr::Atom("unix".into())), Some(true));
+        assert_eq!(cfg_options.check(&kv("target_os", "linux")), Some(true));
+        assert_eq!(cfg_options.check(&kv("feature", "std")), Some(true));
+        assert_eq!(cfg_options.check(&kv("feature", "alloc")), Some(false));
+
+        assert_eq!(crate_graph.env(krate).get("OUT_DIR"), Some("/build/out"));
+
+        let include_dirs = crate_graph
+            .include_dirs(krate)
+            .iter()
+            .map(|dir| (dir.source_root, dir.relative_path.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(include_dirs, vec![(SourceRootId(1), ""), (SourceRootId(0), "gen")]);
+    }
+}
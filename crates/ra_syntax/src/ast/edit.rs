@@ -186,6 +186,101 @@ impl ast::RecordFieldList {
     }
 }
 
+impl ast::ParamList {
+    /// Replaces the parameters after `self`, keeping the layout of the list.
+    #[must_use]
+    pub fn with_params(&self, params: impl Iterator<Item = ast::Param>) -> ast::ParamList {
+        let anchor = match self.self_param() {
+            Some(it) => it.syntax().clone().into(),
+            None => match l_paren(self.syntax()) {
+                Some(it) => it,
+                None => return self.clone(),
+            },
+        };
+        replace_list_items(self, anchor, params.map(|it| it.syntax().clone().into()))
+    }
+}
+
+impl ast::ArgList {
+    /// Replaces the arguments, keeping the layout of the list.
+    #[must_use]
+    pub fn with_args(&self, args: impl Iterator<Item = ast::Expr>) -> ast::ArgList {
+        match l_paren(self.syntax()) {
+            Some(anchor) => {
+                replace_list_items(self, anchor, args.map(|it| it.syntax().clone().into()))
+            }
+            None => self.clone(),
+        }
+    }
+}
+
+fn l_paren(node: &SyntaxNode) -> Option<SyntaxElement> {
+    node.children_with_tokens().find(|it| it.kind() == T!['('])
+}
+
+/// Replaces the comma separated items of a parenthesized list which follow
+/// `anchor`, the opening paren or an item which is kept. Multi-line lists
+/// stay multi-line, with one item per line.
+#[must_use]
+fn replace_list_items<N: AstNode>(
+    list: &N,
+    anchor: SyntaxElement,
+    items: impl Iterator<Item = SyntaxElement>,
+) -> N {
+    let children = list.syntax().children_with_tokens().collect::<Vec<_>>();
+    let start = match children.iter().position(|it| *it == anchor) {
+        Some(idx) => idx + 1,
+        None => return list.clone(),
+    };
+    let end = match children.iter().rposition(|it| it.kind() == T![')']) {
+        Some(idx) if idx >= start => idx,
+        _ => return list.clone(),
+    };
+    let newline_ws = |element: Option<&SyntaxElement>| {
+        element
+            .and_then(|it| it.as_token())
+            .filter(|it| it.kind() == WHITESPACE && it.text().contains('\n'))
+            .cloned()
+    };
+    let l_paren_idx = children.iter().position(|it| it.kind() == T!['(']).unwrap_or(0);
+    let item_ws = newline_ws(children.get(l_paren_idx + 1));
+    let r_paren_ws = newline_ws(children.get(end - 1));
+    let has_trailing_comma = children[..end]
+        .iter()
+        .rev()
+        .find(|it| it.kind() != WHITESPACE && it.kind() != COMMENT)
+        .map_or(false, |it| it.kind() == T![,]);
+
+    let is_first_item = anchor.kind() == T!['('];
+    let mut to_insert: Vec<SyntaxElement> = Vec::new();
+    for (idx, item) in items.enumerate() {
+        if idx > 0 || !is_first_item {
+            to_insert.push(tokens::comma().into());
+        }
+        match &item_ws {
+            Some(ws) => to_insert.push(ws.clone().into()),
+            None if idx > 0 || !is_first_item => to_insert.push(tokens::single_space().into()),
+            None => (),
+        }
+        to_insert.push(item);
+    }
+    if let (Some(ws), Some(_)) = (&r_paren_ws, &item_ws) {
+        if !to_insert.is_empty() || !is_first_item {
+            if has_trailing_comma {
+                to_insert.push(tokens::comma().into());
+            }
+            to_insert.push(ws.clone().into());
+        }
+    }
+
+    if start == end {
+        insert_children(list, InsertPosition::After(anchor), to_insert.into_iter())
+    } else {
+        let range = RangeInclusive::new(children[start].clone(), children[end - 1].clone());
+        replace_children(list, range, to_insert.into_iter())
+    }
+}
+
 impl ast::TypeParam {
     #[must_use]
     pub fn remove_bounds(&self) -> ast::TypeParam {
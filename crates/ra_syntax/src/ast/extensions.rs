@@ -306,6 +306,26 @@ impl ast::BindPat {
     }
 }
 
+impl ast::LiteralPat {
+    pub fn is_negative(&self) -> bool {
+        self.syntax().children_with_tokens().any(|n| n.kind() == T![-])
+    }
+}
+
+impl ast::RangePat {
+    pub fn start(&self) -> Option<ast::Pat> {
+        children(self).nth(0)
+    }
+
+    pub fn end(&self) -> Option<ast::Pat> {
+        children(self).nth(1)
+    }
+
+    pub fn is_inclusive(&self) -> bool {
+        self.syntax().children_with_tokens().any(|n| n.kind() == T![..=] || n.kind() == T![...])
+    }
+}
+
 impl ast::PointerType {
     pub fn is_mut(&self) -> bool {
         self.syntax().children_with_tokens().any(|n| n.kind() == T![mut])
@@ -2,7 +2,10 @@
 //! of smaller pieces.
 use itertools::Itertools;
 
-use crate::{ast, AstNode, SourceFile};
+use crate::{
+    ast::{self, NameOwner, TypeBoundsOwner, TypeParamsOwner},
+    AstNode, SourceFile,
+};
 
 pub fn name_ref(text: &str) -> ast::NameRef {
     ast_from_text(&format!("fn f() {{ {}; }}", text))
@@ -38,13 +41,19 @@ pub fn block_from_expr(e: ast::Expr) -> ast::Block {
 }
 
 pub fn expr_unit() -> ast::Expr {
-    expr_from_text("()")
+    expr_from_text("()").unwrap()
 }
 pub fn expr_unimplemented() -> ast::Expr {
-    expr_from_text("unimplemented!()")
+    expr_from_text("unimplemented!()").unwrap()
 }
-fn expr_from_text(text: &str) -> ast::Expr {
-    ast_from_text(&format!("const C: () = {};", text))
+/// Parses `text`, if it is exactly one valid expression.
+pub fn expr_from_text(text: &str) -> Option<ast::Expr> {
+    checked_ast_from_text(&format!("const C: () = {};", text), text)
+}
+
+/// Parses `text`, like `x: u32`, if it is exactly one valid parameter.
+pub fn param(text: &str) -> Option<ast::Param> {
+    checked_ast_from_text(&format!("fn f({}) {{ }}", text), text)
 }
 
 pub fn bind_pat(name: ast::Name) -> ast::BindPat {
@@ -128,12 +137,75 @@ pub fn where_clause(preds: impl Iterator<Item = ast::WherePred>) -> ast::WhereCl
     }
 }
 
+/// The header of an impl of `def`, like `impl<'a, T: Clone> Foo<'a, T>`, or
+/// of the trait `trait_` for it. Like derives, a trait impl requires every type
+/// parameter to implement the trait.
+pub fn impl_header(def: &ast::NominalDef, trait_: Option<&str>) -> Option<String> {
+    let name = def.name()?;
+    let mut params = Vec::new();
+    let mut args = Vec::new();
+    if let Some(type_params) = def.type_param_list() {
+        for param in type_params.lifetime_params() {
+            params.push(param.syntax().text().to_string());
+            args.push(param.lifetime_token()?.text().to_string());
+        }
+        for param in type_params.type_params() {
+            let param_name = param.name()?.text().to_string();
+            // Defaults are not allowed in impl generics, so the param is
+            // rebuilt from the name and the bounds.
+            let bounds = param
+                .type_bound_list()
+                .into_iter()
+                .flat_map(|it| it.bounds())
+                .map(|bound| bound.syntax().text().to_string())
+                .chain(trait_.map(|it| it.to_string()))
+                .join(" + ");
+            if bounds.is_empty() {
+                params.push(param_name.clone());
+            } else {
+                params.push(format!("{}: {}", param_name, bounds));
+            }
+            args.push(param_name);
+        }
+    }
+
+    let mut res = String::from("impl");
+    if !params.is_empty() {
+        res.push_str(&format!("<{}>", params.join(", ")));
+    }
+    if let Some(trait_) = trait_ {
+        res.push_str(&format!(" {} for", trait_));
+    }
+    res.push_str(&format!(" {}", name.text()));
+    if !args.is_empty() {
+        res.push_str(&format!("<{}>", args.join(", ")));
+    }
+    if let Some(where_clause) = def.where_clause() {
+        res.push_str(&format!(" {}", where_clause.syntax().text()));
+    }
+    Some(res)
+}
+
 fn ast_from_text<N: AstNode>(text: &str) -> N {
     let parse = SourceFile::parse(text);
     let res = parse.tree().syntax().descendants().find_map(N::cast).unwrap();
     res
 }
 
+/// Like `ast_from_text`, for `node_text` which comes from the user and might
+/// not be valid.
+fn checked_ast_from_text<N: AstNode>(text: &str, node_text: &str) -> Option<N> {
+    let parse = SourceFile::parse(text);
+    if !parse.errors().is_empty() {
+        return None;
+    }
+    let res = parse.tree().syntax().descendants().find_map(N::cast)?;
+    if res.syntax().text() != node_text.trim() {
+        return None;
+    }
+    Some(res)
+}
+
 pub mod tokens {
     use crate::{AstNode, Parse, SourceFile, SyntaxKind::*, SyntaxToken, T};
     use once_cell::sync::Lazy;
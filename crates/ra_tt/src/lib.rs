@@ -14,7 +14,7 @@ macro_rules! impl_froms {
     }
 }
 
-use std::fmt;
+use std::{fmt, panic::RefUnwindSafe};
 
 use smol_str::SmolStr;
 
@@ -67,19 +67,19 @@ pub struct Literal {
     pub text: SmolStr,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Punct {
     pub char: char,
     pub spacing: Spacing,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Spacing {
     Alone,
     Joint,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Ident {
     pub text: SmolStr,
     pub id: TokenId,
@@ -166,4 +166,34 @@ impl Subtree {
     }
 }
 
+/// Why an external `TokenExpander` failed to expand a macro.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExpansionError {
+    /// The expander couldn't be reached, for example because it crashed.
+    Io(String),
+    /// The expander sent a response we don't understand.
+    Protocol(String),
+    /// The macro itself reported an error, or panicked.
+    Expansion(String),
+}
+
+impl fmt::Display for ExpansionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExpansionError::Io(msg) => write!(f, "io error: {}", msg),
+            ExpansionError::Protocol(msg) => write!(f, "protocol error: {}", msg),
+            ExpansionError::Expansion(msg) => write!(f, "expansion error: {}", msg),
+        }
+    }
+}
+
+/// Expands macros which are not implemented by rust-analyzer itself, like
+/// procedural macros.
+pub trait TokenExpander: fmt::Debug + Send + Sync + RefUnwindSafe {
+    /// Expands `subtree`, which is the macro call argument or the annotated
+    /// item. `attrs` is the argument of an attribute macro.
+    fn expand(&self, subtree: &Subtree, attrs: Option<&Subtree>)
+        -> Result<Subtree, ExpansionError>;
+}
+
 pub mod buffer;
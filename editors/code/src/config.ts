@@ -14,6 +14,12 @@ export interface CargoWatchOptions {
     trace: CargoWatchTraceOptions;
 }
 
+export interface CargoFeatures {
+    noDefaultFeatures: boolean;
+    allFeatures: boolean;
+    features: string[];
+}
+
 export class Config {
     public highlightingOn = true;
     public rainbowHighlightingOn = false;
@@ -24,6 +30,14 @@ export class Config {
     public displayInlayHints = true;
     public excludeGlobs = [];
     public useClientWatching = false;
+    public formatOnSave = false;
+    public procMacroEnabled = false;
+    public loadOutDirsFromCheck = false;
+    public cargoFeatures: CargoFeatures = {
+        noDefaultFeatures: false,
+        allFeatures: false,
+        features: []
+    };
     public featureFlags = {};
     public cargoWatchOptions: CargoWatchOptions = {
         enableOnStartup: 'ask',
@@ -137,6 +151,34 @@ export class Config {
         if (config.has('useClientWatching')) {
             this.useClientWatching = config.get('useClientWatching') || false;
         }
+        if (config.has('formatOnSave')) {
+            this.formatOnSave = config.get('formatOnSave') || false;
+        }
+        if (config.has('procMacroEnabled')) {
+            this.procMacroEnabled = config.get('procMacroEnabled') || false;
+        }
+        if (config.has('loadOutDirsFromCheck')) {
+            this.loadOutDirsFromCheck =
+                config.get('loadOutDirsFromCheck') || false;
+        }
+        if (config.has('cargoFeatures.noDefaultFeatures')) {
+            this.cargoFeatures.noDefaultFeatures = config.get(
+                'cargoFeatures.noDefaultFeatures',
+                false
+            );
+        }
+        if (config.has('cargoFeatures.allFeatures')) {
+            this.cargoFeatures.allFeatures = config.get(
+                'cargoFeatures.allFeatures',
+                false
+            );
+        }
+        if (config.has('cargoFeatures.features')) {
+            this.cargoFeatures.features = config.get(
+                'cargoFeatures.features',
+                []
+            );
+        }
         if (config.has('featureFlags')) {
             this.featureFlags = config.get('featureFlags') || {};
         }
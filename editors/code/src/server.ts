@@ -47,6 +47,10 @@ export class Server {
                 lruCapacity: Server.config.lruCapacity,
                 excludeGlobs: Server.config.excludeGlobs,
                 useClientWatching: Server.config.useClientWatching,
+                formatOnSave: Server.config.formatOnSave,
+                procMacroEnabled: Server.config.procMacroEnabled,
+                loadOutDirsFromCheck: Server.config.loadOutDirsFromCheck,
+                cargoFeatures: Server.config.cargoFeatures,
                 featureFlags: Server.config.featureFlags
             },
             traceOutputChannel